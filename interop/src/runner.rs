@@ -0,0 +1,644 @@
+//! Endpoint following the [quic-interop-runner] contract
+//!
+//! Behavior is controlled by environment variables:
+//!
+//! - `ROLE`: `client` or `server`
+//! - `TESTCASE`: name of the test case to run, e.g. `handshake` or `transfer`
+//! - `REQUESTS`: space-separated list of URLs for the client to download
+//! - `SSLKEYLOGFILE`: where to log TLS secrets, if set
+//! - `QLOGDIR`: where to write qlog traces, if set
+//!
+//! The server serves files from `/www` on port 443 using the certificate in `/certs`, and the
+//! client saves downloaded files to `/downloads`. Those locations may be overridden by the `WWW`,
+//! `CERTS`, `DOWNLOADS` and `PORT` environment variables for use outside of the runner's
+//! containers.
+//!
+//! Following the runner's conventions, the process exits with status 0 on success, 1 on failure,
+//! and 127 if the test case is not supported.
+//!
+//! Two parts of the contract are not implemented, as quinn lacks the underlying support:
+//!
+//! - The `v2` test case always exits with status 127, since quinn implements drafts 29 through 32
+//!   of QUIC, not QUIC version 2.
+//! - No qlog traces are written to `QLOGDIR`, since quinn doesn't produce any. A warning is logged
+//!   when it is set.
+//!
+//! [quic-interop-runner]: https://github.com/marten-seemann/quic-interop-runner
+
+use std::{
+    env, fmt, fs,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
+    path::{Component, Path, PathBuf},
+    process, slice, str,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use futures::{future, StreamExt, TryFutureExt};
+use tracing::{error, info, info_span, warn};
+use tracing_futures::Instrument as _;
+
+/// ALPN identifier for HTTP/0.9, which kept its draft-29 name through draft 32 of QUIC
+const ALPN: &[u8] = b"hq-29";
+
+/// Exit status signaling that the requested test case isn't implemented
+const EXIT_UNSUPPORTED: i32 = 127;
+
+#[tokio::main]
+async fn main() {
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .with_writer(std::io::stderr)
+            .finish(),
+    )
+    .unwrap();
+
+    let config = match Config::from_env() {
+        Ok(Some(x)) => x,
+        Ok(None) => {
+            eprintln!(
+                "unsupported test case: {}",
+                env::var("TESTCASE").unwrap_or_default()
+            );
+            process::exit(EXIT_UNSUPPORTED);
+        }
+        Err(e) => {
+            eprintln!("invalid configuration: {:#}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(ref dir) = config.qlog_dir {
+        warn!(dir = %dir.display(), "qlog output is not supported");
+    }
+
+    let result = match config.role {
+        Role::Client => run_client(&config).await,
+        Role::Server => {
+            let addr = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), config.port);
+            match server_endpoint(&config, &addr) {
+                Ok((_, incoming)) => serve(config.www.into(), incoming).await,
+                Err(e) => Err(e),
+            }
+        }
+    };
+    if let Err(e) = result {
+        error!("failed: {:#}", e);
+        process::exit(1);
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Role {
+    Client,
+    Server,
+}
+
+/// Test cases defined by the runner
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Testcase {
+    /// A single successful handshake and download
+    Handshake,
+    /// Multiple downloads multiplexed over one connection
+    Transfer,
+    /// The server requires address validation with a Retry packet
+    Retry,
+    /// The first download establishes a session which the second connection resumes
+    Resumption,
+    /// Like `Resumption`, but requests after the first are sent as 0-RTT data
+    ZeroRtt,
+    /// Each download uses a new connection
+    Multiconnect,
+    /// Only the ChaCha20-Poly1305 cipher suite may be used
+    ChaCha20,
+    /// The client updates keys during the transfer
+    KeyUpdate,
+    /// Packets carry ECN markings
+    Ecn,
+    /// QUIC version 2, which this implementation does not support
+    V2,
+}
+
+impl Testcase {
+    const ALL: [Testcase; 10] = [
+        Testcase::Handshake,
+        Testcase::Transfer,
+        Testcase::Retry,
+        Testcase::Resumption,
+        Testcase::ZeroRtt,
+        Testcase::Multiconnect,
+        Testcase::ChaCha20,
+        Testcase::KeyUpdate,
+        Testcase::Ecn,
+        Testcase::V2,
+    ];
+
+    fn name(self) -> &'static str {
+        use Testcase::*;
+        match self {
+            Handshake => "handshake",
+            Transfer => "transfer",
+            Retry => "retry",
+            Resumption => "resumption",
+            ZeroRtt => "zerortt",
+            Multiconnect => "multiconnect",
+            ChaCha20 => "chacha20",
+            KeyUpdate => "keyupdate",
+            Ecn => "ecn",
+            V2 => "v2",
+        }
+    }
+
+    /// Look up a supported test case by the name the runner uses for it
+    fn supported(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|x| x.name() == name)
+            .filter(|&x| x != Testcase::V2)
+    }
+}
+
+impl fmt::Display for Testcase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+struct Config {
+    role: Role,
+    testcase: Testcase,
+    requests: Vec<http::Uri>,
+    www: PathBuf,
+    certs: PathBuf,
+    downloads: PathBuf,
+    port: u16,
+    keylog: bool,
+    qlog_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// Read the configuration from the environment, or `None` if the test case is unsupported
+    fn from_env() -> Result<Option<Self>> {
+        let role = match env::var("ROLE").context("ROLE not set")?.as_str() {
+            "client" => Role::Client,
+            "server" => Role::Server,
+            x => bail!("unknown role {:?}", x),
+        };
+        let testcase = match Testcase::supported(&env::var("TESTCASE").unwrap_or_default()) {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let requests = env::var("REQUESTS")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|x| {
+                x.parse()
+                    .with_context(|| format!("invalid request {:?}", x))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let path = |var: &str, default: &str| {
+            env::var_os(var).map_or_else(|| PathBuf::from(default), PathBuf::from)
+        };
+        let port = match env::var("PORT") {
+            Ok(x) => x.parse().context("invalid PORT")?,
+            Err(_) => 443,
+        };
+        Ok(Some(Self {
+            role,
+            testcase,
+            requests,
+            www: path("WWW", "/www"),
+            certs: path("CERTS", "/certs"),
+            downloads: path("DOWNLOADS", "/downloads"),
+            port,
+            keylog: env::var_os("SSLKEYLOGFILE").is_some(),
+            qlog_dir: env::var_os("QLOGDIR").map(PathBuf::from),
+        }))
+    }
+
+    fn transport(&self) -> quinn::TransportConfig {
+        let mut transport = quinn::TransportConfig::default();
+        transport.send_window(1024 * 1024 * 2);
+        transport.receive_window(1024 * 1024 * 2).unwrap();
+        transport
+    }
+}
+
+fn server_endpoint(
+    config: &Config,
+    addr: &SocketAddr,
+) -> Result<(quinn::Endpoint, quinn::Incoming)> {
+    let key = fs::read(config.certs.join("priv.key")).context("failed to read private key")?;
+    let key = quinn::PrivateKey::from_pem(&key)?;
+    let cert_chain =
+        fs::read(config.certs.join("cert.pem")).context("failed to read certificate chain")?;
+    let cert_chain = quinn::CertificateChain::from_pem(&cert_chain)?;
+
+    let mut server_config = quinn::ServerConfigBuilder::default();
+    server_config.certificate(cert_chain, key)?;
+    server_config.protocols(&[ALPN]);
+    server_config.use_stateless_retry(config.testcase == Testcase::Retry);
+    if config.keylog {
+        server_config.enable_keylog();
+    }
+    let mut server_config = server_config.build();
+    server_config.transport = Arc::new(config.transport());
+    if config.testcase == Testcase::ChaCha20 {
+        Arc::make_mut(&mut server_config.crypto).ciphersuites =
+            vec![&rustls::ciphersuite::TLS13_CHACHA20_POLY1305_SHA256];
+    }
+
+    let mut endpoint = quinn::Endpoint::builder();
+    endpoint.listen(server_config);
+    let (endpoint, incoming) = endpoint.bind(addr)?;
+    info!(addr = %endpoint.local_addr()?, testcase = %config.testcase, "listening");
+    Ok((endpoint, incoming))
+}
+
+/// Serve files from `www` over HTTP/0.9 to every incoming connection
+async fn serve(www: Arc<Path>, mut incoming: quinn::Incoming) -> Result<()> {
+    while let Some(connecting) = incoming.next().await {
+        tokio::spawn(
+            handle_connection(www.clone(), connecting)
+                .unwrap_or_else(|e| error!("connection failed: {:#}", e)),
+        );
+    }
+    Ok(())
+}
+
+async fn handle_connection(www: Arc<Path>, connecting: quinn::Connecting) -> Result<()> {
+    let quinn::NewConnection {
+        connection,
+        mut bi_streams,
+        ..
+    } = match connecting.into_0rtt() {
+        Ok((c, _)) => c,
+        Err(c) => c.await?,
+    };
+    let span = info_span!("connection", remote = %connection.remote_address());
+    async {
+        while let Some(stream) = bi_streams.next().await {
+            let stream = match stream {
+                Err(quinn::ConnectionError::ApplicationClosed { .. }) => return Ok(()),
+                Err(e) => return Err(e.into()),
+                Ok(s) => s,
+            };
+            tokio::spawn(
+                handle_request(www.clone(), stream)
+                    .unwrap_or_else(|e| error!("request failed: {:#}", e))
+                    .instrument(info_span!("request")),
+            );
+        }
+        Ok(())
+    }
+    .instrument(span)
+    .await
+}
+
+async fn handle_request(
+    www: Arc<Path>,
+    (mut send, recv): (quinn::SendStream, quinn::RecvStream),
+) -> Result<()> {
+    let req = recv
+        .read_to_end(64 * 1024)
+        .await
+        .context("failed reading request")?;
+    let path = parse_get(&req)?;
+    info!(path);
+    let data =
+        fs::read(resolve(&www, path)?).with_context(|| format!("failed to read {}", path))?;
+    send.write_all(&data)
+        .await
+        .context("failed to send response")?;
+    send.finish().await.context("failed to shutdown stream")?;
+    Ok(())
+}
+
+/// Extract the path from an HTTP/0.9 `GET` request
+fn parse_get(x: &[u8]) -> Result<&str> {
+    if x.len() < 4 || &x[0..4] != b"GET " {
+        bail!("missing GET");
+    }
+    if x[4..].len() < 2 || &x[x.len() - 2..] != b"\r\n" {
+        bail!("missing \\r\\n");
+    }
+    let x = &x[4..x.len() - 2];
+    let end = x.iter().position(|&c| c == b' ').unwrap_or(x.len());
+    str::from_utf8(&x[..end]).context("path is malformed UTF-8")
+}
+
+/// Map a request path onto a file within `root`, rejecting paths that would escape it
+fn resolve(root: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|x| !matches!(x, Component::Normal(_)))
+    {
+        bail!("invalid path {:?}", path);
+    }
+    Ok(root.join(relative))
+}
+
+async fn run_client(config: &Config) -> Result<()> {
+    let first = config.requests.first().context("no requests")?;
+    let host = first.host().context("request without host")?;
+    let remote = (host, first.port_u16().unwrap_or(443))
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("couldn't resolve to an address"))?;
+    let server_name = if webpki::DNSNameRef::try_from_ascii_str(host).is_ok() {
+        host
+    } else {
+        "localhost"
+    };
+
+    let mut tls_config = rustls::ClientConfig::new();
+    tls_config.versions = vec![rustls::ProtocolVersion::TLSv1_3];
+    tls_config.enable_early_data = true;
+    tls_config.alpn_protocols = vec![ALPN.into()];
+    if config.testcase == Testcase::ChaCha20 {
+        tls_config.ciphersuites = vec![&rustls::ciphersuite::TLS13_CHACHA20_POLY1305_SHA256];
+    }
+    if config.keylog {
+        tls_config.key_log = Arc::new(rustls::KeyLogFile::new());
+    }
+    let client = Client {
+        tls_config,
+        transport: Arc::new(config.transport()),
+        remote,
+        server_name,
+        downloads: &config.downloads,
+    };
+
+    let bind_addr = match remote {
+        SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+    };
+    let (endpoint, _) = quinn::Endpoint::builder().bind(&bind_addr)?;
+    info!(%remote, testcase = %config.testcase, "connecting");
+
+    let requests = &config.requests[..];
+    match config.testcase {
+        Testcase::Multiconnect => {
+            for request in requests {
+                let (conn, _) = client.connect(&endpoint).await?;
+                client
+                    .download_all(&conn, slice::from_ref(request), false)
+                    .await?;
+                conn.close(0u32.into(), b"done");
+            }
+        }
+        Testcase::Resumption | Testcase::ZeroRtt => {
+            let (first, rest) = requests.split_at(1);
+            let (conn, _) = client.connect(&endpoint).await?;
+            client.download_all(&conn, first, false).await?;
+            conn.close(0u32.into(), b"done");
+            // Session tickets are sent after the handshake, so finish with the first connection
+            // before making the second
+            endpoint.wait_idle().await;
+
+            let (conn, saw_cert) = if config.testcase == Testcase::ZeroRtt {
+                let (new_conn, accepted, saw_cert) = client.connect_0rtt(&endpoint)?;
+                let conn = new_conn.connection;
+                client.download_all(&conn, rest, false).await?;
+                if !accepted.await {
+                    bail!("0-RTT rejected");
+                }
+                (conn, saw_cert)
+            } else {
+                let (conn, saw_cert) = client.connect(&endpoint).await?;
+                client.download_all(&conn, rest, false).await?;
+                (conn, saw_cert)
+            };
+            conn.close(0u32.into(), b"done");
+            if *saw_cert.lock().unwrap() {
+                bail!("session was not resumed");
+            }
+        }
+        _ => {
+            let (conn, _) = client.connect(&endpoint).await?;
+            let key_update = config.testcase == Testcase::KeyUpdate;
+            client.download_all(&conn, requests, key_update).await?;
+            conn.close(0u32.into(), b"done");
+        }
+    }
+    endpoint.wait_idle().await;
+    Ok(())
+}
+
+struct Client<'a> {
+    tls_config: rustls::ClientConfig,
+    transport: Arc<quinn::TransportConfig>,
+    remote: SocketAddr,
+    server_name: &'a str,
+    downloads: &'a Path,
+}
+
+impl Client<'_> {
+    /// Construct a connection configuration which records whether the server presented a
+    /// certificate, i.e. whether the handshake was a full one rather than a resumption
+    ///
+    /// Client configurations share a session cache, so later connections may resume sessions
+    /// established by earlier ones.
+    fn config(&self) -> (quinn::ClientConfig, Arc<Mutex<bool>>) {
+        let saw_cert = Arc::new(Mutex::new(false));
+        let mut crypto = self.tls_config.clone();
+        crypto
+            .dangerous()
+            .set_certificate_verifier(Arc::new(InteropVerifier(saw_cert.clone())));
+        let config = quinn::ClientConfig {
+            crypto: Arc::new(crypto),
+            transport: self.transport.clone(),
+        };
+        (config, saw_cert)
+    }
+
+    async fn connect(
+        &self,
+        endpoint: &quinn::Endpoint,
+    ) -> Result<(quinn::Connection, Arc<Mutex<bool>>)> {
+        let (config, saw_cert) = self.config();
+        let new_conn = endpoint
+            .connect_with(config, &self.remote, self.server_name)?
+            .await
+            .context("failed to connect")?;
+        Ok((new_conn.connection, saw_cert))
+    }
+
+    fn connect_0rtt(
+        &self,
+        endpoint: &quinn::Endpoint,
+    ) -> Result<(
+        quinn::NewConnection,
+        quinn::ZeroRttAccepted,
+        Arc<Mutex<bool>>,
+    )> {
+        let (config, saw_cert) = self.config();
+        match endpoint
+            .connect_with(config, &self.remote, self.server_name)?
+            .into_0rtt()
+        {
+            Ok((new_conn, accepted)) => Ok((new_conn, accepted, saw_cert)),
+            Err(_) => bail!("0-RTT unavailable"),
+        }
+    }
+
+    /// Download every request concurrently over `conn`
+    ///
+    /// If `key_update` is set, keys are updated once the first response data is received.
+    async fn download_all(
+        &self,
+        conn: &quinn::Connection,
+        requests: &[http::Uri],
+        key_update: bool,
+    ) -> Result<()> {
+        let updated = Mutex::new(!key_update);
+        let on_data = || {
+            let mut updated = updated.lock().unwrap();
+            if !*updated {
+                info!("updating keys");
+                conn.force_key_update();
+                *updated = true;
+            }
+        };
+        future::try_join_all(
+            requests
+                .iter()
+                .map(|request| self.download(conn, request, &on_data)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn download(
+        &self,
+        conn: &quinn::Connection,
+        request: &http::Uri,
+        on_data: impl Fn(),
+    ) -> Result<()> {
+        let path = request.path();
+        let (mut send, mut recv) = conn.open_bi().await.context("failed to open stream")?;
+        send.write_all(format!("GET {}\r\n", path).as_bytes())
+            .await
+            .context("failed to send request")?;
+        send.finish().await.context("failed to shutdown stream")?;
+
+        let mut body = Vec::new();
+        let mut buf = vec![0; 64 * 1024];
+        while let Some(n) = recv
+            .read(&mut buf)
+            .await
+            .context("failed to read response")?
+        {
+            body.extend_from_slice(&buf[..n]);
+            on_data();
+        }
+        info!(path, len = body.len(), "downloaded");
+        fs::write(resolve(self.downloads, path)?, body)
+            .with_context(|| format!("failed to save {}", path))?;
+        Ok(())
+    }
+}
+
+/// Accepts any certificate, recording that one was presented
+struct InteropVerifier(Arc<Mutex<bool>>);
+
+impl rustls::ServerCertVerifier for InteropVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &rustls::RootCertStore,
+        _presented_certs: &[rustls::Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> std::result::Result<rustls::ServerCertVerified, rustls::TLSError> {
+        *self.0.lock().unwrap() = true;
+        Ok(rustls::ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the client against the local server over loopback for every test case
+    #[tokio::test]
+    async fn loopback() {
+        let root = env::temp_dir().join(format!("quinn-interop-runner-{}", process::id()));
+        let certs = root.join("certs");
+        let www = root.join("www");
+        fs::create_dir_all(&certs).unwrap();
+        fs::create_dir_all(&www).unwrap();
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        fs::write(certs.join("cert.pem"), cert.serialize_pem().unwrap()).unwrap();
+        fs::write(certs.join("priv.key"), cert.serialize_private_key_pem()).unwrap();
+        let files = [("small", 1000), ("medium", 100_000), ("large", 3_000_000)];
+        for &(name, len) in files.iter() {
+            let data = (0..len).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+            fs::write(www.join(name), data).unwrap();
+        }
+
+        for &testcase in Testcase::ALL.iter() {
+            if Testcase::supported(testcase.name()).is_none() {
+                continue;
+            }
+            let downloads = root.join("downloads").join(testcase.name());
+            fs::create_dir_all(&downloads).unwrap();
+            let mut config = Config {
+                role: Role::Server,
+                testcase,
+                requests: Vec::new(),
+                www: www.clone(),
+                certs: certs.clone(),
+                downloads: downloads.clone(),
+                port: 0,
+                keylog: false,
+                qlog_dir: None,
+            };
+            let (server, incoming) =
+                server_endpoint(&config, &"127.0.0.1:0".parse().unwrap()).unwrap();
+            let port = server.local_addr().unwrap().port();
+            config.role = Role::Client;
+            config.requests = files
+                .iter()
+                .map(|&(name, _)| {
+                    format!("https://localhost:{}/{}", port, name)
+                        .parse()
+                        .unwrap()
+                })
+                .collect();
+            tokio::spawn(serve(www.clone().into(), incoming));
+
+            if let Err(e) = run_client(&config).await {
+                panic!("{} failed: {:#}", testcase, e);
+            }
+            for &(name, _) in files.iter() {
+                assert_eq!(
+                    fs::read(downloads.join(name)).unwrap(),
+                    fs::read(www.join(name)).unwrap(),
+                    "{}: {} differs",
+                    testcase,
+                    name
+                );
+            }
+            server.close(0u32.into(), &[]);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn unsupported() {
+        assert_eq!(Testcase::supported("v2"), None);
+        assert_eq!(Testcase::supported("http3"), None);
+        assert_eq!(Testcase::supported("zerortt"), Some(Testcase::ZeroRtt));
+    }
+
+    #[test]
+    fn paths() {
+        let root = Path::new("/www");
+        assert_eq!(resolve(root, "/a/b").unwrap(), Path::new("/www/a/b"));
+        assert!(resolve(root, "/../etc/passwd").is_err());
+        assert_eq!(parse_get(b"GET /file\r\n").unwrap(), "/file");
+        assert!(parse_get(b"POST /file\r\n").is_err());
+    }
+}
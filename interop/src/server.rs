@@ -76,7 +76,7 @@ async fn main() -> Result<()> {
 
     let mut server_config = quinn::ServerConfigBuilder::default();
     server_config.certificate(cert_chain, key)?;
-    server_config.protocols(&[quinn_h3::ALPN, b"hq-29", b"siduck-00"]);
+    server_config.protocols(&[quinn_h3::ALPN, b"h3-29", b"hq-29", b"siduck-00"]);
 
     let main = server(server_config.clone(), SocketAddr::new(opt.listen, 4433));
     let default = server(server_config.clone(), SocketAddr::new(opt.listen, 443));
@@ -109,7 +109,7 @@ async fn server(server_config: quinn::ServerConfigBuilder, addr: SocketAddr) ->
             println!("server received connection");
 
             let result = match &proto[..] {
-                quinn_h3::ALPN => h3_handle_connection(connecting).await,
+                quinn_h3::ALPN | b"h3-29" => h3_handle_connection(connecting).await,
                 b"hq-29" => hq_handle_connection(connecting).await,
                 b"siduck-00" => siduck_handle_connection(connecting).await,
                 _ => unreachable!("unsupported protocol"),
@@ -324,7 +324,7 @@ fn parse_size(literal: &str) -> Result<usize> {
     Ok(num * scale)
 }
 
-const ALT_SVC: &str = "h3-29=\":443\"";
+const ALT_SVC: &str = "h3=\":443\", h3-29=\":443\"";
 
 fn h2_home() -> hyper::Response<hyper::Body> {
     Response::builder()
@@ -0,0 +1,351 @@
+/* Generated by cbindgen from quinn-ffi. Do not edit by hand. */
+
+#ifndef QUINN_H
+#define QUINN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Outcome of an operation
+typedef enum QuinnResult {
+  // The operation succeeded
+  QUINN_RESULT_OK,
+  // Nothing is currently available to be polled
+  QUINN_RESULT_EMPTY,
+  // The operation cannot make progress until the peer grants more resources
+  QUINN_RESULT_BLOCKED,
+  // The stream has been finished by the peer, and all of its data has been read
+  QUINN_RESULT_FINISHED,
+  // An argument was null, out of range, or otherwise malformed
+  QUINN_RESULT_INVALID_ARGUMENT,
+  // The configuration could not be applied
+  QUINN_RESULT_INVALID_CONFIG,
+  // A certificate or private key could not be parsed or used
+  QUINN_RESULT_INVALID_CERTIFICATE,
+  // The supplied buffer is too small for the next datagram, which remains queued
+  QUINN_RESULT_BUFFER_TOO_SMALL,
+  // The connection handle does not refer to a live connection
+  QUINN_RESULT_UNKNOWN_CONNECTION,
+  // The stream has not been opened, or has already been finished, reset or stopped
+  QUINN_RESULT_UNKNOWN_STREAM,
+  // The peer asked us to stop sending on the stream
+  QUINN_RESULT_STOPPED,
+  // The peer abandoned transmitting data on the stream
+  QUINN_RESULT_RESET,
+  // A new connection could not be initiated
+  QUINN_RESULT_CONNECT_FAILED,
+  // The peer does not support receiving datagrams, or datagram support is disabled locally
+  QUINN_RESULT_DATAGRAMS_UNSUPPORTED,
+  // The datagram is larger than the connection can currently accommodate
+  QUINN_RESULT_DATAGRAM_TOO_LARGE,
+} QuinnResult;
+
+// Directionality of a stream
+typedef enum QuinnDir {
+  // Data flows in both directions
+  QUINN_DIR_BI,
+  // Data flows only from the stream's initiator
+  QUINN_DIR_UNI,
+} QuinnDir;
+
+// Address family of a [`QuinnSocketAddr`]
+typedef enum QuinnAddressFamily {
+  // An IPv4 address, stored in the first four bytes of `ip`
+  QUINN_ADDRESS_FAMILY_IPV4,
+  // An IPv6 address
+  QUINN_ADDRESS_FAMILY_IPV6,
+} QuinnAddressFamily;
+
+// Kinds of [`QuinnEvent`]
+typedef enum QuinnEventKind {
+  // A client has initiated a new connection to this endpoint
+  QUINN_EVENT_KIND_CONNECTION_INCOMING,
+  // The connection's handshake completed successfully
+  QUINN_EVENT_KIND_CONNECTED,
+  // The connection was closed by either side, or encountered an error
+  //
+  // `error_code` carries the peer's error code if it closed the connection, and is zero
+  // otherwise. The connection handle remains valid until it is drained.
+  QUINN_EVENT_KIND_CONNECTION_LOST,
+  // The connection has been fully torn down, and its handle is no longer valid
+  QUINN_EVENT_KIND_CONNECTION_DRAINED,
+  // The peer opened one or more streams of direction `dir`, which can be accepted
+  QUINN_EVENT_KIND_STREAM_OPENED,
+  // `stream` has data or errors waiting to be read
+  QUINN_EVENT_KIND_STREAM_READABLE,
+  // `stream` was write-blocked and may now accept more data
+  QUINN_EVENT_KIND_STREAM_WRITABLE,
+  // `stream` has been finished and all of its data acknowledged by the peer
+  QUINN_EVENT_KIND_STREAM_FINISHED,
+  // The peer asked us to stop sending on `stream` with `error_code`
+  QUINN_EVENT_KIND_STREAM_STOPPED,
+  // At least one new stream of direction `dir` may be opened
+  QUINN_EVENT_KIND_STREAM_AVAILABLE,
+  // One or more application datagrams have been received
+  QUINN_EVENT_KIND_DATAGRAM_RECEIVED,
+  // The peer began sending from a new address, retrieved with
+  // `quinn_connection_remote_address`
+  QUINN_EVENT_KIND_PATH_MIGRATED,
+  // The server declined to process 0-RTT data, and streams opened before the handshake
+  // completed were discarded
+  QUINN_EVENT_KIND_ZERO_RTT_REJECTED,
+} QuinnEventKind;
+
+// Parameters governing the core QUIC state machine, shared by clients and servers
+typedef struct QuinnTransportConfig QuinnTransportConfig;
+
+// Parameters governing incoming connections
+typedef struct QuinnServerConfig QuinnServerConfig;
+
+// Parameters governing outgoing connections
+typedef struct QuinnClientConfig QuinnClientConfig;
+
+// A QUIC endpoint and the connections it hosts
+//
+// An endpoint may act as both client and server for different connections. It performs no I/O of
+// its own; see the crate documentation for how to drive it.
+typedef struct QuinnEndpoint QuinnEndpoint;
+
+// Identifies a connection within its endpoint
+//
+// Handles are only meaningful to the endpoint that produced them, and may be reused for new
+// connections after a [`QuinnEventKind::ConnectionDrained`] event has been reported for them.
+typedef uint64_t QuinnConnectionHandle;
+
+// A UDP socket address, independent of the platform's `sockaddr` layout
+typedef struct QuinnSocketAddr {
+  // Which kind of IP address `ip` holds
+  QuinnAddressFamily family;
+  // The IP address in network byte order
+  uint8_t ip[16];
+  // The UDP port in host byte order
+  uint16_t port;
+} QuinnSocketAddr;
+
+// Something which happened on a connection that the application may need to react to
+typedef struct QuinnEvent {
+  // What happened
+  QuinnEventKind kind;
+  // The connection concerned
+  QuinnConnectionHandle connection;
+  // The stream concerned, for stream events
+  uint64_t stream;
+  // The direction of streams concerned, for `StreamOpened` and `StreamAvailable`
+  QuinnDir dir;
+  // An application error code, for `StreamStopped` and `ConnectionLost`
+  uint64_t error_code;
+} QuinnEvent;
+
+// Construct a transport configuration with default values
+QuinnTransportConfig *quinn_transport_config_new(void);
+
+// Release a transport configuration which was not passed to a server or client configuration
+void quinn_transport_config_free(QuinnTransportConfig *config);
+
+// Close connections after `ms` milliseconds without activity, or never if zero
+QuinnResult quinn_transport_config_max_idle_timeout(QuinnTransportConfig *config, uint64_t ms);
+
+// Abandon connections whose handshake hasn't completed after `ms` milliseconds, or never if zero
+void quinn_transport_config_handshake_timeout(QuinnTransportConfig *config, uint64_t ms);
+
+// Limit the number of bidirectional streams the peer may have open concurrently
+QuinnResult quinn_transport_config_stream_window_bidi(QuinnTransportConfig *config, uint64_t value);
+
+// Limit the number of unidirectional streams the peer may have open concurrently
+QuinnResult quinn_transport_config_stream_window_uni(QuinnTransportConfig *config, uint64_t value);
+
+// Limit the number of bytes the peer may send on a single stream before it is read
+QuinnResult quinn_transport_config_stream_receive_window(QuinnTransportConfig *config,
+                                                         uint64_t value);
+
+// Send keep-alive packets every `ms` milliseconds, or never if zero
+void quinn_transport_config_keep_alive_interval(QuinnTransportConfig *config, uint64_t ms);
+
+// Buffer up to `size` bytes of incoming datagrams, or disable datagram support if zero
+void quinn_transport_config_datagram_receive_buffer_size(QuinnTransportConfig *config, size_t size);
+
+// Construct a server configuration presenting a single DER-encoded certificate
+//
+// `key` must be a DER-encoded PKCS#8 private key for the certificate. On success, the new
+// configuration is written to `out`.
+QuinnResult quinn_server_config_new(const uint8_t *cert,
+                                    size_t cert_len,
+                                    const uint8_t *key,
+                                    size_t key_len,
+                                    QuinnServerConfig **out);
+
+// Release a server configuration
+//
+// Endpoints keep their own reference to the configuration they were constructed with, so this
+// may be called as soon as the endpoints have been created.
+void quinn_server_config_free(QuinnServerConfig *config);
+
+// Replace the transport configuration used for incoming connections
+//
+// Takes ownership of `transport`, which must not be used or freed afterwards.
+void quinn_server_config_set_transport(QuinnServerConfig *config, QuinnTransportConfig *transport);
+
+// Construct a client configuration with the platform's default trust anchors
+QuinnClientConfig *quinn_client_config_new(void);
+
+// Release a client configuration
+void quinn_client_config_free(QuinnClientConfig *config);
+
+// Trust servers presenting certificates issued by the DER-encoded certificate `cert`
+QuinnResult quinn_client_config_add_certificate_authority(QuinnClientConfig *config,
+                                                          const uint8_t *cert,
+                                                          size_t cert_len);
+
+// Replace the transport configuration used for outgoing connections
+//
+// Takes ownership of `transport`, which must not be used or freed afterwards.
+void quinn_client_config_set_transport(QuinnClientConfig *config, QuinnTransportConfig *transport);
+
+// Construct an endpoint
+//
+// If `server_config` is non-null, the endpoint accepts incoming connections using a copy of it.
+QuinnEndpoint *quinn_endpoint_new(const QuinnServerConfig *server_config);
+
+// Release an endpoint and all of its connections, without notifying peers
+void quinn_endpoint_free(QuinnEndpoint *endpoint);
+
+// Initiate a connection to `remote`, identifying the server as `server_name`
+//
+// `server_name` must be a NUL-terminated UTF-8 string. On success, the handle of the new
+// connection is written to `out`.
+QuinnResult quinn_endpoint_connect(QuinnEndpoint *endpoint,
+                                   const QuinnClientConfig *config,
+                                   const QuinnSocketAddr *remote,
+                                   const char *server_name,
+                                   QuinnConnectionHandle *out);
+
+// Process a UDP datagram received from `remote` at time `now`
+//
+// `ecn` holds the datagram's Explicit Congestion Notification bits, i.e. the low two bits of the
+// IP TOS or traffic class field, or zero if unknown.
+void quinn_endpoint_handle(QuinnEndpoint *endpoint,
+                           uint64_t now,
+                           const QuinnSocketAddr *remote,
+                           uint8_t ecn,
+                           const uint8_t *data,
+                           size_t len);
+
+// Write the next UDP datagram to be sent into `buf`
+//
+// Returns `Empty` if there is nothing to send, and `BufferTooSmall` if `buf_len` is insufficient
+// for the next datagram. On success, the datagram's length, destination and ECN bits are written
+// to `out_len`, `out_destination` and `out_ecn` respectively.
+QuinnResult quinn_endpoint_poll_transmit(QuinnEndpoint *endpoint,
+                                         uint64_t now,
+                                         uint8_t *buf,
+                                         size_t buf_len,
+                                         size_t *out_len,
+                                         QuinnSocketAddr *out_destination,
+                                         uint8_t *out_ecn);
+
+// Determine when [`quinn_endpoint_handle_timeout`] must next be called
+//
+// Returns `Empty` if no timer is armed. Otherwise, the deadline is written to `out`. Must be
+// called again after any other operation on the endpoint, which may change the deadline.
+QuinnResult quinn_endpoint_poll_timeout(QuinnEndpoint *endpoint, uint64_t *out);
+
+// Process timers which have expired as of `now`
+void quinn_endpoint_handle_timeout(QuinnEndpoint *endpoint, uint64_t now);
+
+// Retrieve the next event that occurred on any of the endpoint's connections
+//
+// Returns `Empty` if there are no events. Otherwise, the event is written to `out`.
+QuinnResult quinn_endpoint_poll_event(QuinnEndpoint *endpoint, QuinnEvent *out);
+
+// Close a connection immediately, sending `error_code` and `reason` to the peer
+//
+// Pending writes are abandoned. The connection is drained, and its handle released, once
+// `ConnectionDrained` is reported.
+QuinnResult quinn_connection_close(QuinnEndpoint *endpoint,
+                                   QuinnConnectionHandle connection,
+                                   uint64_t now,
+                                   uint64_t error_code,
+                                   const uint8_t *reason,
+                                   size_t reason_len);
+
+// Determine the address of the connection's peer, writing it to `out`
+QuinnResult quinn_connection_remote_address(QuinnEndpoint *endpoint,
+                                            QuinnConnectionHandle connection,
+                                            QuinnSocketAddr *out);
+
+// Open a new stream with direction `dir`, writing its ID to `out`
+//
+// Returns `Blocked` if the peer does not currently allow more streams to be opened, in which case
+// a `StreamAvailable` event will be reported once it does.
+QuinnResult quinn_stream_open(QuinnEndpoint *endpoint,
+                              QuinnConnectionHandle connection,
+                              QuinnDir dir,
+                              uint64_t *out);
+
+// Accept the next stream with direction `dir` opened by the peer, writing its ID to `out`
+//
+// Returns `Empty` if no such stream is waiting to be accepted.
+QuinnResult quinn_stream_accept(QuinnEndpoint *endpoint,
+                                QuinnConnectionHandle connection,
+                                QuinnDir dir,
+                                uint64_t *out);
+
+// Write data to a stream, writing the number of bytes accepted to `out_written`
+//
+// Returns `Blocked` if no data could be accepted, in which case a `StreamWritable` event will be
+// reported once more may be.
+QuinnResult quinn_stream_write(QuinnEndpoint *endpoint,
+                               QuinnConnectionHandle connection,
+                               uint64_t stream,
+                               const uint8_t *data,
+                               size_t len,
+                               size_t *out_written);
+
+// Indicate that no more data will be written to a stream
+//
+// A `StreamFinished` event will be reported once the peer has acknowledged all of its data.
+QuinnResult quinn_stream_finish(QuinnEndpoint *endpoint,
+                                QuinnConnectionHandle connection,
+                                uint64_t stream);
+
+// Abandon transmitting data on a stream, sending `error_code` to the peer
+QuinnResult quinn_stream_reset(QuinnEndpoint *endpoint,
+                               QuinnConnectionHandle connection,
+                               uint64_t stream,
+                               uint64_t error_code);
+
+// Read data from a stream into `buf`, writing the number of bytes read to `out_read`
+//
+// Returns `Blocked` if no data is currently available, in which case a `StreamReadable` event will
+// be reported once it is, and `Finished` once the peer has finished the stream and all of its
+// data has been read.
+QuinnResult quinn_stream_read(QuinnEndpoint *endpoint,
+                              QuinnConnectionHandle connection,
+                              uint64_t stream,
+                              uint8_t *buf,
+                              size_t buf_len,
+                              size_t *out_read);
+
+// Ask the peer to stop sending on a stream, discarding any data not yet read
+QuinnResult quinn_stream_stop(QuinnEndpoint *endpoint,
+                              QuinnConnectionHandle connection,
+                              uint64_t stream,
+                              uint64_t error_code);
+
+// Queue an unreliable, unordered datagram for transmission
+QuinnResult quinn_datagram_send(QuinnEndpoint *endpoint,
+                                QuinnConnectionHandle connection,
+                                const uint8_t *data,
+                                size_t len);
+
+// Receive an application datagram into `buf`, writing its length to `out_len`
+//
+// Returns `Empty` if no datagrams are waiting, and `BufferTooSmall` if `buf_len` is insufficient
+// for the next datagram.
+QuinnResult quinn_datagram_recv(QuinnEndpoint *endpoint,
+                                QuinnConnectionHandle connection,
+                                uint8_t *buf,
+                                size_t buf_len,
+                                size_t *out_len);
+
+#endif /* QUINN_H */
@@ -0,0 +1,216 @@
+use std::{sync::Arc, time::Duration};
+
+use proto::{
+    Certificate, CertificateChain, ClientConfig, PrivateKey, ServerConfig, TransportConfig,
+};
+
+use crate::{bytes, QuinnResult};
+
+/// Parameters governing the core QUIC state machine, shared by clients and servers
+pub struct QuinnTransportConfig(pub(crate) TransportConfig);
+
+/// Parameters governing incoming connections
+pub struct QuinnServerConfig(pub(crate) ServerConfig);
+
+/// Parameters governing outgoing connections
+pub struct QuinnClientConfig(pub(crate) ClientConfig);
+
+/// Construct a transport configuration with default values
+#[no_mangle]
+pub extern "C" fn quinn_transport_config_new() -> *mut QuinnTransportConfig {
+    Box::into_raw(Box::new(QuinnTransportConfig(TransportConfig::default())))
+}
+
+/// Release a transport configuration which was not passed to a server or client configuration
+#[no_mangle]
+pub unsafe extern "C" fn quinn_transport_config_free(config: *mut QuinnTransportConfig) {
+    if !config.is_null() {
+        drop(Box::from_raw(config));
+    }
+}
+
+/// Close connections after `ms` milliseconds without activity, or never if zero
+#[no_mangle]
+pub unsafe extern "C" fn quinn_transport_config_max_idle_timeout(
+    config: *mut QuinnTransportConfig,
+    ms: u64,
+) -> QuinnResult {
+    let timeout = if ms == 0 {
+        None
+    } else {
+        Some(Duration::from_millis(ms))
+    };
+    match (*config).0.max_idle_timeout(timeout) {
+        Ok(_) => QuinnResult::Ok,
+        Err(_) => QuinnResult::InvalidConfig,
+    }
+}
+
+/// Abandon connections whose handshake hasn't completed after `ms` milliseconds, or never if zero
+#[no_mangle]
+pub unsafe extern "C" fn quinn_transport_config_handshake_timeout(
+    config: *mut QuinnTransportConfig,
+    ms: u64,
+) {
+    let timeout = if ms == 0 {
+        None
+    } else {
+        Some(Duration::from_millis(ms))
+    };
+    (*config).0.handshake_timeout(timeout);
+}
+
+/// Limit the number of bidirectional streams the peer may have open concurrently
+#[no_mangle]
+pub unsafe extern "C" fn quinn_transport_config_stream_window_bidi(
+    config: *mut QuinnTransportConfig,
+    value: u64,
+) -> QuinnResult {
+    match (*config).0.stream_window_bidi(value) {
+        Ok(_) => QuinnResult::Ok,
+        Err(_) => QuinnResult::InvalidConfig,
+    }
+}
+
+/// Limit the number of unidirectional streams the peer may have open concurrently
+#[no_mangle]
+pub unsafe extern "C" fn quinn_transport_config_stream_window_uni(
+    config: *mut QuinnTransportConfig,
+    value: u64,
+) -> QuinnResult {
+    match (*config).0.stream_window_uni(value) {
+        Ok(_) => QuinnResult::Ok,
+        Err(_) => QuinnResult::InvalidConfig,
+    }
+}
+
+/// Limit the number of bytes the peer may send on a single stream before it is read
+#[no_mangle]
+pub unsafe extern "C" fn quinn_transport_config_stream_receive_window(
+    config: *mut QuinnTransportConfig,
+    value: u64,
+) -> QuinnResult {
+    match (*config).0.stream_receive_window(value) {
+        Ok(_) => QuinnResult::Ok,
+        Err(_) => QuinnResult::InvalidConfig,
+    }
+}
+
+/// Send keep-alive packets every `ms` milliseconds, or never if zero
+#[no_mangle]
+pub unsafe extern "C" fn quinn_transport_config_keep_alive_interval(
+    config: *mut QuinnTransportConfig,
+    ms: u64,
+) {
+    let interval = if ms == 0 {
+        None
+    } else {
+        Some(Duration::from_millis(ms))
+    };
+    (*config).0.keep_alive_interval(interval);
+}
+
+/// Buffer up to `size` bytes of incoming datagrams, or disable datagram support if zero
+#[no_mangle]
+pub unsafe extern "C" fn quinn_transport_config_datagram_receive_buffer_size(
+    config: *mut QuinnTransportConfig,
+    size: usize,
+) {
+    let size = if size == 0 { None } else { Some(size) };
+    (*config).0.datagram_receive_buffer_size(size);
+}
+
+/// Construct a server configuration presenting a single DER-encoded certificate
+///
+/// `key` must be a DER-encoded PKCS#8 private key for the certificate. On success, the new
+/// configuration is written to `out`.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_server_config_new(
+    cert: *const u8,
+    cert_len: usize,
+    key: *const u8,
+    key_len: usize,
+    out: *mut *mut QuinnServerConfig,
+) -> QuinnResult {
+    let cert = match Certificate::from_der(bytes(cert, cert_len)) {
+        Ok(x) => x,
+        Err(_) => return QuinnResult::InvalidCertificate,
+    };
+    let key = match PrivateKey::from_der(bytes(key, key_len)) {
+        Ok(x) => x,
+        Err(_) => return QuinnResult::InvalidCertificate,
+    };
+    let mut config = ServerConfig::default();
+    if config
+        .certificate(CertificateChain::from_certs(vec![cert]), key)
+        .is_err()
+    {
+        return QuinnResult::InvalidCertificate;
+    }
+    *out = Box::into_raw(Box::new(QuinnServerConfig(config)));
+    QuinnResult::Ok
+}
+
+/// Release a server configuration
+///
+/// Endpoints keep their own reference to the configuration they were constructed with, so this
+/// may be called as soon as the endpoints have been created.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_server_config_free(config: *mut QuinnServerConfig) {
+    if !config.is_null() {
+        drop(Box::from_raw(config));
+    }
+}
+
+/// Replace the transport configuration used for incoming connections
+///
+/// Takes ownership of `transport`, which must not be used or freed afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_server_config_set_transport(
+    config: *mut QuinnServerConfig,
+    transport: *mut QuinnTransportConfig,
+) {
+    (*config).0.transport = Arc::new(Box::from_raw(transport).0);
+}
+
+/// Construct a client configuration with the platform's default trust anchors
+#[no_mangle]
+pub extern "C" fn quinn_client_config_new() -> *mut QuinnClientConfig {
+    Box::into_raw(Box::new(QuinnClientConfig(ClientConfig::default())))
+}
+
+/// Release a client configuration
+#[no_mangle]
+pub unsafe extern "C" fn quinn_client_config_free(config: *mut QuinnClientConfig) {
+    if !config.is_null() {
+        drop(Box::from_raw(config));
+    }
+}
+
+/// Trust servers presenting certificates issued by the DER-encoded certificate `cert`
+#[no_mangle]
+pub unsafe extern "C" fn quinn_client_config_add_certificate_authority(
+    config: *mut QuinnClientConfig,
+    cert: *const u8,
+    cert_len: usize,
+) -> QuinnResult {
+    let cert = match Certificate::from_der(bytes(cert, cert_len)) {
+        Ok(x) => x,
+        Err(_) => return QuinnResult::InvalidCertificate,
+    };
+    match (*config).0.add_certificate_authority(cert) {
+        Ok(_) => QuinnResult::Ok,
+        Err(_) => QuinnResult::InvalidCertificate,
+    }
+}
+
+/// Replace the transport configuration used for outgoing connections
+///
+/// Takes ownership of `transport`, which must not be used or freed afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_client_config_set_transport(
+    config: *mut QuinnClientConfig,
+    transport: *mut QuinnTransportConfig,
+) {
+    (*config).0.transport = Arc::new(Box::from_raw(transport).0);
+}
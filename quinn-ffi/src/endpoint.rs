@@ -0,0 +1,699 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::CStr,
+    os::raw::c_char,
+    ptr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::{Bytes, BytesMut};
+use proto::{
+    ConnectionError, ConnectionHandle, DatagramEvent, EcnCodepoint, EndpointConfig, Event,
+    FinishError, ReadError, SendDatagramError, StreamEvent, StreamId, Transmit, WriteError,
+};
+
+use crate::{
+    bytes, bytes_mut, varint, QuinnClientConfig, QuinnDir, QuinnResult, QuinnServerConfig,
+    QuinnSocketAddr,
+};
+
+/// Identifies a connection within its endpoint
+///
+/// Handles are only meaningful to the endpoint that produced them, and may be reused for new
+/// connections after a [`QuinnEventKind::ConnectionDrained`] event has been reported for them.
+pub type QuinnConnectionHandle = u64;
+
+/// A QUIC endpoint and the connections it hosts
+///
+/// An endpoint may act as both client and server for different connections. It performs no I/O of
+/// its own; see the crate documentation for how to drive it.
+pub struct QuinnEndpoint {
+    inner: proto::Endpoint,
+    connections: HashMap<ConnectionHandle, Connection>,
+    events: VecDeque<QuinnEvent>,
+    /// Transmit which did not fit in the buffer supplied by the application
+    pending_transmit: Option<Transmit>,
+    /// Origin of the application's clock
+    epoch: Instant,
+}
+
+struct Connection {
+    inner: proto::Connection,
+    /// Datagram which did not fit in the buffer supplied by the application
+    pending_datagram: Option<Bytes>,
+}
+
+/// Kinds of [`QuinnEvent`]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuinnEventKind {
+    /// A client has initiated a new connection to this endpoint
+    ConnectionIncoming,
+    /// The connection's handshake completed successfully
+    Connected,
+    /// The connection was closed by either side, or encountered an error
+    ///
+    /// `error_code` carries the peer's error code if it closed the connection, and is zero
+    /// otherwise. The connection handle remains valid until it is drained.
+    ConnectionLost,
+    /// The connection has been fully torn down, and its handle is no longer valid
+    ConnectionDrained,
+    /// The peer opened one or more streams of direction `dir`, which can be accepted
+    StreamOpened,
+    /// `stream` has data or errors waiting to be read
+    StreamReadable,
+    /// `stream` was write-blocked and may now accept more data
+    StreamWritable,
+    /// `stream` has been finished and all of its data acknowledged by the peer
+    StreamFinished,
+    /// The peer asked us to stop sending on `stream` with `error_code`
+    StreamStopped,
+    /// At least one new stream of direction `dir` may be opened
+    StreamAvailable,
+    /// One or more application datagrams have been received
+    DatagramReceived,
+    /// The peer began sending from a new address, retrieved with
+    /// `quinn_connection_remote_address`
+    PathMigrated,
+    /// The server declined to process 0-RTT data, and streams opened before the handshake
+    /// completed were discarded
+    ZeroRttRejected,
+}
+
+/// Something which happened on a connection that the application may need to react to
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct QuinnEvent {
+    /// What happened
+    pub kind: QuinnEventKind,
+    /// The connection concerned
+    pub connection: QuinnConnectionHandle,
+    /// The stream concerned, for stream events
+    pub stream: u64,
+    /// The direction of streams concerned, for `StreamOpened` and `StreamAvailable`
+    pub dir: QuinnDir,
+    /// An application error code, for `StreamStopped` and `ConnectionLost`
+    pub error_code: u64,
+}
+
+impl QuinnEvent {
+    fn new(kind: QuinnEventKind, ch: ConnectionHandle) -> Self {
+        Self {
+            kind,
+            connection: ch.0 as u64,
+            stream: 0,
+            dir: QuinnDir::Bi,
+            error_code: 0,
+        }
+    }
+
+    fn stream(kind: QuinnEventKind, ch: ConnectionHandle, id: StreamId) -> Self {
+        Self {
+            stream: id.0,
+            ..Self::new(kind, ch)
+        }
+    }
+
+    fn dir(kind: QuinnEventKind, ch: ConnectionHandle, dir: proto::Dir) -> Self {
+        Self {
+            dir: dir.into(),
+            ..Self::new(kind, ch)
+        }
+    }
+}
+
+impl QuinnEndpoint {
+    fn now(&self, us: u64) -> Instant {
+        self.epoch + Duration::from_micros(us)
+    }
+
+    fn connection(&mut self, ch: QuinnConnectionHandle) -> Result<&mut Connection, QuinnResult> {
+        self.connections
+            .get_mut(&ConnectionHandle(ch as usize))
+            .ok_or(QuinnResult::UnknownConnection)
+    }
+
+    /// Exchange events between a connection and the endpoint, and collect application events
+    fn drive(&mut self, ch: ConnectionHandle) {
+        let conn = match self.connections.get_mut(&ch) {
+            Some(x) => x,
+            None => return,
+        };
+        while let Some(event) = conn.inner.poll_endpoint_events() {
+            if let Some(event) = self.inner.handle_event(ch, event) {
+                conn.inner.handle_event(event);
+            }
+        }
+        while let Some(event) = conn.inner.poll() {
+            use QuinnEventKind::*;
+            self.events.push_back(match event {
+                Event::HandshakeDataReady => continue,
+                Event::Connected => QuinnEvent::new(Connected, ch),
+                Event::ConnectionLost { reason } => QuinnEvent {
+                    error_code: match reason {
+                        ConnectionError::ApplicationClosed(close) => close.error_code.into(),
+                        _ => 0,
+                    },
+                    ..QuinnEvent::new(ConnectionLost, ch)
+                },
+                Event::Stream(StreamEvent::Opened { dir }) => {
+                    QuinnEvent::dir(StreamOpened, ch, dir)
+                }
+                Event::Stream(StreamEvent::Readable { id }) => {
+                    QuinnEvent::stream(StreamReadable, ch, id)
+                }
+                Event::Stream(StreamEvent::Writable { id }) => {
+                    QuinnEvent::stream(StreamWritable, ch, id)
+                }
+                Event::Stream(StreamEvent::Finished { id }) => {
+                    QuinnEvent::stream(StreamFinished, ch, id)
+                }
+                Event::Stream(StreamEvent::Stopped { id, error_code }) => QuinnEvent {
+                    error_code: error_code.into(),
+                    ..QuinnEvent::stream(StreamStopped, ch, id)
+                },
+                Event::Stream(StreamEvent::Available { dir }) => {
+                    QuinnEvent::dir(StreamAvailable, ch, dir)
+                }
+                Event::DatagramReceived => QuinnEvent::new(DatagramReceived, ch),
+                Event::PathMigrated { .. } => QuinnEvent::new(PathMigrated, ch),
+                Event::ZeroRttRejected => QuinnEvent::new(ZeroRttRejected, ch),
+                // Extension frame types cannot be registered through the C API
+                Event::KeysUpdated { .. }
+                | Event::ConnectionIdRotated
+                | Event::Congested { .. }
+                | Event::ExtensionFrameReceived => continue,
+            });
+        }
+        if conn.inner.is_drained() {
+            self.connections.remove(&ch);
+            self.events
+                .push_back(QuinnEvent::new(QuinnEventKind::ConnectionDrained, ch));
+        }
+    }
+
+    fn drive_all(&mut self) {
+        let handles = self.connections.keys().cloned().collect::<Vec<_>>();
+        for ch in handles {
+            self.drive(ch);
+        }
+    }
+
+    fn poll_transmit(&mut self, now: Instant) -> Option<Transmit> {
+        if let Some(t) = self.pending_transmit.take() {
+            return Some(t);
+        }
+        if let Some(t) = self.inner.poll_transmit() {
+            return Some(t);
+        }
+        let handles = self.connections.keys().cloned().collect::<Vec<_>>();
+        for ch in handles {
+            let t = self
+                .connections
+                .get_mut(&ch)
+                .unwrap()
+                .inner
+                .poll_transmit(now);
+            // Sending may retire connection IDs or complete the connection's shutdown
+            self.drive(ch);
+            if t.is_some() {
+                return t;
+            }
+        }
+        None
+    }
+}
+
+/// Construct an endpoint
+///
+/// If `server_config` is non-null, the endpoint accepts incoming connections using a copy of it.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_endpoint_new(
+    server_config: *const QuinnServerConfig,
+) -> *mut QuinnEndpoint {
+    let server_config = if server_config.is_null() {
+        None
+    } else {
+        Some(Arc::new((*server_config).0.clone()))
+    };
+    Box::into_raw(Box::new(QuinnEndpoint {
+        inner: proto::Endpoint::new(Arc::new(EndpointConfig::default()), server_config),
+        connections: HashMap::new(),
+        events: VecDeque::new(),
+        pending_transmit: None,
+        epoch: Instant::now(),
+    }))
+}
+
+/// Release an endpoint and all of its connections, without notifying peers
+#[no_mangle]
+pub unsafe extern "C" fn quinn_endpoint_free(endpoint: *mut QuinnEndpoint) {
+    if !endpoint.is_null() {
+        drop(Box::from_raw(endpoint));
+    }
+}
+
+/// Initiate a connection to `remote`, identifying the server as `server_name`
+///
+/// `server_name` must be a NUL-terminated UTF-8 string. On success, the handle of the new
+/// connection is written to `out`.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_endpoint_connect(
+    endpoint: *mut QuinnEndpoint,
+    config: *const QuinnClientConfig,
+    remote: *const QuinnSocketAddr,
+    server_name: *const c_char,
+    out: *mut QuinnConnectionHandle,
+) -> QuinnResult {
+    let endpoint = &mut *endpoint;
+    let server_name = match CStr::from_ptr(server_name).to_str() {
+        Ok(x) => x,
+        Err(_) => return QuinnResult::InvalidArgument,
+    };
+    let (ch, conn) =
+        match endpoint
+            .inner
+            .connect((*config).0.clone(), (*remote).into(), server_name)
+        {
+            Ok(x) => x,
+            Err(_) => return QuinnResult::ConnectFailed,
+        };
+    endpoint.connections.insert(
+        ch,
+        Connection {
+            inner: conn,
+            pending_datagram: None,
+        },
+    );
+    *out = ch.0 as u64;
+    QuinnResult::Ok
+}
+
+/// Process a UDP datagram received from `remote` at time `now`
+///
+/// `ecn` holds the datagram's Explicit Congestion Notification bits, i.e. the low two bits of the
+/// IP TOS or traffic class field, or zero if unknown.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_endpoint_handle(
+    endpoint: *mut QuinnEndpoint,
+    now: u64,
+    remote: *const QuinnSocketAddr,
+    ecn: u8,
+    data: *const u8,
+    len: usize,
+) {
+    let endpoint = &mut *endpoint;
+    let now = endpoint.now(now);
+    let data = BytesMut::from(bytes(data, len));
+    let ecn = EcnCodepoint::from_bits(ecn);
+    match endpoint
+        .inner
+        .handle(now, (*remote).into(), None, ecn, data)
+    {
+        Some((ch, DatagramEvent::NewConnection(conn))) => {
+            endpoint.connections.insert(
+                ch,
+                Connection {
+                    inner: conn,
+                    pending_datagram: None,
+                },
+            );
+            endpoint
+                .events
+                .push_back(QuinnEvent::new(QuinnEventKind::ConnectionIncoming, ch));
+            endpoint.drive(ch);
+        }
+        Some((ch, DatagramEvent::ConnectionEvent(event))) => {
+            if let Some(conn) = endpoint.connections.get_mut(&ch) {
+                conn.inner.handle_event(event);
+            }
+            endpoint.drive(ch);
+        }
+        None => {}
+    }
+}
+
+/// Write the next UDP datagram to be sent into `buf`
+///
+/// Returns `Empty` if there is nothing to send, and `BufferTooSmall` if `buf_len` is insufficient
+/// for the next datagram. On success, the datagram's length, destination and ECN bits are written
+/// to `out_len`, `out_destination` and `out_ecn` respectively.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_endpoint_poll_transmit(
+    endpoint: *mut QuinnEndpoint,
+    now: u64,
+    buf: *mut u8,
+    buf_len: usize,
+    out_len: *mut usize,
+    out_destination: *mut QuinnSocketAddr,
+    out_ecn: *mut u8,
+) -> QuinnResult {
+    let endpoint = &mut *endpoint;
+    let now = endpoint.now(now);
+    let transmit = match endpoint.poll_transmit(now) {
+        Some(x) => x,
+        None => return QuinnResult::Empty,
+    };
+    if transmit.contents.len() > buf_len {
+        endpoint.pending_transmit = Some(transmit);
+        return QuinnResult::BufferTooSmall;
+    }
+    bytes_mut(buf, buf_len)[..transmit.contents.len()].copy_from_slice(&transmit.contents);
+    *out_len = transmit.contents.len();
+    *out_destination = transmit.destination.into();
+    *out_ecn = transmit.ecn.map_or(0, |x| x as u8);
+    QuinnResult::Ok
+}
+
+/// Determine when [`quinn_endpoint_handle_timeout`] must next be called
+///
+/// Returns `Empty` if no timer is armed. Otherwise, the deadline is written to `out`. Must be
+/// called again after any other operation on the endpoint, which may change the deadline.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_endpoint_poll_timeout(
+    endpoint: *mut QuinnEndpoint,
+    out: *mut u64,
+) -> QuinnResult {
+    let endpoint = &mut *endpoint;
+    let next = endpoint
+        .connections
+        .values_mut()
+        .filter_map(|conn| conn.inner.poll_timeout())
+        .min();
+    match next {
+        Some(t) => {
+            *out = t.saturating_duration_since(endpoint.epoch).as_micros() as u64;
+            QuinnResult::Ok
+        }
+        None => QuinnResult::Empty,
+    }
+}
+
+/// Process timers which have expired as of `now`
+#[no_mangle]
+pub unsafe extern "C" fn quinn_endpoint_handle_timeout(endpoint: *mut QuinnEndpoint, now: u64) {
+    let endpoint = &mut *endpoint;
+    let now = endpoint.now(now);
+    let handles = endpoint.connections.keys().cloned().collect::<Vec<_>>();
+    for ch in handles {
+        let conn = endpoint.connections.get_mut(&ch).unwrap();
+        match conn.inner.poll_timeout() {
+            Some(t) if t <= now => {}
+            _ => continue,
+        }
+        conn.inner.handle_timeout(now);
+        endpoint.drive(ch);
+    }
+}
+
+/// Retrieve the next event that occurred on any of the endpoint's connections
+///
+/// Returns `Empty` if there are no events. Otherwise, the event is written to `out`.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_endpoint_poll_event(
+    endpoint: *mut QuinnEndpoint,
+    out: *mut QuinnEvent,
+) -> QuinnResult {
+    let endpoint = &mut *endpoint;
+    if endpoint.events.is_empty() {
+        endpoint.drive_all();
+    }
+    match endpoint.events.pop_front() {
+        Some(event) => {
+            *out = event;
+            QuinnResult::Ok
+        }
+        None => QuinnResult::Empty,
+    }
+}
+
+/// Close a connection immediately, sending `error_code` and `reason` to the peer
+///
+/// Pending writes are abandoned. The connection is drained, and its handle released, once
+/// `ConnectionDrained` is reported.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_connection_close(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    now: u64,
+    error_code: u64,
+    reason: *const u8,
+    reason_len: usize,
+) -> QuinnResult {
+    let endpoint = &mut *endpoint;
+    let now = endpoint.now(now);
+    let error_code = match varint(error_code) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    let reason = Bytes::copy_from_slice(bytes(reason, reason_len));
+    match endpoint.connection(connection) {
+        Ok(conn) => conn.inner.close(now, error_code, reason),
+        Err(e) => return e,
+    }
+    QuinnResult::Ok
+}
+
+/// Determine the address of the connection's peer, writing it to `out`
+#[no_mangle]
+pub unsafe extern "C" fn quinn_connection_remote_address(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    out: *mut QuinnSocketAddr,
+) -> QuinnResult {
+    match (*endpoint).connection(connection) {
+        Ok(conn) => {
+            *out = conn.inner.remote_address().into();
+            QuinnResult::Ok
+        }
+        Err(e) => e,
+    }
+}
+
+/// Open a new stream with direction `dir`, writing its ID to `out`
+///
+/// Returns `Blocked` if the peer does not currently allow more streams to be opened, in which case
+/// a `StreamAvailable` event will be reported once it does.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_stream_open(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    dir: QuinnDir,
+    out: *mut u64,
+) -> QuinnResult {
+    match (*endpoint).connection(connection) {
+        Ok(conn) => match conn.inner.open(dir.into()) {
+            Some(id) => {
+                *out = id.0;
+                QuinnResult::Ok
+            }
+            None => QuinnResult::Blocked,
+        },
+        Err(e) => e,
+    }
+}
+
+/// Accept the next stream with direction `dir` opened by the peer, writing its ID to `out`
+///
+/// Returns `Empty` if no such stream is waiting to be accepted.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_stream_accept(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    dir: QuinnDir,
+    out: *mut u64,
+) -> QuinnResult {
+    match (*endpoint).connection(connection) {
+        Ok(conn) => match conn.inner.accept(dir.into()) {
+            Some(id) => {
+                *out = id.0;
+                QuinnResult::Ok
+            }
+            None => QuinnResult::Empty,
+        },
+        Err(e) => e,
+    }
+}
+
+/// Write data to a stream, writing the number of bytes accepted to `out_written`
+///
+/// Returns `Blocked` if no data could be accepted, in which case a `StreamWritable` event will be
+/// reported once more may be.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_stream_write(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    stream: u64,
+    data: *const u8,
+    len: usize,
+    out_written: *mut usize,
+) -> QuinnResult {
+    let conn = match (*endpoint).connection(connection) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    match conn.inner.write(StreamId(stream), bytes(data, len)) {
+        Ok(n) => {
+            *out_written = n;
+            QuinnResult::Ok
+        }
+        Err(WriteError::Blocked) => QuinnResult::Blocked,
+        Err(WriteError::Stopped(_)) => QuinnResult::Stopped,
+        Err(WriteError::UnknownStream) => QuinnResult::UnknownStream,
+    }
+}
+
+/// Indicate that no more data will be written to a stream
+///
+/// A `StreamFinished` event will be reported once the peer has acknowledged all of its data.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_stream_finish(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    stream: u64,
+) -> QuinnResult {
+    let conn = match (*endpoint).connection(connection) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    match conn.inner.finish(StreamId(stream)) {
+        Ok(()) => QuinnResult::Ok,
+        Err(FinishError::Stopped(_)) => QuinnResult::Stopped,
+        Err(FinishError::UnknownStream) => QuinnResult::UnknownStream,
+    }
+}
+
+/// Abandon transmitting data on a stream, sending `error_code` to the peer
+#[no_mangle]
+pub unsafe extern "C" fn quinn_stream_reset(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    stream: u64,
+    error_code: u64,
+) -> QuinnResult {
+    let error_code = match varint(error_code) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    match (*endpoint).connection(connection) {
+        Ok(conn) => match conn.inner.reset(StreamId(stream), error_code) {
+            Ok(()) => QuinnResult::Ok,
+            Err(_) => QuinnResult::UnknownStream,
+        },
+        Err(e) => e,
+    }
+}
+
+/// Read data from a stream into `buf`, writing the number of bytes read to `out_read`
+///
+/// Returns `Blocked` if no data is currently available, in which case a `StreamReadable` event will
+/// be reported once it is, and `Finished` once the peer has finished the stream and all of its
+/// data has been read.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_stream_read(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    stream: u64,
+    buf: *mut u8,
+    buf_len: usize,
+    out_read: *mut usize,
+) -> QuinnResult {
+    let conn = match (*endpoint).connection(connection) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    match conn.inner.read(StreamId(stream), bytes_mut(buf, buf_len)) {
+        Ok(Some(n)) => {
+            *out_read = n;
+            QuinnResult::Ok
+        }
+        Ok(None) => QuinnResult::Finished,
+        Err(ReadError::Blocked) => QuinnResult::Blocked,
+        Err(ReadError::Reset(_)) => QuinnResult::Reset,
+        Err(ReadError::UnknownStream) | Err(ReadError::IllegalOrderedRead) => {
+            QuinnResult::UnknownStream
+        }
+    }
+}
+
+/// Ask the peer to stop sending on a stream, discarding any data not yet read
+#[no_mangle]
+pub unsafe extern "C" fn quinn_stream_stop(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    stream: u64,
+    error_code: u64,
+) -> QuinnResult {
+    let error_code = match varint(error_code) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    match (*endpoint).connection(connection) {
+        Ok(conn) => match conn.inner.stop(StreamId(stream), error_code) {
+            Ok(()) => QuinnResult::Ok,
+            Err(_) => QuinnResult::UnknownStream,
+        },
+        Err(e) => e,
+    }
+}
+
+/// Queue an unreliable, unordered datagram for transmission
+#[no_mangle]
+pub unsafe extern "C" fn quinn_datagram_send(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    data: *const u8,
+    len: usize,
+) -> QuinnResult {
+    let conn = match (*endpoint).connection(connection) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    match conn
+        .inner
+        .send_datagram(Bytes::copy_from_slice(bytes(data, len)))
+    {
+        Ok(()) => QuinnResult::Ok,
+        Err(SendDatagramError::UnsupportedByPeer) | Err(SendDatagramError::Disabled) => {
+            QuinnResult::DatagramsUnsupported
+        }
+        Err(SendDatagramError::TooLarge) => QuinnResult::DatagramTooLarge,
+    }
+}
+
+/// Receive an application datagram into `buf`, writing its length to `out_len`
+///
+/// Returns `Empty` if no datagrams are waiting, and `BufferTooSmall` if `buf_len` is insufficient
+/// for the next datagram.
+#[no_mangle]
+pub unsafe extern "C" fn quinn_datagram_recv(
+    endpoint: *mut QuinnEndpoint,
+    connection: QuinnConnectionHandle,
+    buf: *mut u8,
+    buf_len: usize,
+    out_len: *mut usize,
+) -> QuinnResult {
+    let conn = match (*endpoint).connection(connection) {
+        Ok(x) => x,
+        Err(e) => return e,
+    };
+    let datagram = match conn
+        .pending_datagram
+        .take()
+        .or_else(|| conn.inner.recv_datagram())
+    {
+        Some(x) => x,
+        None => return QuinnResult::Empty,
+    };
+    if datagram.len() > buf_len {
+        conn.pending_datagram = Some(datagram);
+        return QuinnResult::BufferTooSmall;
+    }
+    // `buf` may be null when both the buffer and the datagram are empty
+    if !datagram.is_empty() {
+        ptr::copy_nonoverlapping(datagram.as_ptr(), buf, datagram.len());
+    }
+    *out_len = datagram.len();
+    QuinnResult::Ok
+}
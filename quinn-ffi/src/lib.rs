@@ -0,0 +1,181 @@
+//! C bindings for quinn-proto
+//!
+//! This crate exposes the deterministic QUIC state machine of quinn-proto through an opaque-handle
+//! C API, allowing it to be embedded in an existing event loop without adopting an async runtime.
+//! The corresponding header is `include/quinn.h`, generated by cbindgen from this crate.
+//!
+//! A [`QuinnEndpoint`] owns all of its connections, which are referred to by handle. The
+//! application is responsible for all I/O and timekeeping:
+//!
+//! - Datagrams received from the network are passed to [`quinn_endpoint_handle`]
+//! - Datagrams to send are retrieved with [`quinn_endpoint_poll_transmit`] until it reports
+//!   [`QuinnResult::Empty`]
+//! - [`quinn_endpoint_poll_timeout`] reports when [`quinn_endpoint_handle_timeout`] must next be
+//!   called
+//! - [`quinn_endpoint_poll_event`] yields connection and stream events to react to
+//!
+//! Time is expressed as a number of microseconds since an arbitrary origin, which must be the
+//! same for every call on a given endpoint and must never go backwards.
+//!
+//! # Safety
+//!
+//! Unless documented otherwise, pointer arguments must be non-null, properly aligned and valid for
+//! the duration of the call, and buffers must be valid for the given length. Handles returned by
+//! `*_new` functions must be released exactly once with the corresponding `*_free` function.
+//! Endpoints are not thread-safe, and must not be used from multiple threads concurrently.
+#![warn(missing_docs)]
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    slice,
+};
+
+use proto::{Dir, VarInt};
+
+mod config;
+mod endpoint;
+
+pub use config::*;
+pub use endpoint::*;
+
+/// Outcome of an operation
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuinnResult {
+    /// The operation succeeded
+    Ok,
+    /// Nothing is currently available to be polled
+    Empty,
+    /// The operation cannot make progress until the peer grants more resources
+    Blocked,
+    /// The stream has been finished by the peer, and all of its data has been read
+    Finished,
+    /// An argument was null, out of range, or otherwise malformed
+    InvalidArgument,
+    /// The configuration could not be applied
+    InvalidConfig,
+    /// A certificate or private key could not be parsed or used
+    InvalidCertificate,
+    /// The supplied buffer is too small for the next datagram, which remains queued
+    BufferTooSmall,
+    /// The connection handle does not refer to a live connection
+    UnknownConnection,
+    /// The stream has not been opened, or has already been finished, reset or stopped
+    UnknownStream,
+    /// The peer asked us to stop sending on the stream
+    Stopped,
+    /// The peer abandoned transmitting data on the stream
+    Reset,
+    /// A new connection could not be initiated
+    ConnectFailed,
+    /// The peer does not support receiving datagrams, or datagram support is disabled locally
+    DatagramsUnsupported,
+    /// The datagram is larger than the connection can currently accommodate
+    DatagramTooLarge,
+}
+
+/// Directionality of a stream
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuinnDir {
+    /// Data flows in both directions
+    Bi,
+    /// Data flows only from the stream's initiator
+    Uni,
+}
+
+impl From<QuinnDir> for Dir {
+    fn from(x: QuinnDir) -> Self {
+        match x {
+            QuinnDir::Bi => Dir::Bi,
+            QuinnDir::Uni => Dir::Uni,
+        }
+    }
+}
+
+impl From<Dir> for QuinnDir {
+    fn from(x: Dir) -> Self {
+        match x {
+            Dir::Bi => QuinnDir::Bi,
+            Dir::Uni => QuinnDir::Uni,
+        }
+    }
+}
+
+/// Address family of a [`QuinnSocketAddr`]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuinnAddressFamily {
+    /// An IPv4 address, stored in the first four bytes of `ip`
+    Ipv4,
+    /// An IPv6 address
+    Ipv6,
+}
+
+/// A UDP socket address, independent of the platform's `sockaddr` layout
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct QuinnSocketAddr {
+    /// Which kind of IP address `ip` holds
+    pub family: QuinnAddressFamily,
+    /// The IP address in network byte order
+    pub ip: [u8; 16],
+    /// The UDP port in host byte order
+    pub port: u16,
+}
+
+impl From<QuinnSocketAddr> for SocketAddr {
+    fn from(x: QuinnSocketAddr) -> Self {
+        let ip = match x.family {
+            QuinnAddressFamily::Ipv4 => {
+                IpAddr::V4(Ipv4Addr::new(x.ip[0], x.ip[1], x.ip[2], x.ip[3]))
+            }
+            QuinnAddressFamily::Ipv6 => IpAddr::V6(Ipv6Addr::from(x.ip)),
+        };
+        SocketAddr::new(ip, x.port)
+    }
+}
+
+impl From<SocketAddr> for QuinnSocketAddr {
+    fn from(x: SocketAddr) -> Self {
+        let mut ip = [0; 16];
+        let family = match x.ip() {
+            IpAddr::V4(v4) => {
+                ip[..4].copy_from_slice(&v4.octets());
+                QuinnAddressFamily::Ipv4
+            }
+            IpAddr::V6(v6) => {
+                ip = v6.octets();
+                QuinnAddressFamily::Ipv6
+            }
+        };
+        Self {
+            family,
+            ip,
+            port: x.port(),
+        }
+    }
+}
+
+/// Borrow a caller-supplied buffer, tolerating null pointers for empty buffers
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
+
+/// Borrow a caller-supplied mutable buffer, tolerating null pointers for empty buffers
+unsafe fn bytes_mut<'a>(data: *mut u8, len: usize) -> &'a mut [u8] {
+    if len == 0 {
+        &mut []
+    } else {
+        slice::from_raw_parts_mut(data, len)
+    }
+}
+
+fn varint(x: u64) -> Result<VarInt, QuinnResult> {
+    VarInt::from_u64(x).map_err(|_| QuinnResult::InvalidArgument)
+}
@@ -0,0 +1,43 @@
+//! Builds and runs the C test programs against the static library
+#![cfg(unix)]
+
+use std::{env, fs, path::PathBuf, process::Command};
+
+#[test]
+fn in_memory() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // Test executables live in `target/<profile>/deps`, alongside the library built for them
+    let exe = env::current_exe().unwrap();
+    let deps_dir = exe.parent().unwrap();
+    let out_dir = deps_dir.parent().unwrap().join("quinn-ffi-tests");
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let cert_path = out_dir.join("cert.der");
+    let key_path = out_dir.join("key.der");
+    fs::write(&cert_path, cert.serialize_der().unwrap()).unwrap();
+    fs::write(&key_path, cert.serialize_private_key_der()).unwrap();
+
+    let program = out_dir.join("in_memory");
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".into()))
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/in_memory.c"))
+        .arg(deps_dir.join("libquinn_ffi.a"))
+        .args(["-lpthread", "-ldl", "-lm", "-o"].iter())
+        .arg(&program)
+        .status()
+        .expect("failed to run C compiler");
+    assert!(status.success(), "C compilation failed");
+
+    let output = Command::new(&program)
+        .arg(&cert_path)
+        .arg(&key_path)
+        .output()
+        .unwrap();
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.status.success());
+}
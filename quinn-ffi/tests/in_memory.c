@@ -0,0 +1,244 @@
+/* Drives a client and a server endpoint against each other in memory, with a simulated clock.
+ *
+ * Usage: in_memory <cert.der> <key.der>
+ *
+ * The client opens a bidirectional stream and sends a message, which the server echoes back
+ * before the client closes the connection. Exits with a non-zero status on failure. */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "quinn.h"
+
+#define CHECK(x)                                                                \
+    do {                                                                        \
+        if (!(x)) {                                                             \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #x); \
+            exit(1);                                                            \
+        }                                                                       \
+    } while (0)
+
+#define MESSAGE "hello from C"
+
+struct peer {
+    const char *name;
+    QuinnEndpoint *endpoint;
+    QuinnSocketAddr addr;
+    QuinnConnectionHandle connection;
+    int has_connection;
+    int drained;
+    uint64_t stream;
+    uint8_t received[1024];
+    size_t received_len;
+};
+
+static unsigned char *read_file(const char *path, size_t *len) {
+    FILE *f = fopen(path, "rb");
+    CHECK(f != NULL);
+    CHECK(fseek(f, 0, SEEK_END) == 0);
+    long size = ftell(f);
+    CHECK(size > 0);
+    rewind(f);
+    unsigned char *data = malloc((size_t)size);
+    CHECK(data != NULL);
+    CHECK(fread(data, 1, (size_t)size, f) == (size_t)size);
+    fclose(f);
+    *len = (size_t)size;
+    return data;
+}
+
+static QuinnSocketAddr ipv4(uint8_t a, uint8_t b, uint8_t c, uint8_t d, uint16_t port) {
+    QuinnSocketAddr addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.family = QUINN_ADDRESS_FAMILY_IPV4;
+    addr.ip[0] = a;
+    addr.ip[1] = b;
+    addr.ip[2] = c;
+    addr.ip[3] = d;
+    addr.port = port;
+    return addr;
+}
+
+static int same_addr(const QuinnSocketAddr *x, const QuinnSocketAddr *y) {
+    return x->family == y->family && memcmp(x->ip, y->ip, sizeof(x->ip)) == 0 &&
+           x->port == y->port;
+}
+
+/* Deliver every datagram `from` wants to send to `to`, returning how many were sent */
+static int flush(struct peer *from, struct peer *to, uint64_t now) {
+    uint8_t buf[1500];
+    size_t len;
+    QuinnSocketAddr destination;
+    uint8_t ecn;
+    int sent = 0;
+    for (;;) {
+        QuinnResult result = quinn_endpoint_poll_transmit(from->endpoint, now, buf, sizeof(buf),
+                                                          &len, &destination, &ecn);
+        if (result == QUINN_RESULT_EMPTY) {
+            return sent;
+        }
+        CHECK(result == QUINN_RESULT_OK);
+        CHECK(same_addr(&destination, &to->addr));
+        quinn_endpoint_handle(to->endpoint, now, &from->addr, ecn, buf, len);
+        sent++;
+    }
+}
+
+/* Read everything currently available on the peer's stream, returning 1 once it's finished */
+static int drain_stream(struct peer *p) {
+    for (;;) {
+        size_t n;
+        QuinnResult result =
+            quinn_stream_read(p->endpoint, p->connection, p->stream,
+                              p->received + p->received_len,
+                              sizeof(p->received) - p->received_len, &n);
+        if (result == QUINN_RESULT_FINISHED) {
+            return 1;
+        }
+        if (result == QUINN_RESULT_BLOCKED) {
+            return 0;
+        }
+        CHECK(result == QUINN_RESULT_OK);
+        p->received_len += n;
+    }
+}
+
+static void write_all(struct peer *p, const uint8_t *data, size_t len) {
+    size_t written;
+    CHECK(quinn_stream_write(p->endpoint, p->connection, p->stream, data, len, &written) ==
+          QUINN_RESULT_OK);
+    CHECK(written == len);
+    CHECK(quinn_stream_finish(p->endpoint, p->connection, p->stream) == QUINN_RESULT_OK);
+}
+
+static int handle_server_events(struct peer *server) {
+    QuinnEvent event;
+    int handled = 0;
+    while (quinn_endpoint_poll_event(server->endpoint, &event) == QUINN_RESULT_OK) {
+        handled++;
+        switch (event.kind) {
+        case QUINN_EVENT_KIND_CONNECTION_INCOMING:
+            CHECK(!server->has_connection);
+            server->connection = event.connection;
+            server->has_connection = 1;
+            break;
+        case QUINN_EVENT_KIND_STREAM_OPENED:
+            CHECK(event.dir == QUINN_DIR_BI);
+            CHECK(quinn_stream_accept(server->endpoint, server->connection, QUINN_DIR_BI,
+                                      &server->stream) == QUINN_RESULT_OK);
+            /* fall through */
+        case QUINN_EVENT_KIND_STREAM_READABLE:
+            if (drain_stream(server)) {
+                write_all(server, server->received, server->received_len);
+            }
+            break;
+        case QUINN_EVENT_KIND_CONNECTION_LOST:
+            CHECK(event.error_code == 42);
+            break;
+        case QUINN_EVENT_KIND_CONNECTION_DRAINED:
+            server->drained = 1;
+            break;
+        default:
+            break;
+        }
+    }
+    return handled;
+}
+
+static int handle_client_events(struct peer *client, uint64_t now) {
+    QuinnEvent event;
+    int handled = 0;
+    while (quinn_endpoint_poll_event(client->endpoint, &event) == QUINN_RESULT_OK) {
+        handled++;
+        CHECK(event.connection == client->connection);
+        switch (event.kind) {
+        case QUINN_EVENT_KIND_CONNECTED:
+            CHECK(quinn_stream_open(client->endpoint, client->connection, QUINN_DIR_BI,
+                                    &client->stream) == QUINN_RESULT_OK);
+            write_all(client, (const uint8_t *)MESSAGE, strlen(MESSAGE));
+            break;
+        case QUINN_EVENT_KIND_STREAM_READABLE:
+            if (drain_stream(client)) {
+                CHECK(client->received_len == strlen(MESSAGE));
+                CHECK(memcmp(client->received, MESSAGE, strlen(MESSAGE)) == 0);
+                CHECK(quinn_connection_close(client->endpoint, client->connection, now, 42,
+                                             (const uint8_t *)"done", 4) == QUINN_RESULT_OK);
+            }
+            break;
+        case QUINN_EVENT_KIND_CONNECTION_DRAINED:
+            client->drained = 1;
+            break;
+        default:
+            break;
+        }
+    }
+    return handled;
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 3);
+    size_t cert_len, key_len;
+    unsigned char *cert = read_file(argv[1], &cert_len);
+    unsigned char *key = read_file(argv[2], &key_len);
+
+    QuinnServerConfig *server_config;
+    CHECK(quinn_server_config_new(cert, cert_len, key, key_len, &server_config) ==
+          QUINN_RESULT_OK);
+    QuinnClientConfig *client_config = quinn_client_config_new();
+    CHECK(quinn_client_config_add_certificate_authority(client_config, cert, cert_len) ==
+          QUINN_RESULT_OK);
+    QuinnTransportConfig *transport = quinn_transport_config_new();
+    CHECK(quinn_transport_config_max_idle_timeout(transport, 10000) == QUINN_RESULT_OK);
+    quinn_client_config_set_transport(client_config, transport);
+
+    struct peer server = {0}, client = {0};
+    server.name = "server";
+    server.endpoint = quinn_endpoint_new(server_config);
+    server.addr = ipv4(10, 0, 0, 1, 4433);
+    quinn_server_config_free(server_config);
+    client.name = "client";
+    client.endpoint = quinn_endpoint_new(NULL);
+    client.addr = ipv4(10, 0, 0, 2, 50000);
+
+    CHECK(quinn_endpoint_connect(client.endpoint, client_config, &server.addr, "localhost",
+                                 &client.connection) == QUINN_RESULT_OK);
+    client.has_connection = 1;
+    quinn_client_config_free(client_config);
+
+    uint64_t now = 0;
+    int iterations;
+    for (iterations = 0; iterations < 10000 && !(client.drained && server.drained);
+         iterations++) {
+        int progress = flush(&client, &server, now) + flush(&server, &client, now);
+        progress += handle_server_events(&server) + handle_client_events(&client, now);
+        if (progress) {
+            continue;
+        }
+        /* Nothing else to do until a timer expires, so skip ahead to the earliest one */
+        uint64_t client_timeout, server_timeout, next = UINT64_MAX;
+        if (quinn_endpoint_poll_timeout(client.endpoint, &client_timeout) == QUINN_RESULT_OK) {
+            next = client_timeout;
+        }
+        if (quinn_endpoint_poll_timeout(server.endpoint, &server_timeout) == QUINN_RESULT_OK &&
+            server_timeout < next) {
+            next = server_timeout;
+        }
+        CHECK(next != UINT64_MAX);
+        if (next > now) {
+            now = next;
+        }
+        quinn_endpoint_handle_timeout(client.endpoint, now);
+        quinn_endpoint_handle_timeout(server.endpoint, now);
+    }
+    CHECK(client.drained && server.drained);
+    CHECK(server.received_len == strlen(MESSAGE));
+
+    quinn_endpoint_free(client.endpoint);
+    quinn_endpoint_free(server.endpoint);
+    free(cert);
+    free(key);
+    printf("ok: echoed %zu bytes in %d iterations, %llu us simulated\n", client.received_len,
+           iterations, (unsigned long long)now);
+    return 0;
+}
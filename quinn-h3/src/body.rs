@@ -14,11 +14,12 @@ use crate::{
     connection::ConnectionRef,
     frame::FrameStream,
     proto::{
-        frame::{HeadersFrame, HttpFrame},
+        frame::{HeadersFrame, HttpFrame, PushPromiseFrame},
+        priority::Prioritized,
         ErrorCode,
     },
     streams::Reset,
-    Error,
+    Error, Priority,
 };
 
 /// Simple body representation
@@ -82,6 +83,10 @@ pub struct RecvBody {
     stream_id: StreamId,
     recv: FrameStream,
     trailers: Option<HeadersFrame>,
+    /// The response this body belongs to, or answers on the server side
+    prioritized: Prioritized,
+    /// A PUSH_PROMISE frame received, being handled
+    promise: Option<PushPromiseFrame>,
 }
 
 impl RecvBody {
@@ -91,9 +96,15 @@ impl RecvBody {
             stream_id,
             recv,
             trailers: None,
+            prioritized: Prioritized::Request(stream_id.0),
+            promise: None,
         }
     }
 
+    pub(crate) fn set_prioritized(&mut self, prioritized: Prioritized) {
+        self.prioritized = prioritized;
+    }
+
     /// Convenience method to read the entire body in one call
     pub async fn read_to_end(&mut self) -> Result<Bytes, Error> {
         let mut body = BytesMut::with_capacity(10_240);
@@ -127,6 +138,15 @@ impl RecvBody {
         Ok(future::poll_fn(|cx| Pin::new(&mut me).poll_trailers(cx)).await?)
     }
 
+    /// Reprioritize the response
+    ///
+    /// On the client side, the server is sent the new priority of the response being received.
+    /// On the server side, this overrides the priority the client signaled for the response to
+    /// this request body's request.
+    pub fn set_priority(&self, priority: Priority) {
+        self.conn.set_priority(self.prioritized, priority);
+    }
+
     /// Cancel a request or response
     ///
     /// The peer will receive a request error with `REQUEST_CANCELLED` code.
@@ -148,9 +168,20 @@ impl HttpBody for RecvBody {
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
         loop {
+            if let Some(promise) = self.promise.as_ref() {
+                let handled = self.conn.poll_push_promise(cx, self.stream_id, promise);
+                if let Err(e) = ready!(handled) {
+                    return Poll::Ready(Some(Err(e)));
+                }
+                self.promise = None;
+            }
             return match ready!(Pin::new(&mut self.recv).poll_next(cx)) {
                 None => Poll::Ready(None),
                 Some(Ok(HttpFrame::Reserved)) => continue,
+                Some(Ok(HttpFrame::PushPromise(p))) => {
+                    self.promise = Some(p);
+                    continue;
+                }
                 Some(Ok(HttpFrame::Data(d))) => Poll::Ready(Some(Ok(d.payload))),
                 Some(Ok(HttpFrame::Headers(t))) => {
                     self.trailers = Some(t);
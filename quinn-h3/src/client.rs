@@ -14,6 +14,9 @@
 //! Body and trailers are handled through the wrapped [`http_body::Body`] implementation in [`Request<B>`]
 //! and [`Response<RecvBody>`]. You can access them using their respective [`body_mut()`] method.
 //!
+//! Rather than managing connections yourself, you can send requests through a [`Pool`], which
+//! connects to their origin as needed and reuses the connections.
+//!
 //! # Example
 //!
 //! ```
@@ -27,11 +30,8 @@
 //!     // Create an endpoint
 //!     let client = Client::default();
 //!
-//!     // Connect to a server
-//!     let socket = "example.com:443".parse().unwrap();
-//!     let connecting = client.connect(&socket, "example.com").unwrap();
-//!     // Wait for the handshake to succeed
-//!     let connection = connecting.await.unwrap();
+//!     // Connect to a server, waiting for the handshake to succeed
+//!     let connection = client.connect_host("example.com:443").await.unwrap();
 //!
 //!     // Send a request
 //!     let request = Request::get("https://example.com")
@@ -64,6 +64,7 @@
 //! [`Response<RecvBody>`]: https://docs.rs/http/*/http/request/index.html
 //! [`http_body::Body`]: https://docs.rs/http_body/*/http_body/trait.Body.html
 //! [`body_mut()`]: https://docs.rs/http/*/http/request/struct.Request.html
+//! [`Pool`]: struct.Pool.html
 
 #![allow(clippy::needless_doctest_main)]
 
@@ -75,24 +76,33 @@ use std::{
     task::{Context, Poll},
 };
 
-use futures::{channel::oneshot, ready, FutureExt};
+use futures::{channel::oneshot, ready, FutureExt, Stream};
 use http::{request, HeaderMap, Method, Request, Response, Uri};
 use http_body::Body as HttpBody;
 use pin_project::pin_project;
-use quinn::{Certificate, Endpoint, OpenBi, RecvStream};
+use quinn::{Certificate, Endpoint, OpenBi, RecvStream, SendStream};
 use quinn_proto::{Side, StreamId};
 use tracing::{debug, trace};
 
 use crate::{
     body::RecvBody,
-    connection::{ConnectionDriver, ConnectionRef},
-    data::RecvData,
+    connection::{ConnectionDriver, ConnectionRef, PromisedRequest},
+    data::{write_headers_frame, RecvData},
     frame::FrameDecoder,
-    proto::{headers::Header, settings::Settings, ErrorCode},
-    Error, SendData, ZeroRttAccepted,
+    masque::UdpTunnel,
+    proto::{
+        headers::Header,
+        priority::{Prioritized, PRIORITY_HEADER},
+        settings::Settings,
+        ErrorCode,
+    },
+    webtransport::WebTransportSession,
+    Error, Priority, Protocol, RecvTunnel, SendData, SendTunnel, ZeroRttAccepted,
 };
 use futures_util::future;
 
+pub use crate::pool::Pool;
+
 /// Configure and build a new HTTP/3 client
 ///
 /// Creates a [`Client`] with a custom configuration. If you don't need anything specific,
@@ -124,6 +134,7 @@ use futures_util::future;
 pub struct Builder {
     settings: Settings,
     client_config: quinn::ClientConfigBuilder,
+    max_pushes: u64,
 }
 
 impl Default for Builder {
@@ -134,6 +145,7 @@ impl Default for Builder {
         Self {
             client_config,
             settings: Settings::new(),
+            max_pushes: 0,
         }
     }
 }
@@ -157,6 +169,7 @@ impl Builder {
         Ok(Client {
             endpoint,
             settings: self.settings,
+            max_pushes: self.max_pushes,
         })
     }
 
@@ -181,9 +194,22 @@ impl Builder {
         Self {
             client_config,
             settings: Settings::new(),
+            max_pushes: 0,
         }
     }
 
+    /// Set how many pushes the server may promise ahead of the application accepting them
+    ///
+    /// Each connection advertises this limit to the server, raising it by one every time a
+    /// promised push is yielded by [`Connection::pushes()`]. Defaults to 0, which disables server
+    /// push.
+    ///
+    /// [`Connection::pushes()`]: struct.Connection.html#method.pushes
+    pub fn max_pushes(&mut self, count: u64) -> &mut Self {
+        self.max_pushes = count;
+        self
+    }
+
     /// Create a new client bound to an existing QUIC endpoint
     ///
     /// This is usefull if you want to manage several clients, possibly using different protocols,
@@ -192,6 +218,7 @@ impl Builder {
         Client {
             endpoint,
             settings: self.settings,
+            max_pushes: self.max_pushes,
         }
     }
 }
@@ -216,6 +243,7 @@ impl Builder {
 pub struct Client {
     endpoint: Endpoint,
     settings: Settings,
+    max_pushes: u64,
 }
 impl Default for Client {
     /// Create a new HTTP/3 client endpoint with crate's recomended settings
@@ -244,6 +272,7 @@ impl Client {
     ) -> Result<Connecting, quinn::ConnectError> {
         Ok(Connecting {
             settings: self.settings.clone(),
+            max_pushes: self.max_pushes,
             connecting: self.endpoint.connect(addr, server_name)?,
         })
     }
@@ -262,12 +291,75 @@ impl Client {
     ) -> Result<Connecting, quinn::ConnectError> {
         Ok(Connecting {
             settings: self.settings.clone(),
+            max_pushes: self.max_pushes,
             connecting: self
                 .endpoint
                 .connect_with(client_config, addr, server_name)?,
         })
     }
 
+    /// Connect to a remote server by hostname, e.g. `"example.com:443"`
+    ///
+    /// Candidate addresses are raced as described in [`quinn::Endpoint::connect_host()`], and the
+    /// hostname is used as the server name. Resolves once the handshake with the first reachable
+    /// address succeeds.
+    ///
+    /// Must be called from tokio runtime context, as this spawns the connection's driver task.
+    ///
+    /// [`quinn::Endpoint::connect_host()`]: quinn::generic::Endpoint::connect_host
+    pub async fn connect_host(&self, host: &str) -> Result<Connection, quinn::ConnectHostError> {
+        let new_conn = self.endpoint.connect_host(host).await?;
+        Ok(Connection::new(
+            new_conn,
+            self.settings.clone(),
+            self.max_pushes,
+        ))
+    }
+
+    /// Connect to a remote server by hostname with a specific QUIC config
+    ///
+    /// See [`connect_host()`] for details.
+    ///
+    /// [`connect_host()`]: #method.connect_host
+    pub async fn connect_host_with(
+        &self,
+        client_config: quinn::ClientConfig,
+        host: &str,
+    ) -> Result<Connection, quinn::ConnectHostError> {
+        let new_conn = self.endpoint.connect_host_with(client_config, host).await?;
+        Ok(Connection::new(
+            new_conn,
+            self.settings.clone(),
+            self.max_pushes,
+        ))
+    }
+
+    /// Connect to `host` on `port` as looked up by `resolver`
+    ///
+    /// Candidate addresses are raced as for [`connect_host()`], but the server is authenticated as
+    /// `server_name`, as when reaching an alternative service of `server_name`.
+    ///
+    /// Must be called from tokio runtime context, as this spawns the connection's driver task.
+    ///
+    /// [`connect_host()`]: #method.connect_host
+    pub async fn connect_resolved(
+        &self,
+        resolver: &dyn quinn::Resolver,
+        host: &str,
+        port: u16,
+        server_name: &str,
+    ) -> Result<Connection, quinn::ConnectHostError> {
+        let new_conn = self
+            .endpoint
+            .connect_resolved(resolver, host, port, server_name)
+            .await?;
+        Ok(Connection::new(
+            new_conn,
+            self.settings.clone(),
+            self.max_pushes,
+        ))
+    }
+
     /// Wait for all connections on the endpoint to be cleanly shut down
     ///
     /// Waiting for this condition before exiting ensures that a good-faith effort is made to notify
@@ -292,6 +384,7 @@ impl Client {
 pub struct Connecting {
     connecting: quinn::Connecting,
     settings: Settings,
+    max_pushes: u64,
 }
 
 impl Connecting {
@@ -354,23 +447,16 @@ impl Connecting {
         let Self {
             connecting,
             settings,
+            max_pushes,
         } = self;
         match connecting.into_0rtt() {
             Err(connecting) => Err(Self {
                 connecting,
                 settings,
+                max_pushes,
             }),
             Ok((new_conn, zero_rtt)) => {
-                let quinn::NewConnection {
-                    connection,
-                    uni_streams,
-                    bi_streams,
-                    ..
-                } = new_conn;
-                let conn_ref =
-                    ConnectionRef::new(connection, Side::Client, uni_streams, bi_streams, settings);
-                tokio::spawn(ConnectionDriver(conn_ref.clone()));
-                Ok((Connection(conn_ref), zero_rtt))
+                Ok((Connection::new(new_conn, settings, max_pushes), zero_rtt))
             }
         }
     }
@@ -380,21 +466,12 @@ impl Future for Connecting {
     type Output = Result<Connection, Error>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let quinn::NewConnection {
-            connection,
-            uni_streams,
-            bi_streams,
-            ..
-        } = ready!(Pin::new(&mut self.connecting).poll(cx))?;
-        let conn_ref = ConnectionRef::new(
-            connection,
-            Side::Client,
-            uni_streams,
-            bi_streams,
+        let new_conn = ready!(Pin::new(&mut self.connecting).poll(cx))?;
+        Poll::Ready(Ok(Connection::new(
+            new_conn,
             self.settings.clone(),
-        );
-        tokio::spawn(ConnectionDriver(conn_ref.clone()));
-        Poll::Ready(Ok(Connection(conn_ref)))
+            self.max_pushes,
+        )))
     }
 }
 
@@ -428,6 +505,66 @@ impl Future for Connecting {
 pub struct Connection(ConnectionRef);
 
 impl Connection {
+    fn new(new_conn: quinn::NewConnection, settings: Settings, max_pushes: u64) -> Self {
+        let quinn::NewConnection {
+            connection,
+            uni_streams,
+            bi_streams,
+            datagrams,
+            ..
+        } = new_conn;
+        let conn_ref = ConnectionRef::new(
+            connection,
+            Side::Client,
+            uni_streams,
+            bi_streams,
+            datagrams,
+            settings,
+        );
+        conn_ref.h3.lock().unwrap().set_max_pushes(max_pushes);
+        tokio::spawn(ConnectionDriver(conn_ref.clone()));
+        Connection(conn_ref)
+    }
+
+    /// Receive the pushes promised by the server
+    ///
+    /// Yields a [`RecvPushPromise`] for each push promised by the server, provided that
+    /// [`Builder::max_pushes()`] allowed it. Promises are received along with the response to the
+    /// request they are associated with, so that response must be polled for them to show up.
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// use futures::StreamExt;
+    /// use quinn_h3::client::Connection;
+    ///
+    /// async fn pushed_bodies(connection: &Connection) -> Result<()> {
+    ///     let mut pushes = connection.pushes();
+    ///     while let Some(recv_promise) = pushes.next().await {
+    ///         let (request, recv_response) = recv_promise.await?;
+    ///         let mut response = recv_response.await?;
+    ///         let body = response.body_mut().read_to_end().await?;
+    ///         println!("{} pushed {} bytes", request.uri(), body.len());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`RecvPushPromise`]: struct.RecvPushPromise.html
+    /// [`Builder::max_pushes()`]: struct.Builder.html#method.max_pushes
+    pub fn pushes(&self) -> IncomingPush {
+        IncomingPush(self.0.clone())
+    }
+
+    /// Whether new requests are refused, as the connection is closed or shutting down
+    pub(crate) fn is_closing(&self) -> bool {
+        let conn = self.0.h3.lock().unwrap();
+        conn.is_closed() || conn.inner.is_closing()
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.0.h3.lock().unwrap().is_closed()
+    }
+
     /// Send a HTTP/3 request
     ///
     /// This accepts a [`http::Request<B>`] and emits [`SendRequest<B, B::Data>`], that will resolve
@@ -474,6 +611,259 @@ impl Connection {
         (send, recv)
     }
 
+    /// Open a tunnel with a CONNECT request
+    ///
+    /// `request` must use the CONNECT method, and its URI must carry the authority to tunnel to,
+    /// such as `"example.com:443"`. Resolves once the server accepts the tunnel with a successful
+    /// (2xx) response, yielding that response along with the tunnel's halves. Any other status
+    /// fails with [`Error::ConnectRefused`].
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// use http::{Method, Request};
+    /// use quinn_h3::client::Connection;
+    /// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    ///
+    /// async fn tunnel(connection: &Connection) -> Result<()> {
+    ///     let request = Request::builder()
+    ///         .method(Method::CONNECT)
+    ///         .uri("example.com:80")
+    ///         .body(())?;
+    ///     let (_, mut send, mut recv) = connection.connect(request).await?;
+    ///
+    ///     send.write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n").await?;
+    ///     send.shutdown().await?;
+    ///     let mut response = Vec::new();
+    ///     recv.read_to_end(&mut response).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`Error::ConnectRefused`]: ../enum.Error.html#variant.ConnectRefused
+    pub async fn connect(
+        &self,
+        request: Request<()>,
+    ) -> Result<(Response<()>, SendTunnel, RecvTunnel), Error> {
+        let (parts, _) = request.into_parts();
+        let request::Parts {
+            method,
+            uri,
+            headers,
+            ..
+        } = parts;
+        if method != Method::CONNECT {
+            return Err(Error::Header("Tunnels must be opened with CONNECT"));
+        }
+        if uri.authority().is_none() {
+            return Err(Error::Header("Missing authority"));
+        }
+        if self.0.h3.lock().unwrap().inner.is_closing() {
+            return Err(Error::Aborted);
+        }
+
+        let (mut send, recv) = self.0.quic.open_bi().await?;
+        debug!(stream=%send.id(), "connect");
+        if recv.is_0rtt() {
+            return Err(Error::internal("non-idempotent method tried on 0RTT"));
+        }
+        let header = Header::request(method, uri, headers);
+        let mut write = write_headers_frame(header, send.id(), &self.0)?;
+        future::poll_fn(|cx| write.poll_send(&mut send, cx)).await?;
+
+        let recv = RecvData::new(FrameDecoder::stream(recv), self.0.clone(), send.id());
+        let (header, body) = recv.await?;
+        let (status, headers) = header.into_response_parts()?;
+        if !status.is_success() {
+            return Err(Error::ConnectRefused(status));
+        }
+        let mut response = Response::builder()
+            .status(status)
+            .version(http::version::Version::HTTP_3)
+            .body(())
+            .unwrap();
+        *response.headers_mut() = headers;
+
+        Ok((
+            response,
+            SendTunnel::new(send, self.0.clone(), false),
+            RecvTunnel::new(body.into_inner()),
+        ))
+    }
+
+    /// Open a WebTransport session
+    ///
+    /// `request` must use the CONNECT method, and its URI must be complete, such as
+    /// `"https://example.com/chat"`. It's sent as an extended CONNECT with the `webtransport`
+    /// protocol, once the server's settings have been received. Both this client's and the
+    /// server's [`Settings`] must enable WebTransport, or this fails with
+    /// [`Error::SettingDisabled`].
+    ///
+    /// Resolves once the server accepts the session with a successful (2xx) response, yielding
+    /// that response along with the session. Any other status fails with
+    /// [`Error::ConnectRefused`].
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// use bytes::Bytes;
+    /// use http::{Method, Request};
+    /// use quinn_h3::client::Connection;
+    ///
+    /// async fn ping(connection: &Connection) -> Result<()> {
+    ///     let request = Request::builder()
+    ///         .method(Method::CONNECT)
+    ///         .uri("https://example.com/ping")
+    ///         .body(())?;
+    ///     let (_, session) = connection.webtransport(request).await?;
+    ///
+    ///     session.send_datagram(Bytes::from_static(b"ping"))?;
+    ///     let pong = session.recv_datagram().await;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`Settings`]: ../struct.Settings.html
+    /// [`Error::SettingDisabled`]: ../enum.Error.html#variant.SettingDisabled
+    /// [`Error::ConnectRefused`]: ../enum.Error.html#variant.ConnectRefused
+    pub async fn webtransport(
+        &self,
+        request: Request<()>,
+    ) -> Result<(Response<()>, WebTransportSession), Error> {
+        let (response, send) = self
+            .open_session(
+                request,
+                Protocol::WEBTRANSPORT,
+                ("WebTransport", Settings::enable_webtransport),
+            )
+            .await?;
+        Ok((response, WebTransportSession::new(send, self.0.clone())))
+    }
+
+    /// Open a CONNECT-UDP tunnel through a proxy
+    ///
+    /// `request` must use the CONNECT method, and its URI must be complete; see
+    /// [`masque::udp_request()`] to build it. It's sent as an extended CONNECT with the
+    /// `connect-udp` protocol, once the server's settings have been received. Both this
+    /// client's and the server's [`Settings`] must enable HTTP datagrams, and the server's the
+    /// extended CONNECT protocol, or this fails with [`Error::SettingDisabled`].
+    ///
+    /// Resolves once the proxy accepts the tunnel with a successful (2xx) response, yielding
+    /// that response along with the tunnel. Any other status fails with
+    /// [`Error::ConnectRefused`].
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// use bytes::Bytes;
+    /// use quinn_h3::{client::Connection, masque};
+    ///
+    /// async fn dns_query(connection: &Connection, query: Bytes) -> Result<Option<Bytes>> {
+    ///     let request = masque::udp_request("proxy.example.com", "192.0.2.53", 53)?;
+    ///     let (_, tunnel) = connection.connect_udp(request).await?;
+    ///     tunnel.send_datagram(query)?;
+    ///     Ok(tunnel.recv_datagram().await)
+    /// }
+    /// ```
+    ///
+    /// [`masque::udp_request()`]: ../masque/fn.udp_request.html
+    /// [`Settings`]: ../struct.Settings.html
+    /// [`Error::SettingDisabled`]: ../enum.Error.html#variant.SettingDisabled
+    /// [`Error::ConnectRefused`]: ../enum.Error.html#variant.ConnectRefused
+    pub async fn connect_udp(
+        &self,
+        request: Request<()>,
+    ) -> Result<(Response<()>, UdpTunnel), Error> {
+        let (response, send) = self
+            .open_session(
+                request,
+                Protocol::CONNECT_UDP,
+                ("HTTP datagrams", Settings::h3_datagram),
+            )
+            .await?;
+        Ok((response, UdpTunnel::new(send, self.0.clone())))
+    }
+
+    /// Send an extended CONNECT request for `protocol`, whose stream then carries a session
+    ///
+    /// The session is registered before the request is sent, so nothing sent by the server
+    /// once it accepts is lost. Both endpoints' settings must be `enabled`, and the server must
+    /// also allow extended CONNECT.
+    async fn open_session(
+        &self,
+        request: Request<()>,
+        protocol: Protocol,
+        (setting, enabled): (&'static str, fn(&Settings) -> bool),
+    ) -> Result<(Response<()>, SendStream), Error> {
+        let (parts, _) = request.into_parts();
+        let request::Parts {
+            method,
+            uri,
+            headers,
+            ..
+        } = parts;
+        if method != Method::CONNECT {
+            return Err(Error::Header("Sessions must be opened with CONNECT"));
+        }
+        if uri.scheme().is_none() || uri.authority().is_none() {
+            return Err(Error::Header("Sessions need a complete URI"));
+        }
+        if !enabled(self.0.h3.lock().unwrap().inner.local_settings()) {
+            return Err(Error::SettingDisabled(setting));
+        }
+
+        let remote = future::poll_fn(|cx| self.0.h3.lock().unwrap().poll_remote_settings(cx));
+        match remote.await {
+            Err(_) => return Err(Error::Aborted),
+            Ok(settings) if !enabled(&settings) => return Err(Error::SettingDisabled(setting)),
+            Ok(settings) if !settings.enable_connect_protocol() => {
+                return Err(Error::SettingDisabled("Extended CONNECT"));
+            }
+            Ok(_) => (),
+        }
+        if self.0.h3.lock().unwrap().inner.is_closing() {
+            return Err(Error::Aborted);
+        }
+
+        let (mut send, recv) = self.0.quic.open_bi().await?;
+        let id = send.id();
+        debug!(stream=%id, "{}", protocol.as_str());
+        if recv.is_0rtt() {
+            return Err(Error::internal("non-idempotent method tried on 0RTT"));
+        }
+        self.0.h3.lock().unwrap().open_session(id);
+
+        let header = Header::extended_connect(uri, protocol, headers);
+        let accepted = async {
+            let mut write = write_headers_frame(header, id, &self.0)?;
+            future::poll_fn(|cx| write.poll_send(&mut send, cx)).await?;
+
+            let recv = RecvData::new(FrameDecoder::stream(recv), self.0.clone(), id);
+            let (header, body) = recv.await?;
+            let (status, headers) = header.into_response_parts()?;
+            if !status.is_success() {
+                return Err(Error::ConnectRefused(status));
+            }
+            let mut response = Response::builder()
+                .status(status)
+                .version(http::version::Version::HTTP_3)
+                .body(())
+                .unwrap();
+            *response.headers_mut() = headers;
+            Ok((response, body.into_inner()))
+        };
+
+        let accepted = accepted.await;
+        let mut h3 = self.0.h3.lock().unwrap();
+        match accepted {
+            Ok((response, stream)) => {
+                h3.set_session_stream(id, stream);
+                Ok((response, send))
+            }
+            Err(e) => {
+                h3.close_session(id);
+                Err(e)
+            }
+        }
+    }
+
     /// Close the connection immediately
     ///
     /// All ongoing requests will fail. Peer will receive a connection error with `NO_ERROR` code.
@@ -566,10 +956,18 @@ where
         let request::Parts {
             method,
             uri,
-            headers,
+            mut headers,
+            extensions,
             ..
         } = parts;
 
+        if let Some(priority) = extensions.get::<Priority>() {
+            match priority.to_header() {
+                Some(value) => headers.insert(PRIORITY_HEADER, value),
+                None => headers.remove(PRIORITY_HEADER),
+            };
+        }
+
         match (uri.authority(), headers.get("host")) {
             (None, None) => Err(Error::Header("Missing authority")),
             (Some(a), Some(h)) if a.as_str() != h => {
@@ -680,6 +1078,9 @@ pub struct RecvResponse {
     conn: ConnectionRef,
     stream_id: Option<StreamId>,
     recv: Option<RecvData>,
+    push_id: Option<u64>,
+    /// Set before the request stream was open, to be sent once it is
+    priority: Option<Priority>,
 }
 
 enum RecvResponseState {
@@ -698,6 +1099,87 @@ impl RecvResponse {
             recv: None,
             state: RecvResponseState::Opening(recv),
             stream_id: None,
+            push_id: None,
+            priority: None,
+        }
+    }
+
+    fn push(
+        recv: oneshot::Receiver<(RecvStream, StreamId)>,
+        conn: ConnectionRef,
+        push_id: u64,
+    ) -> Self {
+        Self {
+            push_id: Some(push_id),
+            ..Self::new(recv, conn)
+        }
+    }
+
+    /// Reprioritize the response
+    ///
+    /// The server is sent the new priority, which replaces the one set on the request. See
+    /// [`RecvBody::set_priority()`] to reprioritize the response while receiving its body.
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// use http::Request;
+    /// use quinn_h3::{client::Connection, Body, Priority};
+    ///
+    /// async fn prefetch(connection: &Connection) -> Result<()> {
+    ///     let mut request = Request::get("https://example.com/next.html").body(Body::from(()))?;
+    ///     request.extensions_mut().insert(Priority::new(7, true));
+    ///     let (send_request, mut recv_response) = connection.send_request(request);
+    ///     send_request.await?;
+    ///
+    ///     // The user navigated to the page, it's now needed right away
+    ///     recv_response.set_priority(Priority::new(0, false));
+    ///     let response = recv_response.await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`RecvBody::set_priority()`]: ../struct.RecvBody.html#method.set_priority
+    pub fn set_priority(&mut self, priority: Priority) {
+        // The request stream may have been opened since this was last polled
+        if let RecvResponseState::Opening(ref mut open) = self.state {
+            if let Ok(Some((recv, id))) = open.try_recv() {
+                self.opened(recv, id);
+            }
+        }
+        let element = match (self.push_id, self.stream_id) {
+            (Some(push_id), _) => Prioritized::Push(push_id),
+            (None, Some(id)) => Prioritized::Request(id.0),
+            (None, None) => {
+                self.priority = Some(priority);
+                return;
+            }
+        };
+        self.conn.set_priority(element, priority);
+    }
+
+    /// Whether the request stream was opened, so the request may have been processed
+    pub(crate) fn is_opened(&self) -> bool {
+        self.stream_id.is_some()
+    }
+
+    /// Whether the server's GOAWAY tells that the request wasn't processed
+    pub(crate) fn is_beyond_go_away(&self) -> bool {
+        match self.stream_id {
+            Some(id) => self.conn.h3.lock().unwrap().inner.is_beyond_go_away(id),
+            None => false,
+        }
+    }
+
+    fn opened(&mut self, recv: RecvStream, id: StreamId) {
+        self.stream_id = Some(id);
+        self.recv = Some(RecvData::new(
+            FrameDecoder::stream(recv),
+            self.conn.clone(),
+            id,
+        ));
+        self.state = RecvResponseState::Receiving;
+        if let Some(priority) = self.priority.take() {
+            self.set_priority(priority);
         }
     }
 
@@ -729,9 +1211,14 @@ impl RecvResponse {
             }
         };
 
+        let mut conn = self.conn.h3.lock().unwrap();
         if let Some(id) = stream_id {
-            self.conn.h3.lock().unwrap().cancel_request(id);
+            conn.cancel_request(id);
+        }
+        if let Some(push_id) = self.push_id {
+            conn.cancel_push(push_id);
         }
+        drop(conn);
 
         self.state = RecvResponseState::Finished;
     }
@@ -749,18 +1236,26 @@ impl Future for RecvResponse {
                     )))
                 }
                 RecvResponseState::Opening(ref mut open) => {
-                    let (recv, id) = ready!(open.poll_unpin(cx))
-                        .map_err(|_| Error::internal("RecvResponse channel cancelled"))?;
-                    self.stream_id = Some(id);
-                    self.recv = Some(RecvData::new(
-                        FrameDecoder::stream(recv),
-                        self.conn.clone(),
-                        self.stream_id.unwrap(),
-                    ));
-                    self.state = RecvResponseState::Receiving;
+                    let (recv, id) = match ready!(open.poll_unpin(cx)) {
+                        Ok(x) => x,
+                        // The push was cancelled before its stream was received
+                        Err(_) if self.push_id.is_some() => {
+                            self.state = RecvResponseState::Finished;
+                            return Poll::Ready(Err(ErrorCode::REQUEST_CANCELLED.into()));
+                        }
+                        Err(_) => {
+                            return Poll::Ready(Err(Error::internal(
+                                "RecvResponse channel cancelled",
+                            )))
+                        }
+                    };
+                    self.opened(recv, id);
                 }
                 RecvResponseState::Receiving => {
-                    let (headers, body) = ready!(self.recv.as_mut().unwrap().poll_unpin(cx))?;
+                    let (headers, mut body) = ready!(self.recv.as_mut().unwrap().poll_unpin(cx))?;
+                    if let Some(push_id) = self.push_id {
+                        body.set_prioritized(Prioritized::Push(push_id));
+                    }
 
                     let (status, headers) = headers.into_response_parts()?;
                     let mut response = Response::builder()
@@ -778,6 +1273,62 @@ impl Future for RecvResponse {
     }
 }
 
+/// Stream of pushes promised by the server
+///
+/// Obtained from [`Connection::pushes()`]. Ends when the connection is closed.
+///
+/// [`Connection::pushes()`]: struct.Connection.html#method.pushes
+pub struct IncomingPush(ConnectionRef);
+
+impl Stream for IncomingPush {
+    type Item = RecvPushPromise;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut conn = self.0.h3.lock().unwrap();
+        match conn.next_push_promise(cx) {
+            Ok(Some((push_id, request))) => {
+                let recv = conn.claim_push(push_id);
+                Poll::Ready(Some(RecvPushPromise {
+                    request: Some(request),
+                    response: Some(RecvResponse::push(recv, self.0.clone(), push_id)),
+                }))
+            }
+            Ok(None) => Poll::Pending,
+            Err(_) => Poll::Ready(None),
+        }
+    }
+}
+
+/// Receive a push promise
+///
+/// Resolves with the [`Request`] the server is pushing a response for, along with a
+/// [`RecvResponse`] to receive that response.
+/// Cancelling the [`RecvResponse`] cancels the push.
+///
+/// [`Request`]: https://docs.rs/http/*/http/request/struct.Request.html
+/// [`RecvResponse`]: struct.RecvResponse.html
+pub struct RecvPushPromise {
+    request: Option<PromisedRequest>,
+    response: Option<RecvResponse>,
+}
+
+impl Future for RecvPushPromise {
+    type Output = Result<(Request<()>, RecvResponse), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+        let (method, uri, headers) = self.request.take().ok_or(Error::Poll)??;
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .version(http::version::Version::HTTP_3)
+            .body(())
+            .unwrap();
+        *request.headers_mut() = headers;
+        let response = self.response.take().ok_or(Error::Poll)?;
+        Poll::Ready(Ok((request, response)))
+    }
+}
+
 #[cfg(test)]
 impl Connection {
     pub(crate) fn inner(&self) -> &ConnectionRef {
@@ -7,25 +7,31 @@ use std::{
     task::{Context, Poll, Waker},
 };
 
-use bytes::{Buf, BytesMut};
-use futures::{io::AsyncRead, Stream};
-use quinn::{IncomingBiStreams, IncomingUniStreams, RecvStream, SendStream};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{channel::oneshot, io::AsyncRead, ready, Stream};
+use http::{HeaderMap, Method, Uri};
+use quinn::{Datagrams, IncomingBiStreams, IncomingUniStreams, RecvStream, SendStream};
 use quinn_proto::{
-    ConnectionClose, ConnectionError as QuicConnError, Side, StreamId, TransportErrorCode,
+    coding::{BufExt, BufMutExt},
+    ConnectionClose, ConnectionError as QuicConnError, Dir, Side, StreamId, TransportErrorCode,
+    VarInt,
 };
-use tracing::{error, trace, trace_span, warn};
+use tracing::{error, trace, trace_span};
 
 use crate::{
-    frame::{self, FrameStream},
+    frame::{self, FrameDecoder, FrameStream},
     proto::{
         self,
+        capsule::{Capsule, Error as CapsuleError},
         connection::{Connection, DecodeResult, Error as ConnectionError, PendingStreamType},
-        frame::{HeadersFrame, HttpFrame},
-        headers::Header,
+        frame::{HeadersFrame, HttpFrame, PushPromiseFrame},
+        headers::{Error as HeaderError, Header},
+        priority::{Prioritized, Priority},
         settings::Error as SettingsError,
         ErrorCode, StreamType,
     },
-    streams::{NewUni, RecvUni, SendUni},
+    scheduler::Scheduler,
+    streams::{NewBi, NewUni, PushStream, RecvBi, RecvUni, Reset, SendUni},
     Error, Settings,
 };
 
@@ -79,8 +85,14 @@ impl ConnectionRef {
         side: Side,
         uni_streams: IncomingUniStreams,
         bi_streams: IncomingBiStreams,
+        datagrams: Datagrams,
         settings: Settings,
     ) -> Self {
+        // Datagrams are left to the application unless they are used by HTTP
+        let datagrams = match settings.h3_datagram() {
+            true => Some(datagrams),
+            false => None,
+        };
         Self {
             quic: quic.clone(),
             h3: Arc::new(Mutex::new(ConnectionInner {
@@ -89,9 +101,20 @@ impl ConnectionRef {
                 incoming_bi: bi_streams,
                 incoming_uni: uni_streams,
                 pending_uni: VecDeque::with_capacity(3),
+                pending_bi: VecDeque::new(),
+                datagrams,
                 inner: Connection::new(side, settings),
                 requests: VecDeque::with_capacity(16),
                 requests_task: None,
+                pushes: HashMap::new(),
+                promised: BTreeMap::new(),
+                forgotten_promises: 0,
+                max_pushes: 0,
+                push_promises: VecDeque::new(),
+                push_promises_task: None,
+                sessions: HashMap::new(),
+                scheduler: Scheduler::default(),
+                settings_tasks: Vec::new(),
                 recv_control: None,
                 recv_encoder: None,
                 recv_decoder: None,
@@ -107,6 +130,71 @@ impl ConnectionRef {
     }
 }
 
+impl ConnectionRef {
+    /// Send an HTTP datagram associated with request stream `id`
+    ///
+    /// Fails if the peer did not enable HTTP datagrams in its settings.
+    pub fn send_datagram(&self, id: StreamId, payload: &[u8]) -> Result<(), Error> {
+        let enabled = match self.h3.lock().unwrap().inner.remote_settings() {
+            Some(settings) => settings.h3_datagram(),
+            None => false,
+        };
+        if !enabled {
+            return Err(Error::SettingDisabled("HTTP datagrams"));
+        }
+        // Datagrams start with the quarter of their request stream ID
+        let mut datagram = BytesMut::with_capacity(VarInt::MAX_SIZE + payload.len());
+        datagram.write_var(id.0 / 4);
+        datagram.extend_from_slice(payload);
+        self.quic
+            .send_datagram(datagram.freeze())
+            .map_err(Error::Datagram)
+    }
+
+    /// Change the priority of a response
+    ///
+    /// A client sends it to the server in a PRIORITY_UPDATE frame, while a server applies it
+    /// right away, overriding what the client signaled.
+    pub fn set_priority(&self, element: Prioritized, priority: Priority) {
+        let mut h3 = self.h3.lock().unwrap();
+        match h3.side {
+            Side::Client => {
+                trace!(element = ?element, priority = ?priority, "sending priority update");
+                h3.inner.priority_update(element, priority);
+                h3.wake();
+            }
+            Side::Server => {
+                // Like the client's updates, kept only for responses not finished yet
+                let h3 = &mut *h3;
+                let inner = &h3.inner;
+                h3.scheduler
+                    .update(element, priority, |e| inner.is_prioritized_pending(e));
+            }
+        }
+    }
+
+    /// Handle a PUSH_PROMISE frame received on request stream `stream_id`
+    ///
+    /// Pending while a repeated promise is compared with the first one. An invalid promise is
+    /// a connection error, so the connection is closed on failure.
+    pub fn poll_push_promise(
+        &self,
+        cx: &mut Context,
+        stream_id: StreamId,
+        frame: &PushPromiseFrame,
+    ) -> Poll<Result<(), Error>> {
+        let result = ready!(self
+            .h3
+            .lock()
+            .unwrap()
+            .poll_push_promise(cx, stream_id, frame));
+        Poll::Ready(result.map_err(|(code, msg)| {
+            self.quic.close(code.into(), msg.as_bytes());
+            Error::Peer(msg)
+        }))
+    }
+}
+
 enum DriveState {
     Closed,
     Running,
@@ -114,13 +202,38 @@ enum DriveState {
 
 pub(crate) struct ConnectionInner {
     pub inner: Connection,
-    requests: VecDeque<(SendStream, RecvStream)>,
+    requests: VecDeque<(SendStream, FrameStream)>,
     requests_task: Option<Waker>,
+    /// Client: state of the pushes that have been promised or whose stream has been received
+    ///
+    /// Server: the pushes promised whose response is not yet entirely sent
+    pushes: HashMap<u64, Push>,
+    /// Client: the request of each push promised, to compare repeated promises with
+    ///
+    /// Outlives the push itself, as a push may be promised again at any time. Only the latest
+    /// `max_pushes` are remembered, `None` standing for an invalid request.
+    promised: BTreeMap<u64, Option<RequestParts>>,
+    /// Client: pushes below this ID are no longer remembered, their promises are ignored
+    forgotten_promises: u64,
+    /// Client: the number of pushes the server may promise before the application claims them
+    max_pushes: u64,
+    /// Client: promises not yet yielded to the application, with their request
+    push_promises: VecDeque<(u64, PromisedRequest)>,
+    push_promises_task: Option<Waker>,
+    /// WebTransport sessions, established or with streams waiting for the application
+    sessions: HashMap<u64, Session>,
+    /// Server: orders the DATA of concurrent responses by priority
+    pub scheduler: Scheduler,
+    /// Tasks waiting for the peer's settings
+    settings_tasks: Vec<Waker>,
     side: Side,
     driver: Option<Waker>,
     incoming_bi: IncomingBiStreams,
     incoming_uni: IncomingUniStreams,
     pending_uni: VecDeque<Option<RecvUni>>,
+    /// Bidirectional streams whose first frame type is being read, when WebTransport is enabled
+    pending_bi: VecDeque<Option<RecvBi>>,
+    datagrams: Option<Datagrams>,
     recv_control: Option<FrameStream>,
     recv_encoder: Option<(RecvStream, BytesMut)>,
     recv_decoder: Option<(RecvStream, BytesMut)>,
@@ -137,6 +250,8 @@ impl ConnectionInner {
         self.poll_recv_encoder(cx)?;
         self.poll_recv_decoder(cx)?;
         self.poll_incoming_bi(cx)?;
+        self.poll_datagrams(cx)?;
+        self.poll_sessions(cx);
         self.poll_send(cx)?;
 
         self.reset_waker(cx);
@@ -150,7 +265,7 @@ impl ConnectionInner {
     pub fn next_request(
         &mut self,
         cx: &mut Context,
-    ) -> Result<Option<(SendStream, RecvStream)>, ()> {
+    ) -> Result<Option<(SendStream, FrameStream)>, ()> {
         if self.closed {
             return Err(());
         }
@@ -163,17 +278,272 @@ impl ConnectionInner {
         }
     }
 
+    pub fn next_push_promise(
+        &mut self,
+        cx: &mut Context,
+    ) -> Result<Option<(u64, PromisedRequest)>, ()> {
+        if self.closed {
+            return Err(());
+        }
+        match self.push_promises.pop_front() {
+            Some(x) => Ok(Some(x)),
+            None => {
+                self.push_promises_task = Some(cx.waker().clone());
+                Ok(None)
+            }
+        }
+    }
+
+    /// Client: a PUSH_PROMISE frame was received on request stream `stream_id`
+    pub fn poll_push_promise(
+        &mut self,
+        cx: &mut Context,
+        stream_id: StreamId,
+        frame: &PushPromiseFrame,
+    ) -> Poll<Result<(), (ErrorCode, String)>> {
+        if self.side == Side::Server {
+            return Poll::Ready(Err((
+                ErrorCode::FRAME_UNEXPECTED,
+                "client sent a PUSH_PROMISE".into(),
+            )));
+        }
+        if self.inner.check_push_id(frame.id).is_err() {
+            return Poll::Ready(Err((
+                ErrorCode::ID_ERROR,
+                format!("push ID {} exceeds limit", frame.id),
+            )));
+        }
+        if let Some(first) = self.promised.get(&frame.id).cloned() {
+            return self.poll_repeated_promise(cx, stream_id, frame, first);
+        }
+        if frame.id < self.forgotten_promises {
+            trace!(push_id = frame.id, "ignoring promise of forgotten push");
+            return Poll::Ready(Ok(()));
+        }
+        let header = HeadersFrame {
+            encoded: frame.encoded.clone(),
+        };
+        let request = ready!(self.poll_decode(cx, stream_id, &header))
+            .map_err(|e| (ErrorCode::QPACK_DECOMPRESSION_FAILED, format!("{:?}", e)))?
+            .into_request_parts();
+        self.promised
+            .insert(frame.id, request.as_ref().ok().cloned());
+        while self.promised.len() as u64 > self.max_pushes.max(1) {
+            let (&oldest, _) = self.promised.iter().next().unwrap();
+            self.promised.remove(&oldest);
+            self.forgotten_promises = oldest + 1;
+        }
+
+        let push = self.pushes.entry(frame.id).or_default();
+        push.promised = true;
+        if push.cancelled {
+            // Only the server cancels pushes before promising them, it won't open their stream
+            trace!(push_id = frame.id, "ignoring promise of cancelled push");
+            self.pushes.remove(&frame.id);
+            return Poll::Ready(Ok(()));
+        }
+        trace!(push_id = frame.id, stream = %stream_id, "push promised");
+        self.push_promises.push_back((frame.id, request));
+        if let Some(t) = self.push_promises_task.take() {
+            t.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Client: a push was promised again, on request stream `stream_id`
+    ///
+    /// The promised requests must be the same, the push then answering both requests.
+    fn poll_repeated_promise(
+        &mut self,
+        cx: &mut Context,
+        stream_id: StreamId,
+        frame: &PushPromiseFrame,
+        first: Option<RequestParts>,
+    ) -> Poll<Result<(), (ErrorCode, String)>> {
+        let header = HeadersFrame {
+            encoded: frame.encoded.clone(),
+        };
+        let repeated = ready!(self.poll_decode(cx, stream_id, &header))
+            .map_err(|e| (ErrorCode::QPACK_DECOMPRESSION_FAILED, format!("{:?}", e)))?;
+        Poll::Ready(match (first, repeated.into_request_parts()) {
+            (Some(first), Ok(repeated)) if first == repeated => {
+                trace!(push_id = frame.id, stream = %stream_id, "push promised again");
+                Ok(())
+            }
+            _ => Err((
+                ErrorCode::GENERAL_PROTOCOL_ERROR,
+                format!("push ID {} promised for different requests", frame.id),
+            )),
+        })
+    }
+
+    /// Client: let the server promise up to `count` pushes before the application claims them
+    pub fn set_max_pushes(&mut self, count: u64) {
+        self.max_pushes = count;
+        self.inner.allow_pushes(count);
+    }
+
+    /// Client: the application accepted a promised push, so the server may promise another
+    ///
+    /// The returned channel yields the push stream once it's received.
+    pub fn claim_push(&mut self, push_id: u64) -> oneshot::Receiver<(RecvStream, StreamId)> {
+        let (send, recv) = oneshot::channel();
+        let push = self.pushes.entry(push_id).or_default();
+        if let Some(stream) = push.stream.take() {
+            let id = stream.id();
+            let _ = send.send((stream, id));
+            self.pushes.remove(&push_id);
+        } else if !push.cancelled {
+            push.claimed = Some(send);
+        }
+        self.inner.allow_pushes(1);
+        self.wake();
+        recv
+    }
+
+    /// Cancel a push locally, letting the peer know with a CANCEL_PUSH frame
+    pub fn cancel_push(&mut self, push_id: u64) {
+        if let Some(push) = self.pushes.get_mut(&push_id) {
+            push.cancelled = true;
+            push.claimed = None;
+            if let Some(mut stream) = push.stream.take() {
+                let _ = stream.stop(ErrorCode::REQUEST_CANCELLED.into());
+                self.pushes.remove(&push_id);
+            }
+        }
+        self.inner.cancel_push(push_id);
+        self.wake();
+    }
+
+    /// Server: track a promised push until its response is sent, so the client can cancel it
+    pub fn push_promised(&mut self, push_id: u64) {
+        self.pushes.insert(push_id, Push::default());
+    }
+
+    /// Server: resolves once the client cancelled a promised push
+    pub fn poll_push_cancelled(&mut self, push_id: u64, cx: &mut Context) -> Poll<()> {
+        match self.pushes.get_mut(&push_id) {
+            Some(push) if push.cancelled => Poll::Ready(()),
+            Some(push) => {
+                push.task = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            None => Poll::Pending,
+        }
+    }
+
+    /// Server: the push's response is sent or abandoned
+    pub fn push_finished(&mut self, push_id: u64) {
+        self.pushes.remove(&push_id);
+    }
+
     pub fn cancel_request(&mut self, stream_id: StreamId) {
         self.inner.stream_cancel(stream_id);
         self.wake();
     }
 
+    /// Resolves with the peer's settings once received, or fails if the connection closed first
+    pub fn poll_remote_settings(&mut self, cx: &mut Context) -> Poll<Result<Settings, ()>> {
+        if let Some(settings) = self.inner.remote_settings() {
+            return Poll::Ready(Ok(settings.clone()));
+        }
+        if self.closed {
+            return Poll::Ready(Err(()));
+        }
+        self.settings_tasks.push(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Start delivering the streams and datagrams of session `id` to the application
+    pub fn open_session(&mut self, id: StreamId) {
+        trace!(stream = %id, "WebTransport session opened");
+        self.sessions.entry(id.0).or_default().established = true;
+    }
+
+    /// Watch the CONNECT stream of session `id`, which closes the session when it ends
+    pub fn set_session_stream(&mut self, id: StreamId, stream: FrameStream) {
+        if let Some(session) = self.sessions.get_mut(&id.0) {
+            if !session.closed {
+                session.connect = Some(stream);
+                self.wake();
+            }
+        }
+    }
+
+    pub fn close_session(&mut self, id: StreamId) {
+        if let Some(mut session) = self.sessions.remove(&id.0) {
+            trace!(stream = %id, "WebTransport session closed");
+            session.close();
+        }
+        if self.side == Side::Server {
+            self.inner.remote_stream_finished(id);
+        }
+        self.wake();
+    }
+
+    pub fn poll_session_bi(
+        &mut self,
+        id: StreamId,
+        cx: &mut Context,
+    ) -> Poll<Option<(SendStream, RecvStream)>> {
+        match self.sessions.get_mut(&id.0) {
+            Some(session) if !session.closed => match session.bi.pop_front() {
+                Some(x) => Poll::Ready(Some(x)),
+                None => {
+                    session.bi_task = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            },
+            _ => Poll::Ready(None),
+        }
+    }
+
+    pub fn poll_session_uni(&mut self, id: StreamId, cx: &mut Context) -> Poll<Option<RecvStream>> {
+        match self.sessions.get_mut(&id.0) {
+            Some(session) if !session.closed => match session.uni.pop_front() {
+                Some(x) => Poll::Ready(Some(x)),
+                None => {
+                    session.uni_task = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            },
+            _ => Poll::Ready(None),
+        }
+    }
+
+    pub fn poll_session_datagram(&mut self, id: StreamId, cx: &mut Context) -> Poll<Option<Bytes>> {
+        match self.sessions.get_mut(&id.0) {
+            Some(session) if !session.closed => match session.datagrams.pop_front() {
+                Some(x) => Poll::Ready(Some(x)),
+                None => {
+                    session.datagrams_task = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            },
+            _ => Poll::Ready(None),
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
     pub fn terminate(&mut self) {
         self.closed = true;
 
         if let Some(t) = self.requests_task.take() {
             t.wake();
         }
+        if let Some(t) = self.push_promises_task.take() {
+            t.wake();
+        }
+        self.pushes.clear();
+        for session in self.sessions.values_mut() {
+            session.close();
+        }
+        for t in self.settings_tasks.drain(..) {
+            t.wake();
+        }
 
         let requests = mem::replace(&mut self.blocked_streams, BTreeMap::new());
         for (_, waker) in requests.into_iter().map(|(_, v)| v).flatten() {
@@ -224,7 +594,7 @@ impl ConnectionInner {
     fn poll_incoming_bi(&mut self, cx: &mut Context) -> Result<(), DriverError> {
         loop {
             match Pin::new(&mut self.incoming_bi).poll_next(cx) {
-                Poll::Pending | Poll::Ready(None) => return Ok(()),
+                Poll::Pending | Poll::Ready(None) => break,
                 Poll::Ready(Some(Err(e))) => {
                     return Err(DriverError::new(
                         e,
@@ -232,28 +602,209 @@ impl ConnectionInner {
                         "incoming bi error",
                     ))
                 }
-                Poll::Ready(Some(Ok((mut send, mut recv)))) => match self.side {
-                    Side::Client => {
-                        return Err(DriverError::peer(
-                            ErrorCode::STREAM_CREATION_ERROR,
-                            "client does not accept bidirectional streams",
-                        ));
-                    }
-                    Side::Server => match self.inner.remote_stream_initiated(send.id()) {
-                        Err(_) => {
-                            let _ = send.reset(ErrorCode::REQUEST_REJECTED.into());
-                            let _ = recv.stop(ErrorCode::REQUEST_REJECTED.into());
+                Poll::Ready(Some(Ok((mut send, mut recv)))) => {
+                    // Only WebTransport streams tell themselves apart from requests
+                    let webtransport = self.inner.local_settings().enable_webtransport();
+                    match self.side {
+                        Side::Client if webtransport => {
+                            self.pending_bi.push_back(Some(RecvBi::new(send, recv)))
                         }
-                        _ => {
-                            self.requests.push_back((send, recv));
-                            if let Some(t) = self.requests_task.take() {
-                                t.wake();
-                            }
+                        Side::Client => {
+                            return Err(DriverError::peer(
+                                ErrorCode::STREAM_CREATION_ERROR,
+                                "client does not accept bidirectional streams",
+                            ));
                         }
-                    },
+                        Side::Server => match self.inner.remote_stream_initiated(send.id()) {
+                            Err(_) => {
+                                let _ = send.reset(ErrorCode::REQUEST_REJECTED.into());
+                                let _ = recv.stop(ErrorCode::REQUEST_REJECTED.into());
+                            }
+                            _ if webtransport => {
+                                self.pending_bi.push_back(Some(RecvBi::new(send, recv)))
+                            }
+                            _ => self.on_request(send, FrameDecoder::stream(recv)),
+                        },
+                    }
+                }
+            }
+        }
+
+        self.poll_resolve_bi(cx)
+    }
+
+    fn poll_resolve_bi(&mut self, cx: &mut Context) -> Result<(), DriverError> {
+        let resolved: Vec<(usize, Result<NewBi, Error>)> = self
+            .pending_bi
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, x)| match Pin::new(x.as_mut().unwrap()).poll(cx) {
+                Poll::Ready(y) => {
+                    *x = None;
+                    Some((i, y))
+                }
+                Poll::Pending => None,
+            })
+            .collect();
+
+        for (removed, (i, res)) in resolved.into_iter().enumerate() {
+            self.pending_bi.remove(i - removed);
+            match res {
+                Err(e) => trace!("incoming bi stream failed: {:?}", e),
+                Ok(NewBi::Request(mut send, mut recv, prefix)) => match self.side {
+                    Side::Client => {
+                        let _ = send.reset(ErrorCode::STREAM_CREATION_ERROR.into());
+                        let _ = recv.stop(ErrorCode::STREAM_CREATION_ERROR.into());
+                    }
+                    Side::Server => {
+                        self.on_request(send, FrameDecoder::stream_with_prefix(recv, Some(prefix)))
+                    }
                 },
+                Ok(NewBi::WebTransport(session_id, send, recv)) => {
+                    if self.side == Side::Server {
+                        // Not a request, so it must not hold back a graceful shutdown
+                        self.inner.remote_stream_finished(send.id());
+                    }
+                    self.on_session_stream(session_id, SessionStream::Bi(send, recv))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn on_request(&mut self, send: SendStream, recv: FrameStream) {
+        self.requests.push_back((send, recv));
+        if let Some(t) = self.requests_task.take() {
+            t.wake();
+        }
+    }
+
+    fn on_session_stream(
+        &mut self,
+        session_id: u64,
+        stream: SessionStream,
+    ) -> Result<(), DriverError> {
+        let session_stream = StreamId(session_id);
+        if session_stream.initiator() != Side::Client || session_stream.dir() != Dir::Bi {
+            return Err(DriverError::peer(
+                ErrorCode::ID_ERROR,
+                format!("invalid WebTransport session ID {}", session_id),
+            ));
+        }
+
+        if self.side == Side::Server
+            && !self.sessions.contains_key(&session_id)
+            && self.inner.is_request_pending(session_stream)
+        {
+            // The server may see streams of a session before the application accepts it
+            if !self.buffer_session(session_id) {
+                stream.reject(ErrorCode::WEBTRANSPORT_BUFFERED_STREAM_REJECTED);
+                return Ok(());
+            }
+        }
+        let session = match self.sessions.get_mut(&session_id) {
+            Some(s) if !s.closed => s,
+            _ => {
+                trace!(
+                    session = session_id,
+                    "stream of an unknown or closed session"
+                );
+                stream.reject(ErrorCode::WEBTRANSPORT_SESSION_GONE);
+                return Ok(());
+            }
+        };
+        if !session.established && session.bi.len() + session.uni.len() >= MAX_BUFFERED_STREAMS {
+            stream.reject(ErrorCode::WEBTRANSPORT_BUFFERED_STREAM_REJECTED);
+            return Ok(());
+        }
+
+        trace!(session = session_id, "Got WebTransport stream");
+        match stream {
+            SessionStream::Bi(send, recv) => {
+                session.bi.push_back((send, recv));
+                if let Some(t) = session.bi_task.take() {
+                    t.wake();
+                }
+            }
+            SessionStream::Uni(recv) => {
+                session.uni.push_back(recv);
+                if let Some(t) = session.uni_task.take() {
+                    t.wake();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Server: keep the streams of a session not accepted yet, within `MAX_PENDING_SESSIONS`
+    ///
+    /// Returns whether the session's streams can be kept.
+    fn buffer_session(&mut self, session_id: u64) -> bool {
+        let pending =
+            |sessions: &HashMap<u64, Session>| sessions.values().filter(|s| !s.established).count();
+        if pending(&self.sessions) >= MAX_PENDING_SESSIONS {
+            // Forget the sessions whose CONNECT request has been answered otherwise
+            let inner = &self.inner;
+            self.sessions.retain(|id, s| {
+                let keep = s.established || inner.is_request_pending(StreamId(*id));
+                if !keep {
+                    s.close();
+                }
+                keep
+            });
+        }
+        if pending(&self.sessions) >= MAX_PENDING_SESSIONS {
+            trace!(session = session_id, "too many pending sessions");
+            return false;
+        }
+        self.sessions.insert(session_id, Session::default());
+        true
+    }
+
+    fn poll_datagrams(&mut self, cx: &mut Context) -> Result<(), DriverError> {
+        let datagrams = match self.datagrams.as_mut() {
+            Some(d) => d,
+            None => return Ok(()),
+        };
+        loop {
+            let mut datagram = match Pin::new(&mut *datagrams).poll_next(cx)? {
+                Poll::Pending | Poll::Ready(None) => return Ok(()),
+                Poll::Ready(Some(d)) => d,
+            };
+            // Datagrams start with the quarter of their request stream ID
+            let session_id = match datagram.get_var() {
+                Ok(quarter) => quarter.saturating_mul(4),
+                Err(_) => {
+                    return Err(DriverError::peer(
+                        ErrorCode::DATAGRAM_ERROR,
+                        "datagram without stream ID",
+                    ));
+                }
+            };
+            match self.sessions.get_mut(&session_id) {
+                Some(session) if !session.closed => session.on_datagram(datagram),
+                _ => trace!(session = session_id, "dropping datagram"),
+            }
+        }
+    }
+
+    /// Read the capsules sent on the CONNECT stream of each session
+    ///
+    /// A session is closed once the peer ends its stream, or sends something invalid on it.
+    fn poll_sessions(&mut self, cx: &mut Context) {
+        let mut ended = Vec::new();
+        for (id, session) in self.sessions.iter_mut() {
+            if let Err(code) = session.poll_capsules(cx) {
+                if let (Some(code), Some(connect)) = (code, session.connect.as_mut()) {
+                    trace!(session = id, "invalid capsule data");
+                    connect.reset(code);
+                }
+                ended.push(*id);
             }
         }
+        for id in ended {
+            self.close_session(StreamId(id));
+        }
     }
 
     fn poll_incoming_uni(&mut self, cx: &mut Context) -> Result<(), DriverError> {
@@ -287,9 +838,6 @@ impl ConnectionInner {
         for (removed, (i, res)) in resolved.into_iter().enumerate() {
             self.pending_uni.remove(i - removed);
             match res {
-                Err(Error::UnknownStream(ty)) => {
-                    trace!("unknown stream type {}", ty);
-                }
                 Err(e) => {
                     let msg = format!("{:?}", e);
                     return Err(DriverError::new(e, ErrorCode::STREAM_CREATION_ERROR, msg));
@@ -338,14 +886,54 @@ impl ConnectionInner {
                     "encoder stream already open",
                 )),
             },
-            NewUni::Push(_) => {
-                warn!("push stream ignored");
+            NewUni::Push(push) => match self.side {
+                Side::Client => self.on_push_stream(push),
+                Side::Server => Err(DriverError::peer(
+                    ErrorCode::STREAM_CREATION_ERROR,
+                    "client opened a push stream",
+                )),
+            },
+            NewUni::WebTransport(session_id, recv) => {
+                if !self.inner.local_settings().enable_webtransport() {
+                    return Err(DriverError::peer(
+                        ErrorCode::STREAM_CREATION_ERROR,
+                        "WebTransport is not enabled",
+                    ));
+                }
+                self.on_session_stream(session_id, SessionStream::Uni(recv))
+            }
+            NewUni::Unknown(ty, mut recv) => {
+                trace!(stream = %recv.id(), ty = %ty, "ignoring unknown stream");
+                let _ = recv.stop(ErrorCode::STREAM_CREATION_ERROR.into());
                 Ok(())
             }
-            NewUni::Reserved => Ok(()),
         }
     }
 
+    fn on_push_stream(&mut self, push: PushStream) -> Result<(), DriverError> {
+        let PushStream { id, mut recv } = push;
+        self.inner.check_push_id(id)?;
+        trace!(push_id = id, stream = %recv.id(), "Got Push stream");
+        let state = self.pushes.entry(id).or_default();
+        if state.stream.is_some() {
+            return Err(DriverError::peer(
+                ErrorCode::ID_ERROR,
+                format!("push ID {} used by two streams", id),
+            ));
+        }
+        if state.cancelled {
+            let _ = recv.stop(ErrorCode::REQUEST_CANCELLED.into());
+            self.pushes.remove(&id);
+        } else if let Some(claimed) = state.claimed.take() {
+            let stream_id = recv.id();
+            let _ = claimed.send((recv, stream_id));
+            self.pushes.remove(&id);
+        } else {
+            state.stream = Some(recv);
+        }
+        Ok(())
+    }
+
     fn poll_recv_control(&mut self, cx: &mut Context) -> Result<(), DriverError> {
         let mut control = match self.recv_control.as_mut() {
             None => return Ok(()),
@@ -373,20 +961,57 @@ impl ConnectionInner {
                         (_, _, HttpFrame::Settings(s)) => {
                             trace!("Got Settings: {:#?}", s);
                             self.inner.set_remote_settings(Settings::from_frame(s)?)?;
+                            for t in self.settings_tasks.drain(..) {
+                                t.wake();
+                            }
                         }
                         (true, _, HttpFrame::Goaway(id)) => {
                             trace!("Got Goaway({:?})", id);
                             self.inner.leave(StreamId(id));
                         }
-                        (true, Side::Server, HttpFrame::CancelPush(_)) => {
-                            warn!("CANCEL_PUSH frame ignored");
+                        (true, side, HttpFrame::CancelPush(id)) => {
+                            trace!("Got CancelPush({})", id);
+                            self.inner.check_push_id(id)?;
+                            match side {
+                                Side::Client => {
+                                    // The server won't open the stream of a push it cancels, so
+                                    // the push is forgotten once promised
+                                    let push = self.pushes.entry(id).or_default();
+                                    push.cancelled = true;
+                                    push.claimed = None;
+                                    if let Some(mut stream) = push.stream.take() {
+                                        let _ = stream.stop(ErrorCode::REQUEST_CANCELLED.into());
+                                        self.pushes.remove(&id);
+                                    } else if push.promised {
+                                        self.pushes.remove(&id);
+                                    }
+                                }
+                                Side::Server => {
+                                    if let Some(push) = self.pushes.get_mut(&id) {
+                                        push.cancelled = true;
+                                        if let Some(t) = push.task.take() {
+                                            t.wake();
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        (true, Side::Server, HttpFrame::MaxPushId(id)) => {
+                            trace!("Got MaxPushId({})", id);
+                            self.inner.set_max_push_id(id)?;
                         }
-                        (true, Side::Server, HttpFrame::MaxPushId(_)) => {
-                            warn!("MAX_PUSH_ID frame ignored");
+                        (true, Side::Server, HttpFrame::PriorityUpdate(update)) => {
+                            trace!("Got {:?}", update);
+                            self.inner.check_prioritized(update.element)?;
+                            let inner = &self.inner;
+                            self.scheduler.update(update.element, update.priority, |e| {
+                                inner.is_prioritized_pending(e)
+                            });
                         }
                         (true, _, HttpFrame::Reserved) => (),
-                        (false, Side::Server, HttpFrame::CancelPush(_))
+                        (false, _, HttpFrame::CancelPush(_))
                         | (false, Side::Server, HttpFrame::MaxPushId(_))
+                        | (false, Side::Server, HttpFrame::PriorityUpdate(_))
                         | (false, _, HttpFrame::Reserved)
                         | (false, Side::Client, HttpFrame::Goaway(_)) => {
                             return Err(DriverError::peer(
@@ -507,6 +1132,135 @@ impl ConnectionInner {
     }
 }
 
+/// State of a push
+#[derive(Default)]
+struct Push {
+    /// A PUSH_PROMISE frame has been received
+    promised: bool,
+    /// The push stream, received before the application claimed the push
+    stream: Option<RecvStream>,
+    /// Sends the push stream to the application, once received
+    claimed: Option<oneshot::Sender<(RecvStream, StreamId)>>,
+    /// Cancelled by either peer, any push stream received must be stopped
+    cancelled: bool,
+    /// Server: the task sending the pushed response, woken once the client cancels it
+    task: Option<Waker>,
+}
+
+/// The method, URI and headers of a request
+pub(crate) type RequestParts = (Method, Uri, HeaderMap);
+/// The request of a push, as decoded from its first promise
+pub(crate) type PromisedRequest = Result<RequestParts, HeaderError>;
+
+/// State of a request stream carrying HTTP datagrams: a WebTransport session or a UDP tunnel
+#[derive(Default)]
+struct Session {
+    /// The application opened or accepted the session, so its streams are not limited
+    established: bool,
+    closed: bool,
+    /// The CONNECT stream, read until the peer ends it
+    connect: Option<FrameStream>,
+    /// Capsule data received on the CONNECT stream, not yet decoded
+    capsules: BytesMut,
+    /// Bytes of an unknown capsule's payload still to be received, and ignored
+    capsule_skip: u64,
+    bi: VecDeque<(SendStream, RecvStream)>,
+    uni: VecDeque<RecvStream>,
+    datagrams: VecDeque<Bytes>,
+    bi_task: Option<Waker>,
+    uni_task: Option<Waker>,
+    datagrams_task: Option<Waker>,
+}
+
+impl Session {
+    /// Fails once the session must be closed, with the code to reset its stream with, if any
+    fn poll_capsules(&mut self, cx: &mut Context) -> Result<(), Option<ErrorCode>> {
+        loop {
+            let connect = match self.connect.as_mut() {
+                Some(c) => c,
+                None => return Ok(()),
+            };
+            match Pin::new(connect).poll_next(cx) {
+                Poll::Pending => return Ok(()),
+                Poll::Ready(None) | Poll::Ready(Some(Err(frame::Error::Io(_)))) => {
+                    return Err(None)
+                }
+                Poll::Ready(Some(Err(e))) => return Err(Some(e.code())),
+                Poll::Ready(Some(Ok(HttpFrame::Data(mut d)))) => {
+                    let skipped = (d.payload.len() as u64).min(self.capsule_skip);
+                    d.payload.advance(skipped as usize);
+                    self.capsule_skip -= skipped;
+                    self.capsules.extend_from_slice(&d.payload)
+                }
+                Poll::Ready(Some(Ok(HttpFrame::Reserved))) => continue,
+                Poll::Ready(Some(Ok(_))) => return Err(Some(ErrorCode::FRAME_UNEXPECTED)),
+            }
+
+            loop {
+                match Capsule::decode(&mut self.capsules) {
+                    Ok((Capsule::Datagram(payload), _)) => self.on_datagram(payload),
+                    Ok((Capsule::Unknown(ty), skip)) => {
+                        trace!("ignoring capsule {:#x}", ty);
+                        self.capsule_skip = skip;
+                    }
+                    Err(CapsuleError::Incomplete) => break,
+                    Err(CapsuleError::TooLarge(_)) => return Err(Some(ErrorCode::MESSAGE_ERROR)),
+                }
+            }
+        }
+    }
+
+    fn on_datagram(&mut self, datagram: Bytes) {
+        if self.datagrams.len() >= MAX_BUFFERED_DATAGRAMS {
+            self.datagrams.pop_front();
+        }
+        self.datagrams.push_back(datagram);
+        if let Some(t) = self.datagrams_task.take() {
+            t.wake();
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.connect = None;
+        self.capsules.clear();
+        self.datagrams.clear();
+        for (send, recv) in self.bi.drain(..) {
+            SessionStream::Bi(send, recv).reject(ErrorCode::WEBTRANSPORT_SESSION_GONE);
+        }
+        for recv in self.uni.drain(..) {
+            SessionStream::Uni(recv).reject(ErrorCode::WEBTRANSPORT_SESSION_GONE);
+        }
+        let tasks = vec![
+            self.bi_task.take(),
+            self.uni_task.take(),
+            self.datagrams_task.take(),
+        ];
+        for t in tasks.into_iter().flatten() {
+            t.wake();
+        }
+    }
+}
+
+enum SessionStream {
+    Bi(SendStream, RecvStream),
+    Uni(RecvStream),
+}
+
+impl SessionStream {
+    fn reject(self, code: ErrorCode) {
+        match self {
+            SessionStream::Bi(mut send, mut recv) => {
+                let _ = send.reset(code.into());
+                let _ = recv.stop(code.into());
+            }
+            SessionStream::Uni(mut recv) => {
+                let _ = recv.stop(code.into());
+            }
+        }
+    }
+}
+
 struct DriverError(Error, ErrorCode, String);
 
 impl DriverError {
@@ -548,9 +1302,15 @@ impl From<frame::Error> for DriverError {
                 DriverError::peer(ErrorCode::FRAME_ERROR, "Malformed frame received")
             }
             frame::Error::Proto(proto::frame::Error::UnsupportedFrame(t)) => DriverError::peer(
-                ErrorCode::FRAME_ERROR,
+                ErrorCode::FRAME_UNEXPECTED,
                 format!("Unsupported frame received: {:x}", t),
             ),
+            frame::Error::Proto(
+                e @ proto::frame::Error::InvalidSettingId(_)
+                | e @ proto::frame::Error::InvalidSettingValue(..)
+                | e @ proto::frame::Error::SettingRepeated(_)
+                | e @ proto::frame::Error::SettingsExceeded,
+            ) => DriverError::peer(ErrorCode::SETTINGS_ERROR, format!("settings: {:?}", e)),
             frame::Error::Proto(e) => DriverError::internal(format!("frame: {:?}", e)),
         }
     }
@@ -568,8 +1328,15 @@ impl From<ConnectionError> for DriverError {
             ConnectionError::DecodeError { reason } => {
                 DriverError::peer(ErrorCode::QPACK_ENCODER_STREAM_ERROR, format!("{}", reason))
             }
+            ConnectionError::InvalidPushId(id) => {
+                DriverError::peer(ErrorCode::ID_ERROR, format!("invalid push ID {}", id))
+            }
+            ConnectionError::InvalidStreamId(id) => {
+                DriverError::peer(ErrorCode::ID_ERROR, format!("invalid stream ID {}", id))
+            }
             // Those are excepted to happen on in Requests / Responses, just return internal error
             ConnectionError::Aborted
+            | ConnectionError::PushLimitReached
             | ConnectionError::HeaderListTooLarge
             | ConnectionError::InvalidHeaderName(_)
             | ConnectionError::InvalidHeaderValue(_)
@@ -609,3 +1376,9 @@ impl From<SettingsError> for DriverError {
 
 const RECV_ENCODER_INITIAL_CAPACITY: usize = 20480;
 const RECV_DECODER_INITIAL_CAPACITY: usize = 2048;
+/// Streams kept for a WebTransport session the application has not accepted yet
+const MAX_BUFFERED_STREAMS: usize = 16;
+/// Sessions the server keeps streams of before the application accepts them
+const MAX_PENDING_SESSIONS: usize = 16;
+/// Datagrams kept for a WebTransport session, the oldest being dropped beyond
+const MAX_BUFFERED_DATAGRAMS: usize = 64;
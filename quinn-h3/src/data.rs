@@ -17,10 +17,13 @@ use crate::{
     connection::ConnectionRef,
     frame::FrameStream,
     proto::{
-        frame::{DataFrame, FrameHeader, HeadersFrame, HttpFrame, IntoPayload},
+        frame::{DataFrame, FrameHeader, HeadersFrame, HttpFrame, IntoPayload, PushPromiseFrame},
         headers::Header,
+        priority::{Prioritized, Priority},
         ErrorCode,
     },
+    scheduler::{Scheduled, SCHEDULING_QUANTUM},
+    server::PromisedPush,
     streams::Reset,
     Error, HttpError,
 };
@@ -43,6 +46,9 @@ pub struct SendData<B, P> {
     send: SendStream,
     stream_id: StreamId,
     finish: bool,
+    schedule: Option<Scheduled>,
+    /// Fails the response once the client cancels the push
+    promised: Option<PromisedPush>,
 }
 
 #[pin_project(project = SendDataStateProj)]
@@ -77,15 +83,31 @@ where
             stream_id: send.id(),
             send,
             state: SendDataState::Initial,
+            schedule: None,
+            promised: None,
         }
     }
 
+    /// Write the DATA in turns with the other responses of the connection, by priority
+    pub(crate) fn scheduled(mut self, element: Prioritized, priority: Priority) -> Self {
+        self.schedule = Some(Scheduled::new(self.conn.clone(), element, priority));
+        self
+    }
+
+    /// Send the response of a push, until the client cancels it
+    pub(crate) fn pushed(mut self, promised: PromisedPush) -> Self {
+        self.promised = Some(promised);
+        self
+    }
+
     /// Cancel the request
     ///
     /// The peer will receive a request error with `REQUEST_CANCELLED` code.
     pub fn cancel(&mut self) {
         let _ = self.send.reset(ErrorCode::REQUEST_CANCELLED.into());
         self.state = SendDataState::Finished;
+        self.schedule = None;
+        self.promised = None;
     }
 
     /// Monitor stop sending signal from the peer
@@ -106,6 +128,14 @@ where
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         let mut me = self.project();
+        let cancelled = me.promised.as_ref().map(|p| p.poll_cancelled(cx));
+        if let Some(Poll::Ready(())) = cancelled {
+            let _ = me.send.reset(ErrorCode::REQUEST_CANCELLED.into());
+            me.state.set(SendDataState::Finished);
+            *me.schedule = None;
+            *me.promised = None;
+            return Poll::Ready(Err(Error::Http(HttpError::RequestCancelled, None)));
+        }
         loop {
             match &mut me.state.as_mut().project() {
                 SendDataStateProj::Initial => {
@@ -128,10 +158,27 @@ where
                     };
                     me.state.set(next);
                 }
-                SendDataStateProj::Write(write) => {
-                    ready!(write.poll_send(&mut *me.send, cx))?;
-                    me.state.set(SendDataState::PollBody);
-                }
+                SendDataStateProj::Write(write) => match me.schedule {
+                    None => {
+                        ready!(write.poll_send(&mut *me.send, cx))?;
+                        me.state.set(SendDataState::PollBody);
+                    }
+                    Some(schedule) => {
+                        ready!(schedule.poll_turn(cx));
+                        match write.poll_send_part(&mut *me.send, cx, SCHEDULING_QUANTUM)? {
+                            Poll::Ready(true) => {
+                                schedule.idle();
+                                me.state.set(SendDataState::PollBody);
+                            }
+                            Poll::Ready(false) => (),
+                            Poll::Pending => {
+                                // Blocked by flow control: let the responses behind write meanwhile
+                                schedule.idle();
+                                return Poll::Pending;
+                            }
+                        }
+                    }
+                },
                 SendDataStateProj::PollTrailers => {
                     match ready!(Pin::new(&mut me.body).poll_trailers(cx))
                         .map_err(|_| todo!())
@@ -150,6 +197,8 @@ where
                     me.state.set(SendDataState::Closing);
                 }
                 SendDataStateProj::Closing => {
+                    me.schedule.take();
+                    me.promised.take();
                     ready!(Pin::new(me.send).poll_finish(cx))?;
                     if *me.finish {
                         let mut conn = me.conn.h3.lock().unwrap();
@@ -214,6 +263,19 @@ where
         send: &mut SendStream,
         cx: &mut Context,
     ) -> Poll<Result<(), quinn::WriteError>> {
+        while !ready!(self.poll_send_part(send, cx, usize::MAX))? {}
+        Poll::Ready(Ok(()))
+    }
+
+    /// Write the frame's header and at most `max` bytes of its payload
+    ///
+    /// Resolves to whether the whole frame has been written.
+    pub(crate) fn poll_send_part(
+        &mut self,
+        send: &mut SendStream,
+        cx: &mut Context,
+        max: usize,
+    ) -> Poll<Result<bool, quinn::WriteError>> {
         loop {
             match self.state {
                 WriteFrameState::Finished => panic!("polled after finish"),
@@ -231,18 +293,19 @@ where
                 }
                 WriteFrameState::Payload => {
                     let p = self.frame.into_payload();
-                    match ready!(send.write(p.bytes()).poll_unpin(cx)) {
+                    let part = &p.bytes()[..p.bytes().len().min(max)];
+                    match ready!(send.write(part).poll_unpin(cx)) {
                         Err(e) => return Poll::Ready(Err(e)),
                         Ok(wrote) => {
                             p.advance(wrote);
                             if p.has_remaining() {
-                                continue;
+                                return Poll::Ready(Ok(false));
                             }
                         }
                     }
 
                     self.state = WriteFrameState::Finished;
-                    return Poll::Ready(Ok(()));
+                    return Poll::Ready(Ok(true));
                 }
             }
         }
@@ -254,6 +317,8 @@ pub struct RecvData {
     conn: ConnectionRef,
     recv: Option<FrameStream>,
     stream_id: StreamId,
+    /// A PUSH_PROMISE frame received, being handled
+    promise: Option<PushPromiseFrame>,
 }
 
 enum RecvDataState {
@@ -269,6 +334,7 @@ impl RecvData {
             stream_id,
             recv: Some(recv),
             state: RecvDataState::Receiving,
+            promise: None,
         }
     }
 
@@ -285,8 +351,13 @@ impl Future for RecvData {
         loop {
             match &mut self.state {
                 RecvDataState::Receiving => {
+                    if let Some(promise) = self.promise.as_ref() {
+                        ready!(self.conn.poll_push_promise(cx, self.stream_id, promise))?;
+                        self.promise = None;
+                    }
                     match ready!(Pin::new(self.recv.as_mut().unwrap()).poll_next(cx)) {
                         Some(Ok(HttpFrame::Reserved)) => continue,
+                        Some(Ok(HttpFrame::PushPromise(p))) => self.promise = Some(p),
                         Some(Ok(HttpFrame::Headers(h))) => {
                             self.state = RecvDataState::Decoding(DecodeHeaders::new(
                                 h,
@@ -1,6 +1,6 @@
 use std::io;
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use quinn::RecvStream;
 use tokio::io::AsyncRead;
 use tokio_util::codec::{Decoder, FramedRead};
@@ -20,15 +20,26 @@ impl Reset for FrameStream {
 pub struct FrameDecoder {
     partial: Option<PartialData>,
     expected: Option<usize>,
+    /// Bytes already read from the stream, decoded before anything else
+    prefix: Option<Bytes>,
 }
 
 impl FrameDecoder {
     pub fn stream<T: AsyncRead>(stream: T) -> FramedRead<T, Self> {
+        Self::stream_with_prefix(stream, None)
+    }
+
+    /// Decode frames from a stream whose first bytes have already been read as `prefix`
+    pub fn stream_with_prefix<T: AsyncRead>(
+        stream: T,
+        prefix: Option<Bytes>,
+    ) -> FramedRead<T, Self> {
         FramedRead::with_capacity(
             stream,
             FrameDecoder {
                 expected: None,
                 partial: None,
+                prefix,
             },
             65535,
         )
@@ -48,6 +59,12 @@ impl Decoder for FrameDecoder {
     type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(prefix) = self.prefix.take() {
+            let rest = src.split();
+            src.extend_from_slice(&prefix);
+            src.unsplit(rest);
+        }
+
         if src.is_empty() {
             return Ok(None);
         }
@@ -113,7 +130,11 @@ impl Error {
     pub fn code(&self) -> ErrorCode {
         match self {
             Error::Io(_) => ErrorCode::GENERAL_PROTOCOL_ERROR,
-            Error::Proto(frame::Error::Settings(_)) => ErrorCode::SETTINGS_ERROR,
+            Error::Proto(frame::Error::Settings(_))
+            | Error::Proto(frame::Error::InvalidSettingId(_))
+            | Error::Proto(frame::Error::InvalidSettingValue(..))
+            | Error::Proto(frame::Error::SettingRepeated(_))
+            | Error::Proto(frame::Error::SettingsExceeded) => ErrorCode::SETTINGS_ERROR,
             Error::Proto(frame::Error::UnsupportedFrame(_)) => ErrorCode::FRAME_UNEXPECTED,
             Error::Proto(_) => ErrorCode::FRAME_ERROR,
         }
@@ -8,7 +8,7 @@
 //!
 //! # About HTTP/3
 //!
-//! HTTP/3 is the upcoming third major version of the Hypertext Transfer Protocol. It's built on top
+//! HTTP/3 is the third major version of the Hypertext Transfer Protocol. It's built on top
 //! of a new transport protocol over UDP: [QUIC]. It addresses the shortcomings that [HTTP/2]
 //! suffers from being built on top of TCP, such as [Head-Of-Line blocking] and handshake latencies.
 //! Thanks to QUIC streams multiplexing features over UDP, requests are made independent from each other
@@ -18,8 +18,8 @@
 //!
 //! It also offers Server Push features similar to HTTP/2.
 //!
-//! The HTTP/3 specification has not yet been stabilized. The version currently implemented by this
-//! crate is [draft-27].
+//! This crate implements HTTP/3 as published in [RFC 9114], along with [QPACK] from [RFC 9204].
+//! Servers can also offer the last draft version, for peers which don't speak the final one yet.
 //!
 //! # Crate overview
 //!
@@ -33,14 +33,15 @@
 //! you'll have to implement your own if you want more power. For the receive side, body can be
 //! handled using [`RecvBody`].
 //!
+//! With the `tower` feature, the [`service`] module makes clients and servers usable as [`tower`]
+//! services.
+//!
 //! # Crate status
 //!
 //! This crate is in an experimental state. For now, it has multiple improvement vectors:
 //!
 //! * Though having been tested as compatible with a majority of other HTTP/3 implementations,
 //!   `quinn-h3` does not implement all interoperability tests for the moment.
-//! * The CONNECT method and associated proxy features are not implemented.
-//! * Server PUSH is not implemented.
 //!
 //! # Getting started
 //!
@@ -58,8 +59,9 @@
 //! [QUIC]: https://en.wikipedia.org/wiki/QUIC
 //! [HTTP/2]: https://en.wikipedia.org/wiki/HTTP/2
 //! [Head-Of-Line blocking]: https://en.wikipedia.org/wiki/Head-of-line_blocking
-//! [QPACK]: https://datatracker.ietf.org/doc/draft-ietf-quic-qpack/
-//! [draft-27]: https://tools.ietf.org/html/draft-ietf-quic-http-27
+//! [QPACK]: https://www.rfc-editor.org/rfc/rfc9204.html
+//! [RFC 9114]: https://www.rfc-editor.org/rfc/rfc9114.html
+//! [RFC 9204]: https://www.rfc-editor.org/rfc/rfc9204.html
 //! [Server Push]: https://en.wikipedia.org/wiki/HTTP/2_Server_Push
 //! [`client`]: client/index.html
 //! [`server`]: server/index.html
@@ -69,6 +71,8 @@
 //! [`http_body`]: https://docs.rs/http-body/*/http_body/index.html
 //! [`SimpleBody`]: struct.SimpleBody.html
 //! [`RecvBody`]: struct.RecvBody.html
+//! [`service`]: service/index.html
+//! [`tower`]: https://docs.rs/tower
 
 #![warn(missing_docs)]
 #![allow(clippy::identity_op)]
@@ -89,17 +93,26 @@ mod tests;
 
 pub use body::{Body, RecvBody};
 pub use data::SendData;
-pub use proto::settings::Settings;
+pub use proto::{headers::Protocol, priority::Priority, settings::Settings};
+pub use qpack::InsertionPolicy;
+pub use tunnel::{RecvTunnel, SendTunnel};
 
 pub mod client;
+pub mod masque;
 pub mod server;
+#[cfg(feature = "tower")]
+pub mod service;
+pub mod webtransport;
 
 mod body;
 mod connection;
 mod data;
 mod frame;
+mod pool;
 mod proto;
+mod scheduler;
 mod streams;
+mod tunnel;
 
 #[cfg(not(feature = "interop-test-accessors"))]
 mod qpack;
@@ -144,9 +157,6 @@ pub enum Error {
     /// The peer's behavior was detected as incorrect or malicious
     #[error("Incorrect peer behavior: {0}")]
     Peer(String),
-    /// The peer tried to open an unidirectional stream with an unknown type code
-    #[error("unknown stream type {0}")]
-    UnknownStream(u64),
     /// An IO error occurred
     #[error("IO error: {0}")]
     Io(#[source] std::io::Error),
@@ -165,6 +175,18 @@ pub enum Error {
     /// Polling the issued body data yielded an error
     #[error("Polling body error: {0}")]
     Body(Box<dyn StdError + Send + Sync>),
+    /// The server answered a CONNECT request with an unsuccessful status
+    #[error("CONNECT refused with status {0}")]
+    ConnectRefused(http::StatusCode),
+    /// A setting the operation relies on is not enabled locally or by the peer
+    #[error("{0} is not enabled")]
+    SettingDisabled(&'static str),
+    /// A `QUIC` datagram could not be sent
+    #[error("Datagram error: {0}")]
+    Datagram(#[source] quinn::SendDatagramError),
+    /// No connection could be established to the server
+    #[error("Connect error: {0}")]
+    Connect(#[from] quinn::ConnectHostError),
 }
 
 impl Error {
@@ -289,7 +311,7 @@ impl From<proto::headers::Error> for Error {
 ///
 /// Read the [HTTP/3 specification] for more details.
 ///
-/// [HTTP/3 specification]: https://quicwg.org/base-drafts/draft-ietf-quic-http.html#name-http-3-error-codes
+/// [HTTP/3 specification]: https://www.rfc-editor.org/rfc/rfc9114.html#name-http-3-error-codes
 #[derive(Clone, Debug)]
 pub enum HttpError {
     /// This is used when the connection or stream needs to be closed, but there is no error to signal
@@ -320,6 +342,8 @@ pub enum HttpError {
     RequestCancelled,
     /// The client's stream terminated without containing a fully-formed request
     RequestIncomplete,
+    /// An HTTP message was malformed and cannot be processed
+    MessageError,
     /// The connection established in response to a CONNECT request was reset or abnormally closed
     ConnectError,
     /// The requested operation cannot be served over HTTP/3. The peer should retry over HTTP/1.1
@@ -351,6 +375,7 @@ impl From<ErrorCode> for HttpError {
             ErrorCode::REQUEST_REJECTED => HttpError::RequestRejected,
             ErrorCode::REQUEST_CANCELLED => HttpError::RequestCancelled,
             ErrorCode::REQUEST_INCOMPLETE => HttpError::RequestIncomplete,
+            ErrorCode::MESSAGE_ERROR => HttpError::MessageError,
             ErrorCode::CONNECT_ERROR => HttpError::ConnectError,
             ErrorCode::VERSION_FALLBACK => HttpError::VersionFallback,
             ErrorCode::QPACK_DECOMPRESSION_FAILED => HttpError::QpackDecompressionFailed,
@@ -368,7 +393,12 @@ impl From<VarInt> for HttpError {
 }
 
 /// TLS ALPN value for the HTTP/3 protocol
-pub const ALPN: &[u8] = b"h3-29";
+pub const ALPN: &[u8] = b"h3";
+
+/// TLS ALPN values of the HTTP/3 drafts a server can also offer
+///
+/// See [`server::Builder::draft_versions()`](server/struct.Builder.html#method.draft_versions).
+pub const DRAFT_ALPN: &[&[u8]] = &[b"h3-29"];
 
 impl From<frame::Error> for (ErrorCode, String, Error) {
     fn from(err: frame::Error) -> Self {
@@ -0,0 +1,283 @@
+//! Proxying UDP in HTTP, also known as MASQUE CONNECT-UDP
+//!
+//! A client asks a proxy to open a UDP flow to a target with an extended CONNECT request, whose
+//! `:protocol` is [`Protocol::CONNECT_UDP`], as defined by [RFC 9298]. Such a request is built
+//! with [`udp_request()`] and sent with [`client::Connection::connect_udp()`]. The proxy finds
+//! the target with [`udp_target()`], accepts the request with
+//! [`server::Sender::accept_connect_udp()`], and can relay the flow with [`UdpTunnel::proxy()`].
+//!
+//! UDP payloads are carried in HTTP datagrams, as defined by [RFC 9297], which both endpoints
+//! must enable in their [`Settings`]. The proxy must also enable the extended CONNECT protocol.
+//!
+//! ```
+//! use std::net::{IpAddr, SocketAddr};
+//!
+//! use anyhow::{anyhow, Result};
+//! use quinn_h3::{masque, server::RecvRequest};
+//! use tokio::net::UdpSocket;
+//!
+//! async fn proxy_udp(recv_request: RecvRequest) -> Result<()> {
+//!     let (request, mut sender) = recv_request.await?;
+//!     let (host, port) = masque::udp_target(request.uri()).ok_or(anyhow!("no target"))?;
+//!     let target = SocketAddr::new(host.parse::<IpAddr>()?, port);
+//!     let socket = UdpSocket::bind("[::]:0".parse::<SocketAddr>()?).await?;
+//!     socket.connect(target).await?;
+//!
+//!     let tunnel = sender.accept_connect_udp(request).await?;
+//!     tunnel.proxy(socket).await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [`Protocol::CONNECT_UDP`]: ../struct.Protocol.html#associatedconstant.CONNECT_UDP
+//! [RFC 9298]: https://www.rfc-editor.org/rfc/rfc9298.html
+//! [RFC 9297]: https://www.rfc-editor.org/rfc/rfc9297.html
+//! [`udp_request()`]: fn.udp_request.html
+//! [`udp_target()`]: fn.udp_target.html
+//! [`client::Connection::connect_udp()`]: ../client/struct.Connection.html#method.connect_udp
+//! [`server::Sender::accept_connect_udp()`]: ../server/struct.Sender.html#method.accept_connect_udp
+//! [`UdpTunnel::proxy()`]: struct.UdpTunnel.html#method.proxy
+//! [`Settings`]: ../struct.Settings.html
+
+use std::{
+    fmt, io,
+    task::{Context, Poll},
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::future;
+use http::{Method, Request, Uri};
+use quinn::{SendDatagramError, SendStream};
+use quinn_proto::{coding::BufExt, StreamId};
+use tokio::net::UdpSocket;
+use tracing::{debug, trace};
+
+use crate::{
+    connection::ConnectionRef,
+    data::WriteFrame,
+    proto::{capsule::Capsule, frame::DataFrame},
+    Error,
+};
+
+/// Path of the default URI template, followed by the target host and port
+const WELL_KNOWN_PATH: &str = "/.well-known/masque/udp/";
+/// Context ID of the datagrams carrying UDP payloads
+const UDP_PAYLOAD_CONTEXT: u8 = 0;
+/// Largest UDP payload read from a proxied socket
+const MAX_UDP_PAYLOAD: usize = 65_527;
+
+/// Build a CONNECT-UDP request for `proxy`, to reach `target_host` on `target_port`
+///
+/// `proxy` is the authority of the proxy, such as `"proxy.example.com:443"`. The request follows
+/// the default URI template, `https://{proxy}/.well-known/masque/udp/{host}/{port}/`.
+pub fn udp_request(
+    proxy: &str,
+    target_host: &str,
+    target_port: u16,
+) -> Result<Request<()>, http::Error> {
+    // Colons of IPv6 addresses are not allowed in a path segment
+    let host = target_host.replace(':', "%3A");
+    Request::builder()
+        .method(Method::CONNECT)
+        .uri(format!(
+            "https://{}{}{}/{}/",
+            proxy, WELL_KNOWN_PATH, host, target_port
+        ))
+        .header("capsule-protocol", "?1")
+        .body(())
+}
+
+/// Find the target host and port of a CONNECT-UDP request following the default URI template
+pub fn udp_target(uri: &Uri) -> Option<(String, u16)> {
+    let target = uri.path().strip_prefix(WELL_KNOWN_PATH)?;
+    let mut segments = target.splitn(3, '/');
+    let host = segments.next()?.replace("%3A", ":").replace("%3a", ":");
+    let port = segments.next()?.parse().ok()?;
+    match segments.next() {
+        Some("") if !host.is_empty() => Some((host, port)),
+        _ => None,
+    }
+}
+
+/// An established CONNECT-UDP tunnel
+///
+/// Each datagram carries one UDP payload. Dropping the tunnel closes it, ending the request
+/// stream.
+pub struct UdpTunnel {
+    id: StreamId,
+    conn: ConnectionRef,
+    send: SendStream,
+}
+
+impl UdpTunnel {
+    pub(crate) fn new(send: SendStream, conn: ConnectionRef) -> Self {
+        Self {
+            id: send.id(),
+            send,
+            conn,
+        }
+    }
+
+    /// Send a UDP payload in a datagram
+    ///
+    /// Fails if the peer did not enable HTTP datagrams in its settings.
+    pub fn send_datagram(&self, payload: Bytes) -> Result<(), Error> {
+        self.conn.send_datagram(self.id, &udp_payload(&payload))
+    }
+
+    /// Send a UDP payload in a capsule on the request stream
+    ///
+    /// This is reliable, unlike [`send_datagram()`], and works without QUIC datagram support.
+    ///
+    /// [`send_datagram()`]: #method.send_datagram
+    pub async fn send_capsule(&mut self, payload: Bytes) -> Result<(), Error> {
+        let capsule = Capsule::Datagram(udp_payload(&payload));
+        let mut buf = BytesMut::with_capacity(capsule.len());
+        capsule.encode(&mut buf);
+        let mut write = WriteFrame::new(DataFrame {
+            payload: buf.freeze(),
+        });
+        let send = &mut self.send;
+        future::poll_fn(|cx| write.poll_send(send, cx)).await?;
+        Ok(())
+    }
+
+    /// Receive the next UDP payload sent by the peer, in a datagram or a capsule
+    ///
+    /// Returns `None` once the tunnel is closed. Only a limited number of payloads are kept
+    /// waiting, the oldest being dropped first.
+    pub async fn recv_datagram(&self) -> Option<Bytes> {
+        future::poll_fn(|cx| self.poll_recv_datagram(cx)).await
+    }
+
+    /// Relay the tunnel's UDP payloads to and from `socket`, until the tunnel is closed
+    ///
+    /// `socket` must be connected to the target. Payloads too large for a datagram and
+    /// payloads the socket can't take right away are dropped, as UDP would. When the target
+    /// refuses an earlier payload, the error is logged and the relaying goes on; other errors
+    /// receiving from the socket end it.
+    pub async fn proxy(self, socket: UdpSocket) -> Result<(), Error> {
+        let mut buf = vec![0; MAX_UDP_PAYLOAD];
+        future::poll_fn(|cx| loop {
+            let mut progress = false;
+            match socket.poll_recv(cx, &mut buf) {
+                Poll::Ready(Ok(read)) => {
+                    let payload = Bytes::copy_from_slice(&buf[..read]);
+                    match self.send_datagram(payload) {
+                        Err(Error::Datagram(SendDatagramError::TooLarge)) => {
+                            trace!(stream = %self.id, "dropping large UDP payload");
+                        }
+                        Err(e) => return Poll::Ready(Err(e)),
+                        Ok(()) => (),
+                    }
+                    progress = true;
+                }
+                // An ICMP error for an earlier payload, which UDP doesn't stop at
+                Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                    debug!(stream = %self.id, "UDP receive error: {}", e);
+                    progress = true;
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => (),
+            }
+            match self.poll_recv_datagram(cx) {
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Ready(Some(payload)) => {
+                    let _ = socket.poll_send(cx, &payload);
+                    progress = true;
+                }
+                Poll::Pending => (),
+            }
+            if !progress {
+                return Poll::Pending;
+            }
+        })
+        .await
+    }
+
+    fn poll_recv_datagram(&self, cx: &mut Context) -> Poll<Option<Bytes>> {
+        loop {
+            let poll = self
+                .conn
+                .h3
+                .lock()
+                .unwrap()
+                .poll_session_datagram(self.id, cx);
+            let mut datagram = match poll {
+                Poll::Ready(Some(d)) => d,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            // Datagrams with unknown context IDs must be dropped
+            match datagram.get_var() {
+                Ok(context) if context == u64::from(UDP_PAYLOAD_CONTEXT) => {
+                    return Poll::Ready(Some(datagram))
+                }
+                _ => trace!(stream = %self.id, "dropping datagram of unknown context"),
+            }
+        }
+    }
+}
+
+impl Drop for UdpTunnel {
+    fn drop(&mut self) {
+        self.conn.h3.lock().unwrap().close_session(self.id);
+    }
+}
+
+impl fmt::Debug for UdpTunnel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpTunnel").field("id", &self.id).finish()
+    }
+}
+
+fn udp_payload(payload: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(1 + payload.len());
+    buf.extend_from_slice(&[UDP_PAYLOAD_CONTEXT]);
+    buf.extend_from_slice(payload);
+    buf.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_round_trip() {
+        let request = udp_request("proxy.example.com:443", "192.0.2.6", 443).unwrap();
+        assert_eq!(
+            request.uri(),
+            "https://proxy.example.com:443/.well-known/masque/udp/192.0.2.6/443/"
+        );
+        assert_eq!(
+            udp_target(request.uri()),
+            Some(("192.0.2.6".to_string(), 443))
+        );
+    }
+
+    #[test]
+    fn ipv6_target() {
+        let request = udp_request("proxy.example.com", "2001:db8::42", 53).unwrap();
+        assert_eq!(
+            request.uri().path(),
+            "/.well-known/masque/udp/2001%3Adb8%3A%3A42/53/"
+        );
+        assert_eq!(
+            udp_target(request.uri()),
+            Some(("2001:db8::42".to_string(), 53))
+        );
+    }
+
+    #[test]
+    fn invalid_targets() {
+        for uri in &[
+            "https://proxy/.well-known/masque/udp/example.com/",
+            "https://proxy/.well-known/masque/udp/example.com/port/",
+            "https://proxy/.well-known/masque/udp//53/",
+            "https://proxy/.well-known/masque/udp/example.com/53",
+            "https://proxy/.well-known/masque/tcp/example.com/53/",
+        ] {
+            assert_eq!(udp_target(&uri.parse().unwrap()), None, "{}", uri);
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use bytes::Buf;
+use futures::{
+    future::{self, BoxFuture, Shared},
+    ready, FutureExt,
+};
+use http::{request, uri::Authority, HeaderMap, Method, Request, Response, Uri, Version};
+use http_body::Body as HttpBody;
+use quinn::{ConnectHostError, Resolver, SystemResolver};
+use tracing::{debug, trace};
+
+use crate::{
+    body::RecvBody,
+    client::{Client, Connection},
+    proto::alt_svc::{Alternative, ALT_SVC_HEADER},
+    Error, HttpError, Priority,
+};
+
+/// A client reusing its connections across requests
+///
+/// Requests are sent over a connection to their origin, the host and port of their URI, opened
+/// on the first request to it. Requests arriving while it's being opened wait for it, and the
+/// next ones reuse it, until it's closed or the server sends GOAWAY: a new connection is then
+/// opened. As each connection serves at most [`max_concurrent_requests()`] at once, more
+/// connections are opened when they are all busy.
+///
+/// A request the server provably didn't process, as when it was sent on a connection going away
+/// or closing meanwhile, is sent once more on another connection, unless its body already
+/// yielded data.
+///
+/// Servers can advertise an HTTP/3 alternative for their origin with an `Alt-Svc` response
+/// header. The pool then sends the origin's requests to the alternative until it expires, or
+/// until it fails to connect.
+///
+/// Dropping the pool closes its connections.
+///
+/// ```
+/// # use anyhow::Result;
+/// use http::Request;
+/// use quinn_h3::{client::{Client, Pool}, Body};
+///
+/// async fn get_both(pool: &Pool) -> Result<()> {
+///     // The second request waits for the connection opened by the first one
+///     let (first, second) = futures::join!(
+///         pool.send_request(Request::get("https://example.com/a").body(Body::from(()))?),
+///         pool.send_request(Request::get("https://example.com/b").body(Body::from(()))?),
+///     );
+///     let body = first?.body_mut().read_to_end().await?;
+///     Ok(())
+/// }
+/// ```
+///
+/// [`max_concurrent_requests()`]: #method.max_concurrent_requests
+pub struct Pool {
+    client: Arc<Client>,
+    resolver: Arc<dyn Resolver>,
+    max_concurrent_requests: usize,
+    state: Arc<Mutex<State>>,
+}
+
+impl Pool {
+    /// Pool the connections of `client`
+    pub fn new(client: Client) -> Self {
+        Self {
+            client: Arc::new(client),
+            resolver: Arc::new(SystemResolver),
+            max_concurrent_requests: 100,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    /// Maximum number of requests sent concurrently over one connection, 100 by default
+    ///
+    /// A request counts until its response headers are received.
+    pub fn max_concurrent_requests(&mut self, count: usize) -> &mut Self {
+        self.max_concurrent_requests = count.max(1);
+        self
+    }
+
+    /// Set the resolver used to look up the hosts to connect to
+    ///
+    /// Defaults to the operating system's resolver.
+    pub fn resolver(&mut self, resolver: Arc<dyn Resolver>) -> &mut Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Send a request over a connection to its origin, and receive the response
+    ///
+    /// Resolves once the request is sent and the response headers are received. The URI's
+    /// authority, or the `host` header, designates the origin, on port 443 unless specified.
+    pub async fn send_request<B>(&self, request: Request<B>) -> Result<Response<RecvBody>, Error>
+    where
+        B: HttpBody + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>> + Send + Sync,
+    {
+        let origin = Host::origin(&request).ok_or(Error::Header("Missing authority"))?;
+        let (parts, body) = request.into_parts();
+        let retry = Retry::new(&parts, body);
+        let mut request = Request::from_parts(parts, retry.body());
+        let mut retried = false;
+        let response = loop {
+            let pooled = self.connection(&origin).await?;
+            let (send, mut recv) = pooled.0.connection.send_request(request);
+            let (sent, response) = future::join(send, &mut recv).await;
+            let error = match response {
+                Ok(response) => {
+                    if let Err(e) = sent {
+                        trace!("request not entirely sent: {}", e);
+                    }
+                    break response;
+                }
+                // The request's own error tells why its stream was never opened
+                Err(e) => match sent {
+                    Err(sent) if !recv.is_opened() => sent,
+                    _ => e,
+                },
+            };
+
+            let rejected = matches!(error.reason(), Some(HttpError::RequestRejected));
+            if rejected {
+                // The server is going away, its GOAWAY may be yet to come
+                pooled.0.rejected.store(true, Ordering::Relaxed);
+            }
+            let unprocessed = match error {
+                _ if rejected || recv.is_beyond_go_away() => true,
+                Error::Aborted | Error::Quic(_) => !recv.is_opened(),
+                _ => false,
+            };
+            if retried || !unprocessed || retry.is_consumed() {
+                return Err(error);
+            }
+            debug!("sending request to {} again: {}", origin, error);
+            retried = true;
+            request = retry.request();
+        };
+
+        if response.headers().contains_key(ALT_SVC_HEADER) {
+            self.alternatives_received(origin, &response);
+        }
+        Ok(response)
+    }
+
+    /// Find a connection that can take a request for `origin`, or open one
+    async fn connection(&self, origin: &Host) -> Result<InFlight, Error> {
+        let alternative = self.alternative(origin);
+        let mut error = None;
+        for target in alternative.iter().chain(Some(origin)) {
+            match self.connection_to(origin, target).await {
+                Ok(in_flight) => return Ok(in_flight),
+                Err(e) if alternative.as_ref() == Some(target) => {
+                    debug!("alternative for {} failed: {}", origin, e);
+                    self.state.lock().unwrap().alternatives.remove(origin);
+                    error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(error.unwrap())
+    }
+
+    /// Take a request slot on a connection to `target`, waiting for the one being opened, if any
+    async fn connection_to(&self, origin: &Host, target: &Host) -> Result<InFlight, Error> {
+        loop {
+            let pending = {
+                let mut state = self.state.lock().unwrap();
+                if let Some(in_flight) = self.pooled(&mut state, origin, target) {
+                    return Ok(in_flight);
+                }
+                let key = (origin.clone(), target.clone());
+                match state.pending.get(&key) {
+                    Some(pending) => pending.clone(),
+                    None => {
+                        let pending = self.connect(origin, target);
+                        state.pending.insert(key, pending.clone());
+                        pending
+                    }
+                }
+            };
+            // Once connected, the slots are taken on a first come, first served basis
+            pending.await.map_err(|e| clone_error(&e))?;
+        }
+    }
+
+    /// Take a request slot on a pooled connection to `target`, dropping the closed connections
+    fn pooled(&self, state: &mut State, origin: &Host, target: &Host) -> Option<InFlight> {
+        let connections = state.connections.get_mut(origin)?;
+        // Connections going away are kept until closed, as responses may still be received
+        connections.retain(|c| !c.connection.is_closed());
+        let pooled = connections.iter().find(|c| {
+            c.target == *target
+                && !c.connection.is_closing()
+                && !c.rejected.load(Ordering::Relaxed)
+                && c.in_flight() < self.max_concurrent_requests
+        })?;
+        pooled.in_flight.fetch_add(1, Ordering::Relaxed);
+        Some(InFlight(pooled.clone()))
+    }
+
+    /// Open a connection to `target` for `origin`, pooled once established
+    fn connect(&self, origin: &Host, target: &Host) -> PendingConnect {
+        let client = self.client.clone();
+        let resolver = self.resolver.clone();
+        // Weak, so that a connection left pending doesn't keep the pool's state alive
+        let state = Arc::downgrade(&self.state);
+        let key = (origin.clone(), target.clone());
+        async move {
+            let (origin, target) = &key;
+            let result = client
+                .connect_resolved(&*resolver, &target.name, target.port, &origin.name)
+                .await;
+            let state = match Weak::upgrade(&state) {
+                Some(state) => state,
+                None => return Ok(()),
+            };
+            let mut state = state.lock().unwrap();
+            state.pending.remove(&key);
+            let connection = result.map_err(Arc::new)?;
+            debug!("connected to {} for {}", target, origin);
+            let (origin, target) = key;
+            state
+                .connections
+                .entry(origin)
+                .or_default()
+                .push(Arc::new(Pooled {
+                    connection,
+                    target,
+                    in_flight: AtomicUsize::new(0),
+                    rejected: AtomicBool::new(false),
+                }));
+            Ok(())
+        }
+        .boxed()
+        .shared()
+    }
+
+    /// The alternative to connect to for `origin`, if one is advertised and still fresh
+    fn alternative(&self, origin: &Host) -> Option<Host> {
+        let mut state = self.state.lock().unwrap();
+        let (alternative, expiry) = state.alternatives.get(origin)?;
+        if *expiry > Instant::now() {
+            return Some(alternative.clone());
+        }
+        state.alternatives.remove(origin);
+        None
+    }
+
+    /// Replace the alternatives cached for `origin` by the ones advertised in `response`
+    fn alternatives_received<B>(&self, origin: Host, response: &Response<B>) {
+        let alternative = response
+            .headers()
+            .get_all(ALT_SVC_HEADER)
+            .iter()
+            .flat_map(|value| Alternative::parse(value.as_bytes(), "h3"))
+            .next();
+
+        let mut state = self.state.lock().unwrap();
+        match alternative {
+            Some(alternative) if alternative.max_age.as_secs() > 0 => {
+                trace!("alternative for {}: {:?}", origin, alternative);
+                let target = Host {
+                    name: match alternative.host.is_empty() {
+                        true => origin.name.clone(),
+                        false => alternative.host,
+                    },
+                    port: alternative.port,
+                };
+                let expiry = Instant::now() + alternative.max_age;
+                state.alternatives.insert(origin, (target, expiry));
+            }
+            _ => {
+                state.alternatives.remove(&origin);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    connections: HashMap<Host, Vec<Arc<Pooled>>>,
+    /// Connection being opened for each origin and target, awaited by the requests to them
+    pending: HashMap<(Host, Host), PendingConnect>,
+    /// Advertised alternative for each origin, along with its expiry
+    alternatives: HashMap<Host, (Host, Instant)>,
+}
+
+type PendingConnect = Shared<BoxFuture<'static, Result<(), Arc<ConnectHostError>>>>;
+
+/// Copy the error of a connection attempt for each request that awaited it
+fn clone_error(error: &ConnectHostError) -> Error {
+    match error {
+        ConnectHostError::InvalidHost(host) => ConnectHostError::InvalidHost(host.clone()),
+        ConnectHostError::Resolve(e) => {
+            ConnectHostError::Resolve(io::Error::new(e.kind(), e.to_string()))
+        }
+        ConnectHostError::NoAddresses => ConnectHostError::NoAddresses,
+        ConnectHostError::Connect(e) => ConnectHostError::Connect(e.clone()),
+        ConnectHostError::Connection(e) => ConnectHostError::Connection(e.clone()),
+    }
+    .into()
+}
+
+struct Pooled {
+    connection: Connection,
+    /// Where the connection goes, the origin or one of its alternatives
+    target: Host,
+    in_flight: AtomicUsize,
+    /// The server rejected a request, so no more are sent
+    rejected: AtomicBool,
+}
+
+impl Pooled {
+    fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+}
+
+/// A request slot on a pooled connection, released on drop
+struct InFlight(Arc<Pooled>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// What's needed to send a request again: its head, and a body shared by both attempts
+struct Retry<B> {
+    method: Method,
+    uri: Uri,
+    version: Version,
+    headers: HeaderMap,
+    priority: Option<Priority>,
+    body: Arc<Mutex<Replay<B>>>,
+}
+
+impl<B: HttpBody> Retry<B> {
+    fn new(parts: &request::Parts, body: B) -> Self {
+        Self {
+            method: parts.method.clone(),
+            uri: parts.uri.clone(),
+            version: parts.version,
+            headers: parts.headers.clone(),
+            priority: parts.extensions.get::<Priority>().copied(),
+            body: Arc::new(Mutex::new(Replay {
+                body: Box::pin(body),
+                consumed: false,
+                ended: false,
+                trailers: None,
+            })),
+        }
+    }
+
+    /// A copy of the request, the other extensions than its priority being lost
+    fn request(&self) -> Request<RetryBody<B>> {
+        let mut request = Request::new(self.body());
+        *request.method_mut() = self.method.clone();
+        *request.uri_mut() = self.uri.clone();
+        *request.version_mut() = self.version;
+        *request.headers_mut() = self.headers.clone();
+        if let Some(priority) = self.priority {
+            request.extensions_mut().insert(priority);
+        }
+        request
+    }
+
+    fn body(&self) -> RetryBody<B> {
+        RetryBody(self.body.clone())
+    }
+
+    /// Whether the body can't be sent again
+    fn is_consumed(&self) -> bool {
+        self.body.lock().unwrap().consumed
+    }
+}
+
+/// A request body which can be sent again until it yields data
+struct RetryBody<B>(Arc<Mutex<Replay<B>>>);
+
+struct Replay<B> {
+    body: Pin<Box<B>>,
+    /// Data or an error was yielded
+    consumed: bool,
+    /// The end of the data was yielded, the body isn't polled for data again
+    ended: bool,
+    /// The trailers yielded, to be yielded again
+    trailers: Option<Option<HeaderMap>>,
+}
+
+impl<B: HttpBody> HttpBody for RetryBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let mut replay = self.0.lock().unwrap();
+        if replay.ended {
+            return Poll::Ready(None);
+        }
+        let data = ready!(replay.body.as_mut().poll_data(cx));
+        match data {
+            None => replay.ended = true,
+            Some(Ok(ref data)) if !data.has_remaining() => (),
+            Some(_) => replay.consumed = true,
+        }
+        Poll::Ready(data)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let mut replay = self.0.lock().unwrap();
+        if let Some(ref trailers) = replay.trailers {
+            return Poll::Ready(Ok(trailers.clone()));
+        }
+        let trailers = ready!(replay.body.as_mut().poll_trailers(cx));
+        match trailers {
+            Ok(ref trailers) => replay.trailers = Some(trailers.clone()),
+            Err(_) => replay.consumed = true,
+        }
+        Poll::Ready(trailers)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Host {
+    name: String,
+    port: u16,
+}
+
+impl Host {
+    fn origin<B>(request: &Request<B>) -> Option<Self> {
+        let host_header = || {
+            let host = request.headers().get("host")?.to_str().ok()?;
+            host.parse::<Authority>().ok()
+        };
+        let authority = match request.uri().authority() {
+            Some(authority) => authority.clone(),
+            None => host_header()?,
+        };
+        let name = authority.host();
+        let name = match name.strip_prefix('[') {
+            Some(name) => name.strip_suffix(']')?,
+            None => name,
+        };
+        Some(Self {
+            name: name.to_ascii_lowercase(),
+            port: authority.port_u16().unwrap_or(443),
+        })
+    }
+}
+
+impl std::fmt::Display for Host {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.name, self.port)
+    }
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+/// An alternative service advertised by an origin, as defined by [RFC 7838]
+///
+/// [RFC 7838]: https://www.rfc-editor.org/rfc/rfc7838.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alternative {
+    /// Empty when the alternative is on the origin's host
+    pub host: String,
+    pub port: u16,
+    /// How long the alternative can be used for
+    pub max_age: Duration,
+}
+
+impl Alternative {
+    /// Parse an `alt-svc` header value, keeping the alternatives for `protocol`
+    ///
+    /// A value clearing the alternatives yields none, while invalid alternatives are skipped.
+    pub fn parse(value: &[u8], protocol: &str) -> Vec<Self> {
+        let value = match std::str::from_utf8(value) {
+            Ok(v) => v,
+            Err(_) => return Vec::new(),
+        };
+        value
+            .split(',')
+            .filter_map(|member| Self::parse_member(member, protocol))
+            .collect()
+    }
+
+    fn parse_member(member: &str, protocol: &str) -> Option<Self> {
+        let mut params = member.split(';');
+        let mut alternative = params.next().unwrap().trim().splitn(2, '=');
+        match (alternative.next(), alternative.next()) {
+            (Some(id), Some(authority)) if id == protocol => {
+                let authority = authority.strip_prefix('"')?.strip_suffix('"')?;
+                let colon = authority.rfind(':')?;
+                let host = &authority[..colon];
+                let host = match host.strip_prefix('[') {
+                    Some(host) => host.strip_suffix(']')?,
+                    None => host,
+                };
+                let mut max_age = DEFAULT_MAX_AGE;
+                for param in params {
+                    let mut key_value = param.trim().splitn(2, '=');
+                    if let (Some("ma"), Some(ma)) = (key_value.next(), key_value.next()) {
+                        max_age = Duration::from_secs(ma.parse().ok()?);
+                    }
+                }
+                Some(Self {
+                    host: host.to_ascii_lowercase(),
+                    port: authority[colon + 1..].parse().ok()?,
+                    max_age,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Freshness of alternatives which don't specify any
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub const ALT_SVC_HEADER: &str = "alt-svc";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Vec<Alternative> {
+        Alternative::parse(value.as_bytes(), "h3")
+    }
+
+    fn alternative(host: &str, port: u16, max_age: u64) -> Alternative {
+        Alternative {
+            host: host.into(),
+            port,
+            max_age: Duration::from_secs(max_age),
+        }
+    }
+
+    #[test]
+    fn parse_alternatives() {
+        assert_eq!(parse(r#"h3=":443""#), vec![alternative("", 443, 86400)]);
+        assert_eq!(
+            parse(r#"h3="Alt.example.com:8443"; ma=60; persist=1, h3=":443""#),
+            vec![
+                alternative("alt.example.com", 8443, 60),
+                alternative("", 443, 86400)
+            ]
+        );
+        assert_eq!(
+            parse(r#"h3="[::1]:4433";ma=0"#),
+            vec![alternative("::1", 4433, 0)]
+        );
+    }
+
+    #[test]
+    fn parse_other_protocols_and_clear() {
+        assert_eq!(parse(r#"h2=":443", h3-29=":443""#), vec![]);
+        assert_eq!(
+            parse(r#"h2=":443"; ma=60, h3=":4433""#),
+            vec![alternative("", 4433, 86400)]
+        );
+        assert_eq!(parse("clear"), vec![]);
+    }
+
+    #[test]
+    fn parse_skips_invalid_alternatives() {
+        assert_eq!(
+            parse(r#"h3=:443, h3=":x", h3=":443"; ma=soon, h3="[::1:443", h3=":4433""#),
+            vec![alternative("", 4433, 86400)]
+        );
+        assert_eq!(Alternative::parse(&[0xff, b'h'], "h3"), vec![]);
+    }
+}
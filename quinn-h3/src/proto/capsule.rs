@@ -0,0 +1,167 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use quinn_proto::{
+    coding::{BufExt, BufMutExt, Codec, UnexpectedEnd},
+    VarInt,
+};
+
+/// Largest known capsule accepted, so a peer can't make us buffer an unbounded amount of data
+///
+/// Unknown capsules are skipped as they arrive, whatever their size.
+pub const MAX_CAPSULE_SIZE: usize = 65_536;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CapsuleType(u64);
+
+macro_rules! capsule_types {
+    {$($name:ident = $val:expr,)*} => {
+        impl CapsuleType {
+            $(pub const $name: CapsuleType = CapsuleType($val);)*
+        }
+    }
+}
+
+capsule_types! {
+    DATAGRAM = 0x00,
+}
+
+impl Codec for CapsuleType {
+    fn decode<B: Buf>(buf: &mut B) -> Result<Self, UnexpectedEnd> {
+        Ok(CapsuleType(buf.get_var()?))
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B) {
+        buf.write_var(self.0);
+    }
+}
+
+/// A capsule, as defined by the Capsule Protocol of RFC 9297
+///
+/// Capsules are carried in the DATA frames of a request stream, a capsule possibly spanning
+/// several frames.
+#[derive(Debug, PartialEq)]
+pub enum Capsule {
+    /// An HTTP datagram sent on the request stream instead of a QUIC datagram
+    Datagram(Bytes),
+    /// A capsule whose type is unknown, and must be ignored
+    Unknown(u64),
+}
+
+impl Capsule {
+    /// Decode a capsule, leaving `buf` untouched unless it holds a complete capsule
+    ///
+    /// The payload of an unknown capsule is not needed: it's consumed as far as `buf` holds
+    /// it, and the number of its bytes yet to come is returned, for the caller to skip.
+    pub fn decode(buf: &mut BytesMut) -> Result<(Self, u64), Error> {
+        let mut header = &buf[..];
+        let ty = CapsuleType::decode(&mut header).map_err(|_| Error::Incomplete)?;
+        let len = header.get_var().map_err(|_| Error::Incomplete)?;
+        let header_len = buf.len() - header.len();
+        if ty != CapsuleType::DATAGRAM {
+            buf.advance(header_len);
+            let skipped = (buf.len() as u64).min(len);
+            buf.advance(skipped as usize);
+            return Ok((Capsule::Unknown(ty.0), len - skipped));
+        }
+        // Compared before any cast, which would truncate on 32-bit targets
+        if len > MAX_CAPSULE_SIZE as u64 {
+            return Err(Error::TooLarge(len));
+        }
+        if buf.len() < header_len + len as usize {
+            return Err(Error::Incomplete);
+        }
+
+        buf.advance(header_len);
+        let payload = buf.split_to(len as usize);
+        Ok((Capsule::Datagram(payload.freeze()), 0))
+    }
+
+    pub fn encode<B: BufMut>(&self, buf: &mut B) {
+        match self {
+            Capsule::Datagram(payload) => {
+                CapsuleType::DATAGRAM.encode(buf);
+                buf.write_var(payload.len() as u64);
+                buf.put_slice(payload);
+            }
+            Capsule::Unknown(ty) => {
+                CapsuleType(*ty).encode(buf);
+                buf.write_var(0);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let (ty, payload) = match self {
+            Capsule::Datagram(payload) => (CapsuleType::DATAGRAM.0, payload.len()),
+            Capsule::Unknown(ty) => (*ty, 0),
+        };
+        VarInt::from_u64(ty).unwrap().size()
+            + VarInt::from_u64(payload as u64).unwrap().size()
+            + payload
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// More data is needed to decode the next capsule
+    Incomplete,
+    /// The capsule's length exceeds `MAX_CAPSULE_SIZE`
+    TooLarge(u64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn datagram_capsule() {
+        let capsule = Capsule::Datagram(Bytes::from_static(b"hello"));
+        let mut buf = Vec::new();
+        capsule.encode(&mut buf);
+        assert_eq!(buf.len(), capsule.len());
+        assert_eq!(&buf[..2], &[0, 5]);
+
+        let mut read = BytesMut::from(&buf[..]);
+        assert_eq!(Capsule::decode(&mut read), Ok((capsule, 0)));
+        assert!(read.is_empty());
+    }
+
+    #[test]
+    fn unknown_capsule_skipped() {
+        let mut read = BytesMut::from(&[0x3f, 2, 0xff, 0xff, 0, 1, 42][..]);
+        assert_eq!(Capsule::decode(&mut read), Ok((Capsule::Unknown(0x3f), 0)));
+        assert_eq!(
+            Capsule::decode(&mut read),
+            Ok((Capsule::Datagram(Bytes::from_static(&[42])), 0))
+        );
+    }
+
+    #[test]
+    fn large_unknown_capsule_skipped() {
+        // 1 MiB of payload, only 2 bytes of which are received yet
+        let mut read = BytesMut::from(&[0x3f, 0x80, 0x10, 0x00, 0x00, 0xff, 0xff][..]);
+        assert_eq!(
+            Capsule::decode(&mut read),
+            Ok((Capsule::Unknown(0x3f), 1024 * 1024 - 2))
+        );
+        assert!(read.is_empty());
+    }
+
+    #[test]
+    fn incomplete_capsule() {
+        let mut read = BytesMut::from(&[0, 3, 1, 2][..]);
+        assert_eq!(Capsule::decode(&mut read), Err(Error::Incomplete));
+        assert_eq!(read.len(), 4);
+    }
+
+    #[test]
+    fn capsule_too_large() {
+        let mut read = BytesMut::from(&[0, 0x80, 0x01, 0, 1][..]);
+        assert_eq!(Capsule::decode(&mut read), Err(Error::TooLarge(65_537)));
+
+        // Would be taken for a 1-byte capsule if truncated to 32 bits
+        let mut read = BytesMut::from(&[0, 0xc0, 0, 0, 0x01, 0, 0, 0, 0x01, 0xff][..]);
+        assert_eq!(
+            Capsule::decode(&mut read),
+            Err(Error::TooLarge(0x1_0000_0001))
+        );
+    }
+}
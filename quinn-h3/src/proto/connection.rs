@@ -7,8 +7,9 @@ use tracing::{debug, trace, warn};
 
 use crate::{
     proto::{
-        frame::{HeadersFrame, HttpFrame},
+        frame::{HeadersFrame, HttpFrame, PriorityUpdateFrame},
         headers::{self, Header},
+        priority::{Prioritized, Priority},
     },
     qpack::{self, DecoderError, DynamicTable, EncoderError, HeaderField},
     Settings,
@@ -42,6 +43,7 @@ impl PendingStreamType {
 
 pub struct Connection {
     side: Side,
+    local_settings: Settings,
     remote_settings: Option<Settings>,
     decoder_table: DynamicTable,
     encoder_table: DynamicTable,
@@ -49,6 +51,10 @@ pub struct Connection {
     requests_in_flight: HashSet<StreamId>,
     max_id_in_flight: StreamId,
     go_away: Option<StreamId>,
+    /// Largest push ID the client allows: advertised by the client, received by the server
+    max_push_id: Option<u64>,
+    /// Push ID of the next push promised by the server
+    next_push_id: u64,
 
     #[cfg(feature = "interop-test-accessors")]
     pub had_refs: bool,
@@ -64,8 +70,16 @@ impl Connection {
             .set_max_size(settings.qpack_max_table_capacity() as usize)
             .expect("set max table size");
 
+        let mut encoder_table = DynamicTable::new();
+        encoder_table.set_insertion_policy(settings.qpack_insertion_policy());
+
+        // Grease the SETTINGS frame and the control stream with reserved values, so peers
+        // keep ignoring unknown extensions
+        let mut settings_frame = settings.to_frame();
+        settings_frame.grease();
         let mut pending_control = BytesMut::with_capacity(128);
-        settings.to_frame().encode(&mut pending_control);
+        settings_frame.encode(&mut pending_control);
+        HttpFrame::Reserved.encode(&mut pending_control);
         let pending_streams = [
             pending_control,
             BytesMut::with_capacity(2048),
@@ -81,11 +95,14 @@ impl Connection {
             side,
             decoder_table,
             pending_streams,
+            local_settings: settings,
             remote_settings: None,
-            encoder_table: DynamicTable::new(),
+            encoder_table,
             requests_in_flight: HashSet::with_capacity(32),
             max_id_in_flight: StreamId::new(side, dir, 0),
             go_away: None,
+            max_push_id: None,
+            next_push_id: 0,
 
             #[cfg(feature = "interop-test-accessors")]
             had_refs: false,
@@ -159,6 +176,10 @@ impl Connection {
         Ok(qpack::on_decoder_recv(&mut self.encoder_table, read)?)
     }
 
+    pub fn local_settings(&self) -> &Settings {
+        &self.local_settings
+    }
+
     pub fn remote_settings(&self) -> &Option<Settings> {
         &self.remote_settings
     }
@@ -204,7 +225,7 @@ impl Connection {
         }
         debug!(id=%id, "accepting");
         self.requests_in_flight.insert(id);
-        if id.index() > self.max_id_in_flight.index() {
+        if id.index() > self.max_id_in_flight.index() || self.none_received() {
             self.max_id_in_flight = id
         };
         Ok(())
@@ -241,6 +262,11 @@ impl Connection {
         self.requests_in_flight.retain(|i| i.0 <= max_id.0);
     }
 
+    // Client: whether the server's GOAWAY tells that request stream `id` wasn't processed
+    pub fn is_beyond_go_away(&self, id: StreamId) -> bool {
+        matches!(self.go_away, Some(max_id) if id.index() > max_id.index())
+    }
+
     pub fn stream_cancel(&mut self, stream_id: StreamId) {
         qpack::stream_canceled(
             stream_id.0,
@@ -248,6 +274,99 @@ impl Connection {
         );
     }
 
+    // Client: let the server promise `count` more pushes
+    pub fn allow_pushes(&mut self, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let max_push_id = match self.max_push_id {
+            Some(x) => x + count,
+            None => count - 1,
+        };
+        trace!(max_push_id=%max_push_id, "allowing pushes");
+        self.max_push_id = Some(max_push_id);
+        HttpFrame::MaxPushId(max_push_id)
+            .encode(&mut self.pending_streams[PendingStreamType::Control as usize]);
+    }
+
+    // Server: the client allowed pushes up to `max_push_id`
+    pub fn set_max_push_id(&mut self, max_push_id: u64) -> Result<()> {
+        if self.max_push_id.map_or(false, |x| max_push_id < x) {
+            return Err(Error::InvalidPushId(max_push_id));
+        }
+        self.max_push_id = Some(max_push_id);
+        Ok(())
+    }
+
+    // Server: allocate the ID of a new push
+    pub fn next_push_id(&mut self) -> Result<u64> {
+        match self.max_push_id {
+            Some(max) if self.next_push_id <= max => {
+                self.next_push_id += 1;
+                Ok(self.next_push_id - 1)
+            }
+            _ => Err(Error::PushLimitReached),
+        }
+    }
+
+    // Check that the peer referred to a push ID which could have been promised
+    pub fn check_push_id(&self, push_id: u64) -> Result<()> {
+        let valid = match self.side {
+            Side::Client => self.max_push_id.map_or(false, |x| push_id <= x),
+            Side::Server => push_id < self.next_push_id,
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(Error::InvalidPushId(push_id)),
+        }
+    }
+
+    // Client: reprioritize a request or a push
+    pub fn priority_update(&mut self, element: Prioritized, priority: Priority) {
+        HttpFrame::PriorityUpdate(PriorityUpdateFrame { element, priority })
+            .encode(&mut self.pending_streams[PendingStreamType::Control as usize]);
+    }
+
+    // Server: check that a PRIORITY_UPDATE refers to a request stream or a promised push
+    pub fn check_prioritized(&self, element: Prioritized) -> Result<()> {
+        match element {
+            Prioritized::Request(id) => {
+                let id = StreamId(id);
+                match (id.initiator(), id.dir()) {
+                    (Side::Client, Dir::Bi) => Ok(()),
+                    _ => Err(Error::InvalidStreamId(id.0)),
+                }
+            }
+            Prioritized::Push(id) => self.check_push_id(id),
+        }
+    }
+
+    // Server: whether a response may still be sent for a request in flight or not opened yet
+    pub fn is_prioritized_pending(&self, element: Prioritized) -> bool {
+        match element {
+            Prioritized::Request(id) => self.is_request_pending(StreamId(id)),
+            Prioritized::Push(_) => true,
+        }
+    }
+
+    // Server: whether request stream `id` is in flight, or not opened yet
+    pub fn is_request_pending(&self, id: StreamId) -> bool {
+        self.requests_in_flight.contains(&id)
+            || self.none_received()
+            || id.index() > self.max_id_in_flight.index()
+    }
+
+    // Until a stream is received, the local side's first stream stands as the largest ID
+    fn none_received(&self) -> bool {
+        self.max_id_in_flight.initiator() == self.side
+    }
+
+    pub fn cancel_push(&mut self, push_id: u64) {
+        debug!(push_id=%push_id, "cancelling push");
+        HttpFrame::CancelPush(push_id)
+            .encode(&mut self.pending_streams[PendingStreamType::Control as usize]);
+    }
+
     pub fn shutdown_complete(&self) -> bool {
         self.go_away.is_some() && self.requests_in_flight.is_empty()
     }
@@ -267,6 +386,9 @@ pub enum Error {
     InvalidHeaderValue(String),
     InvalidRequest(String),
     InvalidResponse(String),
+    InvalidPushId(u64),
+    InvalidStreamId(u64),
+    PushLimitReached,
     Settings { reason: String },
     EncodeError { reason: EncoderError },
     DecodeError { reason: DecoderError },
@@ -303,6 +425,9 @@ impl From<headers::Error> for Error {
             headers::Error::ContradictedAuthority => {
                 Error::InvalidRequest(":authority and Host are different".into())
             }
+            headers::Error::InvalidConnect => {
+                Error::InvalidRequest("CONNECT with :scheme or :path".into())
+            }
             headers::Error::MissingStatus => Error::InvalidResponse("missing status".into()),
         }
     }
@@ -317,6 +442,20 @@ mod tests {
         Method,
     };
 
+    #[test]
+    fn request_pending() {
+        let mut conn = Connection::new(Side::Server, Settings::new());
+        assert!(conn.is_request_pending(StreamId(0)));
+        conn.remote_stream_initiated(StreamId(0)).unwrap();
+        conn.remote_stream_finished(StreamId(0));
+        assert!(!conn.is_request_pending(StreamId(0)));
+        conn.remote_stream_initiated(StreamId(4)).unwrap();
+        assert!(conn.is_request_pending(StreamId(4)));
+        assert!(conn.is_request_pending(StreamId(8)));
+        conn.remote_stream_finished(StreamId(4));
+        assert!(!conn.is_request_pending(StreamId(4)));
+    }
+
     #[test]
     fn encode_no_dynamic() {
         let mut header_map = HeaderMap::new();
@@ -363,6 +502,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn push_ids() {
+        let mut client = Connection::new(Side::Client, Settings::new());
+        assert_eq!(client.check_push_id(0), Err(Error::InvalidPushId(0)));
+        client.allow_pushes(2);
+        assert_matches!(client.check_push_id(1), Ok(()));
+        assert_eq!(client.check_push_id(2), Err(Error::InvalidPushId(2)));
+        client.allow_pushes(1);
+        assert_matches!(client.check_push_id(2), Ok(()));
+
+        let mut server = Connection::new(Side::Server, Settings::new());
+        assert_eq!(server.next_push_id(), Err(Error::PushLimitReached));
+        server.set_max_push_id(1).unwrap();
+        assert_eq!(server.next_push_id(), Ok(0));
+        assert_eq!(server.next_push_id(), Ok(1));
+        assert_eq!(server.next_push_id(), Err(Error::PushLimitReached));
+        assert_matches!(server.check_push_id(1), Ok(()));
+        assert_eq!(server.check_push_id(2), Err(Error::InvalidPushId(2)));
+        assert_eq!(server.set_max_push_id(0), Err(Error::InvalidPushId(0)));
+    }
+
     #[test]
     fn decode_header() {
         let mut header_map = HeaderMap::new();
@@ -7,7 +7,10 @@ use quinn_proto::{
 };
 use tracing::trace;
 
-use super::settings::{Error as SettingsError, SettingId, SettingsFrame};
+use super::{
+    priority::{Prioritized, Priority},
+    settings::{Error as SettingsError, SettingId, SettingsFrame},
+};
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
@@ -34,7 +37,9 @@ pub enum HttpFrame {
     PushPromise(PushPromiseFrame),
     Goaway(u64),
     MaxPushId(u64),
-    DuplicatePush(u64),
+    /// Opens a bidirectional WebTransport stream, for the session with this ID
+    WebTransportStream(u64),
+    PriorityUpdate(PriorityUpdateFrame),
     Reserved,
 }
 
@@ -48,14 +53,28 @@ impl HttpFrame {
             HttpFrame::PushPromise(f) => f.encode(buf),
             HttpFrame::Goaway(id) => simple_frame_encode(Type::GOAWAY, *id, buf),
             HttpFrame::MaxPushId(id) => simple_frame_encode(Type::MAX_PUSH_ID, *id, buf),
-            HttpFrame::DuplicatePush(id) => simple_frame_encode(Type::DUPLICATE_PUSH, *id, buf),
-            HttpFrame::Reserved => (),
+            HttpFrame::WebTransportStream(id) => {
+                Type::WEBTRANSPORT_STREAM.encode(buf);
+                buf.write_var(*id);
+            }
+            HttpFrame::PriorityUpdate(f) => f.encode(buf),
+            HttpFrame::Reserved => {
+                Type(super::reserved_id()).encode(buf);
+                buf.write_var(0);
+            }
         }
     }
 
     pub fn decode<T: Buf>(buf: &mut T) -> Result<Self, Error> {
         let remaining = buf.remaining();
         let ty = Type::decode(buf).map_err(|_| Error::Incomplete(remaining + 1))?;
+        if ty == Type::WEBTRANSPORT_STREAM {
+            // Not a frame: the session ID is followed by the stream's payload, with no length
+            let session_id = buf
+                .get_var()
+                .map_err(|_| Error::Incomplete(remaining + 1))?;
+            return Ok(HttpFrame::WebTransportStream(session_id));
+        }
         let len = buf
             .get_var()
             .map_err(|_| Error::Incomplete(remaining + 1))?;
@@ -80,11 +99,13 @@ impl HttpFrame {
             )?)),
             Type::GOAWAY => Ok(HttpFrame::Goaway(payload.get_var()?)),
             Type::MAX_PUSH_ID => Ok(HttpFrame::MaxPushId(payload.get_var()?)),
-            Type::DUPLICATE_PUSH => Ok(HttpFrame::DuplicatePush(payload.get_var()?)),
+            Type::PRIORITY_UPDATE_REQUEST | Type::PRIORITY_UPDATE_PUSH => Ok(
+                HttpFrame::PriorityUpdate(PriorityUpdateFrame::decode(ty, &mut payload)?),
+            ),
             Type::H2_PRIORITY | Type::H2_PING | Type::H2_WINDOW_UPDATE | Type::H2_CONTINUATION => {
                 Err(Error::UnsupportedFrame(ty.0))
             }
-            t if t.0 > 0x21 && (t.0 - 0x21) % 0x1f == 0 => {
+            t if super::is_reserved(t.0) => {
                 buf.advance(len as usize);
                 Ok(HttpFrame::Reserved)
             }
@@ -115,7 +136,8 @@ impl fmt::Display for HttpFrame {
             HttpFrame::PushPromise(frame) => write!(f, "PushPromise({})", frame.id),
             HttpFrame::Goaway(id) => write!(f, "GoAway({})", id),
             HttpFrame::MaxPushId(id) => write!(f, "MaxPushId({})", id),
-            HttpFrame::DuplicatePush(id) => write!(f, "DuplicatePush({})", id),
+            HttpFrame::WebTransportStream(id) => write!(f, "WebTransportStream({})", id),
+            HttpFrame::PriorityUpdate(frame) => write!(f, "PriorityUpdate({:?})", frame.element),
             HttpFrame::Reserved => write!(f, "Reserved"),
         }
     }
@@ -140,11 +162,13 @@ frame_types! {
     H2_WINDOW_UPDATE = 0x8,
     H2_CONTINUATION = 0x9,
     MAX_PUSH_ID = 0xD,
-    DUPLICATE_PUSH = 0xE,
+    WEBTRANSPORT_STREAM = 0x41,
+    PRIORITY_UPDATE_REQUEST = 0xF0700,
+    PRIORITY_UPDATE_PUSH = 0xF0701,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub(crate) struct Type(u64);
+pub(crate) struct Type(pub(crate) u64);
 
 impl Codec for Type {
     fn decode<B: Buf>(buf: &mut B) -> Result<Self, UnexpectedEnd> {
@@ -267,8 +291,8 @@ impl IntoPayload for HeadersFrame {
 
 #[derive(Debug, PartialEq)]
 pub struct PushPromiseFrame {
-    id: u64,
-    encoded: Bytes,
+    pub id: u64,
+    pub encoded: Bytes,
 }
 
 impl FrameHeader for PushPromiseFrame {
@@ -292,6 +316,38 @@ impl PushPromiseFrame {
     }
 }
 
+/// Reprioritizes a request or a push, sent by the client on its control stream
+#[derive(Debug, PartialEq)]
+pub struct PriorityUpdateFrame {
+    pub element: Prioritized,
+    pub priority: Priority,
+}
+
+impl PriorityUpdateFrame {
+    fn decode<B: Buf>(ty: Type, buf: &mut B) -> Result<Self, UnexpectedEnd> {
+        let id = buf.get_var()?;
+        let element = match ty {
+            Type::PRIORITY_UPDATE_PUSH => Prioritized::Push(id),
+            _ => Prioritized::Request(id),
+        };
+        // Parameters absent from the update take their default value, not their previous one
+        let priority = Priority::default().parse(&buf.to_bytes());
+        Ok(Self { element, priority })
+    }
+
+    fn encode<B: BufMut>(&self, buf: &mut B) {
+        let (ty, id) = match self.element {
+            Prioritized::Request(id) => (Type::PRIORITY_UPDATE_REQUEST, id),
+            Prioritized::Push(id) => (Type::PRIORITY_UPDATE_PUSH, id),
+        };
+        let value = self.priority.to_string();
+        ty.encode(buf);
+        buf.write_var((VarInt::from_u64(id).unwrap().size() + value.len()) as u64);
+        buf.write_var(id);
+        buf.put_slice(value.as_bytes());
+    }
+}
+
 fn simple_frame_encode<B: BufMut>(ty: Type, id: u64, buf: &mut B) {
     ty.encode(buf);
     buf.write_var(1);
@@ -395,7 +451,56 @@ mod tests {
         codec_frame_check(HttpFrame::CancelPush(2), &[3, 1, 2]);
         codec_frame_check(HttpFrame::Goaway(2), &[7, 1, 2]);
         codec_frame_check(HttpFrame::MaxPushId(2), &[13, 1, 2]);
-        codec_frame_check(HttpFrame::DuplicatePush(2), &[14, 1, 2]);
+    }
+
+    #[test]
+    fn duplicate_push_ignored() {
+        // DUPLICATE_PUSH was dropped from the final specification
+        let mut buf = Cursor::new(&[14, 1, 2, 3, 1, 2]);
+        assert_eq!(HttpFrame::decode(&mut buf), Err(Error::UnknownFrame(14)));
+        assert_eq!(HttpFrame::decode(&mut buf), Ok(HttpFrame::CancelPush(2)));
+    }
+
+    #[test]
+    fn h2_frames_rejected() {
+        for &ty in &[2, 6, 8, 9] {
+            let mut buf = Cursor::new(vec![ty, 0]);
+            assert_eq!(
+                HttpFrame::decode(&mut buf),
+                Err(Error::UnsupportedFrame(ty as u64))
+            );
+        }
+    }
+
+    #[test]
+    fn webtransport_stream() {
+        codec_frame_check(HttpFrame::WebTransportStream(4), &[0x40, 0x41, 4]);
+
+        // The stream's payload follows, and is left untouched
+        let mut buf = Cursor::new(&[0x40, 0x41, 4, 1, 2, 3]);
+        let decoded = HttpFrame::decode(&mut buf);
+        assert_eq!(decoded, Ok(HttpFrame::WebTransportStream(4)));
+        assert_eq!(buf.remaining(), 3);
+    }
+
+    #[test]
+    fn priority_update_frames() {
+        codec_frame_check(
+            HttpFrame::PriorityUpdate(PriorityUpdateFrame {
+                element: Prioritized::Request(4),
+                priority: Priority::new(1, true),
+            }),
+            &[
+                0x80, 0x0f, 0x07, 0x00, 7, 4, b'u', b'=', b'1', b',', b' ', b'i',
+            ],
+        );
+        codec_frame_check(
+            HttpFrame::PriorityUpdate(PriorityUpdateFrame {
+                element: Prioritized::Push(2),
+                priority: Priority::default(),
+            }),
+            &[0x80, 0x0f, 0x07, 0x01, 1, 2],
+        );
     }
 
     #[test]
@@ -423,5 +528,14 @@ mod tests {
         let mut buf = Cursor::new(&raw);
         let decoded = HttpFrame::decode(&mut buf);
         assert_eq!(decoded, Ok(HttpFrame::Reserved));
+
+        // The first reserved type, and the greasing frames sent
+        let mut buf = Cursor::new(&[0x21, 0]);
+        assert_eq!(HttpFrame::decode(&mut buf), Ok(HttpFrame::Reserved));
+        let mut raw = vec![];
+        HttpFrame::Reserved.encode(&mut raw);
+        let mut buf = Cursor::new(&raw);
+        assert_eq!(HttpFrame::decode(&mut buf), Ok(HttpFrame::Reserved));
+        assert!(!buf.has_remaining());
     }
 }
@@ -25,7 +25,14 @@ pub struct Header {
 impl Header {
     pub fn request(method: Method, uri: Uri, fields: HeaderMap) -> Self {
         Self {
-            pseudo: Pseudo::request(method, uri),
+            pseudo: Pseudo::request(method, uri, None),
+            fields,
+        }
+    }
+
+    pub fn extended_connect(uri: Uri, protocol: Protocol, fields: HeaderMap) -> Self {
+        Self {
+            pseudo: Pseudo::request(Method::CONNECT, uri, Some(protocol)),
             fields,
         }
     }
@@ -45,6 +52,16 @@ impl Header {
     }
 
     pub fn into_request_parts(self) -> Result<(Method, Uri, HeaderMap), Error> {
+        match (&self.pseudo.method, &self.pseudo.protocol) {
+            (Some(m), None) if m == Method::CONNECT => return self.into_connect_parts(),
+            // An extended CONNECT request is otherwise a regular request, see RFC 8441
+            (Some(m), Some(_)) if m != Method::CONNECT => return Err(Error::InvalidConnect),
+            (_, Some(_)) if self.pseudo.scheme.is_none() || self.pseudo.path.is_none() => {
+                return Err(Error::InvalidConnect)
+            }
+            _ => (),
+        }
+
         let mut uri = Uri::builder();
 
         if let Some(path) = self.pseudo.path {
@@ -70,6 +87,23 @@ impl Header {
         ))
     }
 
+    // A CONNECT request only carries the authority to tunnel to, see RFC 7540 section 8.3.
+    fn into_connect_parts(self) -> Result<(Method, Uri, HeaderMap), Error> {
+        if self.pseudo.scheme.is_some() || self.pseudo.path.is_some() {
+            return Err(Error::InvalidConnect);
+        }
+        let authority = self.pseudo.authority.ok_or(Error::MissingAuthority)?;
+        let uri = Uri::builder()
+            .authority(authority.as_str().as_bytes())
+            .build()
+            .map_err(Error::InvalidRequest)?;
+        Ok((Method::CONNECT, uri, self.fields))
+    }
+
+    pub fn protocol(&self) -> Option<&Protocol> {
+        self.pseudo.protocol.as_ref()
+    }
+
     pub fn into_response_parts(self) -> Result<(StatusCode, HeaderMap), Error> {
         Ok((self.pseudo.status.ok_or(Error::MissingStatus)?, self.fields))
     }
@@ -125,6 +159,10 @@ impl Iterator for HeaderIter {
                 return Some((":path", path.as_str().as_bytes()).into());
             }
 
+            if let Some(protocol) = pseudo.protocol.take() {
+                return Some((":protocol", protocol.as_str().as_bytes()).into());
+            }
+
             if let Some(status) = pseudo.status.take() {
                 return Some((":status", status.as_str()).into());
             }
@@ -134,7 +172,8 @@ impl Iterator for HeaderIter {
 
         while let Some(f) = self.fields.next() {
             if let (Some(n), v) = f {
-                return Some((n.as_str(), v.as_bytes()).into());
+                let field = HeaderField::from((n.as_str(), v.as_bytes()));
+                return Some(field.with_sensitive(v.is_sensitive()));
             }
         }
 
@@ -149,6 +188,7 @@ impl TryFrom<Vec<HeaderField>> for Header {
         let mut pseudo = Pseudo::default();
 
         for field in headers.into_iter() {
+            let sensitive = field.sensitive;
             let (name, value) = field.into_inner();
             match Field::parse(name, value)? {
                 Field::Method(m) => {
@@ -171,7 +211,12 @@ impl TryFrom<Vec<HeaderField>> for Header {
                     pseudo.status = Some(s);
                     pseudo.len += 1;
                 }
-                Field::Header((n, v)) => {
+                Field::Protocol(p) => {
+                    pseudo.protocol = Some(p);
+                    pseudo.len += 1;
+                }
+                Field::Header((n, mut v)) => {
+                    v.set_sensitive(sensitive);
                     fields.append(n, v);
                 }
             }
@@ -187,6 +232,7 @@ enum Field {
     Authority(Authority),
     Path(PathAndQuery),
     Status(StatusCode),
+    Protocol(Protocol),
     Header((HeaderName, HeaderValue)),
 }
 
@@ -226,6 +272,11 @@ impl Field {
                 StatusCode::from_bytes(value.as_ref())
                     .map_err(|_| Error::invalid_value(name, value))?,
             ),
+            PseudoType::PROTOCOL => Field::Protocol(Protocol(
+                String::from_utf8(value.as_ref().to_vec())
+                    .map_err(|_| Error::invalid_value(name, value))?
+                    .into(),
+            )),
         })
     }
 }
@@ -254,6 +305,7 @@ struct Pseudo {
     scheme: Option<Scheme>,
     authority: Option<Authority>,
     path: Option<PathAndQuery>,
+    protocol: Option<Protocol>,
 
     // Response
     status: Option<StatusCode>,
@@ -263,7 +315,7 @@ struct Pseudo {
 
 #[allow(clippy::len_without_is_empty)]
 impl Pseudo {
-    fn request(method: Method, uri: Uri) -> Self {
+    fn request(method: Method, uri: Uri, protocol: Option<Protocol>) -> Self {
         let Parts {
             scheme,
             authority,
@@ -271,6 +323,16 @@ impl Pseudo {
             ..
         } = uri::Parts::from(uri);
 
+        if method == Method::CONNECT && protocol.is_none() {
+            let len = 1 + if authority.is_some() { 1 } else { 0 };
+            return Self {
+                method: Some(method),
+                authority,
+                len,
+                ..Self::default()
+            };
+        }
+
         let path = path_and_query.map_or_else(
             || PathAndQuery::from_static("/"),
             |path| {
@@ -282,13 +344,15 @@ impl Pseudo {
             },
         );
 
-        let len = 3 + if authority.is_some() { 1 } else { 0 };
+        let len =
+            3 + if authority.is_some() { 1 } else { 0 } + if protocol.is_some() { 1 } else { 0 };
 
         Self {
             method: Some(method),
             scheme: scheme.or(Some(Scheme::HTTPS)),
             authority,
             path: Some(path),
+            protocol,
             status: None,
             len,
         }
@@ -300,6 +364,7 @@ impl Pseudo {
             scheme: None,
             authority: None,
             path: None,
+            protocol: None,
             status: Some(status),
             len: 1,
         }
@@ -333,8 +398,38 @@ pseudo_type![
     (AUTHORITY, b":authority"),
     (PATH, b":path"),
     (STATUS, b":status"),
+    (PROTOCOL, b":protocol"),
 ];
 
+/// The protocol of an extended CONNECT request
+///
+/// Extended CONNECT requests, defined by [RFC 8441], bootstrap another protocol such as
+/// WebTransport on a request stream. The protocol is conveyed by the `:protocol` pseudo-header,
+/// which is found in the [`Request`] extensions on the server side.
+///
+/// [RFC 8441]: https://tools.ietf.org/html/rfc8441
+/// [`Request`]: https://docs.rs/http/*/http/request/struct.Request.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Protocol(Cow<'static, str>);
+
+impl Protocol {
+    /// The WebTransport protocol
+    pub const WEBTRANSPORT: Protocol = Protocol(Cow::Borrowed("webtransport"));
+
+    /// Proxying UDP in HTTP, as defined by RFC 9298
+    pub const CONNECT_UDP: Protocol = Protocol(Cow::Borrowed("connect-udp"));
+
+    /// Create a custom protocol
+    pub fn from_static(protocol: &'static str) -> Self {
+        Protocol(Cow::Borrowed(protocol))
+    }
+
+    /// The protocol as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     InvalidHeaderName(String),
@@ -344,6 +439,7 @@ pub enum Error {
     MissingStatus,
     MissingAuthority,
     ContradictedAuthority,
+    InvalidConnect,
 }
 
 impl Error {
@@ -422,4 +518,94 @@ mod tests {
             Err(Error::ContradictedAuthority)
         );
     }
+
+    #[test]
+    fn connect_request() {
+        let uri = Uri::from_static("proxied.com:443");
+        let header = Header::request(Method::CONNECT, uri.clone(), HeaderMap::new());
+        assert_eq!(header.len(), 2);
+        let headers = Header::try_from(header.into_iter().collect::<Vec<_>>()).unwrap();
+        assert!(headers.pseudo.scheme.is_none());
+        assert!(headers.pseudo.path.is_none());
+        assert_matches!(
+            headers.into_request_parts(),
+            Ok((Method::CONNECT, u, _)) if u == uri
+        );
+    }
+
+    #[test]
+    fn extended_connect_request() {
+        let uri = Uri::from_static("https://example.com/session");
+        let header =
+            Header::extended_connect(uri.clone(), Protocol::WEBTRANSPORT, HeaderMap::new());
+        assert_eq!(header.len(), 5);
+        let headers = Header::try_from(header.into_iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(headers.protocol(), Some(&Protocol::WEBTRANSPORT));
+        assert_matches!(
+            headers.into_request_parts(),
+            Ok((Method::CONNECT, u, _)) if u == uri
+        );
+    }
+
+    #[test]
+    fn extended_connect_request_has_no_path() {
+        let headers = Header::try_from(vec![
+            (b":method", Method::CONNECT.as_str()).into(),
+            (b":protocol", b"webtransport").into(),
+            (b":scheme", b"https").into(),
+            (b":authority", b"example.com").into(),
+        ])
+        .unwrap();
+        assert_matches!(headers.into_request_parts(), Err(Error::InvalidConnect));
+    }
+
+    #[test]
+    fn protocol_without_connect() {
+        let headers = Header::try_from(vec![
+            (b":method", Method::GET.as_str()).into(),
+            (b":protocol", b"webtransport").into(),
+            (b":scheme", b"https").into(),
+            (b":authority", b"example.com").into(),
+            (b":path", b"/").into(),
+        ])
+        .unwrap();
+        assert_matches!(headers.into_request_parts(), Err(Error::InvalidConnect));
+    }
+
+    #[test]
+    fn connect_request_has_no_authority() {
+        let headers = Header::try_from(vec![
+            (b":method", Method::CONNECT.as_str()).into(),
+            (b"host", b"proxied.com:443").into(),
+        ])
+        .unwrap();
+        assert_matches!(headers.into_request_parts(), Err(Error::MissingAuthority));
+    }
+
+    #[test]
+    fn connect_request_has_path() {
+        let headers = Header::try_from(vec![
+            (b":method", Method::CONNECT.as_str()).into(),
+            (b":authority", b"proxied.com:443").into(),
+            (b":path", b"/").into(),
+        ])
+        .unwrap();
+        assert_matches!(headers.into_request_parts(), Err(Error::InvalidConnect));
+    }
+
+    #[test]
+    fn sensitive_values_round_trip() {
+        let headers = Header::try_from(vec![
+            (b":method", Method::GET.as_str()).into(),
+            (b":authority", b"test.com").into(),
+            HeaderField::new("x-token", "secret").with_sensitive(true),
+            HeaderField::new("accept", "*/*"),
+        ])
+        .unwrap();
+        let fields: Vec<_> = headers.into_iter().collect();
+        let token = fields.iter().find(|f| &f.name[..] == b"x-token").unwrap();
+        assert!(token.sensitive);
+        let accept = fields.iter().find(|f| &f.name[..] == b"accept").unwrap();
+        assert!(!accept.sensitive);
+    }
 }
@@ -5,9 +5,12 @@ use quinn_proto::{
 };
 use std::fmt;
 
+pub mod alt_svc;
+pub mod capsule;
 pub mod connection;
 pub mod frame;
 pub mod headers;
+pub mod priority;
 pub mod settings;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -26,6 +29,7 @@ stream_types! {
     PUSH = 0x01,
     ENCODER = 0x02,
     DECODER = 0x03,
+    WEBTRANSPORT = 0x54,
 }
 
 impl StreamType {
@@ -49,13 +53,26 @@ impl fmt::Display for StreamType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             &StreamType::CONTROL => write!(f, "Control"),
+            &StreamType::PUSH => write!(f, "Push"),
             &StreamType::ENCODER => write!(f, "Encoder"),
             &StreamType::DECODER => write!(f, "Decoder"),
+            &StreamType::WEBTRANSPORT => write!(f, "WebTransport"),
             x => write!(f, "StreamType({})", x.0),
         }
     }
 }
 
+/// Whether `id` is of the form `0x1f * N + 0x21`, reserved for the frame types, stream types and
+/// settings that exist to exercise the requirement of ignoring unknown ones
+pub(crate) fn is_reserved(id: u64) -> bool {
+    id >= 0x21 && (id - 0x21) % 0x1f == 0
+}
+
+/// A random reserved identifier, so peers don't end up relying on any given value
+pub(crate) fn reserved_id() -> u64 {
+    0x1f * rand::random::<u16>() as u64 + 0x21
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct ErrorCode(pub(super) u32);
 
@@ -82,11 +99,15 @@ error_codes! {
     REQUEST_REJECTED = 0x10B,
     REQUEST_CANCELLED = 0x10C,
     REQUEST_INCOMPLETE = 0x10D,
+    MESSAGE_ERROR = 0x10E,
     CONNECT_ERROR = 0x10F,
     VERSION_FALLBACK = 0x110,
     QPACK_DECOMPRESSION_FAILED = 0x200,
     QPACK_ENCODER_STREAM_ERROR = 0x201,
     QPACK_DECODER_STREAM_ERROR = 0x202,
+    DATAGRAM_ERROR = 0x33,
+    WEBTRANSPORT_BUFFERED_STREAM_REJECTED = 0x3994_bd84,
+    WEBTRANSPORT_SESSION_GONE = 0x170d_7b68,
 }
 
 impl From<ErrorCode> for VarInt {
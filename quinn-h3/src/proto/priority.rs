@@ -0,0 +1,184 @@
+use std::fmt;
+
+use http::{HeaderMap, HeaderValue};
+
+/// The priority of a request's response, as defined by [RFC 9218]
+///
+/// A client signals it with the `priority` request header, and can change it later with
+/// PRIORITY_UPDATE frames. The server sends the DATA of concurrent responses in order of
+/// urgency, `0` being the most urgent.
+///
+/// Set it as a [`Request`] extension for the client to send it along with the request:
+///
+/// ```
+/// # use http::Request;
+/// # use quinn_h3::{Body, Priority};
+/// let mut request = Request::get("https://example.com/style.css").body(Body::from(()))?;
+/// request.extensions_mut().insert(Priority::new(0, false));
+/// # Ok::<(), http::Error>(())
+/// ```
+///
+/// [RFC 9218]: https://www.rfc-editor.org/rfc/rfc9218.html
+/// [`Request`]: https://docs.rs/http/*/http/request/struct.Request.html
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Priority {
+    urgency: u8,
+    incremental: bool,
+}
+
+impl Priority {
+    /// Urgency of the requests which don't signal any
+    pub const DEFAULT_URGENCY: u8 = 3;
+    /// Least urgent level
+    pub const MAX_URGENCY: u8 = 7;
+
+    /// Create a priority, `urgency` being capped to [`MAX_URGENCY`]
+    ///
+    /// An `incremental` response is useful to the client as its data arrives, such as a
+    /// progressive image, so it can share bandwidth with responses of the same urgency.
+    ///
+    /// [`MAX_URGENCY`]: #associatedconstant.MAX_URGENCY
+    pub fn new(urgency: u8, incremental: bool) -> Self {
+        Self {
+            urgency: urgency.min(Self::MAX_URGENCY),
+            incremental,
+        }
+    }
+
+    /// Urgency level, from `0`, the most urgent, to [`MAX_URGENCY`]
+    ///
+    /// [`MAX_URGENCY`]: #associatedconstant.MAX_URGENCY
+    pub fn urgency(&self) -> u8 {
+        self.urgency
+    }
+
+    /// Whether the response can be processed incrementally
+    pub fn incremental(&self) -> bool {
+        self.incremental
+    }
+
+    /// Read the priority signaled by the `priority` header of `headers`
+    ///
+    /// Parameters that are missing, unknown or invalid leave their default value.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get_all(PRIORITY_HEADER)
+            .iter()
+            .fold(Self::default(), |p, value| p.parse(value.as_bytes()))
+    }
+
+    pub(crate) fn to_header(self) -> Option<HeaderValue> {
+        match self == Self::default() {
+            true => None,
+            false => Some(HeaderValue::from_str(&self.to_string()).unwrap()),
+        }
+    }
+
+    /// Parse a Structured Fields dictionary, updating `self` with the parameters it holds
+    pub(crate) fn parse(mut self, value: &[u8]) -> Self {
+        let value = match std::str::from_utf8(value) {
+            Ok(v) => v,
+            Err(_) => return self,
+        };
+        for member in value.split(',') {
+            // Parameters of a member are ignored
+            let member = member.split(';').next().unwrap().trim();
+            let mut key_value = member.splitn(2, '=');
+            match (key_value.next(), key_value.next()) {
+                (Some("u"), Some(u)) => match u.parse::<u8>() {
+                    Ok(u) if u <= Self::MAX_URGENCY => self.urgency = u,
+                    _ => (),
+                },
+                (Some("i"), None) | (Some("i"), Some("?1")) => self.incremental = true,
+                (Some("i"), Some("?0")) => self.incremental = false,
+                _ => (),
+            }
+        }
+        self
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self {
+            urgency: Self::DEFAULT_URGENCY,
+            incremental: false,
+        }
+    }
+}
+
+/// Formats as a `priority` header value, omitting the parameters that have their default value
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.urgency, self.incremental) {
+            (Self::DEFAULT_URGENCY, false) => Ok(()),
+            (Self::DEFAULT_URGENCY, true) => write!(f, "i"),
+            (u, false) => write!(f, "u={}", u),
+            (u, true) => write!(f, "u={}, i", u),
+        }
+    }
+}
+
+/// What a PRIORITY_UPDATE frame reprioritizes
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Prioritized {
+    /// The response to the request sent on this stream
+    Request(u64),
+    /// The response pushed with this push ID
+    Push(u64),
+}
+
+pub const PRIORITY_HEADER: &str = "priority";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Priority {
+        Priority::default().parse(value.as_bytes())
+    }
+
+    #[test]
+    fn parse_members() {
+        assert_eq!(parse("u=0"), Priority::new(0, false));
+        assert_eq!(parse("u=5, i"), Priority::new(5, true));
+        assert_eq!(parse("i=?1,u=1"), Priority::new(1, true));
+        assert_eq!(parse("i, i=?0"), Priority::new(3, false));
+        assert_eq!(parse(""), Priority::default());
+    }
+
+    #[test]
+    fn parse_ignores_invalid_members() {
+        assert_eq!(parse("u=8, i"), Priority::new(3, true));
+        assert_eq!(parse("u=-1, x=2, i=1"), Priority::default());
+        assert_eq!(parse("u=2;foo=bar, unknown"), Priority::new(2, false));
+        assert_eq!(
+            Priority::default().parse(&[0xff, b'u']),
+            Priority::default()
+        );
+    }
+
+    #[test]
+    fn format_round_trip() {
+        for &(urgency, incremental, formatted) in &[
+            (3, false, ""),
+            (3, true, "i"),
+            (0, false, "u=0"),
+            (7, true, "u=7, i"),
+        ] {
+            let priority = Priority::new(urgency, incremental);
+            assert_eq!(priority.to_string(), formatted);
+            assert_eq!(parse(formatted), priority);
+        }
+        assert_eq!(Priority::default().to_header(), None);
+    }
+
+    #[test]
+    fn from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Priority::from_headers(&headers), Priority::default());
+        headers.append(PRIORITY_HEADER, HeaderValue::from_static("u=1"));
+        headers.append(PRIORITY_HEADER, HeaderValue::from_static("i"));
+        assert_eq!(Priority::from_headers(&headers), Priority::new(1, true));
+    }
+}
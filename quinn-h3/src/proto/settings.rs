@@ -5,6 +5,7 @@ use quinn_proto::{
 };
 
 use super::frame::{FrameHeader, Type as FrameType};
+use crate::qpack::InsertionPolicy;
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub struct SettingId(pub u64);
@@ -20,20 +21,32 @@ macro_rules! setting_identifiers {
 impl SettingId {
     const NONE: SettingId = SettingId(0);
 
+    /// Settings carried by SETTINGS frames, other than reserved and unknown ones which are ignored
     fn is_supported(self) -> bool {
         matches!(
             self,
             SettingId::MAX_HEADER_LIST_SIZE
                 | SettingId::QPACK_MAX_TABLE_CAPACITY
                 | SettingId::QPACK_MAX_BLOCKED_STREAMS
+                | SettingId::ENABLE_CONNECT_PROTOCOL
+                | SettingId::H3_DATAGRAM
+                | SettingId::ENABLE_WEBTRANSPORT
         )
     }
+
+    /// HTTP/2 settings with no HTTP/3 equivalent, which a peer must not send
+    fn is_h2_reserved(self) -> bool {
+        matches!(self.0, 0x0 | 0x2 | 0x3 | 0x4 | 0x5)
+    }
 }
 
 setting_identifiers! {
     QPACK_MAX_TABLE_CAPACITY = 0x1,
     QPACK_MAX_BLOCKED_STREAMS = 0x7,
     MAX_HEADER_LIST_SIZE = 0x6,
+    ENABLE_CONNECT_PROTOCOL = 0x8,
+    H3_DATAGRAM = 0x33,
+    ENABLE_WEBTRANSPORT = 0x2b60_3742,
 }
 
 impl Codec for SettingId {
@@ -48,16 +61,20 @@ impl Codec for SettingId {
 /// Settings for a HTTP/3 connection
 ///
 /// The HTTP/3 protocol offers a few settings to configure limits and header encoding
-/// parameters of a connection.
+/// parameters of a connection, along with extensions to enable.
 ///
 /// See the [QPACK] specification for more details.
 ///
-/// [QPACK]: https://quicwg.org/base-drafts/draft-ietf-quic-qpack.html
+/// [QPACK]: https://www.rfc-editor.org/rfc/rfc9204.html
 #[derive(Clone, Debug)]
 pub struct Settings {
     max_header_list_size: u64,
     qpack_max_table_capacity: u64,
     qpack_max_blocked_streams: u64,
+    enable_connect_protocol: bool,
+    h3_datagram: bool,
+    enable_webtransport: bool,
+    qpack_insertion_policy: InsertionPolicy,
 }
 
 impl Default for Settings {
@@ -67,6 +84,10 @@ impl Default for Settings {
             max_header_list_size: DEFAULT_MAX_HEADER_LIST_SIZE,
             qpack_max_table_capacity: DEFAULT_QPACK_MAX_TABLE_CAPACITY,
             qpack_max_blocked_streams: DEFAULT_QPACK_MAX_BLOCKED_STREAMS,
+            enable_connect_protocol: false,
+            h3_datagram: false,
+            enable_webtransport: false,
+            qpack_insertion_policy: InsertionPolicy::default(),
         }
     }
 }
@@ -80,6 +101,10 @@ impl Settings {
             max_header_list_size: 0,
             qpack_max_table_capacity: 4096,
             qpack_max_blocked_streams: 128,
+            enable_connect_protocol: false,
+            h3_datagram: false,
+            enable_webtransport: false,
+            qpack_insertion_policy: InsertionPolicy::default(),
         }
     }
 
@@ -140,6 +165,60 @@ impl Settings {
         Ok(self)
     }
 
+    /// Whether extended CONNECT requests are accepted, see RFC 8441
+    pub fn enable_connect_protocol(&self) -> bool {
+        self.enable_connect_protocol
+    }
+
+    /// Whether HTTP datagrams are supported, see RFC 9297
+    pub fn h3_datagram(&self) -> bool {
+        self.h3_datagram
+    }
+
+    /// Whether WebTransport sessions are supported
+    pub fn enable_webtransport(&self) -> bool {
+        self.enable_webtransport
+    }
+
+    /// Accept extended CONNECT requests, carrying a `:protocol` pseudo-header
+    ///
+    /// Only meaningful on the server side.
+    pub fn set_enable_connect_protocol(&mut self, value: bool) -> &mut Self {
+        self.enable_connect_protocol = value;
+        self
+    }
+
+    /// Support HTTP datagrams, carried by QUIC datagrams
+    pub fn set_h3_datagram(&mut self, value: bool) -> &mut Self {
+        self.h3_datagram = value;
+        self
+    }
+
+    /// Support WebTransport sessions
+    ///
+    /// Also enables extended CONNECT and HTTP datagrams, which WebTransport relies on.
+    pub fn set_enable_webtransport(&mut self, value: bool) -> &mut Self {
+        self.enable_webtransport = value;
+        if value {
+            self.enable_connect_protocol = true;
+            self.h3_datagram = true;
+        }
+        self
+    }
+
+    /// How the `QPACK` encoder fills the dynamic table
+    pub fn qpack_insertion_policy(&self) -> InsertionPolicy {
+        self.qpack_insertion_policy
+    }
+
+    /// Set how the `QPACK` encoder fills the dynamic table the peer allows
+    ///
+    /// This is a local choice, not sent to the peer.
+    pub fn set_qpack_insertion_policy(&mut self, policy: InsertionPolicy) -> &mut Self {
+        self.qpack_insertion_policy = policy;
+        self
+    }
+
     pub(crate) fn from_frame(settings: SettingsFrame) -> Result<Settings, Error> {
         let mut this = Self::default();
         for (id, val) in settings.entries[..settings.len].iter() {
@@ -147,7 +226,19 @@ impl Settings {
                 SettingId::MAX_HEADER_LIST_SIZE => this.set_max_header_list_size(*val)?,
                 SettingId::QPACK_MAX_TABLE_CAPACITY => this.set_qpack_max_table_capacity(*val)?,
                 SettingId::QPACK_MAX_BLOCKED_STREAMS => this.set_qpack_max_blocked_streams(*val)?,
-                x => return Err(Error::InvalidSettingId(x.0)),
+                SettingId::ENABLE_CONNECT_PROTOCOL => {
+                    this.enable_connect_protocol = flag(*id, *val)?;
+                    &mut this
+                }
+                SettingId::H3_DATAGRAM => {
+                    this.h3_datagram = flag(*id, *val)?;
+                    &mut this
+                }
+                SettingId::ENABLE_WEBTRANSPORT => {
+                    this.enable_webtransport = flag(*id, *val)?;
+                    &mut this
+                }
+                _ => &mut this,
             };
         }
         Ok(this)
@@ -176,20 +267,40 @@ impl Settings {
                 )
                 .expect("qpack max blocked");
         }
+        for &(id, enabled) in &[
+            (
+                SettingId::ENABLE_CONNECT_PROTOCOL,
+                self.enable_connect_protocol,
+            ),
+            (SettingId::H3_DATAGRAM, self.h3_datagram),
+            (SettingId::ENABLE_WEBTRANSPORT, self.enable_webtransport),
+        ] {
+            if enabled {
+                frame.insert(id, 1).expect("extension setting");
+            }
+        }
         frame
     }
 }
 
+fn flag(id: SettingId, value: u64) -> Result<bool, InvalidValue> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        x => Err(InvalidValue(id, x)),
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct SettingsFrame {
-    entries: [(SettingId, u64); 3],
+    entries: [(SettingId, u64); 7],
     len: usize,
 }
 
 impl Default for SettingsFrame {
     fn default() -> Self {
         Self {
-            entries: [(SettingId::NONE, 0); 3],
+            entries: [(SettingId::NONE, 0); 7],
             len: 0,
         }
     }
@@ -214,6 +325,15 @@ impl SettingsFrame {
         Ok(())
     }
 
+    /// Add a reserved setting with a random value, so peers don't choke on unknown settings
+    pub(super) fn grease(&mut self) {
+        if self.len < self.entries.len() {
+            let value = rand::random::<u32>() as u64;
+            self.entries[self.len] = (SettingId(super::reserved_id()), value);
+            self.len += 1;
+        }
+    }
+
     pub(super) fn encode<T: BufMut>(&self, buf: &mut T) {
         self.encode_header(buf);
         for (id, val) in self.entries[..self.len].iter() {
@@ -233,6 +353,9 @@ impl SettingsFrame {
             let identifier = SettingId::decode(buf).map_err(|_| Error::Malformed)?;
             let value = buf.get_var().map_err(|_| Error::Malformed)?;
 
+            if identifier.is_h2_reserved() {
+                return Err(Error::InvalidSettingId(identifier.0));
+            }
             if identifier.is_supported() {
                 settings.insert(identifier, value)?;
             }
@@ -344,6 +467,28 @@ mod tests {
         assert_eq!(frame.entries[0], (SettingId::QPACK_MAX_TABLE_CAPACITY, 42));
     }
 
+    #[test]
+    fn settings_extensions() {
+        let mut settings = Settings::default();
+        settings.set_enable_webtransport(true);
+        let frame = settings.to_frame();
+        assert_eq!(frame.len, 3);
+        let settings = Settings::from_frame(frame).unwrap();
+        assert!(settings.enable_connect_protocol());
+        assert!(settings.h3_datagram());
+        assert!(settings.enable_webtransport());
+    }
+
+    #[test]
+    fn settings_extension_invalid_value() {
+        let mut frame = SettingsFrame::default();
+        frame.insert(SettingId::H3_DATAGRAM, 2).unwrap();
+        assert_matches!(
+            Settings::from_frame(frame),
+            Err(Error::InvalidSettingValue(SettingId::H3_DATAGRAM, 2))
+        );
+    }
+
     #[test]
     fn settings_all_defaults() {
         assert_eq!(Settings::default().to_frame().len, 0);
@@ -360,4 +505,36 @@ mod tests {
         assert_matches!(frame.entries[1], (SettingId::QPACK_MAX_TABLE_CAPACITY, _));
         assert_matches!(frame.entries[2], (SettingId::QPACK_MAX_BLOCKED_STREAMS, _));
     }
+
+    #[test]
+    fn settings_h2_reserved_rejected() {
+        // SETTINGS_ENABLE_PUSH
+        let mut buf = Cursor::new(&[2, 1, 6, 1]);
+        assert_matches!(
+            SettingsFrame::decode(&mut buf),
+            Err(Error::InvalidSettingId(2))
+        );
+    }
+
+    #[test]
+    fn settings_greased() {
+        let mut settings = Settings::default();
+        settings.set_qpack_max_table_capacity(42).unwrap();
+        let mut frame = settings.to_frame();
+        frame.grease();
+        assert_eq!(frame.len, 2);
+        assert!(crate::proto::is_reserved(frame.entries[1].0 .0));
+
+        let mut buf = Vec::new();
+        frame.encode(&mut buf);
+        let mut read = Cursor::new(&buf[2..]);
+        let decoded = SettingsFrame::decode(&mut read).unwrap();
+        assert_eq!(decoded.len, 1);
+        assert_eq!(
+            Settings::from_frame(decoded)
+                .unwrap()
+                .qpack_max_table_capacity(),
+            42
+        );
+    }
 }
@@ -75,6 +75,10 @@ impl HeaderPrefix {
         total_inserted: usize,
         max_table_size: usize,
     ) -> Result<(usize, usize), ParseError> {
+        let max_entries = max_table_size / 32;
+        if self.encoded_insert_count > 2 * max_entries {
+            return Err(ParseError::InvalidInsertCount(self.encoded_insert_count));
+        }
         if max_table_size == 0 {
             return Ok((0, 0));
         }
@@ -83,7 +87,6 @@ impl HeaderPrefix {
             0
         } else {
             let mut insert_count = self.encoded_insert_count - 1;
-            let max_entries = max_table_size / 32;
             let mut wrapped = total_inserted % (2 * max_entries);
 
             if wrapped >= insert_count + max_entries {
@@ -92,7 +95,10 @@ impl HeaderPrefix {
                 wrapped += 2 * max_entries;
             }
 
-            insert_count + total_inserted - wrapped
+            match insert_count + total_inserted - wrapped {
+                0 => return Err(ParseError::InvalidInsertCount(self.encoded_insert_count)),
+                required => required,
+            }
         };
 
         let base = if required == 0 {
@@ -169,8 +175,16 @@ impl IndexedWithPostBase {
 
 #[derive(Debug, PartialEq)]
 pub enum LiteralWithNameRef {
-    Static { index: usize, value: Vec<u8> },
-    Dynamic { index: usize, value: Vec<u8> },
+    Static {
+        index: usize,
+        value: Vec<u8>,
+        never_indexed: bool,
+    },
+    Dynamic {
+        index: usize,
+        value: Vec<u8>,
+        never_indexed: bool,
+    },
 }
 
 impl LiteralWithNameRef {
@@ -178,6 +192,7 @@ impl LiteralWithNameRef {
         LiteralWithNameRef::Static {
             index,
             value: value.into(),
+            never_indexed: false,
         }
     }
 
@@ -185,7 +200,23 @@ impl LiteralWithNameRef {
         LiteralWithNameRef::Dynamic {
             index,
             value: value.into(),
+            never_indexed: false,
+        }
+    }
+
+    /// Set the 'N' bit, telling intermediaries not to index the field either
+    pub fn never_indexed(mut self, never: bool) -> Self {
+        match self {
+            LiteralWithNameRef::Static {
+                ref mut never_indexed,
+                ..
+            }
+            | LiteralWithNameRef::Dynamic {
+                ref mut never_indexed,
+                ..
+            } => *never_indexed = never,
         }
+        self
     }
 
     pub fn decode<R: Buf>(buf: &mut R) -> Result<Self, ParseError> {
@@ -193,23 +224,33 @@ impl LiteralWithNameRef {
             (f, i) if f & 0b0101 == 0b0101 => Ok(LiteralWithNameRef::new_static(
                 i,
                 prefix_string::decode(8, buf)?,
-            )),
+            )
+            .never_indexed(f & 0b0010 != 0)),
             (f, i) if f & 0b0101 == 0b0100 => Ok(LiteralWithNameRef::new_dynamic(
                 i,
                 prefix_string::decode(8, buf)?,
-            )),
+            )
+            .never_indexed(f & 0b0010 != 0)),
             (f, _) => Err(ParseError::InvalidPrefix(f)),
         }
     }
 
     pub fn encode<W: BufMut>(&self, buf: &mut W) -> Result<(), prefix_string::Error> {
         match self {
-            LiteralWithNameRef::Static { index, value } => {
-                prefix_int::encode(4, 0b0101, *index, buf);
+            LiteralWithNameRef::Static {
+                index,
+                value,
+                never_indexed,
+            } => {
+                prefix_int::encode(4, 0b0101 | n_bit(*never_indexed, 1), *index, buf);
                 prefix_string::encode(8, 0, value, buf)?;
             }
-            LiteralWithNameRef::Dynamic { index, value } => {
-                prefix_int::encode(4, 0b0100, *index, buf);
+            LiteralWithNameRef::Dynamic {
+                index,
+                value,
+                never_indexed,
+            } => {
+                prefix_int::encode(4, 0b0100 | n_bit(*never_indexed, 1), *index, buf);
                 prefix_string::encode(8, 0, value, buf)?;
             }
         }
@@ -221,6 +262,7 @@ impl LiteralWithNameRef {
 pub struct LiteralWithPostBaseNameRef {
     pub index: usize,
     pub value: Vec<u8>,
+    pub never_indexed: bool,
 }
 
 impl LiteralWithPostBaseNameRef {
@@ -228,21 +270,23 @@ impl LiteralWithPostBaseNameRef {
         LiteralWithPostBaseNameRef {
             index,
             value: value.into(),
+            never_indexed: false,
         }
     }
 
     pub fn decode<R: Buf>(buf: &mut R) -> Result<Self, ParseError> {
         match prefix_int::decode(3, buf)? {
-            (f, i) if f & 0b1111_0000 == 0 => Ok(LiteralWithPostBaseNameRef::new(
-                i,
-                prefix_string::decode(8, buf)?,
-            )),
+            (f, i) if f & 0b1111_0000 == 0 => Ok(LiteralWithPostBaseNameRef {
+                index: i,
+                value: prefix_string::decode(8, buf)?,
+                never_indexed: f & 0b0001 != 0,
+            }),
             (f, _) => Err(ParseError::InvalidPrefix(f)),
         }
     }
 
     pub fn encode<W: BufMut>(&self, buf: &mut W) -> Result<(), prefix_string::Error> {
-        prefix_int::encode(3, 0b0000, self.index, buf);
+        prefix_int::encode(3, n_bit(self.never_indexed, 0), self.index, buf);
         prefix_string::encode(8, 0, &self.value, buf)?;
         Ok(())
     }
@@ -252,6 +296,7 @@ impl LiteralWithPostBaseNameRef {
 pub struct Literal {
     pub name: Vec<u8>,
     pub value: Vec<u8>,
+    pub never_indexed: bool,
 }
 
 impl Literal {
@@ -259,6 +304,7 @@ impl Literal {
         Literal {
             name: name.into(),
             value: value.into(),
+            never_indexed: false,
         }
     }
 
@@ -268,19 +314,26 @@ impl Literal {
         } else if buf.bytes()[0] & 0b1110_0000 != 0b0010_0000 {
             return Err(ParseError::InvalidPrefix(buf.bytes()[0]));
         }
-        Ok(Literal::new(
-            prefix_string::decode(4, buf)?,
-            prefix_string::decode(8, buf)?,
-        ))
+        let never_indexed = buf.bytes()[0] & 0b0001_0000 != 0;
+        Ok(Literal {
+            name: prefix_string::decode(4, buf)?,
+            value: prefix_string::decode(8, buf)?,
+            never_indexed,
+        })
     }
 
     pub fn encode<W: BufMut>(&self, buf: &mut W) -> Result<(), prefix_string::Error> {
-        prefix_string::encode(4, 0b0010, &self.name, buf)?;
+        prefix_string::encode(4, 0b0010 | n_bit(self.never_indexed, 0), &self.name, buf)?;
         prefix_string::encode(8, 0, &self.value, buf)?;
         Ok(())
     }
 }
 
+/// The 'N' bit of literal field lines, at `shift` in the flags preceding the prefixed integer
+fn n_bit(never_indexed: bool, shift: u8) -> u8 {
+    (never_indexed as u8) << shift
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -342,6 +395,36 @@ mod test {
         assert_eq!(Literal::decode(&mut read), Ok(field));
     }
 
+    #[test]
+    fn literals_never_indexed() {
+        let mut buf = vec![];
+        let field = LiteralWithNameRef::new_static(84, "secret").never_indexed(true);
+        field.encode(&mut buf).unwrap();
+        assert_eq!(buf[0] & 0b1111_0000, 0b0111_0000);
+        let mut read = Cursor::new(&buf);
+        assert_eq!(LiteralWithNameRef::decode(&mut read), Ok(field));
+
+        let mut buf = vec![];
+        let field = LiteralWithPostBaseNameRef {
+            never_indexed: true,
+            ..LiteralWithPostBaseNameRef::new(2, "secret")
+        };
+        field.encode(&mut buf).unwrap();
+        assert_eq!(buf[0], 0b0000_1010);
+        let mut read = Cursor::new(&buf);
+        assert_eq!(LiteralWithPostBaseNameRef::decode(&mut read), Ok(field));
+
+        let mut buf = vec![];
+        let field = Literal {
+            never_indexed: true,
+            ..Literal::new("x-secret", "secret")
+        };
+        field.encode(&mut buf).unwrap();
+        assert_eq!(buf[0] & 0b1111_0000, 0b0011_0000);
+        let mut read = Cursor::new(&buf);
+        assert_eq!(Literal::decode(&mut read), Ok(field));
+    }
+
     #[test]
     fn header_prefix() {
         let prefix = HeaderPrefix::new(10, 5, 12, TABLE_SIZE);
@@ -358,6 +441,20 @@ mod test {
         HeaderPrefix::new(10, 5, 12, 0).get(1, 0).unwrap();
     }
 
+    #[test]
+    fn insert_count_out_of_range() {
+        let mut buf = vec![];
+        prefix_int::encode(8, 0, 2 * TABLE_SIZE / 32 + 1, &mut buf);
+        prefix_int::encode(7, 0, 0, &mut buf);
+
+        let mut read = Cursor::new(&buf);
+        let prefix = HeaderPrefix::decode(&mut read).unwrap();
+        assert_eq!(
+            prefix.get(2, TABLE_SIZE),
+            Err(ParseError::InvalidInsertCount(257))
+        );
+    }
+
     #[test]
     fn base_index_too_small() {
         let mut buf = vec![];
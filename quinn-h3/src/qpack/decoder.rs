@@ -41,6 +41,8 @@ pub enum Error {
     MissingRefs(usize),
     #[error("header prefix contains invalid base index: {0:?}")]
     BadBaseIndex(isize),
+    #[error("header prefix contains invalid required insert count: {0}")]
+    BadInsertCount(usize),
     #[error("data is unexpectedly truncated")]
     UnexpectedEnd,
 }
@@ -53,13 +55,13 @@ pub fn stream_canceled<W: BufMut>(stream_id: u64, decoder: &mut W) {
     StreamCancel(stream_id).encode(decoder);
 }
 
-// Decode a header bloc received on Request of Push stream. (draft: 4.5)
+// Decode a header bloc received on Request of Push stream. (RFC 9204: 4.5)
 pub fn decode_header<T: Buf>(
     table: &DynamicTable,
     buf: &mut T,
 ) -> Result<(Vec<HeaderField>, bool), Error> {
     let (required_ref, base) =
-        HeaderPrefix::decode(buf)?.get(table.total_inserted(), table.max_mem_size())?;
+        HeaderPrefix::decode(buf)?.get(table.total_inserted(), table.max_capacity())?;
 
     if required_ref > table.total_inserted() {
         return Err(Error::MissingRefs(required_ref));
@@ -90,20 +92,32 @@ fn parse_header_field<R: Buf>(
             table.get_postbase(index)?.clone()
         }
         HeaderBlockField::LiteralWithNameRef => match LiteralWithNameRef::decode(buf)? {
-            LiteralWithNameRef::Static { index, value } => {
-                StaticTable::get(index)?.with_value(value)
-            }
-            LiteralWithNameRef::Dynamic { index, value } => {
-                table.get_relative(index)?.with_value(value)
-            }
+            LiteralWithNameRef::Static {
+                index,
+                value,
+                never_indexed,
+            } => StaticTable::get(index)?
+                .with_value(value)
+                .with_sensitive(never_indexed),
+            LiteralWithNameRef::Dynamic {
+                index,
+                value,
+                never_indexed,
+            } => table
+                .get_relative(index)?
+                .with_value(value)
+                .with_sensitive(never_indexed),
         },
         HeaderBlockField::LiteralWithPostBaseNameRef => {
             let literal = LiteralWithPostBaseNameRef::decode(buf)?;
-            table.get_postbase(literal.index)?.with_value(literal.value)
+            table
+                .get_postbase(literal.index)?
+                .with_value(literal.value)
+                .with_sensitive(literal.never_indexed)
         }
         HeaderBlockField::Literal => {
             let literal = Literal::decode(buf)?;
-            HeaderField::new(literal.name, literal.value)
+            HeaderField::new(literal.name, literal.value).with_sensitive(literal.never_indexed)
         }
         _ => return Err(Error::UnknownPrefix),
     };
@@ -237,6 +251,7 @@ impl From<ParseError> for Error {
             ParseError::InvalidString(x) => Error::InvalidString(x),
             ParseError::InvalidPrefix(_) => Error::UnknownPrefix,
             ParseError::InvalidBase(b) => Error::BadBaseIndex(b),
+            ParseError::InvalidInsertCount(c) => Error::BadInsertCount(c),
         }
     }
 }
@@ -5,14 +5,16 @@ use std::{
 
 use thiserror::Error;
 
-use super::{field::HeaderField, static_::StaticTable};
+use super::{encoder::InsertionPolicy, field::HeaderField, static_::StaticTable};
 use crate::qpack::vas::{self, VirtualAddressSpace};
 
 /**
- * https://quicwg.org/base-drafts/draft-ietf-quic-qpack.html#maximum-dynamic-table-capacity
+ * https://www.rfc-editor.org/rfc/rfc9204.html#name-maximum-dynamic-table-capac
  */
 const SETTINGS_MAX_TABLE_CAPACITY_MAX: usize = 1_073_741_823; // 2^30 -1
 const SETTINGS_MAX_BLOCKED_STREAMS_MAX: usize = 65_535; // 2^16 - 1
+/// Most fields whose uses are counted at once, for `InsertionPolicy::Frequency`
+const MAX_COUNTED_FIELDS: usize = 512;
 
 #[derive(Debug, PartialEq, Error)]
 pub enum Error {
@@ -126,8 +128,8 @@ impl<'a> Drop for DynamicTableEncoder<'a> {
 }
 
 impl<'a> DynamicTableEncoder<'a> {
-    pub(super) fn max_size(&self) -> usize {
-        self.table.max_size
+    pub(super) fn max_capacity(&self) -> usize {
+        self.table.max_capacity
     }
 
     pub(super) fn base(&self) -> usize {
@@ -245,7 +247,23 @@ impl<'a> DynamicTableEncoder<'a> {
         Ok(result)
     }
 
-    fn find_name(&mut self, name: &[u8]) -> DynamicLookupResult {
+    pub(super) fn insertion_policy(&self) -> InsertionPolicy {
+        self.table.insertion_policy
+    }
+
+    /// Count one more use of `field`, returning how many times it was sent so far
+    pub(super) fn count_use(&mut self, field: &HeaderField) -> u32 {
+        let uses = &mut self.table.uses;
+        if uses.len() >= MAX_COUNTED_FIELDS && !uses.contains_key(field) {
+            // Forget the fields seen so far, rather than growing without bound
+            uses.clear();
+        }
+        let count = uses.entry(field.clone()).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    pub(super) fn find_name(&mut self, name: &[u8]) -> DynamicLookupResult {
         if let Some(index) = StaticTable::find_name(name) {
             return DynamicLookupResult::Static(index);
         }
@@ -299,6 +317,7 @@ pub struct DynamicTable {
     fields: VecDeque<HeaderField>,
     curr_size: usize,
     max_size: usize,
+    max_capacity: usize,
     vas: VirtualAddressSpace,
     field_map: HashMap<HeaderField, usize>,
     name_map: HashMap<Cow<'static, [u8]>, usize>,
@@ -308,6 +327,8 @@ pub struct DynamicTable {
     blocked_max: usize,
     blocked_count: usize,
     blocked_streams: BTreeMap<usize, usize>, // <required_ref, blocked_count>
+    insertion_policy: InsertionPolicy,
+    uses: HashMap<HeaderField, u32>,
 }
 
 impl DynamicTable {
@@ -340,6 +361,10 @@ impl DynamicTable {
         }
     }
 
+    pub fn set_insertion_policy(&mut self, policy: InsertionPolicy) {
+        self.insertion_policy = policy;
+    }
+
     pub fn set_max_blocked(&mut self, max: usize) -> Result<(), Error> {
         // TODO handle existing data
         if max >= SETTINGS_MAX_BLOCKED_STREAMS_MAX {
@@ -354,6 +379,7 @@ impl DynamicTable {
             return Err(Error::MaximumTableSizeTooLarge);
         }
 
+        self.max_capacity = self.max_capacity.max(size);
         if size >= self.max_size {
             self.max_size = size;
             return Ok(());
@@ -550,9 +576,18 @@ impl DynamicTable {
         }
     }
 
+    #[cfg(test)]
     pub(super) fn max_mem_size(&self) -> usize {
         self.max_size
     }
+
+    /// Largest capacity the table was set to, that is the one announced in SETTINGS
+    ///
+    /// Field section prefixes encode the Required Insert Count modulo the number of entries it
+    /// allows, even if the encoder later lowers the table's capacity.
+    pub(super) fn max_capacity(&self) -> usize {
+        self.max_capacity
+    }
 }
 
 impl From<vas::Error> for Error {
@@ -60,7 +60,7 @@ where
         required_ref,
         table.base(),
         table.total_inserted(),
-        table.max_size(),
+        table.max_capacity(),
     )
     .encode(block);
     block.put(block_buf.as_slice());
@@ -76,69 +76,168 @@ fn encode_field<W: BufMut>(
     encoder: &mut W,
     field: &HeaderField,
 ) -> Result<Option<usize>, Error> {
-    if let Some(index) = StaticTable::find(field) {
-        Indexed::Static(index).encode(block);
-        return Ok(None);
+    let sensitive = field.sensitive || is_sensitive(&field.name);
+    if !sensitive {
+        if let Some(index) = StaticTable::find(field) {
+            Indexed::Static(index).encode(block);
+            return Ok(None);
+        }
+
+        if let DynamicLookupResult::Relative { index, absolute } = table.find(field) {
+            Indexed::Dynamic(index).encode(block);
+            return Ok(Some(absolute));
+        }
     }
 
-    if let DynamicLookupResult::Relative { index, absolute } = table.find(field) {
-        Indexed::Dynamic(index).encode(block);
-        return Ok(Some(absolute));
+    let name_ref = match insertion(table, field, sensitive) {
+        Insertion::Field => match insert(table, encoder, field)? {
+            Ok((postbase, absolute)) => {
+                IndexedWithPostBase(postbase).encode(block);
+                return Ok(Some(absolute));
+            }
+            Err(name_ref) => name_ref,
+        },
+        Insertion::Name => match table.find_name(&field.name) {
+            DynamicLookupResult::NotFound => {
+                let name = HeaderField::new(field.name.clone(), Vec::new());
+                match insert(table, encoder, &name)? {
+                    Ok((index, absolute)) => DynamicLookupResult::PostBase { index, absolute },
+                    Err(name_ref) => name_ref,
+                }
+            }
+            name_ref => name_ref,
+        },
+        Insertion::None => table.find_name(&field.name),
+    };
+
+    let value = field.value.clone();
+    let reference = match name_ref {
+        DynamicLookupResult::Static(index) => {
+            LiteralWithNameRef::new_static(index, value)
+                .never_indexed(sensitive)
+                .encode(block)?;
+            None
+        }
+        DynamicLookupResult::Relative { index, absolute } => {
+            LiteralWithNameRef::new_dynamic(index, value)
+                .never_indexed(sensitive)
+                .encode(block)?;
+            Some(absolute)
+        }
+        DynamicLookupResult::PostBase { index, absolute } => {
+            LiteralWithPostBaseNameRef {
+                never_indexed: sensitive,
+                ..LiteralWithPostBaseNameRef::new(index, value)
+            }
+            .encode(block)?;
+            Some(absolute)
+        }
+        DynamicLookupResult::NotFound => {
+            Literal {
+                never_indexed: sensitive,
+                ..Literal::new(field.name.clone(), value)
+            }
+            .encode(block)?;
+            None
+        }
+    };
+    Ok(reference)
+}
+
+enum Insertion {
+    Field,
+    Name,
+    None,
+}
+
+fn insertion(table: &mut DynamicTableEncoder, field: &HeaderField, sensitive: bool) -> Insertion {
+    if sensitive {
+        return Insertion::None;
     }
+    match table.insertion_policy() {
+        InsertionPolicy::Always => Insertion::Field,
+        InsertionPolicy::StaticOnly => Insertion::None,
+        InsertionPolicy::NameOnly => Insertion::Name,
+        InsertionPolicy::Frequency(min) => match table.count_use(field) >= min {
+            true => Insertion::Field,
+            false => Insertion::None,
+        },
+    }
+}
 
-    let reference = match table.insert(field)? {
+/// Insert `entry` into the dynamic table, returning its post-base index and absolute index
+///
+/// If it can't be inserted, the result of looking its name up is returned instead.
+fn insert<W: BufMut>(
+    table: &mut DynamicTableEncoder,
+    encoder: &mut W,
+    entry: &HeaderField,
+) -> Result<Result<(usize, usize), DynamicLookupResult>, Error> {
+    let inserted = match table.insert(entry)? {
         DynamicInsertionResult::Duplicated {
             relative,
             postbase,
             absolute,
         } => {
             Duplicate(relative).encode(encoder);
-            IndexedWithPostBase(postbase).encode(block);
-            Some(absolute)
+            (postbase, absolute)
         }
         DynamicInsertionResult::Inserted { postbase, absolute } => {
-            InsertWithoutNameRef::new(field.name.clone(), field.value.clone()).encode(encoder)?;
-            IndexedWithPostBase(postbase).encode(block);
-            Some(absolute)
+            InsertWithoutNameRef::new(entry.name.clone(), entry.value.clone()).encode(encoder)?;
+            (postbase, absolute)
         }
         DynamicInsertionResult::InsertedWithStaticNameRef {
             postbase,
             index,
             absolute,
         } => {
-            InsertWithNameRef::new_static(index, field.value.clone()).encode(encoder)?;
-            IndexedWithPostBase(postbase).encode(block);
-            Some(absolute)
+            InsertWithNameRef::new_static(index, entry.value.clone()).encode(encoder)?;
+            (postbase, absolute)
         }
         DynamicInsertionResult::InsertedWithNameRef {
             postbase,
             relative,
             absolute,
         } => {
-            InsertWithNameRef::new_dynamic(relative, field.value.clone()).encode(encoder)?;
-            IndexedWithPostBase(postbase).encode(block);
-            Some(absolute)
+            InsertWithNameRef::new_dynamic(relative, entry.value.clone()).encode(encoder)?;
+            (postbase, absolute)
         }
-        DynamicInsertionResult::NotInserted(lookup_result) => match lookup_result {
-            DynamicLookupResult::Static(index) => {
-                LiteralWithNameRef::new_static(index, field.value.clone()).encode(block)?;
-                None
-            }
-            DynamicLookupResult::Relative { index, absolute } => {
-                LiteralWithNameRef::new_dynamic(index, field.value.clone()).encode(block)?;
-                Some(absolute)
-            }
-            DynamicLookupResult::PostBase { index, absolute } => {
-                LiteralWithPostBaseNameRef::new(index, field.value.clone()).encode(block)?;
-                Some(absolute)
-            }
-            DynamicLookupResult::NotFound => {
-                Literal::new(field.name.clone(), field.value.clone()).encode(block)?;
-                None
-            }
-        },
+        DynamicInsertionResult::NotInserted(name_ref) => return Ok(Err(name_ref)),
     };
-    Ok(reference)
+    Ok(Ok(inserted))
+}
+
+/// Credentials, always encoded as never-indexed literals
+fn is_sensitive(name: &[u8]) -> bool {
+    matches!(
+        name,
+        b"authorization" | b"proxy-authorization" | b"cookie" | b"set-cookie"
+    )
+}
+
+/// How the `QPACK` encoder fills the dynamic table
+///
+/// Whatever the policy, sensitive fields are never inserted. Those are the values marked with
+/// [`HeaderValue::set_sensitive()`], along with the `authorization`, `proxy-authorization`,
+/// `cookie` and `set-cookie` headers. They are also flagged so intermediaries don't index them.
+///
+/// [`HeaderValue::set_sensitive()`]: https://docs.rs/http/*/http/header/struct.HeaderValue.html#method.set_sensitive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertionPolicy {
+    /// Insert every field that fits into the table, the default
+    Always,
+    /// Only reference the static table, sending anything else as literals
+    StaticOnly,
+    /// Insert names only, values always being sent as literals
+    NameOnly,
+    /// Insert a field once it has been sent this many times on the connection
+    Frequency(u32),
+}
+
+impl Default for InsertionPolicy {
+    fn default() -> Self {
+        InsertionPolicy::Always
+    }
 }
 
 pub fn on_decoder_recv<R: Buf>(table: &mut DynamicTable, read: &mut R) -> Result<(), Error> {
@@ -403,6 +502,115 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encode_sensitive() {
+        let field = HeaderField::new("foo", "secret");
+        let sensitive = field.clone().with_sensitive(true);
+        check_encode_field(&[field], &[sensitive], &|mut b, e| {
+            assert_eq!(
+                LiteralWithNameRef::decode(&mut b),
+                Ok(LiteralWithNameRef::new_dynamic(0, "secret").never_indexed(true))
+            );
+            assert_eq!(e.get_ref().len(), 0);
+        });
+    }
+
+    #[test]
+    fn encode_credentials_sensitive() {
+        let field = HeaderField::new("authorization", "Basic Zm9vOmJhcg==");
+        check_encode_field(&[], &[field], &|mut b, e| {
+            assert_eq!(
+                LiteralWithNameRef::decode(&mut b),
+                Ok(LiteralWithNameRef::new_static(84, "Basic Zm9vOmJhcg==").never_indexed(true))
+            );
+            assert_eq!(e.get_ref().len(), 0);
+        });
+
+        let field = HeaderField::new("x-token", "secret").with_sensitive(true);
+        check_encode_field(&[], &[field], &|mut b, e| {
+            assert_eq!(
+                Literal::decode(&mut b),
+                Ok(Literal {
+                    never_indexed: true,
+                    ..Literal::new("x-token", "secret")
+                })
+            );
+            assert_eq!(e.get_ref().len(), 0);
+        });
+    }
+
+    #[test]
+    fn encode_static_only() {
+        let mut table = build_table();
+        table.set_max_size(TABLE_SIZE).unwrap();
+        table.set_insertion_policy(InsertionPolicy::StaticOnly);
+        let fields = [
+            HeaderField::new("location", "/bar"),
+            HeaderField::new("foo", "bar"),
+        ];
+        check_encode_field_table(&mut table, &[], &fields, 1, &|mut b, e| {
+            assert_eq!(
+                LiteralWithNameRef::decode(&mut b),
+                Ok(LiteralWithNameRef::new_static(12, "/bar"))
+            );
+            assert_eq!(Literal::decode(&mut b), Ok(Literal::new("foo", "bar")));
+            assert_eq!(e.get_ref().len(), 0);
+        });
+    }
+
+    #[test]
+    fn encode_name_only() {
+        let mut table = build_table();
+        table.set_max_size(TABLE_SIZE).unwrap();
+        table.set_insertion_policy(InsertionPolicy::NameOnly);
+        let fields = [
+            HeaderField::new("foo", "bar"),
+            HeaderField::new("foo", "baz"),
+            HeaderField::new("location", "/bar"),
+        ];
+        check_encode_field_table(&mut table, &[], &fields, 1, &|mut b, mut e| {
+            assert_eq!(
+                LiteralWithPostBaseNameRef::decode(&mut b),
+                Ok(LiteralWithPostBaseNameRef::new(0, "bar"))
+            );
+            assert_eq!(
+                LiteralWithPostBaseNameRef::decode(&mut b),
+                Ok(LiteralWithPostBaseNameRef::new(0, "baz"))
+            );
+            assert_eq!(
+                LiteralWithNameRef::decode(&mut b),
+                Ok(LiteralWithNameRef::new_static(12, "/bar"))
+            );
+            assert_eq!(
+                InsertWithoutNameRef::decode(&mut e),
+                Ok(Some(InsertWithoutNameRef::new("foo", "")))
+            );
+            assert_eq!(e.get_ref().len() as u64, e.position());
+        });
+    }
+
+    #[test]
+    fn encode_frequency() {
+        let mut table = build_table();
+        table.set_max_size(TABLE_SIZE).unwrap();
+        table.set_insertion_policy(InsertionPolicy::Frequency(2));
+        let fields = [HeaderField::new("foo", "bar")];
+        check_encode_field_table(&mut table, &[], &fields, 1, &|mut b, e| {
+            assert_eq!(Literal::decode(&mut b), Ok(Literal::new("foo", "bar")));
+            assert_eq!(e.get_ref().len(), 0);
+        });
+        check_encode_field_table(&mut table, &[], &fields, 2, &|mut b, mut e| {
+            assert_eq!(
+                IndexedWithPostBase::decode(&mut b),
+                Ok(IndexedWithPostBase(0))
+            );
+            assert_eq!(
+                InsertWithoutNameRef::decode(&mut e),
+                Ok(Some(InsertWithoutNameRef::new("foo", "bar")))
+            );
+        });
+    }
+
     #[test]
     fn encode_with_header_block() {
         let mut table = build_table();
@@ -13,6 +13,8 @@ pub const ESTIMATED_OVERHEAD_BYTES: usize = 32;
 pub struct HeaderField {
     pub name: Cow<'static, [u8]>,
     pub value: Cow<'static, [u8]>,
+    /// Never inserted into the dynamic table, and encoded so intermediaries don't either
+    pub sensitive: bool,
 }
 
 impl HeaderField {
@@ -24,9 +26,15 @@ impl HeaderField {
         HeaderField {
             name: Cow::Owned(name.into()),
             value: Cow::Owned(value.into()),
+            sensitive: false,
         }
     }
 
+    pub fn with_sensitive(mut self, sensitive: bool) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
     pub fn mem_size(&self) -> usize {
         self.name.len() + self.value.len() + ESTIMATED_OVERHEAD_BYTES
     }
@@ -38,6 +46,7 @@ impl HeaderField {
         Self {
             name: self.name.to_owned(),
             value: Cow::Owned(value.into()),
+            sensitive: false,
         }
     }
 
@@ -85,6 +94,7 @@ where
             // FIXME: could avoid allocation if HeaderField had a lifetime
             name: Cow::Owned(Vec::from(name.as_ref())),
             value: Cow::Owned(Vec::from(value.as_ref())),
+            sensitive: false,
         }
     }
 }
@@ -105,6 +115,7 @@ mod tests {
         let field = HeaderField {
             name: Cow::Borrowed(b"Name"),
             value: Cow::Borrowed(b"Value"),
+            sensitive: false,
         };
         assert_eq!(field.mem_size(), 4 + 5 + 32);
     }
@@ -114,12 +125,14 @@ mod tests {
         let field = HeaderField {
             name: Cow::Borrowed(b"Name"),
             value: Cow::Borrowed(b"Value"),
+            sensitive: false,
         };
         assert_eq!(
             field.with_value("New value"),
             HeaderField {
                 name: Cow::Borrowed(b"Name"),
                 value: Cow::Borrowed(b"New value"),
+                sensitive: false,
             }
         );
     }
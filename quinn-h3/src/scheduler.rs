@@ -0,0 +1,277 @@
+use std::{
+    collections::HashMap,
+    task::{Context, Poll, Waker},
+};
+
+use tracing::trace;
+
+use crate::{
+    connection::ConnectionRef,
+    proto::priority::{Prioritized, Priority},
+};
+
+/// Orders the DATA of the responses a server sends concurrently, following their priority
+///
+/// Before each write, a response waits for its turn: it gets it unless another response with
+/// DATA ready to be written would be served first. That is a more urgent response, or a
+/// non-incremental response of the same urgency sent on a lower stream, as those are delivered
+/// one at a time. Incremental responses of the same urgency share the connection.
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    responses: HashMap<Prioritized, Response>,
+}
+
+#[derive(Default)]
+struct Response {
+    priority: Priority,
+    /// The client reprioritized the response, overriding the request's header
+    updated: bool,
+    /// The response is being sent, so its entry goes away once done
+    sending: bool,
+    /// Has DATA to write, waiting for its turn or being written
+    ready: bool,
+    /// Waiting for a response served first to be done writing
+    blocked: Option<Waker>,
+}
+
+impl Scheduler {
+    /// A response starts being sent, with the priority signaled by its request
+    pub fn start(&mut self, element: Prioritized, priority: Priority) {
+        let response = self.responses.entry(element).or_default();
+        if !response.updated {
+            response.priority = priority;
+        }
+        response.sending = true;
+        trace!(element = ?element, priority = ?response.priority, "scheduling response");
+    }
+
+    pub fn finish(&mut self, element: Prioritized) {
+        if self.responses.remove(&element).is_some() {
+            self.wake_blocked();
+        }
+    }
+
+    /// The client sent a PRIORITY_UPDATE frame
+    ///
+    /// Updates received before the response starts are kept, up to a limit, beyond which
+    /// `is_pending()` tells which of those are still worth keeping.
+    pub fn update(
+        &mut self,
+        element: Prioritized,
+        priority: Priority,
+        is_pending: impl Fn(Prioritized) -> bool,
+    ) {
+        if !self.responses.contains_key(&element) {
+            if !is_pending(element) {
+                return;
+            }
+            if self.responses.values().filter(|r| !r.sending).count() >= MAX_PENDING_UPDATES {
+                // Pushes can't be told apart, so their updates go first
+                self.responses.retain(|e, r| match e {
+                    _ if r.sending => true,
+                    Prioritized::Request(_) => is_pending(*e),
+                    Prioritized::Push(_) => false,
+                });
+            }
+            if self.responses.values().filter(|r| !r.sending).count() >= MAX_PENDING_UPDATES {
+                trace!(element = ?element, "too many pending priority updates");
+                return;
+            }
+        }
+        let response = self.responses.entry(element).or_default();
+        response.priority = priority;
+        response.updated = true;
+        trace!(element = ?element, priority = ?priority, "reprioritized");
+        self.wake_blocked();
+    }
+
+    pub fn priority(&self, element: Prioritized) -> Option<Priority> {
+        self.responses.get(&element).map(|r| r.priority)
+    }
+
+    /// Resolves once `element` may write its next DATA, which it's ready to until `idle()`
+    pub fn poll_turn(&mut self, element: Prioritized, cx: &mut Context) -> Poll<()> {
+        let priority = match self.responses.get_mut(&element) {
+            Some(r) => {
+                r.ready = true;
+                r.priority
+            }
+            None => return Poll::Ready(()),
+        };
+        let blocked = self
+            .responses
+            .iter()
+            .any(|(other, r)| r.ready && precedes((*other, r.priority), (element, priority)));
+        if blocked {
+            self.responses.get_mut(&element).unwrap().blocked = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        Poll::Ready(())
+    }
+
+    /// `element` has written all its ready DATA, letting the responses it held back go
+    pub fn idle(&mut self, element: Prioritized) {
+        if let Some(r) = self.responses.get_mut(&element) {
+            r.ready = false;
+            self.wake_blocked();
+        }
+    }
+
+    fn wake_blocked(&mut self) {
+        for response in self.responses.values_mut() {
+            if let Some(w) = response.blocked.take() {
+                w.wake();
+            }
+        }
+    }
+}
+
+fn precedes(a: (Prioritized, Priority), b: (Prioritized, Priority)) -> bool {
+    let ((a, a_priority), (b, b_priority)) = (a, b);
+    match a_priority.urgency().cmp(&b_priority.urgency()) {
+        std::cmp::Ordering::Less => true,
+        std::cmp::Ordering::Greater => false,
+        std::cmp::Ordering::Equal => {
+            !a_priority.incremental() && !b_priority.incremental() && a < b
+        }
+    }
+}
+
+/// A response sent under the scheduler's control, leaving it once dropped
+pub(crate) struct Scheduled {
+    conn: ConnectionRef,
+    element: Prioritized,
+}
+
+impl Scheduled {
+    pub fn new(conn: ConnectionRef, element: Prioritized, priority: Priority) -> Self {
+        conn.h3.lock().unwrap().scheduler.start(element, priority);
+        Self { conn, element }
+    }
+
+    pub fn poll_turn(&self, cx: &mut Context) -> Poll<()> {
+        self.conn
+            .h3
+            .lock()
+            .unwrap()
+            .scheduler
+            .poll_turn(self.element, cx)
+    }
+
+    pub fn idle(&self) {
+        self.conn.h3.lock().unwrap().scheduler.idle(self.element);
+    }
+}
+
+impl Drop for Scheduled {
+    fn drop(&mut self) {
+        self.conn.h3.lock().unwrap().scheduler.finish(self.element);
+    }
+}
+
+/// Largest amount of DATA a scheduled response writes in one turn
+pub(crate) const SCHEDULING_QUANTUM: usize = 16 * 1024;
+/// Priority updates kept for responses that haven't started yet
+const MAX_PENDING_UPDATES: usize = 64;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker_ref;
+
+    fn turn(scheduler: &mut Scheduler, element: Prioritized) -> Poll<()> {
+        scheduler.poll_turn(element, &mut Context::from_waker(noop_waker_ref()))
+    }
+
+    const CSS: Prioritized = Prioritized::Request(0);
+    const IMAGE: Prioritized = Prioritized::Request(4);
+    const SCRIPT: Prioritized = Prioritized::Request(8);
+
+    #[test]
+    fn urgent_response_goes_first() {
+        let mut scheduler = Scheduler::default();
+        scheduler.start(IMAGE, Priority::new(5, true));
+        scheduler.start(CSS, Priority::new(0, false));
+
+        // The image goes while the stylesheet has nothing to send
+        assert_eq!(turn(&mut scheduler, IMAGE), Poll::Ready(()));
+        scheduler.idle(IMAGE);
+
+        // Then waits while the stylesheet has DATA ready
+        assert_eq!(turn(&mut scheduler, CSS), Poll::Ready(()));
+        assert_eq!(turn(&mut scheduler, IMAGE), Poll::Pending);
+        assert!(scheduler.responses[&IMAGE].blocked.is_some());
+        scheduler.idle(CSS);
+        assert!(scheduler.responses[&IMAGE].blocked.is_none());
+        assert_eq!(turn(&mut scheduler, IMAGE), Poll::Ready(()));
+
+        // The stylesheet isn't held back by the image being written
+        assert_eq!(turn(&mut scheduler, CSS), Poll::Ready(()));
+        scheduler.finish(CSS);
+        assert_eq!(turn(&mut scheduler, IMAGE), Poll::Ready(()));
+    }
+
+    #[test]
+    fn same_urgency() {
+        let mut scheduler = Scheduler::default();
+        scheduler.start(CSS, Priority::default());
+        scheduler.start(IMAGE, Priority::default());
+        scheduler.start(SCRIPT, Priority::new(3, true));
+
+        // Non-incremental responses go in stream order
+        assert_eq!(turn(&mut scheduler, CSS), Poll::Ready(()));
+        assert_eq!(turn(&mut scheduler, IMAGE), Poll::Pending);
+        // Incremental ones share the connection
+        assert_eq!(turn(&mut scheduler, SCRIPT), Poll::Ready(()));
+    }
+
+    #[test]
+    fn reprioritized_while_blocked() {
+        let mut scheduler = Scheduler::default();
+        scheduler.start(CSS, Priority::new(1, false));
+        scheduler.start(IMAGE, Priority::new(5, false));
+        assert_eq!(turn(&mut scheduler, CSS), Poll::Ready(()));
+        assert_eq!(turn(&mut scheduler, IMAGE), Poll::Pending);
+
+        scheduler.update(IMAGE, Priority::new(0, false), |_| true);
+        assert!(scheduler.responses[&IMAGE].blocked.is_none());
+        assert_eq!(turn(&mut scheduler, IMAGE), Poll::Ready(()));
+        assert_eq!(turn(&mut scheduler, CSS), Poll::Pending);
+    }
+
+    #[test]
+    fn update_overrides_header() {
+        let mut scheduler = Scheduler::default();
+        scheduler.update(IMAGE, Priority::new(6, true), |_| true);
+        scheduler.start(IMAGE, Priority::new(1, false));
+        assert_eq!(scheduler.priority(IMAGE), Some(Priority::new(6, true)));
+
+        scheduler.update(IMAGE, Priority::new(2, false), |_| true);
+        assert_eq!(scheduler.priority(IMAGE), Some(Priority::new(2, false)));
+
+        scheduler.finish(IMAGE);
+        assert_eq!(scheduler.priority(IMAGE), None);
+    }
+
+    #[test]
+    fn pending_updates_limited() {
+        let mut scheduler = Scheduler::default();
+        scheduler.update(CSS, Priority::new(0, false), |_| false);
+        assert_eq!(scheduler.priority(CSS), None);
+
+        for i in 0..MAX_PENDING_UPDATES as u64 {
+            scheduler.update(Prioritized::Request(i * 4), Priority::default(), |_| true);
+        }
+        scheduler.update(Prioritized::Push(0), Priority::default(), |_| true);
+        assert_eq!(scheduler.priority(Prioritized::Push(0)), None);
+
+        // Updates of requests which are done make room
+        let pending = |e| e != CSS;
+        scheduler.update(Prioritized::Push(0), Priority::default(), pending);
+        assert_eq!(scheduler.priority(CSS), None);
+        assert_eq!(
+            scheduler.priority(Prioritized::Push(0)),
+            Some(Priority::default())
+        );
+    }
+}
@@ -107,24 +107,30 @@ use std::{
     task::{Context, Poll},
 };
 
-use futures::{ready, FutureExt, Stream};
-use http::{response, Request, Response};
+use bytes::BytesMut;
+use futures::{future, ready, FutureExt, Stream};
+use http::{request, response, Method, Request, Response};
 use http_body::Body as HttpBody;
-use quinn::{
-    CertificateChain, EndpointBuilder, PrivateKey, RecvStream, SendStream, ZeroRttAccepted,
-};
-use quinn_proto::Side;
+use quinn::{CertificateChain, EndpointBuilder, PrivateKey, SendStream, ZeroRttAccepted};
+use quinn_proto::{coding::BufMutExt, Side};
 use rustls::TLSError;
 use tracing::trace;
 
 use crate::{
     body::RecvBody,
     connection::{ConnectionDriver, ConnectionRef},
-    data::{RecvData, SendData},
-    frame::FrameDecoder,
-    proto::{headers::Header, ErrorCode},
+    data::{write_headers_frame, RecvData, SendData},
+    frame::FrameStream,
+    masque::UdpTunnel,
+    proto::{
+        frame::{HttpFrame, PushPromiseFrame},
+        headers::{Error as HeaderError, Header},
+        priority::Prioritized,
+        ErrorCode, StreamType,
+    },
     streams::Reset,
-    Error, Settings,
+    webtransport::WebTransportSession,
+    Error, HttpError, Priority, Protocol, RecvTunnel, SendTunnel, Settings,
 };
 
 /// Configure and build a HTTP/3.0 server
@@ -188,17 +194,35 @@ impl Builder {
         self
     }
 
+    /// Also offer the draft versions of HTTP/3, for clients which don't speak the final one yet
+    ///
+    /// The server then negotiates [`DRAFT_ALPN`] protocols along with [`ALPN`], preferring the
+    /// latter. Disabled by default.
+    ///
+    /// [`ALPN`]: ../constant.ALPN.html
+    /// [`DRAFT_ALPN`]: ../constant.DRAFT_ALPN.html
+    pub fn draft_versions(&mut self, enabled: bool) -> &mut Self {
+        let mut protocols = vec![crate::ALPN];
+        if enabled {
+            protocols.extend_from_slice(crate::DRAFT_ALPN);
+        }
+        self.config.protocols(&protocols);
+        self
+    }
+
     /// Specify a base QUIC configuration
     ///
     /// Useful for fine-tuning QUIC settings like flow control, cryptography... See
     /// [`ServerConfigBuilder`] for the list of configurable settings.
     ///
-    /// The ALPN protocol will be overwritten with [`quinn-h3's protocol`](../constant.ALPN.html).
-    /// If you want to set your own, use [`Builder::endpoint()`] with an explicitly configured endpoint.
+    /// The ALPN protocol will be overwritten with [`quinn-h3's protocol`](../constant.ALPN.html),
+    /// see [`Builder::draft_versions()`] to offer older ones too. If you want to set your own, use
+    /// [`Builder::endpoint()`] with an explicitly configured endpoint.
     ///
     /// [`ServerConfigBuilder`]: ../../quinn/generic/struct.ServerConfigBuilder.html
     /// [`ServerConfig`]: /quinn/struct.ServerConfig.html
     /// [`quinn-h3's ALPN`]: ../constant.ALPN.html
+    /// [`Builder::draft_versions()`]: #method.draft_versions
     /// [`Builder::endpoint()`]: #method.endpoint
     pub fn with_quic_config(mut config: quinn::ServerConfigBuilder) -> Self {
         config.protocols(&[crate::ALPN]);
@@ -374,11 +398,18 @@ impl Connecting {
             connection,
             bi_streams,
             uni_streams,
+            datagrams,
             ..
         } = new_connection;
 
-        let conn_ref =
-            ConnectionRef::new(connection, Side::Server, uni_streams, bi_streams, settings);
+        let conn_ref = ConnectionRef::new(
+            connection,
+            Side::Server,
+            uni_streams,
+            bi_streams,
+            datagrams,
+            settings,
+        );
         tokio::spawn(ConnectionDriver(conn_ref.clone()));
         Ok((IncomingRequest(conn_ref), zerortt_accepted))
     }
@@ -456,6 +487,7 @@ impl Future for Connecting {
             connection,
             bi_streams,
             uni_streams,
+            datagrams,
             ..
         } = ready!(Pin::new(&mut self.connecting).poll(cx))?;
         let conn_ref = ConnectionRef::new(
@@ -463,6 +495,7 @@ impl Future for Connecting {
             Side::Server,
             uni_streams,
             bi_streams,
+            datagrams,
             self.settings.clone(),
         );
         tokio::spawn(ConnectionDriver(conn_ref.clone()));
@@ -582,13 +615,9 @@ impl RecvRequest {
         }
     }
 
-    fn new(recv: RecvStream, send: SendStream, conn: ConnectionRef) -> Self {
-        let is_0rtt = recv.is_0rtt();
-        let recv = Some(RecvData::new(
-            FrameDecoder::stream(recv),
-            conn.clone(),
-            send.id(),
-        ));
+    fn new(recv: FrameStream, send: SendStream, conn: ConnectionRef) -> Self {
+        let is_0rtt = recv.get_ref().is_0rtt();
+        let recv = Some(RecvData::new(recv, conn.clone(), send.id()));
         Self {
             conn,
             recv,
@@ -602,7 +631,13 @@ impl RecvRequest {
         headers: Header,
         body: RecvBody,
     ) -> Result<Request<RecvBody>, Error> {
-        let (method, uri, headers) = match headers.into_request_parts() {
+        let protocol = headers.protocol().cloned();
+        let parts = match protocol {
+            // Extended CONNECT is only allowed once advertised in the settings
+            Some(_) if !self.extended_connect_enabled() => Err(HeaderError::InvalidConnect),
+            _ => headers.into_request_parts(),
+        };
+        let (method, uri, headers) = match parts {
             Ok(x) => x,
             Err(e) => {
                 body.into_inner().reset(ErrorCode::REQUEST_REJECTED);
@@ -636,8 +671,16 @@ impl RecvRequest {
             .unwrap();
 
         *request.headers_mut() = headers;
+        if let Some(protocol) = protocol {
+            request.extensions_mut().insert(protocol);
+        }
         Ok(request)
     }
+
+    fn extended_connect_enabled(&self) -> bool {
+        let conn = self.conn.h3.lock().unwrap();
+        conn.inner.local_settings().enable_connect_protocol()
+    }
 }
 
 impl Future for RecvRequest {
@@ -648,6 +691,7 @@ impl Future for RecvRequest {
         let request = self.build_request(header, body)?;
         trace!("Got {:?}", request);
         let sender = Sender {
+            priority: Priority::from_headers(request.headers()),
             send: self.send.take(),
             conn: Some(self.conn.clone()),
         };
@@ -665,14 +709,20 @@ impl Future for RecvRequest {
 /// The request can also be cancelled with [`cancel()`], after which the client will receive a request
 /// error with `REQUEST_CANCELLED` cause.
 ///
+/// The response's DATA is sent in turns with the other responses of the connection, in order of
+/// [`priority()`]: the one the client signaled with the request, or later updated.
+///
 /// [`RecvRequest`]: struct.RecvRequest.html
 /// [`http::Response`]: https://docs.rs/http/*/http/response/struct.Response.html
 /// [`send_response()`]: #method.send_response
 /// [`BodyReader`]: ../body/struct.BodyReader.html
 /// [`cancel()`]: #method.cancel
+/// [`priority()`]: #method.priority
 pub struct Sender {
     send: Option<SendStream>,
     conn: Option<ConnectionRef>,
+    /// Signaled by the request's header
+    priority: Priority,
 }
 
 impl Sender {
@@ -716,15 +766,300 @@ impl Sender {
         B::Data: Send,
         B::Error: Into<Box<dyn StdError + Send + Sync>> + Send + Sync,
     {
-        let (response, body) = response.into_parts();
+        let (header, body) = response_header(response);
+        let (send, conn) = (self.send.take().unwrap(), self.conn.take().unwrap());
+        let element = Prioritized::Request(send.id().0);
+        SendData::new(send, conn, header, body, true).scheduled(element, self.priority)
+    }
 
-        let response::Parts {
-            status, headers, ..
-        } = response;
-        let header = Header::response(status, headers);
+    /// The priority the response will be sent with, unless reprioritized by the client
+    ///
+    /// Until the response is sent, it's the one signaled by the request's `priority` header,
+    /// unless the client already sent an update.
+    pub fn priority(&self) -> Priority {
+        let element = match self.send.as_ref() {
+            Some(send) => Prioritized::Request(send.id().0),
+            None => return self.priority,
+        };
+        self.conn
+            .as_ref()
+            .and_then(|c| c.h3.lock().unwrap().scheduler.priority(element))
+            .unwrap_or(self.priority)
+    }
 
-        let (send, conn) = (self.send.take().unwrap(), self.conn.take().unwrap());
-        SendData::new(send, conn, header, body, true)
+    /// Accept a CONNECT request, establishing a tunnel
+    ///
+    /// Sends `response`, which must have a successful (2xx) status, without ending the request
+    /// stream. The stream then carries the tunneled bytes in both directions, through the
+    /// returned halves. To refuse the tunnel, use [`send_response()`] instead.
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use http::{Method, Response, StatusCode};
+    /// use quinn_h3::{server::RecvRequest, Body};
+    /// use tokio::io::{self, AsyncWriteExt};
+    ///
+    /// async fn echo_tunnel(recv_request: RecvRequest) -> Result<()> {
+    ///     let (request, mut sender) = recv_request.await?;
+    ///     if request.method() != Method::CONNECT {
+    ///         let response = Response::builder()
+    ///             .status(StatusCode::METHOD_NOT_ALLOWED)
+    ///             .body(Body::from(()))?;
+    ///         sender.send_response(response).await?;
+    ///         return Ok(());
+    ///     }
+    ///
+    ///     let (mut send, mut recv) = sender.accept_connect(request, Response::new(())).await?;
+    ///     io::copy(&mut recv, &mut send).await?;
+    ///     send.shutdown().await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`send_response()`]: #method.send_response
+    pub async fn accept_connect(
+        &mut self,
+        request: Request<RecvBody>,
+        response: Response<()>,
+    ) -> Result<(SendTunnel, RecvTunnel), Error> {
+        if request.method() != Method::CONNECT {
+            return Err(Error::Header("Only CONNECT requests can be tunneled"));
+        }
+        if !response.status().is_success() {
+            return Err(Error::Header("Tunnels must be accepted with a 2xx status"));
+        }
+        let (mut send, conn) = match (self.send.take(), self.conn.take()) {
+            (Some(send), Some(conn)) => (send, conn),
+            _ => return Err(Error::internal("response already sent")),
+        };
+
+        let (header, _) = response_header(response);
+        let mut write = write_headers_frame(header, send.id(), &conn)?;
+        future::poll_fn(|cx| write.poll_send(&mut send, cx)).await?;
+        trace!(stream = %send.id(), "tunnel accepted");
+
+        Ok((
+            SendTunnel::new(send, conn, true),
+            RecvTunnel::new(request.into_body().into_inner()),
+        ))
+    }
+
+    /// Accept a WebTransport session
+    ///
+    /// `request` must be an extended CONNECT with the `webtransport` protocol, which the
+    /// [`Protocol`] request extension tells. This requires WebTransport to be enabled in the
+    /// server's [`Settings`]. A successful response is sent, and the request stream stays open
+    /// for the whole session. To refuse the session, use [`send_response()`] instead.
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use quinn_h3::{server::RecvRequest, Protocol};
+    ///
+    /// async fn echo_datagrams(recv_request: RecvRequest) -> Result<()> {
+    ///     let (request, mut sender) = recv_request.await?;
+    ///     if request.extensions().get() == Some(&Protocol::WEBTRANSPORT) {
+    ///         let session = sender.accept_webtransport(request).await?;
+    ///         while let Some(datagram) = session.recv_datagram().await {
+    ///             session.send_datagram(datagram)?;
+    ///         }
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`Protocol`]: ../struct.Protocol.html
+    /// [`Settings`]: ../struct.Settings.html
+    /// [`send_response()`]: #method.send_response
+    pub async fn accept_webtransport(
+        &mut self,
+        request: Request<RecvBody>,
+    ) -> Result<WebTransportSession, Error> {
+        let (send, conn) = self
+            .accept_session(
+                request,
+                Protocol::WEBTRANSPORT,
+                ("WebTransport", Settings::enable_webtransport),
+                Response::new(()),
+            )
+            .await?;
+        Ok(WebTransportSession::new(send, conn))
+    }
+
+    /// Accept a CONNECT-UDP request, establishing a UDP tunnel
+    ///
+    /// `request` must be an extended CONNECT with the `connect-udp` protocol, which the
+    /// [`Protocol`] request extension tells. This requires HTTP datagrams and the extended CONNECT
+    /// protocol to be enabled in the server's [`Settings`]. A successful response is sent, and the
+    /// request stream stays open for the whole tunnel. To refuse the tunnel, use
+    /// [`send_response()`] instead.
+    ///
+    /// See the [`masque`] module for an example.
+    ///
+    /// [`Protocol`]: ../struct.Protocol.html
+    /// [`Settings`]: ../struct.Settings.html
+    /// [`send_response()`]: #method.send_response
+    /// [`masque`]: ../masque/index.html
+    pub async fn accept_connect_udp(
+        &mut self,
+        request: Request<RecvBody>,
+    ) -> Result<UdpTunnel, Error> {
+        let response = Response::builder()
+            .header("capsule-protocol", "?1")
+            .body(())
+            .unwrap();
+        let (send, conn) = self
+            .accept_session(
+                request,
+                Protocol::CONNECT_UDP,
+                ("HTTP datagrams", Settings::h3_datagram),
+                response,
+            )
+            .await?;
+        Ok(UdpTunnel::new(send, conn))
+    }
+
+    /// Accept an extended CONNECT request for `protocol`, whose stream then carries a session
+    ///
+    /// The session is registered before the response is sent, so nothing sent by the client
+    /// in the meantime is lost.
+    async fn accept_session(
+        &mut self,
+        request: Request<RecvBody>,
+        protocol: Protocol,
+        (setting, enabled): (&'static str, fn(&Settings) -> bool),
+        response: Response<()>,
+    ) -> Result<(SendStream, ConnectionRef), Error> {
+        if request.method() != Method::CONNECT || request.extensions().get() != Some(&protocol) {
+            return Err(Error::Header("Unexpected CONNECT protocol"));
+        }
+        let allowed = self.conn.as_ref().map(|conn| {
+            let h3 = conn.h3.lock().unwrap();
+            enabled(h3.inner.local_settings())
+        });
+        if allowed == Some(false) {
+            return Err(Error::SettingDisabled(setting));
+        }
+        let (mut send, conn) = match (self.send.take(), self.conn.take()) {
+            (Some(send), Some(conn)) => (send, conn),
+            _ => return Err(Error::internal("response already sent")),
+        };
+
+        let id = send.id();
+        {
+            let mut h3 = conn.h3.lock().unwrap();
+            h3.open_session(id);
+            h3.set_session_stream(id, request.into_body().into_inner());
+        }
+        let (header, _) = response_header(response);
+        let sent = async {
+            let mut write = write_headers_frame(header, id, &conn)?;
+            future::poll_fn(|cx| write.poll_send(&mut send, cx)).await?;
+            Ok(())
+        };
+        if let Err(e) = sent.await {
+            conn.h3.lock().unwrap().close_session(id);
+            return Err(e);
+        }
+        trace!(stream = %id, "{} session accepted", protocol.as_str());
+
+        Ok((send, conn))
+    }
+
+    /// Promise to push a response for `request`
+    ///
+    /// Sends a PUSH_PROMISE frame ahead of this request's response, then opens the stream the
+    /// pushed response will be sent on. Must therefore be called before [`send_response()`].
+    ///
+    /// The pushed request must use a safe method, such as `GET`, and have an authority. Fails
+    /// if the client does not allow more pushes; see the client's
+    /// [`Builder::max_pushes()`].
+    ///
+    /// ```
+    /// use anyhow::Result;
+    /// use http::{Request, Response, StatusCode};
+    /// use quinn_h3::{server::Sender, Body};
+    ///
+    /// async fn push_style(mut sender: Sender) -> Result<()> {
+    ///     let request = Request::get("https://example.com/style.css").body(())?;
+    ///     let mut push = sender.push_promise(request).await?;
+    ///     push.send_response(Response::new(Body::from("body {}"))).await?;
+    ///
+    ///     let response = Response::builder()
+    ///         .status(StatusCode::OK)
+    ///         .body(Body::from("<link rel=stylesheet href=/style.css>"))?;
+    ///     sender.send_response(response).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`send_response()`]: #method.send_response
+    /// [`Builder::max_pushes()`]: ../client/struct.Builder.html#method.max_pushes
+    pub async fn push_promise(&mut self, request: Request<()>) -> Result<PushSender, Error> {
+        let (request, _) = request.into_parts();
+        let request::Parts {
+            method,
+            uri,
+            headers,
+            ..
+        } = request;
+        if !method.is_safe() {
+            return Err(Error::Header("Pushed requests must use a safe method"));
+        }
+        if uri.authority().is_none() {
+            return Err(Error::Header("Missing authority"));
+        }
+        let priority = Priority::from_headers(&headers);
+        let (send, conn) = match (self.send.as_mut(), self.conn.as_ref()) {
+            (Some(send), Some(conn)) => (send, conn.clone()),
+            _ => return Err(Error::internal("response already sent")),
+        };
+
+        let push_id = {
+            let mut h3 = conn.h3.lock().unwrap();
+            let push_id = h3.inner.next_push_id()?;
+            h3.push_promised(push_id);
+            push_id
+        };
+        let promised = PromisedPush {
+            conn: conn.clone(),
+            push_id,
+        };
+        let header = Header::request(method, uri, headers);
+        write_push_promise(send, &conn, push_id, header).await?;
+
+        let cancelled = future::poll_fn(|cx| promised.poll_cancelled(cx));
+        let mut push = match future::select(conn.quic.open_uni(), cancelled).await {
+            future::Either::Left((push, _)) => push?,
+            future::Either::Right(_) => {
+                trace!(push_id, "push cancelled before its stream opened");
+                return Err(Error::Http(HttpError::RequestCancelled, None));
+            }
+        };
+        let mut prefix = BytesMut::new();
+        StreamType::PUSH.encode(&mut prefix);
+        prefix.write_var(push_id);
+        push.write_all(&prefix).await?;
+
+        Ok(PushSender {
+            send: Some(push),
+            conn,
+            promised: Some(promised),
+            push_id,
+            priority,
+        })
+    }
+
+    /// Monitor stop sending signal from the client
+    ///
+    /// This will return `Ready` when the client stopped reading the response before it's sent,
+    /// as it does when cancelling the request, so the application can give up on it. Else, it
+    /// will return `Pending` indefinitely. Fails once the response is sent.
+    pub fn poll_stopped(&mut self, cx: &mut Context) -> Poll<Result<HttpError, Error>> {
+        let send = match self.send.as_mut() {
+            Some(send) => send,
+            None => return Poll::Ready(Err(Error::internal("response already sent"))),
+        };
+        Poll::Ready(Ok(ready!(send.poll_stopped(cx)?).into()))
     }
 
     /// Cancel request processing
@@ -742,3 +1077,126 @@ impl Sender {
             .reset(ErrorCode::REQUEST_CANCELLED.into());
     }
 }
+
+#[cfg(test)]
+impl Sender {
+    /// Promise push `push_id` again, as a server may for another request
+    pub(crate) async fn repeat_push_promise(
+        &mut self,
+        push_id: u64,
+        request: Request<()>,
+    ) -> Result<(), Error> {
+        let (request, _) = request.into_parts();
+        let header = Header::request(request.method, request.uri, request.headers);
+        match (self.send.as_mut(), self.conn.as_ref()) {
+            (Some(send), Some(conn)) => write_push_promise(send, conn, push_id, header).await,
+            _ => Err(Error::internal("response already sent")),
+        }
+    }
+}
+
+async fn write_push_promise(
+    send: &mut SendStream,
+    conn: &ConnectionRef,
+    push_id: u64,
+    header: Header,
+) -> Result<(), Error> {
+    let mut frame = BytesMut::new();
+    {
+        let mut h3 = conn.h3.lock().unwrap();
+        let encoded = h3.inner.encode_header(send.id(), header)?.encoded;
+        h3.wake();
+        HttpFrame::PushPromise(PushPromiseFrame {
+            id: push_id,
+            encoded,
+        })
+        .encode(&mut frame);
+    }
+    send.write_all(&frame).await?;
+    trace!(push_id, stream = %send.id(), "push promised");
+    Ok(())
+}
+
+/// Send a pushed response
+///
+/// Obtained from [`Sender::push_promise()`], once the push has been promised to the client.
+///
+/// [`Sender::push_promise()`]: struct.Sender.html#method.push_promise
+pub struct PushSender {
+    send: Option<SendStream>,
+    conn: ConnectionRef,
+    promised: Option<PromisedPush>,
+    push_id: u64,
+    /// Signaled by the pushed request's header
+    priority: Priority,
+}
+
+impl PushSender {
+    /// Start sending the pushed response
+    ///
+    /// See [`Sender::send_response()`] for details.
+    ///
+    /// [`Sender::send_response()`]: struct.Sender.html#method.send_response
+    pub fn send_response<B>(&mut self, response: Response<B>) -> SendData<B, B::Data>
+    where
+        B: HttpBody + Send + 'static,
+        B::Data: Send,
+        B::Error: Into<Box<dyn StdError + Send + Sync>> + Send + Sync,
+    {
+        let (header, body) = response_header(response);
+        let send = self.send.take().expect("response already sent");
+        let promised = self.promised.take().expect("response already sent");
+        SendData::new(send, self.conn.clone(), header, body, false)
+            .scheduled(Prioritized::Push(self.push_id), self.priority)
+            .pushed(promised)
+    }
+
+    /// Cancel the push
+    ///
+    /// The client is sent a CANCEL_PUSH frame. If the response has not been sent yet, the push
+    /// stream is also reset with `REQUEST_CANCELLED`; otherwise, use [`SendData::cancel()`].
+    ///
+    /// [`SendData::cancel()`]: ../struct.SendData.html#method.cancel
+    pub fn cancel(&mut self) {
+        if let Some(mut send) = self.send.take() {
+            let _ = send.reset(ErrorCode::REQUEST_CANCELLED.into());
+        }
+        self.promised = None;
+        self.conn.h3.lock().unwrap().cancel_push(self.push_id);
+    }
+
+    /// The push ID the response is promised under
+    pub fn push_id(&self) -> u64 {
+        self.push_id
+    }
+}
+
+/// A promised push the client can cancel, until its response is sent or abandoned
+pub(crate) struct PromisedPush {
+    conn: ConnectionRef,
+    push_id: u64,
+}
+
+impl PromisedPush {
+    pub fn poll_cancelled(&self, cx: &mut Context) -> Poll<()> {
+        self.conn
+            .h3
+            .lock()
+            .unwrap()
+            .poll_push_cancelled(self.push_id, cx)
+    }
+}
+
+impl Drop for PromisedPush {
+    fn drop(&mut self) {
+        self.conn.h3.lock().unwrap().push_finished(self.push_id);
+    }
+}
+
+fn response_header<B>(response: Response<B>) -> (Header, B) {
+    let (response, body) = response.into_parts();
+    let response::Parts {
+        status, headers, ..
+    } = response;
+    (Header::response(status, headers), body)
+}
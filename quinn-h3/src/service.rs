@@ -0,0 +1,307 @@
+//! Integration with [`tower`] services
+//!
+//! A client [`Connection`] is a [`Service`] that sends each [`Request`] it's called with, and
+//! resolves to the [`Response`] once its headers are received. On the server side, [`Server`]
+//! drives an [`IncomingConnection`] with any service taking requests with a [`RecvBody`]. This way,
+//! middlewares written for tower, such as authentication, tracing or timeouts, can be used over
+//! HTTP/3.
+//!
+//! This module is only available with the `tower` feature.
+//!
+//! ```
+//! use std::{
+//!     convert::Infallible,
+//!     future::{ready, Ready},
+//!     task::{Context, Poll},
+//! };
+//!
+//! use futures::{channel::oneshot, FutureExt};
+//! use http::{Request, Response};
+//! use quinn_h3::{server::IncomingConnection, service::Server, Body, RecvBody};
+//! use tower_service::Service;
+//!
+//! #[derive(Clone)]
+//! struct Hello;
+//!
+//! impl Service<Request<RecvBody>> for Hello {
+//!     type Response = Response<Body>;
+//!     type Error = Infallible;
+//!     type Future = Ready<Result<Self::Response, Self::Error>>;
+//!
+//!     fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+//!         Poll::Ready(Ok(()))
+//!     }
+//!
+//!     fn call(&mut self, request: Request<RecvBody>) -> Self::Future {
+//!         ready(Ok(Response::new(Body::from("hello"))))
+//!     }
+//! }
+//!
+//! async fn serve(incoming: IncomingConnection, stop: oneshot::Receiver<()>) {
+//!     Server::new(incoming, Hello)
+//!         .serve_with_shutdown(stop.map(|_| ()))
+//!         .await;
+//! }
+//! ```
+//!
+//! [`tower`]: https://docs.rs/tower
+//! [`Connection`]: ../client/struct.Connection.html
+//! [`Service`]: https://docs.rs/tower-service/*/tower_service/trait.Service.html
+//! [`Request`]: https://docs.rs/http/*/http/request/struct.Request.html
+//! [`Response`]: https://docs.rs/http/*/http/response/struct.Response.html
+//! [`Server`]: struct.Server.html
+//! [`IncomingConnection`]: ../server/struct.IncomingConnection.html
+//! [`RecvBody`]: ../struct.RecvBody.html
+
+use std::{
+    error::Error as StdError,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{
+    channel::oneshot,
+    future::{self, Either, Shared},
+    pin_mut, ready, select,
+    stream::FuturesUnordered,
+    FutureExt, StreamExt,
+};
+use http::{Request, Response};
+use http_body::Body as HttpBody;
+use tower_service::Service;
+use tracing::{debug, trace};
+
+use crate::{
+    body::RecvBody,
+    client::{self, RecvResponse, SendRequest},
+    server::{Connecting, IncomingConnection, RecvRequest},
+    Error,
+};
+
+impl<B> Service<Request<B>> for client::Connection
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>> + Send + Sync,
+{
+    type Response = Response<RecvBody>;
+    type Error = Error;
+    type Future = ResponseFuture<B>;
+
+    fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+        // Requests wait for the peer to allow opening their stream, so readiness only depends on
+        // the connection not shutting down
+        match self.is_closing() {
+            true => Poll::Ready(Err(Error::Aborted)),
+            false => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let (send, recv) = self.send_request(request);
+        ResponseFuture {
+            send: Some(Box::pin(send)),
+            recv,
+        }
+    }
+}
+
+/// Response future of a client [`Connection`] service
+///
+/// Sends the request and receives the response concurrently. It resolves once the response headers
+/// are received, the rest of the request being sent in a task of its own, as its body may depend on
+/// the response. The request transmission failing after the response was received, as when the
+/// server ignores the rest of a request body, isn't an error.
+///
+/// [`Connection`]: ../client/struct.Connection.html
+pub struct ResponseFuture<B: HttpBody> {
+    send: Option<PinnedSend<B>>,
+    recv: RecvResponse,
+}
+
+type PinnedSend<B> = Pin<Box<SendRequest<B, <B as HttpBody>::Data>>>;
+
+impl<B> Future for ResponseFuture<B>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>> + Send + Sync,
+{
+    type Output = Result<Response<RecvBody>, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Poll::Ready(response) = self.recv.poll_unpin(cx) {
+            let response = response?;
+            if let Some(send) = self.send.take() {
+                tokio::spawn(async move {
+                    if let Err(e) = send.await {
+                        trace!("request not entirely sent: {}", e);
+                    }
+                });
+            }
+            return Poll::Ready(Ok(response));
+        }
+
+        if let Some(send) = self.send.as_mut() {
+            let result = ready!(send.as_mut().poll(cx));
+            self.send = None;
+            result?;
+        }
+        Poll::Pending
+    }
+}
+
+/// Serve HTTP/3 connections with a [`Service`]
+///
+/// Each connection is handled in its own task, where requests are handled concurrently, with a
+/// clone of the service each. The response is sent once the service resolves, unless the client
+/// cancelled the request meanwhile, in which case the service's future is dropped. A failing
+/// service cancels the request.
+///
+/// [`Service`]: https://docs.rs/tower-service/*/tower_service/trait.Service.html
+pub struct Server<S> {
+    incoming: IncomingConnection,
+    service: S,
+}
+
+impl<S, B> Server<S>
+where
+    S: Service<Request<RecvBody>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>> + Send + Sync,
+{
+    /// Serve the connections from `incoming` with `service`
+    pub fn new(incoming: IncomingConnection, service: S) -> Self {
+        Self { incoming, service }
+    }
+
+    /// Serve connections until the endpoint is closed
+    pub async fn serve(self) {
+        self.serve_with_shutdown(future::pending()).await
+    }
+
+    /// Serve connections until `signal` resolves, then shut down gracefully
+    ///
+    /// No more connections are accepted, and the open ones are sent a GOAWAY frame. This resolves
+    /// once the requests received so far have been responded to.
+    pub async fn serve_with_shutdown<F>(self, signal: F)
+    where
+        F: Future<Output = ()>,
+    {
+        let Self {
+            mut incoming,
+            service,
+        } = self;
+        let (shut_down, shutdown) = oneshot::channel();
+        let shutdown = shutdown.shared();
+        let mut connections = FuturesUnordered::new();
+        let signal = signal.fuse();
+        pin_mut!(signal);
+
+        loop {
+            select! {
+                connecting = incoming.next().fuse() => match connecting {
+                    Some(connecting) => {
+                        let serve = serve_connection(connecting, service.clone(), shutdown.clone());
+                        connections.push(tokio::spawn(serve));
+                    }
+                    None => break,
+                },
+                _ = connections.select_next_some() => (),
+                _ = signal => break,
+            }
+        }
+
+        debug!("shutting down");
+        drop(incoming);
+        let _ = shut_down.send(());
+        while connections.next().await.is_some() {}
+    }
+}
+
+async fn serve_connection<S, B>(
+    connecting: Connecting,
+    service: S,
+    shutdown: Shared<oneshot::Receiver<()>>,
+) where
+    S: Service<Request<RecvBody>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>> + Send + Sync,
+{
+    let mut incoming = match connecting.await {
+        Ok(incoming) => incoming,
+        Err(e) => {
+            debug!("handshake failed: {}", e);
+            return;
+        }
+    };
+    let mut requests = FuturesUnordered::new();
+    let mut shutdown = shutdown.fuse();
+
+    loop {
+        select! {
+            recv_request = incoming.next().fuse() => match recv_request {
+                Some(recv_request) => {
+                    let serve = serve_request(recv_request, service.clone());
+                    requests.push(tokio::spawn(serve));
+                }
+                None => break,
+            },
+            _ = requests.select_next_some() => (),
+            _ = shutdown => incoming.go_away(0),
+        }
+    }
+
+    while requests.next().await.is_some() {}
+}
+
+async fn serve_request<S, B>(recv_request: RecvRequest, mut service: S)
+where
+    S: Service<Request<RecvBody>, Response = Response<B>>,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>> + Send + Sync,
+{
+    let (request, mut sender) = match recv_request.await {
+        Ok(x) => x,
+        Err(e) => {
+            debug!("receiving request failed: {}", e);
+            return;
+        }
+    };
+
+    let response = async {
+        future::poll_fn(|cx| service.poll_ready(cx)).await?;
+        service.call(request).await
+    }
+    .map(|r| r.map_err(Into::<Box<dyn StdError + Send + Sync>>::into));
+    pin_mut!(response);
+    let stopped = future::poll_fn(|cx| sender.poll_stopped(cx));
+    let response = match future::select(response, stopped).await {
+        Either::Left((response, _)) => response,
+        Either::Right((code, _)) => {
+            trace!("request cancelled by the client: {:?}", code);
+            return;
+        }
+    };
+
+    match response {
+        Ok(response) => {
+            if let Err(e) = sender.send_response(response).await {
+                debug!("sending response failed: {}", e);
+            }
+        }
+        Err(e) => {
+            debug!("service failed: {}", e);
+            sender.cancel();
+        }
+    }
+}
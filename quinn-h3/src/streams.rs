@@ -13,21 +13,23 @@ use futures::{
     ready,
 };
 use quinn::{OpenUni, RecvStream, SendStream};
-use quinn_proto::VarInt;
+use quinn_proto::{coding::BufExt, VarInt};
 use tracing::trace;
 
 use crate::{
     frame::{FrameDecoder, FrameStream},
-    proto::{ErrorCode, StreamType},
+    proto::{frame, ErrorCode, StreamType},
     Error,
 };
 
 pub enum NewUni {
     Control(FrameStream),
     Push(PushStream),
+    WebTransport(u64, RecvStream),
     Encoder(RecvStream),
     Decoder(RecvStream),
-    Reserved,
+    /// Reserved or unknown stream type, which must be ignored
+    Unknown(StreamType, RecvStream),
 }
 
 impl TryFrom<(StreamType, RecvStream)> for NewUni {
@@ -36,23 +38,25 @@ impl TryFrom<(StreamType, RecvStream)> for NewUni {
         let (ty, recv) = value;
         Ok(match ty {
             StreamType::CONTROL => NewUni::Control(FrameDecoder::stream(recv)),
-            StreamType::PUSH => NewUni::Push(PushStream(FrameDecoder::stream(recv))),
             StreamType::ENCODER => NewUni::Encoder(recv),
             StreamType::DECODER => NewUni::Decoder(recv),
-            t if t.0 > 0x21 && (t.0 - 0x21) % 0x1f == 0 => NewUni::Reserved,
-            _ => return Err(Error::UnknownStream(ty.0)),
+            _ => NewUni::Unknown(ty, recv),
         })
     }
 }
 
 pub struct RecvUni {
     inner: Option<(RecvStream, [u8; VarInt::MAX_SIZE], usize, usize)>,
+    /// Set once the type of a push or WebTransport stream has been received, and the push or
+    /// session ID that follows is being read
+    prefixed: Option<StreamType>,
 }
 
 impl RecvUni {
     pub fn new(recv: RecvStream) -> Self {
         Self {
             inner: Some((recv, [0u8; VarInt::MAX_SIZE], 1, 0)),
+            prefixed: None,
         }
     }
 }
@@ -61,8 +65,9 @@ impl Future for RecvUni {
     type Output = Result<NewUni, Error>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
         loop {
-            match self.inner {
+            match this.inner {
                 None => panic!("polled after resolved"),
                 Some((ref mut recv, ref mut buf, ref mut expected, ref mut len)) => {
                     match ready!(Pin::new(recv).poll_read(cx, &mut buf[*len..*expected]))? {
@@ -77,15 +82,30 @@ impl Future for RecvUni {
                                 *expected = VarInt::encoded_size(buf[0]);
                             }
                             if len == expected {
-                                let mut cur = io::Cursor::new(&buf);
-                                let ty = StreamType::decode(&mut cur)
-                                    .map_err(|_| Error::internal("stream type decode"))?;
-                                match mem::replace(&mut self.inner, None) {
-                                    Some((recv, _, _, _)) => {
-                                        return Poll::Ready(NewUni::try_from((ty, recv)))
-                                    }
+                                let value = StreamType::decode(&mut io::Cursor::new(&buf))
+                                    .map_err(|_| Error::internal("stream header decode"))?
+                                    .0;
+                                let ty = StreamType(value);
+                                if this.prefixed.is_none()
+                                    && (ty == StreamType::PUSH || ty == StreamType::WEBTRANSPORT)
+                                {
+                                    // The push or session ID follows the stream type
+                                    this.prefixed = Some(ty);
+                                    *len = 0;
+                                    *expected = 1;
+                                    continue;
+                                }
+                                let recv = match this.inner.take() {
+                                    Some((recv, _, _, _)) => recv,
                                     _ => unreachable!(),
                                 };
+                                return Poll::Ready(match this.prefixed {
+                                    Some(StreamType::PUSH) => {
+                                        Ok(NewUni::Push(PushStream { id: value, recv }))
+                                    }
+                                    Some(_) => Ok(NewUni::WebTransport(value, recv)),
+                                    None => NewUni::try_from((ty, recv)),
+                                });
                             }
                         }
                     }
@@ -95,7 +115,85 @@ impl Future for RecvUni {
     }
 }
 
-pub struct PushStream(FrameStream);
+/// A push stream, whose push ID has been received
+pub struct PushStream {
+    pub id: u64,
+    pub recv: RecvStream,
+}
+
+/// An incoming bidirectional stream, told apart by its first frame type
+pub enum NewBi {
+    /// A request stream, with the bytes of the frame type already read
+    Request(SendStream, RecvStream, Bytes),
+    /// A WebTransport stream, with its session ID
+    WebTransport(u64, SendStream, RecvStream),
+}
+
+/// Reads the first frame type of a bidirectional stream, and the session ID following a
+/// WebTransport stream signal
+pub struct RecvBi {
+    inner: Option<(SendStream, RecvStream)>,
+    buf: [u8; VarInt::MAX_SIZE],
+    expected: usize,
+    len: usize,
+    /// Set once the WebTransport signal has been received, and the session ID is being read
+    signal: bool,
+}
+
+impl RecvBi {
+    pub fn new(send: SendStream, recv: RecvStream) -> Self {
+        Self {
+            inner: Some((send, recv)),
+            buf: [0u8; VarInt::MAX_SIZE],
+            expected: 1,
+            len: 0,
+            signal: false,
+        }
+    }
+}
+
+impl Future for RecvBi {
+    type Output = Result<NewBi, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            let recv = match this.inner {
+                None => panic!("polled after resolved"),
+                Some((_, ref mut recv)) => recv,
+            };
+            let range = this.len..this.expected;
+            match ready!(Pin::new(recv).poll_read(cx, &mut this.buf[range]))? {
+                0 => return Poll::Ready(Err(Error::peer("Bi stream closed before frame type"))),
+                read => this.len += read,
+            }
+            if this.len == 1 {
+                this.expected = VarInt::encoded_size(this.buf[0]);
+            }
+            if this.len < this.expected {
+                continue;
+            }
+
+            let mut cur = io::Cursor::new(&this.buf[..this.len]);
+            let value = cur
+                .get_var()
+                .map_err(|_| Error::internal("stream header decode"))?;
+            if !this.signal && frame::Type(value) == frame::Type::WEBTRANSPORT_STREAM {
+                // The session ID follows the signal
+                this.signal = true;
+                this.len = 0;
+                this.expected = 1;
+                continue;
+            }
+
+            let (send, recv) = this.inner.take().unwrap();
+            return Poll::Ready(Ok(match this.signal {
+                true => NewBi::WebTransport(value, send, recv),
+                false => NewBi::Request(send, recv, Bytes::copy_from_slice(&this.buf[..this.len])),
+            }));
+        }
+    }
+}
 
 pub struct SendUni {
     ty: StreamType,
@@ -20,7 +20,7 @@ use crate::{
     proto::frame::HttpFrame,
     proto::headers::Header,
     server::{self, IncomingConnection},
-    SendData, ZeroRttAccepted,
+    SendData, Settings, ZeroRttAccepted,
 };
 use quinn_proto::StreamId;
 use tracing_subscriber::EnvFilter;
@@ -61,30 +61,35 @@ impl Helper {
         server.listen(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), port));
 
         let mut client = client::Builder::default();
-        client.add_certificate_authority(cert.clone()).unwrap();
-
-        let mut client_config = quinn::ClientConfigBuilder::default();
-        client_config
-            .protocols(&[crate::ALPN])
-            .add_certificate_authority(cert)
-            .expect("client cert");
-        client_config.enable_0rtt();
-        let client_config = client_config.build();
-        let mut endpoint_builder = quinn::Endpoint::builder();
-        endpoint_builder.default_client_config(client_config);
-        let (client_endpoint, _) = endpoint_builder
-            .bind(&"[::]:0".parse().unwrap())
-            .expect("bind client endpoint");
+        client.add_certificate_authority(cert).unwrap();
 
         Self {
             server,
             client,
             port,
-            client_endpoint,
+            client_endpoint: client_endpoint(&[crate::ALPN]),
             got_0rtt: false,
         }
     }
 
+    /// Offer `protocols` from the client instead of quinn-h3's ALPN
+    pub fn client_protocols(&mut self, protocols: &[&[u8]]) {
+        self.client_endpoint = client_endpoint(protocols);
+    }
+
+    pub fn draft_versions(&mut self) {
+        self.server.draft_versions(true);
+    }
+
+    pub fn max_pushes(&mut self, count: u64) {
+        self.client.max_pushes(count);
+    }
+
+    pub fn settings(&mut self, settings: Settings) {
+        self.server.settings(settings.clone());
+        self.client.settings(settings);
+    }
+
     pub fn make_server(&self) -> IncomingConnection {
         self.server.clone().build().expect("server build")
     }
@@ -93,12 +98,13 @@ impl Helper {
         self.client.clone().endpoint(self.client_endpoint.clone())
     }
 
+    pub fn server_addr(&self) -> SocketAddr {
+        SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), self.port)
+    }
+
     pub async fn make_connection(&self) -> client::Connection {
         self.make_client()
-            .connect(
-                &SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), self.port),
-                "localhost",
-            )
+            .connect(&self.server_addr(), "localhost")
             .expect("connect")
             .await
             .expect("connecting")
@@ -236,6 +242,21 @@ impl FakeRequest {
     }
 }
 
+fn client_endpoint(protocols: &[&[u8]]) -> quinn::Endpoint {
+    let mut client_config = quinn::ClientConfigBuilder::default();
+    client_config
+        .protocols(protocols)
+        .add_certificate_authority(CERTS.cert.clone())
+        .expect("client cert");
+    client_config.enable_0rtt();
+    let mut endpoint_builder = quinn::Endpoint::builder();
+    endpoint_builder.default_client_config(client_config.build());
+    let (client_endpoint, _) = endpoint_builder
+        .bind(&"[::]:0".parse().unwrap())
+        .expect("bind client endpoint");
+    client_endpoint
+}
+
 #[derive(Clone)]
 struct Certs {
     chain: CertificateChain,
@@ -1,15 +1,31 @@
+use std::{
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
 use bytes::{BufMut, Bytes};
-use futures::{future, StreamExt};
-use http::{request, Request, Response, StatusCode, Uri};
-use tokio::time::{delay_for, Duration};
+use futures::{channel::oneshot, future, StreamExt};
+use http::{request, Method, Request, Response, StatusCode, Uri};
+use quinn_proto::coding::BufMutExt;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UdpSocket,
+    time::{delay_for, timeout, Duration},
+};
 
 use crate::{
-    proto::{frame::DataFrame, headers::Header},
+    masque,
+    proto::{frame::DataFrame, headers::Header, ErrorCode, StreamType},
     server::IncomingConnection,
-    Body, Error, HttpError, SendData,
+    Body, Error, HttpError, Priority, Protocol, SendData, Settings,
 };
 
 mod helpers;
+mod pool;
+#[cfg(feature = "tower")]
+mod service;
 use helpers::{get, post, timeout_join, Helper};
 
 async fn serve_one(mut incoming: IncomingConnection) -> Result<(), crate::Error> {
@@ -42,6 +58,35 @@ async fn incoming_request_stream_ends_on_client_closure() {
     timeout_join(server_handle).await.unwrap();
 }
 
+#[tokio::test(threaded_scheduler)]
+async fn draft_alpn() {
+    let mut helper = Helper::new();
+    helper.draft_versions();
+    helper.client_protocols(&[b"h3-29"]);
+    let incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move { serve_one(incoming).await });
+
+    let conn = helper.make_connection().await;
+    let (req, resp) = conn.send_request(get("/"));
+    req.await.expect("request");
+    assert_eq!(resp.await.expect("response").status(), StatusCode::OK);
+    conn.close();
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn draft_alpn_not_offered_by_default() {
+    let mut helper = Helper::new();
+    helper.client_protocols(&[b"h3-29"]);
+    let _incoming = helper.make_server();
+
+    let connecting = helper
+        .make_client()
+        .connect(&helper.server_addr(), "localhost")
+        .expect("connect");
+    assert!(connecting.await.is_err());
+}
+
 #[tokio::test(threaded_scheduler)]
 async fn incoming_request_stream_closed_on_client_drop() {
     let helper = Helper::new();
@@ -602,3 +647,693 @@ async fn client_rejects_contradicted_authority() {
     drop(conn);
     assert_matches!(timeout_join(server_handle).await, Ok(()));
 }
+
+fn pushed() -> Request<()> {
+    Request::get("https://localhost/pushed").body(()).unwrap()
+}
+
+#[tokio::test]
+async fn server_push() {
+    let mut helper = Helper::new();
+    helper.max_pushes(1);
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let recv_req = incoming_req.next().await.expect("wait request");
+        let (_, mut sender) = recv_req.await?;
+        let mut push = sender.push_promise(pushed()).await?;
+        push.send_response(Response::new(Body::from("pushed")))
+            .await?;
+        sender.send_response(Response::new(Body::from(()))).await?;
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let (req, resp) = conn.send_request(get("/"));
+    req.await.unwrap();
+    resp.await.expect("response");
+
+    let recv_promise = conn.pushes().next().await.expect("push promise");
+    let (request, recv_response) = recv_promise.await.expect("promised request");
+    assert_eq!(request.uri().path(), "/pushed");
+    let mut response = recv_response.await.expect("pushed response");
+    assert_eq!(
+        response
+            .body_mut()
+            .read_to_end()
+            .await
+            .expect("pushed body"),
+        "pushed"
+    );
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn push_promised_for_two_requests() {
+    let mut helper = Helper::new();
+    helper.max_pushes(1);
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let (_, mut first) = incoming_req.next().await.expect("wait request").await?;
+        let (_, mut second) = incoming_req.next().await.expect("wait request").await?;
+        let mut push = first.push_promise(pushed()).await?;
+        second.repeat_push_promise(push.push_id(), pushed()).await?;
+        first.send_response(Response::new(Body::from(()))).await?;
+        second.send_response(Response::new(Body::from(()))).await?;
+        push.send_response(Response::new(Body::from("pushed")))
+            .await?;
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let (req, first) = conn.send_request(get("/"));
+    req.await.unwrap();
+    let (req, second) = conn.send_request(get("/other"));
+    req.await.unwrap();
+    first.await.expect("first response");
+    second.await.expect("second response");
+
+    // The push is yielded once, for both requests
+    let mut pushes = conn.pushes();
+    let recv_promise = pushes.next().await.expect("push promise");
+    let (request, recv_response) = recv_promise.await.expect("promised request");
+    assert_eq!(request.uri().path(), "/pushed");
+    let mut response = recv_response.await.expect("pushed response");
+    assert_eq!(response.body_mut().read_to_end().await.unwrap(), "pushed");
+    assert!(timeout(Duration::from_millis(50), pushes.next())
+        .await
+        .is_err());
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn push_promised_again_after_response_read() {
+    let mut helper = Helper::new();
+    helper.max_pushes(1);
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let (_, mut first) = incoming_req.next().await.expect("wait request").await?;
+        let mut push = first.push_promise(pushed()).await?;
+        let push_id = push.push_id();
+        first.send_response(Response::new(Body::from(()))).await?;
+        push.send_response(Response::new(Body::from("pushed")))
+            .await?;
+
+        // Sent once the client read the pushed response
+        let (_, mut second) = incoming_req.next().await.expect("wait request").await?;
+        second.repeat_push_promise(push_id, pushed()).await?;
+        second.send_response(Response::new(Body::from(()))).await?;
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let (req, first) = conn.send_request(get("/"));
+    req.await.unwrap();
+    first.await.expect("first response");
+    let mut pushes = conn.pushes();
+    let recv_promise = pushes.next().await.expect("push promise");
+    let (_, recv_response) = recv_promise.await.expect("promised request");
+    let mut response = recv_response.await.expect("pushed response");
+    assert_eq!(response.body_mut().read_to_end().await.unwrap(), "pushed");
+
+    // The push is not yielded again
+    let (req, second) = conn.send_request(get("/other"));
+    req.await.unwrap();
+    second.await.expect("second response");
+    assert!(timeout(Duration::from_millis(50), pushes.next())
+        .await
+        .is_err());
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn push_promised_for_different_requests() {
+    let mut helper = Helper::new();
+    helper.max_pushes(1);
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let (_, mut first) = incoming_req.next().await.expect("wait request").await?;
+        let (_, mut second) = incoming_req.next().await.expect("wait request").await?;
+        let push = first.push_promise(pushed()).await?;
+        let other = Request::get("https://localhost/other").body(()).unwrap();
+        second.repeat_push_promise(push.push_id(), other).await?;
+        first.send_response(Response::new(Body::from(()))).await?;
+        second.send_response(Response::new(Body::from(()))).await?;
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let (req, first) = conn.send_request(get("/"));
+    req.await.unwrap();
+    let (req, second) = conn.send_request(get("/other"));
+    req.await.unwrap();
+    first.await.expect("first response");
+    assert_matches!(
+        second.await,
+        Err(Error::Peer(ref msg)) if msg == "push ID 0 promised for different requests"
+    );
+    // The client closed the connection
+    assert_matches!(conn.send_request(get("/")).0.await, Err(Error::Quic(_)));
+    let _ = timeout_join(server_handle).await;
+}
+
+#[tokio::test]
+async fn server_push_not_allowed() {
+    let helper = Helper::new();
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let recv_req = incoming_req.next().await.expect("wait request");
+        let (_, mut sender) = recv_req.await?;
+        let push = sender.push_promise(pushed()).await.map(|_| ());
+        sender.send_response(Response::new(Body::from(()))).await?;
+        push
+    });
+
+    let conn = helper.make_connection().await;
+    let (req, resp) = conn.send_request(get("/"));
+    req.await.unwrap();
+    resp.await.expect("response");
+    assert_matches!(
+        timeout_join(server_handle).await,
+        Err(Error::Proto(
+            crate::proto::connection::Error::PushLimitReached
+        ))
+    );
+}
+
+#[tokio::test]
+async fn client_cancel_push() {
+    let mut helper = Helper::new();
+    helper.max_pushes(1);
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let recv_req = incoming_req.next().await.expect("wait request");
+        let (_, mut sender) = recv_req.await?;
+        let mut push = sender.push_promise(pushed()).await?;
+        sender.send_response(Response::new(Body::from(()))).await?;
+        // Only the CANCEL_PUSH frame can end a response waiting for its body
+        push.send_response(Response::new(StalledBody)).await
+    });
+
+    let conn = helper.make_connection().await;
+    let (req, resp) = conn.send_request(get("/"));
+    req.await.unwrap();
+    resp.await.expect("response");
+
+    let recv_promise = conn.pushes().next().await.expect("push promise");
+    let (_, mut recv_response) = recv_promise.await.expect("promised request");
+    recv_response.cancel().await;
+
+    assert_matches!(
+        timeout_join(server_handle).await,
+        Err(Error::Http(HttpError::RequestCancelled, _))
+    );
+}
+
+/// A body whose data never comes
+struct StalledBody;
+
+impl http_body::Body for StalledBody {
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        _: &mut Context,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Poll::Pending
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Poll::Pending
+    }
+}
+
+#[tokio::test]
+async fn server_cancel_push() {
+    let mut helper = Helper::new();
+    helper.max_pushes(1);
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let recv_req = incoming_req.next().await.expect("wait request");
+        let (_, mut sender) = recv_req.await?;
+        let mut push = sender.push_promise(pushed()).await?;
+        sender.send_response(Response::new(Body::from(()))).await?;
+        // Let the client receive the promise before cancelling it
+        delay_for(Duration::from_millis(25)).await;
+        push.cancel();
+        // Keep the connection alive until the client is done
+        while incoming_req.next().await.is_some() {}
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let (req, resp) = conn.send_request(get("/"));
+    req.await.unwrap();
+    resp.await.expect("response");
+
+    let recv_promise = conn.pushes().next().await.expect("push promise");
+    let (_, recv_response) = recv_promise.await.expect("promised request");
+    assert_matches!(
+        recv_response.await,
+        Err(Error::Http(HttpError::RequestCancelled, _))
+    );
+    conn.close();
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+fn connect_request() -> Request<()> {
+    Request::builder()
+        .method(Method::CONNECT)
+        .uri("proxied.com:443")
+        .body(())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn connect_tunnel() {
+    let helper = Helper::new();
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let recv_req = incoming_req.next().await.expect("wait request");
+        let (request, mut sender) = recv_req.await?;
+        assert_eq!(request.method(), Method::CONNECT);
+        assert_eq!(request.uri(), "proxied.com:443");
+        let (mut send, mut recv) = sender.accept_connect(request, Response::new(())).await?;
+        tokio::io::copy(&mut recv, &mut send).await?;
+        send.shutdown().await?;
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let (response, mut send, mut recv) = conn.connect(connect_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    send.write_all(b"ping").await.unwrap();
+    send.write_all(b"pong").await.unwrap();
+    send.shutdown().await.unwrap();
+    let mut echoed = Vec::new();
+    recv.read_to_end(&mut echoed).await.unwrap();
+    assert_eq!(echoed, b"pingpong");
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn connect_refused() {
+    let helper = Helper::new();
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let recv_req = incoming_req.next().await.expect("wait request");
+        let (_, mut sender) = recv_req.await?;
+        let response = Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .body(Body::from(()))
+            .unwrap();
+        sender.send_response(response).await?;
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    assert_matches!(
+        conn.connect(connect_request()).await,
+        Err(Error::ConnectRefused(StatusCode::FORBIDDEN))
+    );
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+fn webtransport_settings() -> Settings {
+    let mut settings = Settings::new();
+    settings.set_enable_webtransport(true);
+    settings
+}
+
+#[tokio::test]
+async fn webtransport_session() {
+    let mut helper = Helper::new();
+    helper.settings(webtransport_settings());
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+
+        // Requests are still served alongside sessions
+        let (_, mut sender) = incoming_req.next().await.expect("wait request").await?;
+        sender.send_response(Response::new(Body::from(()))).await?;
+
+        let (request, mut sender) = incoming_req.next().await.expect("wait session").await?;
+        assert_eq!(request.method(), Method::CONNECT);
+        assert_eq!(
+            request.extensions().get::<Protocol>(),
+            Some(&Protocol::WEBTRANSPORT)
+        );
+        let session = sender.accept_webtransport(request).await?;
+
+        let (mut send, recv) = session.accept_bi().await.expect("accept bi");
+        let data = recv.read_to_end(1024).await.expect("read bi");
+        send.write_all(&data).await?;
+        send.finish().await?;
+
+        let recv = session.accept_uni().await.expect("accept uni");
+        let data = recv.read_to_end(1024).await.expect("read uni");
+        let mut send = session.open_uni().await?;
+        send.write_all(&data).await?;
+        send.finish().await?;
+
+        let datagram = session.recv_datagram().await.expect("recv datagram");
+        session.send_datagram(datagram)?;
+
+        // The client closing the session ends it
+        assert!(session.accept_bi().await.is_none());
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let (req, resp) = conn.send_request(get("/"));
+    req.await.expect("request");
+    resp.await.expect("response");
+
+    let request = Request::connect("https://localhost/echo").body(()).unwrap();
+    let (response, session) = conn.webtransport(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (mut send, recv) = session.open_bi().await.unwrap();
+    send.write_all(b"bi").await.unwrap();
+    send.finish().await.unwrap();
+    assert_eq!(recv.read_to_end(1024).await.unwrap(), b"bi");
+
+    let mut send = session.open_uni().await.unwrap();
+    send.write_all(b"uni").await.unwrap();
+    send.finish().await.unwrap();
+    let recv = session.accept_uni().await.expect("accept uni");
+    assert_eq!(recv.read_to_end(1024).await.unwrap(), b"uni");
+
+    session
+        .send_datagram(Bytes::from_static(b"datagram"))
+        .unwrap();
+    assert_eq!(
+        session.recv_datagram().await,
+        Some(Bytes::from_static(b"datagram"))
+    );
+
+    drop(session);
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn webtransport_stream_of_request_rejected() {
+    let mut helper = Helper::new();
+    helper.settings(webtransport_settings());
+    let mut incoming = helper.make_server();
+    let (answered_send, answered) = oneshot::channel();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let (_, mut sender) = incoming_req.next().await.expect("wait request").await?;
+        sender.send_response(Response::new(Body::from(()))).await?;
+        let _ = answered_send.send(());
+        while incoming_req.next().await.is_some() {}
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let (req, resp) = conn.send_request(get("/"));
+    req.await.expect("request");
+    resp.await.expect("response");
+    answered.await.unwrap();
+
+    // A stream claiming the answered request, on stream 0, as its session is not kept
+    let mut send = conn.inner().quic.open_uni().await.unwrap();
+    let mut prefix = Vec::new();
+    StreamType::WEBTRANSPORT.encode(&mut prefix);
+    prefix.write_var(0);
+    send.write_all(&prefix).await.unwrap();
+    let stopped = timeout(
+        Duration::from_millis(500),
+        future::poll_fn(|cx| send.poll_stopped(cx)),
+    )
+    .await;
+    assert_matches!(
+        stopped,
+        Ok(Ok(code)) if code == ErrorCode::WEBTRANSPORT_SESSION_GONE.into()
+    );
+
+    conn.close();
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn webtransport_disabled_by_server() {
+    let mut helper = Helper::new();
+    let incoming = helper.make_server();
+    // Only the client enables WebTransport
+    helper.settings(webtransport_settings());
+    let server_handle = tokio::spawn(async move { serve_one(incoming).await });
+
+    let conn = helper.make_connection().await;
+    let request = Request::connect("https://localhost/echo").body(()).unwrap();
+    assert_matches!(
+        conn.webtransport(request).await,
+        Err(Error::SettingDisabled(_))
+    );
+    conn.close();
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn connect_udp_proxy() {
+    let localhost = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 0);
+    let mut echo = UdpSocket::bind(localhost).await.unwrap();
+    let echo_port = echo.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut buf = [0; 1500];
+        while let Ok((len, addr)) = echo.recv_from(&mut buf).await {
+            let _ = echo.send_to(&buf[..len], addr).await;
+        }
+    });
+
+    let mut helper = Helper::new();
+    let mut settings = Settings::new();
+    settings
+        .set_h3_datagram(true)
+        .set_enable_connect_protocol(true);
+    helper.settings(settings);
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let (request, mut sender) = incoming_req.next().await.expect("wait request").await?;
+        assert_eq!(
+            request.extensions().get::<Protocol>(),
+            Some(&Protocol::CONNECT_UDP)
+        );
+        let (host, port) = masque::udp_target(request.uri()).expect("target");
+        let socket = UdpSocket::bind(localhost).await?;
+        let target = IpAddr::from_str(&host).expect("target address");
+        socket.connect(SocketAddr::new(target, port)).await?;
+
+        let tunnel = sender.accept_connect_udp(request).await?;
+        tunnel.proxy(socket).await
+    });
+
+    let conn = helper.make_connection().await;
+    let request = masque::udp_request("localhost", "::1", echo_port).unwrap();
+    let (response, mut tunnel) = conn.connect_udp(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["capsule-protocol"], "?1");
+
+    tunnel.send_datagram(Bytes::from_static(b"ping")).unwrap();
+    assert_eq!(
+        tunnel.recv_datagram().await,
+        Some(Bytes::from_static(b"ping"))
+    );
+    // Capsules reach the target too, with datagram replies
+    tunnel
+        .send_capsule(Bytes::from_static(b"capsule"))
+        .await
+        .unwrap();
+    assert_eq!(
+        tunnel.recv_datagram().await,
+        Some(Bytes::from_static(b"capsule"))
+    );
+
+    drop(tunnel);
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn connect_udp_proxy_survives_refusal() {
+    let localhost = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 0);
+    // Nothing listens on the target port at first
+    let target = std::net::UdpSocket::bind(localhost)
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let mut helper = Helper::new();
+    let mut settings = Settings::new();
+    settings
+        .set_h3_datagram(true)
+        .set_enable_connect_protocol(true);
+    helper.settings(settings);
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let (request, mut sender) = incoming_req.next().await.expect("wait request").await?;
+        let socket = UdpSocket::bind(localhost).await?;
+        socket.connect(target).await?;
+        let tunnel = sender.accept_connect_udp(request).await?;
+        tunnel.proxy(socket).await
+    });
+
+    let conn = helper.make_connection().await;
+    let request = masque::udp_request("localhost", "::1", target.port()).unwrap();
+    let (_, mut tunnel) = conn.connect_udp(request).await.unwrap();
+    // The target refuses this one, failing the proxy's next receive
+    tunnel
+        .send_capsule(Bytes::from_static(b"refused"))
+        .await
+        .unwrap();
+    delay_for(Duration::from_millis(50)).await;
+
+    let mut echo = UdpSocket::bind(target).await.unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0; 1500];
+        while let Ok((len, addr)) = echo.recv_from(&mut buf).await {
+            let _ = echo.send_to(&buf[..len], addr).await;
+        }
+    });
+    tunnel
+        .send_capsule(Bytes::from_static(b"ping"))
+        .await
+        .unwrap();
+    assert_eq!(
+        tunnel.recv_datagram().await,
+        Some(Bytes::from_static(b"ping"))
+    );
+
+    drop(tunnel);
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn request_priority() {
+    let helper = Helper::new();
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let (request, mut sender) = incoming_req.next().await.expect("request").await?;
+        assert_eq!(request.headers()["priority"], "u=0");
+        assert_eq!(sender.priority(), Priority::new(0, false));
+        sender.send_response(Response::new(Body::from(()))).await?;
+
+        let (_, mut sender) = incoming_req.next().await.expect("request").await?;
+        // The update is sent on the control stream, so it may come after the request
+        while sender.priority() != Priority::new(6, true) {
+            delay_for(Duration::from_millis(5)).await;
+        }
+        sender.send_response(Response::new(Body::from(()))).await?;
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let mut request = get("/style.css");
+    request.extensions_mut().insert(Priority::new(0, false));
+    let (req, resp) = conn.send_request(request);
+    req.await.unwrap();
+    resp.await.expect("response");
+
+    let (req, mut resp) = conn.send_request(get("/image.png"));
+    req.await.unwrap();
+    resp.set_priority(Priority::new(6, true));
+    resp.await.expect("response");
+
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn responses_scheduled_by_priority() {
+    let helper = Helper::new();
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let (_, mut image) = incoming_req.next().await.expect("request").await?;
+        let (_, mut style) = incoming_req.next().await.expect("request").await?;
+        assert_eq!(style.priority(), Priority::new(0, false));
+
+        // Both bodies take several turns, those of the stylesheet being served first
+        let image_body = Body::from(Bytes::from(vec![b'i'; 256 * 1024]));
+        let style_body = Body::from(Bytes::from(vec![b's'; 64 * 1024]));
+        future::try_join(
+            image.send_response(Response::new(image_body)),
+            style.send_response(Response::new(style_body)),
+        )
+        .await?;
+        Ok::<_, Error>(())
+    });
+
+    let conn = helper.make_connection().await;
+    let mut image = get("/image.png");
+    image.extensions_mut().insert(Priority::new(5, true));
+    let (req, image) = conn.send_request(image);
+    req.await.unwrap();
+    let mut style = get("/style.css");
+    style.extensions_mut().insert(Priority::new(0, false));
+    let (req, style) = conn.send_request(style);
+    req.await.unwrap();
+
+    let (mut image, mut style) = future::try_join(image, style).await.expect("responses");
+    let (image, style) = future::try_join(
+        image.body_mut().read_to_end(),
+        style.body_mut().read_to_end(),
+    )
+    .await
+    .expect("bodies");
+    assert_eq!(image.len(), 256 * 1024);
+    assert_eq!(style.len(), 64 * 1024);
+
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
+
+#[tokio::test]
+async fn stalled_response_yields_its_turn() {
+    let helper = Helper::new();
+    let mut incoming = helper.make_server();
+    let server_handle = tokio::spawn(async move {
+        let mut incoming_req = incoming.next().await.expect("accept").await?;
+        let (_, mut image) = incoming_req.next().await.expect("request").await?;
+        let (_, mut style) = incoming_req.next().await.expect("request").await?;
+
+        // The stylesheet exceeds the stream's flow control window, until the client reads it
+        let style_body = Body::from(Bytes::from(vec![b's'; 2 * 1024 * 1024]));
+        let style = tokio::spawn(style.send_response(Response::new(style_body)));
+        delay_for(Duration::from_millis(50)).await;
+        let image_body = Body::from(Bytes::from(vec![b'i'; 64 * 1024]));
+        image.send_response(Response::new(image_body)).await?;
+        style.await.expect("style task")
+    });
+
+    let conn = helper.make_connection().await;
+    let mut image = get("/image.png");
+    image.extensions_mut().insert(Priority::new(5, true));
+    let (req, image) = conn.send_request(image);
+    req.await.unwrap();
+    let mut style = get("/style.css");
+    style.extensions_mut().insert(Priority::new(0, false));
+    let (req, style) = conn.send_request(style);
+    req.await.unwrap();
+
+    let (mut image, mut style) = future::try_join(image, style).await.expect("responses");
+    let image = timeout(Duration::from_secs(1), image.body_mut().read_to_end()).await;
+    assert_matches!(image, Ok(Ok(ref body)) if body.len() == 64 * 1024);
+    let style = style.body_mut().read_to_end().await.expect("style");
+    assert_eq!(style.len(), 2 * 1024 * 1024);
+
+    assert_matches!(timeout_join(server_handle).await, Ok(()));
+}
@@ -0,0 +1,199 @@
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use futures::{future, StreamExt};
+use http::{Request, Response, StatusCode};
+use quinn::StaticResolver;
+use tokio::time::{delay_for, Duration};
+
+use super::helpers::Helper;
+use crate::{client::Pool, server::IncomingConnection, Body};
+
+#[derive(Clone, Default)]
+struct Behavior {
+    delay: Duration,
+    go_away: bool,
+    alt_svc: Option<String>,
+}
+
+#[derive(Default)]
+struct Counts {
+    connections: AtomicUsize,
+    requests: AtomicUsize,
+}
+
+impl Counts {
+    fn get(&self) -> (usize, usize) {
+        (
+            self.connections.load(Ordering::SeqCst),
+            self.requests.load(Ordering::SeqCst),
+        )
+    }
+}
+
+fn serve(mut incoming: IncomingConnection, behavior: Behavior) -> Arc<Counts> {
+    let counts = Arc::new(Counts::default());
+    let server_counts = counts.clone();
+    tokio::spawn(async move {
+        while let Some(connecting) = incoming.next().await {
+            let (counts, behavior) = (server_counts.clone(), behavior.clone());
+            tokio::spawn(async move {
+                let mut incoming_req = connecting.await.expect("accept");
+                counts.connections.fetch_add(1, Ordering::SeqCst);
+                while let Some(recv_req) = incoming_req.next().await {
+                    let (_, mut sender) = recv_req.await.expect("recv_req");
+                    counts.requests.fetch_add(1, Ordering::SeqCst);
+                    if behavior.go_away {
+                        // The request being handled is still honored
+                        incoming_req.go_away(0);
+                    }
+                    let behavior = behavior.clone();
+                    tokio::spawn(async move {
+                        delay_for(behavior.delay).await;
+                        let mut response = Response::builder().status(StatusCode::OK);
+                        if let Some(alt_svc) = behavior.alt_svc {
+                            response = response.header("alt-svc", alt_svc);
+                        }
+                        let response = response.body(Body::from(())).unwrap();
+                        let _ = sender.send_response(response).await;
+                    });
+                }
+            });
+        }
+    });
+    counts
+}
+
+fn make_pool(helper: &Helper) -> Pool {
+    let mut resolver = StaticResolver::new();
+    resolver.insert("localhost", vec![IpAddr::V6(Ipv6Addr::LOCALHOST)]);
+    let mut pool = Pool::new(helper.make_client());
+    pool.resolver(Arc::new(resolver));
+    pool
+}
+
+fn get(helper: &Helper) -> Request<Body> {
+    Request::get(format!(
+        "https://localhost:{}/",
+        helper.server_addr().port()
+    ))
+    .body(Body::from(()))
+    .unwrap()
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn pool_reuses_connection() {
+    let helper = Helper::new();
+    let counts = serve(helper.make_server(), Behavior::default());
+    let pool = make_pool(&helper);
+
+    for _ in 0..3 {
+        let response = pool.send_request(get(&helper)).await.expect("response");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(counts.get(), (1, 3));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn pool_shares_pending_connection() {
+    let helper = Helper::new();
+    let counts = serve(helper.make_server(), Behavior::default());
+    let pool = make_pool(&helper);
+
+    // The second request waits for the connection opened for the first one
+    let (first, second) = future::join(
+        pool.send_request(get(&helper)),
+        pool.send_request(get(&helper)),
+    )
+    .await;
+    assert!(first.is_ok() && second.is_ok());
+    assert_eq!(counts.get(), (1, 2));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn pool_caps_concurrent_requests() {
+    let helper = Helper::new();
+    let behavior = Behavior {
+        delay: Duration::from_millis(50),
+        ..Behavior::default()
+    };
+    let counts = serve(helper.make_server(), behavior);
+    let mut pool = make_pool(&helper);
+    pool.max_concurrent_requests(1);
+
+    pool.send_request(get(&helper)).await.expect("response");
+    assert_eq!(counts.get(), (1, 1));
+
+    // The pooled connection takes one of the requests, another one is opened for the other
+    let (first, second) = future::join(
+        pool.send_request(get(&helper)),
+        pool.send_request(get(&helper)),
+    )
+    .await;
+    assert!(first.is_ok() && second.is_ok());
+    assert_eq!(counts.get(), (2, 3));
+
+    // Both connections are available again
+    let (first, second) = future::join(
+        pool.send_request(get(&helper)),
+        pool.send_request(get(&helper)),
+    )
+    .await;
+    assert!(first.is_ok() && second.is_ok());
+    assert_eq!(counts.get(), (2, 5));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn pool_reconnects_after_go_away() {
+    let helper = Helper::new();
+    let behavior = Behavior {
+        go_away: true,
+        ..Behavior::default()
+    };
+    let counts = serve(helper.make_server(), behavior);
+    let pool = make_pool(&helper);
+
+    // Sent right away, maybe before the GOAWAY is received, in which case it's sent again
+    for _ in 0..2 {
+        pool.send_request(get(&helper)).await.expect("response");
+    }
+    assert_eq!(counts.get(), (2, 2));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn pool_follows_alt_svc() {
+    let alt_helper = Helper::new();
+    let alt_counts = serve(
+        alt_helper.make_server(),
+        Behavior {
+            alt_svc: Some("clear".into()),
+            ..Behavior::default()
+        },
+    );
+
+    let helper = Helper::new();
+    let alt_svc = format!(r#"h3=":{}"; ma=60"#, alt_helper.server_addr().port());
+    let counts = serve(
+        helper.make_server(),
+        Behavior {
+            alt_svc: Some(alt_svc),
+            ..Behavior::default()
+        },
+    );
+    let pool = make_pool(&helper);
+
+    // The origin advertises the alternative, which is used for the next request
+    pool.send_request(get(&helper)).await.expect("response");
+    assert_eq!((counts.get(), alt_counts.get()), ((1, 1), (0, 0)));
+    pool.send_request(get(&helper)).await.expect("response");
+    assert_eq!((counts.get(), alt_counts.get()), ((1, 1), (1, 1)));
+
+    // The alternative clears itself, so the origin's connection is used again
+    pool.send_request(get(&helper)).await.expect("response");
+    assert_eq!((counts.get(), alt_counts.get()), ((1, 2), (1, 1)));
+}
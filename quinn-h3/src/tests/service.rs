@@ -0,0 +1,230 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::{
+    channel::{mpsc, oneshot},
+    future, FutureExt, StreamExt,
+};
+use http::{HeaderMap, Request, Response, StatusCode};
+use tokio::time::{delay_for, timeout, Duration};
+use tower_service::Service;
+
+use super::helpers::{get, post, Helper};
+use crate::{service::Server, Body, Error, HttpError, RecvBody};
+
+#[derive(Clone)]
+struct ServiceFn<F>(F);
+
+impl<F, R> Service<Request<RecvBody>> for ServiceFn<F>
+where
+    F: FnMut(Request<RecvBody>) -> R,
+    R: Future<Output = Result<Response<Body>, Error>>,
+{
+    type Response = Response<Body>;
+    type Error = Error;
+    type Future = R;
+
+    fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<RecvBody>) -> Self::Future {
+        (self.0)(request)
+    }
+}
+
+async fn echo(mut request: Request<RecvBody>) -> Result<Response<Body>, Error> {
+    let body = request.body_mut().read_to_end().await?;
+    Ok(Response::new(Body::from(body)))
+}
+
+async fn call<S: Service<Request<Body>>>(service: &mut S, request: Request<Body>) -> S::Future {
+    future::poll_fn(|cx| service.poll_ready(cx))
+        .await
+        .map_err(|_| ())
+        .expect("ready");
+    service.call(request)
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn service_request_response() {
+    let helper = Helper::new();
+    let server = Server::new(helper.make_server(), ServiceFn(echo));
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server_handle = tokio::spawn(server.serve_with_shutdown(stopped.map(|_| ())));
+
+    let mut conn = helper.make_connection().await;
+    let responses = future::join_all(vec![
+        call(&mut conn, post("/", "foo")).await,
+        call(&mut conn, post("/", "bar")).await,
+    ])
+    .await;
+    for (mut response, expected) in responses.into_iter().zip(&["foo", "bar"]) {
+        let response = response.as_mut().expect("response");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.body_mut().read_to_end().await.expect("body");
+        assert_eq!(&body[..], expected.as_bytes());
+    }
+
+    stop.send(()).unwrap();
+    timeout(Duration::from_millis(500), server_handle)
+        .await
+        .expect("server shut down")
+        .unwrap();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn service_graceful_shutdown() {
+    let helper = Helper::new();
+    let server = Server::new(
+        helper.make_server(),
+        ServiceFn(|_| async {
+            delay_for(Duration::from_millis(50)).await;
+            Ok(Response::new(Body::from(())))
+        }),
+    );
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server_handle = tokio::spawn(server.serve_with_shutdown(stopped.map(|_| ())));
+
+    let mut conn = helper.make_connection().await;
+    let in_flight = call(&mut conn, get("/")).await;
+    delay_for(Duration::from_millis(10)).await;
+    stop.send(()).unwrap();
+
+    // The request received before shutting down is still responded to
+    assert_eq!(in_flight.await.expect("response").status(), StatusCode::OK);
+    timeout(Duration::from_millis(500), server_handle)
+        .await
+        .expect("server shut down")
+        .unwrap();
+
+    assert_matches!(
+        future::poll_fn(|cx| Service::<Request<Body>>::poll_ready(&mut conn, cx)).await,
+        Err(Error::Aborted)
+    );
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn service_cancelled_by_client() {
+    struct Dropped(Arc<AtomicBool>);
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let helper = Helper::new();
+    let dropped = Arc::new(AtomicBool::new(false));
+    let service_dropped = dropped.clone();
+    let server = Server::new(
+        helper.make_server(),
+        ServiceFn(move |_| {
+            let guard = Dropped(service_dropped.clone());
+            async move {
+                future::pending::<()>().await;
+                drop(guard);
+                Ok(Response::new(Body::from(())))
+            }
+        }),
+    );
+    tokio::spawn(server.serve());
+
+    let conn = helper.make_connection().await;
+    let (req, mut resp) = conn.send_request(get("/"));
+    req.await.unwrap();
+    delay_for(Duration::from_millis(10)).await;
+    resp.cancel().await;
+    delay_for(Duration::from_millis(50)).await;
+
+    assert!(dropped.load(Ordering::SeqCst));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn service_error_cancels_request() {
+    let helper = Helper::new();
+    let server = Server::new(
+        helper.make_server(),
+        ServiceFn(|_| future::err(Error::internal("failed"))),
+    );
+    tokio::spawn(server.serve());
+
+    let mut conn = helper.make_connection().await;
+    assert_matches!(
+        call(&mut conn, get("/")).await.await,
+        Err(Error::Http(HttpError::RequestCancelled, _))
+    );
+}
+
+/// A request body whose data is sent by the test as it goes
+struct ChannelBody(mpsc::UnboundedReceiver<Bytes>);
+
+impl http_body::Body for ChannelBody {
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        self.0.poll_next_unpin(cx).map(|data| data.map(Ok))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn service_response_before_request_sent() {
+    let helper = Helper::new();
+    let (received, mut bodies) = mpsc::unbounded();
+    let server = Server::new(
+        helper.make_server(),
+        ServiceFn(move |mut request: Request<RecvBody>| {
+            let received = received.clone();
+            tokio::spawn(async move {
+                let body = request.body_mut().read_to_end().await;
+                let _ = received.unbounded_send(body);
+            });
+            future::ok(Response::new(Body::from(())))
+        }),
+    );
+    tokio::spawn(server.serve());
+
+    let mut conn = helper.make_connection().await;
+    let (data, body) = mpsc::unbounded();
+    let request = Request::post("https://localhost/")
+        .body(ChannelBody(body))
+        .unwrap();
+    future::poll_fn(|cx| Service::<Request<ChannelBody>>::poll_ready(&mut conn, cx))
+        .await
+        .expect("ready");
+    data.unbounded_send(Bytes::from("foo")).unwrap();
+
+    // The response comes while the request body is still open
+    let response = timeout(Duration::from_millis(500), conn.call(request))
+        .await
+        .expect("response before the request is sent")
+        .expect("response");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The rest of the request is still sent
+    data.unbounded_send(Bytes::from("bar")).unwrap();
+    drop(data);
+    let body = timeout(Duration::from_millis(500), bodies.next())
+        .await
+        .expect("request body")
+        .unwrap();
+    assert_eq!(&body.expect("request body")[..], b"foobar");
+}
@@ -0,0 +1,219 @@
+use std::{
+    cmp, fmt, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use futures::{ready, Stream as _};
+use quinn::SendStream;
+use quinn_proto::StreamId;
+
+use crate::{
+    connection::ConnectionRef,
+    data::WriteFrame,
+    frame::{self, FrameStream},
+    proto::{
+        frame::{DataFrame, HttpFrame},
+        ErrorCode,
+    },
+    streams::Reset,
+};
+
+/// Sending half of a CONNECT tunnel
+///
+/// Bytes written are sent to the peer in DATA frames. Shutting it down finishes the request
+/// stream, which the other end of the tunnel sees as the end of its [`RecvTunnel`].
+///
+/// Obtained from [`client::Connection::connect()`] or [`server::Sender::accept_connect()`].
+///
+/// [`RecvTunnel`]: struct.RecvTunnel.html
+/// [`client::Connection::connect()`]: client/struct.Connection.html#method.connect
+/// [`server::Sender::accept_connect()`]: server/struct.Sender.html#method.accept_connect
+pub struct SendTunnel {
+    send: SendStream,
+    conn: ConnectionRef,
+    write: Option<WriteFrame<DataFrame<Bytes>>>,
+    stream_id: StreamId,
+    finish: bool,
+}
+
+impl SendTunnel {
+    pub(crate) fn new(send: SendStream, conn: ConnectionRef, finish: bool) -> Self {
+        Self {
+            stream_id: send.id(),
+            send,
+            conn,
+            write: None,
+            finish,
+        }
+    }
+
+    /// Abort the tunnel
+    ///
+    /// The peer will receive a stream error with `CONNECT_ERROR` code.
+    pub fn cancel(&mut self) {
+        self.write = None;
+        let _ = self.send.reset(ErrorCode::CONNECT_ERROR.into());
+    }
+
+    fn poll_write_frame(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        if let Some(ref mut write) = self.write {
+            ready!(write.poll_send(&mut self.send, cx))?;
+            self.write = None;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_write_buf(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        ready!(self.poll_write_frame(cx))?;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        // The frame is accepted right away and flushed on the next call
+        self.write = Some(WriteFrame::new(DataFrame {
+            payload: Bytes::copy_from_slice(buf),
+        }));
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_close_stream(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_frame(cx))?;
+        ready!(self.send.poll_finish(cx))?;
+        if self.finish {
+            let mut conn = self.conn.h3.lock().unwrap();
+            conn.inner.remote_stream_finished(self.stream_id);
+            conn.wake();
+            self.finish = false;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl futures::io::AsyncWrite for SendTunnel {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write_buf(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_frame(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.get_mut().poll_close_stream(cx)
+    }
+}
+
+impl tokio::io::AsyncWrite for SendTunnel {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write_buf(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_frame(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.get_mut().poll_close_stream(cx)
+    }
+}
+
+impl fmt::Debug for SendTunnel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendTunnel")
+            .field("stream", &self.stream_id)
+            .finish()
+    }
+}
+
+/// Receiving half of a CONNECT tunnel
+///
+/// Reads the payload of the DATA frames sent by the peer, reaching the end when the peer shuts
+/// down its [`SendTunnel`].
+///
+/// Obtained from [`client::Connection::connect()`] or [`server::Sender::accept_connect()`].
+///
+/// [`SendTunnel`]: struct.SendTunnel.html
+/// [`client::Connection::connect()`]: client/struct.Connection.html#method.connect
+/// [`server::Sender::accept_connect()`]: server/struct.Sender.html#method.accept_connect
+pub struct RecvTunnel {
+    recv: FrameStream,
+    buf: Bytes,
+}
+
+impl RecvTunnel {
+    pub(crate) fn new(recv: FrameStream) -> Self {
+        Self {
+            recv,
+            buf: Bytes::new(),
+        }
+    }
+
+    /// Abort the tunnel
+    ///
+    /// The peer will receive a stream error with `CONNECT_ERROR` code.
+    pub fn cancel(&mut self) {
+        self.recv.reset(ErrorCode::CONNECT_ERROR);
+    }
+
+    fn poll_read_buf(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        loop {
+            if !self.buf.is_empty() {
+                let n = cmp::min(buf.len(), self.buf.len());
+                buf[..n].copy_from_slice(&self.buf[..n]);
+                self.buf.advance(n);
+                return Poll::Ready(Ok(n));
+            }
+
+            match ready!(Pin::new(&mut self.recv).poll_next(cx)) {
+                None => return Poll::Ready(Ok(0)),
+                Some(Ok(HttpFrame::Data(d))) => self.buf = d.payload,
+                Some(Ok(HttpFrame::Reserved)) => continue,
+                Some(Err(frame::Error::Io(e))) => return Poll::Ready(Err(e)),
+                Some(Err(e)) => {
+                    self.recv.reset(e.code());
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("received an invalid frame: {:?}", e),
+                    )));
+                }
+                Some(Ok(f)) => {
+                    // Only DATA frames are allowed once the tunnel is established
+                    self.recv.reset(ErrorCode::FRAME_UNEXPECTED);
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid frame type in tunnel: {:?}", f),
+                    )));
+                }
+            }
+        }
+    }
+}
+
+impl futures::io::AsyncRead for RecvTunnel {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_read_buf(cx, buf)
+    }
+}
+
+impl tokio::io::AsyncRead for RecvTunnel {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_read_buf(cx, buf)
+    }
+}
+
+impl fmt::Debug for RecvTunnel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecvTunnel")
+            .field("stream", &self.recv.get_ref().id())
+            .finish()
+    }
+}
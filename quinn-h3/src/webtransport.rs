@@ -0,0 +1,121 @@
+//! WebTransport sessions over HTTP/3
+//!
+//! A session is established with an extended CONNECT request, whose `:protocol` is
+//! [`Protocol::WEBTRANSPORT`]: the client opens it with [`client::Connection::webtransport()`],
+//! and the server accepts it with [`server::Sender::accept_webtransport()`]. Both endpoints
+//! need WebTransport enabled in their [`Settings`].
+//!
+//! Once established, a [`WebTransportSession`] carries streams and datagrams much like a QUIC
+//! connection, sharing the HTTP/3 connection with other requests and sessions.
+//!
+//! [`Protocol::WEBTRANSPORT`]: ../struct.Protocol.html#associatedconstant.WEBTRANSPORT
+//! [`client::Connection::webtransport()`]: ../client/struct.Connection.html#method.webtransport
+//! [`server::Sender::accept_webtransport()`]: ../server/struct.Sender.html#method.accept_webtransport
+//! [`Settings`]: ../struct.Settings.html
+//! [`WebTransportSession`]: struct.WebTransportSession.html
+
+use std::fmt;
+
+use bytes::{Bytes, BytesMut};
+use futures::future;
+use quinn::{RecvStream, SendStream};
+use quinn_proto::{
+    coding::{BufMutExt, Codec},
+    StreamId, VarInt,
+};
+
+use crate::{
+    connection::ConnectionRef,
+    proto::{frame, StreamType},
+    Error,
+};
+
+/// An established WebTransport session
+///
+/// Streams opened by the peer are yielded by [`accept_bi()`] and [`accept_uni()`], which return
+/// `None` once the session or the connection is closed. Dropping the session closes it,
+/// ending the CONNECT request stream and rejecting the streams the peer opens afterwards.
+///
+/// [`accept_bi()`]: #method.accept_bi
+/// [`accept_uni()`]: #method.accept_uni
+pub struct WebTransportSession {
+    pub(crate) id: StreamId,
+    pub(crate) conn: ConnectionRef,
+    /// Keeps the CONNECT request stream open for the session's lifetime
+    _send: SendStream,
+}
+
+impl WebTransportSession {
+    pub(crate) fn new(send: SendStream, conn: ConnectionRef) -> Self {
+        Self {
+            id: send.id(),
+            _send: send,
+            conn,
+        }
+    }
+
+    /// Open a bidirectional stream in this session
+    pub async fn open_bi(&self) -> Result<(SendStream, RecvStream), Error> {
+        let (mut send, recv) = self.conn.quic.open_bi().await?;
+        let mut header = BytesMut::with_capacity(2 * VarInt::MAX_SIZE);
+        frame::Type::WEBTRANSPORT_STREAM.encode(&mut header);
+        header.write_var(self.id.0);
+        send.write_all(&header).await?;
+        Ok((send, recv))
+    }
+
+    /// Open a unidirectional stream in this session
+    pub async fn open_uni(&self) -> Result<SendStream, Error> {
+        let mut send = self.conn.quic.open_uni().await?;
+        let mut header = BytesMut::with_capacity(2 * VarInt::MAX_SIZE);
+        StreamType::WEBTRANSPORT.encode(&mut header);
+        header.write_var(self.id.0);
+        send.write_all(&header).await?;
+        Ok(send)
+    }
+
+    /// Accept the next bidirectional stream opened by the peer in this session
+    pub async fn accept_bi(&self) -> Option<(SendStream, RecvStream)> {
+        future::poll_fn(|cx| self.conn.h3.lock().unwrap().poll_session_bi(self.id, cx)).await
+    }
+
+    /// Accept the next unidirectional stream opened by the peer in this session
+    pub async fn accept_uni(&self) -> Option<RecvStream> {
+        future::poll_fn(|cx| self.conn.h3.lock().unwrap().poll_session_uni(self.id, cx)).await
+    }
+
+    /// Send an unreliable datagram to the peer in this session
+    ///
+    /// Fails if the peer did not enable HTTP datagrams in its settings.
+    pub fn send_datagram(&self, data: Bytes) -> Result<(), Error> {
+        self.conn.send_datagram(self.id, &data)
+    }
+
+    /// Receive the next datagram sent by the peer in this session
+    ///
+    /// Only a limited number of datagrams are kept waiting, the oldest being dropped first.
+    pub async fn recv_datagram(&self) -> Option<Bytes> {
+        future::poll_fn(|cx| {
+            self.conn
+                .h3
+                .lock()
+                .unwrap()
+                .poll_session_datagram(self.id, cx)
+        })
+        .await
+    }
+}
+
+impl Drop for WebTransportSession {
+    fn drop(&mut self) {
+        self.conn.h3.lock().unwrap().close_session(self.id);
+    }
+}
+
+impl fmt::Debug for WebTransportSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebTransportSession")
+            .field("id", &self.id)
+            .finish()
+    }
+}
@@ -74,3 +74,44 @@ impl ConnectionIdGenerator for RandomConnectionIdGenerator {
         self.lifetime
     }
 }
+
+/// Generates connection IDs identifying one of several endpoints sharing a socket address
+///
+/// The first byte of every CID is replaced with the shard index, allowing datagrams received by
+/// the wrong endpoint to be routed to the one that owns the connection without any shared state.
+/// The remaining bytes are produced by the wrapped generator, which must issue non-empty CIDs.
+pub struct ShardedConnectionIdGenerator {
+    inner: Box<dyn ConnectionIdGenerator>,
+    shard: u8,
+}
+
+impl ShardedConnectionIdGenerator {
+    /// Embed `shard` in the CIDs produced by `inner`
+    pub fn new(inner: Box<dyn ConnectionIdGenerator>, shard: u8) -> Self {
+        debug_assert!(inner.cid_len() > 0);
+        Self { inner, shard }
+    }
+
+    /// The shard index embedded in a CID produced by this generator
+    pub fn shard_of(cid: &[u8]) -> Option<u8> {
+        cid.first().cloned()
+    }
+}
+
+impl ConnectionIdGenerator for ShardedConnectionIdGenerator {
+    fn generate_cid(&mut self) -> ConnectionId {
+        let mut cid = self.inner.generate_cid();
+        if let Some(first) = cid.first_mut() {
+            *first = self.shard;
+        }
+        cid
+    }
+
+    fn cid_len(&self) -> usize {
+        self.inner.cid_len()
+    }
+
+    fn cid_lifetime(&self) -> Option<Duration> {
+        self.inner.cid_lifetime()
+    }
+}
@@ -1,14 +1,27 @@
-use std::{convert::TryInto, fmt, num::TryFromIntError, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+    fmt,
+    num::TryFromIntError,
+    sync::Arc,
+    time::Duration,
+};
 
+use bytes::Bytes;
 use rand::RngCore;
 use thiserror::Error;
 
 #[cfg(feature = "rustls")]
 use crate::crypto::types::{Certificate, CertificateChain, PrivateKey};
 use crate::{
-    cid_generator::{ConnectionIdGenerator, RandomConnectionIdGenerator},
+    cid_generator::{
+        ConnectionIdGenerator, RandomConnectionIdGenerator, ShardedConnectionIdGenerator,
+    },
     congestion,
     crypto::{self, ClientConfig as _, HandshakeTokenKey as _, HmacKey as _, ServerConfig as _},
+    extension::{self, ExtensionFrame},
+    pcap::PacketCapture,
+    transport_parameters::TransportParameters,
     VarInt, VarIntBoundsExceeded,
 };
 
@@ -30,6 +43,7 @@ pub struct TransportConfig {
     pub(crate) stream_window_bidi: VarInt,
     pub(crate) stream_window_uni: VarInt,
     pub(crate) max_idle_timeout: Option<Duration>,
+    pub(crate) handshake_timeout: Option<Duration>,
     pub(crate) stream_receive_window: VarInt,
     pub(crate) receive_window: VarInt,
     pub(crate) send_window: u64,
@@ -45,6 +59,9 @@ pub struct TransportConfig {
     pub(crate) allow_spin: bool,
     pub(crate) datagram_receive_buffer_size: Option<usize>,
     pub(crate) datagram_send_buffer_size: usize,
+    pub(crate) custom_transport_parameters: BTreeMap<VarInt, Bytes>,
+    pub(crate) extension_frames: Arc<extension::Registry>,
+    pub(crate) extension_frame_receive_buffer_size: usize,
 
     pub(crate) congestion_controller_factory: Box<dyn congestion::ControllerFactory + Send + Sync>,
 }
@@ -92,6 +109,20 @@ impl TransportConfig {
         Ok(self)
     }
 
+    /// Maximum duration allowed for the handshake to complete before abandoning the connection
+    ///
+    /// Applies to both outgoing and incoming connections, counting from when the connection is
+    /// created. Unlike the idle timeout, this is not reset by activity, so an unreachable server
+    /// or one that never completes the handshake is given up on promptly. Expiry fails the
+    /// connection with [`ConnectionError::HandshakeTimedOut`]. `None`, the default, leaves the
+    /// handshake limited only by the idle timeout.
+    ///
+    /// [`ConnectionError::HandshakeTimedOut`]: crate::ConnectionError::HandshakeTimedOut
+    pub fn handshake_timeout(&mut self, value: Option<Duration>) -> &mut Self {
+        self.handshake_timeout = value;
+        self
+    }
+
     /// Maximum number of bytes the peer may transmit without acknowledgement on any one stream
     /// before becoming blocked.
     ///
@@ -208,6 +239,55 @@ impl TransportConfig {
         self
     }
 
+    /// Send an application-defined transport parameter to the peer
+    ///
+    /// `value` is opaque to the transport and replaces any value previously set for `id`. The
+    /// peer's application-defined parameters can be read with `Connection::peer_transport_parameter`
+    /// once the handshake has progressed far enough. Parameters unknown to the peer are ignored by
+    /// it, so this is suitable for negotiating protocol extensions.
+    ///
+    /// Fails if `id` belongs to a transport parameter used by this implementation or is reserved
+    /// for greasing.
+    pub fn custom_transport_parameter(
+        &mut self,
+        id: VarInt,
+        value: impl Into<Bytes>,
+    ) -> Result<&mut Self, ConfigError> {
+        if TransportParameters::is_reserved_id(id.into_inner()) {
+            return Err(ConfigError::ReservedTransportParameter);
+        }
+        self.custom_transport_parameters.insert(id, value.into());
+        Ok(self)
+    }
+
+    /// Register an application-defined frame type
+    ///
+    /// Frames of this type may then be sent with `Connection::send_extension_frame`, and received
+    /// with `Connection::recv_extension_frame`. Replaces any previous registration of the
+    /// same frame type. See the [`extension`](crate::extension) module for details.
+    ///
+    /// Fails if the frame type is defined by QUIC itself.
+    pub fn extension_frame(
+        &mut self,
+        frame: impl ExtensionFrame + 'static,
+    ) -> Result<&mut Self, ConfigError> {
+        let ty = frame.ty().into_inner();
+        if extension::is_reserved(ty) {
+            return Err(ConfigError::ReservedFrameType);
+        }
+        Arc::make_mut(&mut self.extension_frames).insert(ty, Arc::new(frame));
+        Ok(self)
+    }
+
+    /// Maximum number of payload bytes of received extension frames to buffer
+    ///
+    /// Frames are buffered until read with `Connection::recv_extension_frame`. As they may be
+    /// reliable, they are not dropped when the buffer is full: the connection is closed instead.
+    pub fn extension_frame_receive_buffer_size(&mut self, value: usize) -> &mut Self {
+        self.extension_frame_receive_buffer_size = value;
+        self
+    }
+
     /// How to construct new `congestion::Controller`s
     ///
     /// Typically the refcounted configuration of a `congestion::Controller`,
@@ -240,6 +320,7 @@ impl Default for TransportConfig {
             stream_window_bidi: 32u32.into(),
             stream_window_uni: 32u32.into(),
             max_idle_timeout: Some(Duration::from_millis(10_000)),
+            handshake_timeout: None,
             stream_receive_window: STREAM_RWND.into(),
             receive_window: VarInt::MAX,
             send_window: (8 * STREAM_RWND).into(),
@@ -255,6 +336,9 @@ impl Default for TransportConfig {
             allow_spin: true,
             datagram_receive_buffer_size: Some(STREAM_RWND as usize),
             datagram_send_buffer_size: 1024 * 1024,
+            custom_transport_parameters: BTreeMap::new(),
+            extension_frames: Arc::new(extension::Registry::new()),
+            extension_frame_receive_buffer_size: STREAM_RWND as usize,
 
             congestion_controller_factory: Box::new(Arc::new(congestion::NewRenoConfig::default())),
         }
@@ -267,6 +351,7 @@ impl fmt::Debug for TransportConfig {
             .field("stream_window_bidi", &self.stream_window_bidi)
             .field("stream_window_uni", &self.stream_window_uni)
             .field("max_idle_timeout", &self.max_idle_timeout)
+            .field("handshake_timeout", &self.handshake_timeout)
             .field("stream_receive_window", &self.stream_receive_window)
             .field("receive_window", &self.receive_window)
             .field("send_window", &self.send_window)
@@ -286,6 +371,18 @@ impl fmt::Debug for TransportConfig {
                 &self.datagram_receive_buffer_size,
             )
             .field("datagram_send_buffer_size", &self.datagram_send_buffer_size)
+            .field(
+                "custom_transport_parameters",
+                &self.custom_transport_parameters,
+            )
+            .field(
+                "extension_frames",
+                &self.extension_frames.keys().collect::<Vec<_>>(),
+            )
+            .field(
+                "extension_frame_receive_buffer_size",
+                &self.extension_frame_receive_buffer_size,
+            )
             .field("congestion_controller_factory", &"[ opaque ]")
             .finish()
     }
@@ -305,6 +402,7 @@ where
     /// Create a cid generator for local cid in Endpoint struct
     pub(crate) connection_id_generator_factory:
         Arc<dyn Fn() -> Box<dyn ConnectionIdGenerator> + Send + Sync>,
+    pub(crate) packet_capture: Option<Arc<dyn PacketCapture>>,
 }
 
 impl<S> EndpointConfig<S>
@@ -319,6 +417,7 @@ where
             reset_key: Arc::new(reset_key),
             max_udp_payload_size: 1480u32.into(), // Typical internet MTU minus IPv4 and UDP overhead, rounded up to a multiple of 8
             connection_id_generator_factory: Arc::new(cid_factory),
+            packet_capture: None,
         }
     }
 
@@ -340,6 +439,31 @@ where
         self
     }
 
+    /// Embed a shard index in every CID issued by endpoints using this configuration
+    ///
+    /// Wraps the current CID generator factory in a [`ShardedConnectionIdGenerator`], allowing
+    /// several `Endpoint`s to share one socket address while still being able to route datagrams to
+    /// the endpoint that owns the connection. The current generator must issue non-empty CIDs.
+    pub fn shard(&mut self, index: u8) -> &mut Self {
+        let factory = self.connection_id_generator_factory.clone();
+        self.cid_generator(move || {
+            Box::new(ShardedConnectionIdGenerator::new(factory(), index))
+                as Box<dyn ConnectionIdGenerator>
+        })
+    }
+
+    /// Report every datagram exchanged by endpoints using this configuration to `capture`
+    ///
+    /// Covers datagrams passed to `Endpoint::handle` and those returned by the `poll_transmit`
+    /// methods of the endpoint and its connections. See [`PcapNgWriter`] for a capture that can be
+    /// opened in Wireshark.
+    ///
+    /// [`PcapNgWriter`]: crate::pcap::PcapNgWriter
+    pub fn packet_capture(&mut self, capture: Arc<dyn PacketCapture>) -> &mut Self {
+        self.packet_capture = Some(capture);
+        self
+    }
+
     /// Private key used to send authenticated connection resets to peers who were
     /// communicating with a previous instance of this endpoint.
     pub fn reset_key(&mut self, value: &[u8]) -> Result<&mut Self, ConfigError> {
@@ -376,6 +500,7 @@ impl<S: crypto::Session> fmt::Debug for EndpointConfig<S> {
             .field("reset_key", &"[ elided ]")
             .field("max_udp_payload_size", &self.max_udp_payload_size)
             .field("cid_generator_factory", &"[ elided ]")
+            .field("packet_capture", &self.packet_capture.is_some())
             .finish()
     }
 }
@@ -397,6 +522,7 @@ impl<S: crypto::Session> Clone for EndpointConfig<S> {
             reset_key: self.reset_key.clone(),
             max_udp_payload_size: self.max_udp_payload_size,
             connection_id_generator_factory: self.connection_id_generator_factory.clone(),
+            packet_capture: self.packet_capture.clone(),
         }
     }
 }
@@ -434,6 +560,12 @@ where
     /// Improves behavior for clients that move between different internet connections or suffer NAT
     /// rebinding. Enabled by default.
     pub(crate) migration: bool,
+
+    /// Selects configuration by the server name requested by the client
+    pub(crate) server_name_resolver: Option<Arc<dyn ServerNameResolver<S>>>,
+
+    /// Where the TLS secrets of incoming connections are embedded, whatever their server name
+    pub(crate) secrets_capture: Option<Arc<dyn crypto::KeyLog>>,
 }
 
 impl<S> ServerConfig<S>
@@ -453,6 +585,10 @@ where
             concurrent_connections: 100_000,
 
             migration: true,
+
+            server_name_resolver: None,
+
+            secrets_capture: None,
         }
     }
 
@@ -492,6 +628,53 @@ where
         self.migration = value;
         self
     }
+
+    /// Select the transport and cryptographic configuration of incoming connections by the server
+    /// name requested by the client
+    ///
+    /// Allows a single endpoint to serve many domains, each with its own certificate chain and
+    /// application protocols. `transport` and `crypto` are used for clients which don't send a
+    /// server name, or for which the resolver returns `None`.
+    pub fn server_name_resolver(
+        &mut self,
+        resolver: impl ServerNameResolver<S> + 'static,
+    ) -> &mut Self {
+        self.server_name_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Pass the TLS secrets of incoming connections to `key_log`, e.g. a [`PcapNgWriter`]
+    ///
+    /// Applies to the configurations selected by [`server_name_resolver()`] too, so that a
+    /// capture can be decrypted on its own.
+    ///
+    /// [`server_name_resolver()`]: Self::server_name_resolver
+    /// [`PcapNgWriter`]: crate::pcap::PcapNgWriter
+    pub fn capture_secrets(&mut self, key_log: Arc<dyn crypto::KeyLog>) -> &mut Self {
+        self.crypto.capture_secrets(key_log.clone());
+        self.secrets_capture = Some(key_log);
+        self
+    }
+
+    /// The configuration to use for an incoming connection to `server_name`
+    pub(crate) fn resolve(self: &Arc<Self>, server_name: Option<&str>) -> Arc<Self> {
+        let mut config = match self
+            .server_name_resolver
+            .as_ref()
+            .and_then(|x| x.resolve(server_name))
+        {
+            Some(x) => x,
+            None => return self.clone(),
+        };
+        if let Some(ref capture) = self.secrets_capture {
+            config.crypto.capture_secrets(capture.clone());
+        }
+        Arc::new(Self {
+            transport: config.transport.unwrap_or_else(|| self.transport.clone()),
+            crypto: config.crypto,
+            ..(**self).clone()
+        })
+    }
 }
 
 #[cfg(feature = "rustls")]
@@ -520,6 +703,11 @@ where
             .field("retry_token_lifetime", &self.retry_token_lifetime)
             .field("concurrent_connections", &self.concurrent_connections)
             .field("migration", &self.migration)
+            .field(
+                "server_name_resolver",
+                &self.server_name_resolver.as_ref().map(|_| "[ opaque ]"),
+            )
+            .field("secrets_capture", &self.secrets_capture.is_some())
             .finish()
     }
 }
@@ -552,6 +740,169 @@ where
             retry_token_lifetime: self.retry_token_lifetime,
             concurrent_connections: self.concurrent_connections,
             migration: self.migration,
+            server_name_resolver: self.server_name_resolver.clone(),
+            secrets_capture: self.secrets_capture.clone(),
+        }
+    }
+}
+
+/// Selects the configuration of incoming connections by the server name requested by the client
+///
+/// The server name is taken from the TLS server name indication extension of the ClientHello in
+/// the client's first Initial packet.
+pub trait ServerNameResolver<S>: Send + Sync
+where
+    S: crypto::Session,
+{
+    /// The configuration to use for a connection to `server_name`
+    ///
+    /// `server_name` is `None` if the client didn't indicate one. Returning `None` selects the
+    /// endpoint's own `ServerConfig`.
+    fn resolve(&self, server_name: Option<&str>) -> Option<ServerNameConfig<S>>;
+}
+
+/// Configuration of incoming connections to a particular server name
+pub struct ServerNameConfig<S>
+where
+    S: crypto::Session,
+{
+    /// Transport configuration, or `None` to use that of the endpoint's `ServerConfig`
+    pub transport: Option<Arc<TransportConfig>>,
+
+    /// TLS configuration, including the certificate chain to present
+    ///
+    /// Must be set to use TLS 1.3 only.
+    pub crypto: S::ServerConfig,
+}
+
+#[cfg(feature = "rustls")]
+impl ServerNameConfig<crypto::rustls::TlsSession> {
+    /// Set the certificate chain that will be presented to clients
+    pub fn certificate(
+        &mut self,
+        cert_chain: CertificateChain,
+        key: PrivateKey,
+    ) -> Result<&mut Self, rustls::TLSError> {
+        Arc::make_mut(&mut self.crypto).set_single_cert(cert_chain.certs, key.inner)?;
+        Ok(self)
+    }
+
+    /// Set the application-layer protocols to accept, in order of descending preference
+    pub fn protocols(&mut self, protocols: &[&[u8]]) -> &mut Self {
+        Arc::make_mut(&mut self.crypto).alpn_protocols =
+            protocols.iter().map(|x| x.to_vec()).collect();
+        self
+    }
+}
+
+impl<S> fmt::Debug for ServerNameConfig<S>
+where
+    S: crypto::Session,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ServerNameConfig<T>")
+            .field("transport", &self.transport)
+            .field("crypto", &"ServerConfig { elided }")
+            .finish()
+    }
+}
+
+impl<S> Default for ServerNameConfig<S>
+where
+    S: crypto::Session,
+{
+    fn default() -> Self {
+        Self {
+            transport: None,
+            crypto: S::ServerConfig::new(),
+        }
+    }
+}
+
+impl<S> Clone for ServerNameConfig<S>
+where
+    S: crypto::Session,
+{
+    fn clone(&self) -> Self {
+        Self {
+            transport: self.transport.clone(),
+            crypto: self.crypto.clone(),
+        }
+    }
+}
+
+/// A [`ServerNameResolver`] holding the configuration of each server name in memory
+///
+/// Server names are matched exactly, ignoring ASCII case.
+pub struct ServerNameStore<S>
+where
+    S: crypto::Session,
+{
+    configs: HashMap<String, ServerNameConfig<S>>,
+}
+
+impl<S> ServerNameStore<S>
+where
+    S: crypto::Session,
+{
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self {
+            configs: HashMap::new(),
+        }
+    }
+
+    /// Use `config` for connections to `server_name`, replacing any previous configuration
+    pub fn insert(&mut self, server_name: &str, config: ServerNameConfig<S>) -> &mut Self {
+        self.configs
+            .insert(server_name.to_ascii_lowercase(), config);
+        self
+    }
+
+    /// Stop using a specific configuration for connections to `server_name`
+    pub fn remove(&mut self, server_name: &str) -> Option<ServerNameConfig<S>> {
+        self.configs.remove(&server_name.to_ascii_lowercase())
+    }
+}
+
+impl<S> ServerNameResolver<S> for ServerNameStore<S>
+where
+    S: crypto::Session,
+{
+    fn resolve(&self, server_name: Option<&str>) -> Option<ServerNameConfig<S>> {
+        self.configs
+            .get(&server_name?.to_ascii_lowercase())
+            .cloned()
+    }
+}
+
+impl<S> fmt::Debug for ServerNameStore<S>
+where
+    S: crypto::Session,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ServerNameStore<T>")
+            .field("server_names", &self.configs.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<S> Default for ServerNameStore<S>
+where
+    S: crypto::Session,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Clone for ServerNameStore<S>
+where
+    S: crypto::Session,
+{
+    fn clone(&self) -> Self {
+        Self {
+            configs: self.configs.clone(),
         }
     }
 }
@@ -629,6 +980,12 @@ pub enum ConfigError {
     /// Value exceeds supported bounds
     #[error("value exceeds supported bounds")]
     OutOfBounds,
+    /// Transport parameter ID is used by the transport itself or reserved for greasing
+    #[error("transport parameter ID is reserved")]
+    ReservedTransportParameter,
+    /// Frame type is defined by QUIC itself
+    #[error("frame type is reserved")]
+    ReservedFrameType,
 }
 
 impl From<TryFromIntError> for ConfigError {
@@ -17,11 +17,13 @@ use crate::{
     cid_queue::CidQueue,
     coding::BufMutExt,
     config::{ServerConfig, TransportConfig},
+    congestion,
     crypto::{self, HeaderKey, KeyPair, Keys, PacketKey},
-    frame,
+    extension, frame,
     frame::{Close, Datagram, FrameStruct, ShouldTransmit},
     is_supported_version,
     packet::{Header, LongType, Packet, PacketNumber, PartialDecode, PartialEncode, SpaceId},
+    pcap::PacketCapture,
     range_set::RangeSet,
     shared::{
         ConnectionEvent, ConnectionEventInner, ConnectionId, EcnCodepoint, EndpointEvent,
@@ -46,7 +48,7 @@ mod spaces;
 use spaces::{PacketSpace, Retransmits, SentPacket};
 
 mod stats;
-pub use stats::ConnectionStats;
+pub use stats::{ConnectionStats, UdpStats};
 
 mod streams;
 pub use streams::Streams;
@@ -154,6 +156,10 @@ where
     permit_idle_reset: bool,
     /// Negotiated idle timeout
     idle_timeout: Option<Duration>,
+    /// Interval at which to send keep-alives, initially from `TransportConfig`
+    keep_alive_interval: Option<Duration>,
+    /// Replaces `TransportConfig::congestion_controller_factory` for new paths, if set
+    congestion_controller_factory: Option<Box<dyn congestion::ControllerFactory + Send + Sync>>,
     timers: TimerTable,
     /// Number of packets received which could not be authenticated
     authentication_failures: u64,
@@ -193,8 +199,12 @@ where
     local_cid_state: CidState,
     /// State of the unreliable datagram extension
     datagrams: DatagramState,
+    /// Application-defined frames received but not yet delivered to the application
+    extension_frames: ExtensionFrameState,
     /// Connection level statistics
     stats: ConnectionStats,
+    /// Observer of transmitted datagrams, from the endpoint's configuration
+    capture: Option<Arc<dyn PacketCapture>>,
 }
 
 impl<S> Connection<S>
@@ -211,6 +221,7 @@ where
         local_ip: Option<IpAddr>,
         crypto: S,
         cid_gen: &dyn ConnectionIdGenerator,
+        capture: Option<Arc<dyn PacketCapture>>,
         now: Instant,
     ) -> Self {
         let side = if server_config.is_some() {
@@ -267,6 +278,8 @@ where
             accepted_0rtt: false,
             permit_idle_reset: true,
             idle_timeout: config.max_idle_timeout,
+            keep_alive_interval: config.keep_alive_interval,
+            congestion_controller_factory: None,
             timers: TimerTable::default(),
             authentication_failures: 0,
 
@@ -291,11 +304,16 @@ where
                 config.stream_receive_window,
             ),
             datagrams: DatagramState::new(),
+            extension_frames: ExtensionFrameState::default(),
             config,
             rem_cids: CidQueue::new(rem_cid),
             rng,
             stats: ConnectionStats::default(),
+            capture,
         };
+        if let Some(timeout) = this.config.handshake_timeout {
+            this.timers.set(Timer::Handshake, now + timeout);
+        }
         if side.is_client() {
             // Kick off the connection
             this.write_crypto();
@@ -348,6 +366,14 @@ where
     /// - a call was made to `handle_timeout`
     #[must_use]
     pub fn poll_transmit(&mut self, now: Instant) -> Option<Transmit> {
+        let transmit = self.poll_transmit_inner(now)?;
+        if let Some(ref capture) = self.capture {
+            capture.sent(self.local_ip, &transmit);
+        }
+        Some(transmit)
+    }
+
+    fn poll_transmit_inner(&mut self, now: Instant) -> Option<Transmit> {
         if self.anti_amplification_blocked() {
             trace!("blocked by anti-amplification");
             return None;
@@ -432,12 +458,17 @@ where
 
         for space_id in spaces {
             let buf_start = buf.len();
-            let mut ack_eliciting =
-                !self.spaces[space_id].pending.is_empty() || self.spaces[space_id].ping_pending;
+            let mut ack_eliciting = self.spaces[space_id].pending.is_ack_eliciting()
+                || self.spaces[space_id].ping_pending;
             if space_id == SpaceId::Data {
                 ack_eliciting |= self.can_send_1rtt();
+                let congestion_controlled =
+                    self.spaces[space_id].pending.is_congestion_controlled()
+                        || self.spaces[space_id].ping_pending
+                        || self.can_send_1rtt();
                 // Tail loss probes must not be blocked by congestion, or a deadlock could arise
-                if ack_eliciting && self.spaces[space_id].loss_probes == 0 {
+                if ack_eliciting && congestion_controlled && self.spaces[space_id].loss_probes == 0
+                {
                     if self.congestion_blocked() {
                         continue;
                     }
@@ -802,6 +833,10 @@ where
                 Timer::Idle => {
                     self.kill(ConnectionError::TimedOut);
                 }
+                Timer::Handshake => {
+                    debug!("handshake timed out");
+                    self.kill(ConnectionError::HandshakeTimedOut);
+                }
                 Timer::KeepAlive => {
                     trace!("sending keep-alive");
                     self.ping();
@@ -883,10 +918,71 @@ where
     /// Returns `None` if there are no new incoming streams for this connection.
     pub fn accept(&mut self, dir: Dir) -> Option<StreamId> {
         let id = self.streams.accept(dir)?;
-        self.alloc_remote_stream(id.dir());
+        self.alloc_remote_streams(id.dir());
         Some(id)
     }
 
+    /// Change the number of remotely initiated streams which may be open but not yet accepted
+    ///
+    /// Overrides [`TransportConfig::stream_window_bidi()`] or
+    /// [`TransportConfig::stream_window_uni()`] for this connection. A larger limit is announced
+    /// to the peer immediately. Streams the peer is already permitted to open cannot be revoked,
+    /// so a smaller limit takes effect gradually as incoming streams are accepted.
+    pub fn set_max_concurrent_streams(&mut self, dir: Dir, count: VarInt) {
+        self.streams.set_remote_window(dir, count);
+        self.alloc_remote_streams(dir);
+    }
+
+    /// Change the maximum amount of data the peer may send across all streams without
+    /// acknowledgement
+    ///
+    /// Overrides [`TransportConfig::receive_window()`] for this connection. Credit already issued
+    /// to the peer cannot be revoked, so a smaller window takes effect as data is read.
+    pub fn set_receive_window(&mut self, receive_window: VarInt) {
+        if self
+            .streams
+            .set_receive_window(receive_window)
+            .should_transmit()
+        {
+            self.spaces[SpaceId::Data].pending.max_data = true;
+        }
+    }
+
+    /// Change the maximum amount of data the peer may send on any one stream without
+    /// acknowledgement
+    ///
+    /// Overrides [`TransportConfig::stream_receive_window()`] for this connection. Credit already
+    /// issued to the peer cannot be revoked, so a smaller window takes effect as data is read.
+    pub fn set_stream_receive_window(&mut self, window: VarInt) {
+        self.streams
+            .set_stream_receive_window(window, &mut self.spaces[SpaceId::Data].pending);
+    }
+
+    /// Change the interval at which keep-alives are sent, or disable them with `None`
+    ///
+    /// Overrides [`TransportConfig::keep_alive_interval()`] for this connection.
+    pub fn set_keep_alive_interval(&mut self, now: Instant, interval: Option<Duration>) {
+        self.keep_alive_interval = interval;
+        if interval.is_none() {
+            self.timers.stop(Timer::KeepAlive);
+        }
+        self.reset_keep_alive(now);
+    }
+
+    /// Replace the congestion controller with one constructed by `factory`
+    ///
+    /// The new controller starts from its initial state, and is also used for any paths the
+    /// connection migrates to. Overrides [`TransportConfig::congestion_controller_factory()`] for
+    /// this connection.
+    pub fn set_congestion_controller_factory(
+        &mut self,
+        now: Instant,
+        factory: impl congestion::ControllerFactory + Send + Sync + 'static,
+    ) {
+        self.path.congestion = factory.build(now);
+        self.congestion_controller_factory = Some(Box::new(factory));
+    }
+
     /// Read from the given recv stream, in undefined order
     ///
     /// While stream data is typically processed by applications in-order, unordered reads improve
@@ -993,6 +1089,40 @@ where
         Ok(())
     }
 
+    /// Queue an application-defined frame for transmission
+    ///
+    /// `ty` must have been registered with `TransportConfig::extension_frame`, which determines
+    /// how the frame is encoded, acknowledged, retransmitted, and congestion controlled.
+    pub fn send_extension_frame(
+        &mut self,
+        ty: VarInt,
+        payload: &[u8],
+    ) -> Result<(), SendExtensionFrameError> {
+        let frame = match self.config.extension_frames.get(&ty.into_inner()) {
+            Some(x) => extension::Outgoing::new(&**x, payload),
+            None => return Err(SendExtensionFrameError::UnknownType),
+        };
+        let tag_len = self.spaces[SpaceId::Data]
+            .crypto
+            .as_ref()
+            .map(|x| x.packet.local.tag_len())
+            .or_else(|| self.zero_rtt_crypto.as_ref().map(|x| x.packet.tag_len()))
+            .unwrap_or(16);
+        let max_size = self.mtu as usize
+            - 1                 // flags byte
+            - self.rem_cids.active().len()
+            - 4                 // worst-case packet number size
+            - tag_len;
+        if frame.encoded.len() > max_size {
+            return Err(SendExtensionFrameError::TooLarge);
+        }
+        self.spaces[SpaceId::Data]
+            .pending
+            .extension_frames
+            .push_back(frame);
+        Ok(())
+    }
+
     /// Receive an unreliable, unordered datagram
     pub fn recv_datagram(&mut self) -> Option<Bytes> {
         let x = self.datagrams.incoming.pop_front()?.data;
@@ -1000,6 +1130,16 @@ where
         Some(x)
     }
 
+    /// Receive an application-defined frame, as its type and decoded payload
+    ///
+    /// Frames are received in the order they were decoded, which may differ from the order they
+    /// were sent in.
+    pub fn recv_extension_frame(&mut self) -> Option<(VarInt, Bytes)> {
+        let x = self.extension_frames.incoming.pop_front()?;
+        self.extension_frames.recv_buffered -= x.1.len();
+        Some(x)
+    }
+
     /// Compute the maximum size of datagrams that may passed to `send_datagram`
     ///
     /// Returns `None` if datagrams are unsupported by the peer or disabled locally.
@@ -1115,6 +1255,14 @@ where
         self.path.rtt.get()
     }
 
+    /// Value of an application-defined transport parameter sent by the peer
+    ///
+    /// Returns `None` if the peer did not send a parameter with this ID, or if its transport
+    /// parameters have not been received yet. See `TransportConfig::custom_transport_parameter`.
+    pub fn peer_transport_parameter(&self, id: VarInt) -> Option<&[u8]> {
+        self.peer_params.custom.get(&id).map(|x| &x[..])
+    }
+
     fn on_packet_sent(
         &mut self,
         now: Instant,
@@ -1259,6 +1407,10 @@ where
                 self.path
                     .congestion
                     .on_congestion_event(now, largest_sent_time, false);
+                self.events.push_back(Event::Congested {
+                    ecn: true,
+                    persistent: false,
+                });
             }
         }
     }
@@ -1424,6 +1576,10 @@ where
                     largest_lost_sent,
                     in_persistent_congestion,
                 );
+                self.events.push_back(Event::Congested {
+                    ecn: false,
+                    persistent: in_persistent_congestion,
+                });
             }
         }
     }
@@ -1580,7 +1736,7 @@ where
     }
 
     fn reset_keep_alive(&mut self, now: Instant) {
-        let interval = match self.config.keep_alive_interval {
+        let interval = match self.keep_alive_interval {
             Some(x) if self.state.is_established() => x,
             _ => return,
         };
@@ -1967,7 +2123,7 @@ where
                     code: TransportErrorCode::AEAD_LIMIT_REACHED,
                     ..
                 }) => State::Drained,
-                ConnectionError::TimedOut => {
+                ConnectionError::TimedOut | ConnectionError::HandshakeTimedOut => {
                     unreachable!("timeouts aren't generated by packet processing");
                 }
                 ConnectionError::TransportError(err) => {
@@ -2147,6 +2303,7 @@ where
                             self.discard_space(now, SpaceId::Handshake);
                         }
 
+                        self.timers.stop(Timer::Handshake);
                         self.events.push_back(Event::Connected);
                         self.state = State::Established;
                         trace!("established");
@@ -2304,7 +2461,9 @@ where
         let is_0rtt = self.spaces[SpaceId::Data].crypto.is_none();
         let mut is_probing_packet = true;
         let mut close = None;
-        for frame in frame::Iter::new(payload) {
+        let frames =
+            frame::Iter::new(payload).with_extensions(self.config.extension_frames.clone());
+        for frame in frames {
             let span = match frame {
                 Frame::Padding => None,
                 _ => Some(trace_span!("frame", ty = %frame.ty())),
@@ -2325,6 +2484,7 @@ where
             // Check for ack-eliciting frames
             match frame {
                 Frame::Ack(_) | Frame::Padding | Frame::Close(_) => {}
+                Frame::Extension(ref x) if !x.ack_eliciting => {}
                 _ => {
                     self.spaces[SpaceId::Data].permit_ack_only = true;
                 }
@@ -2516,6 +2676,7 @@ where
                         // active remote CID is invalid (due to packet loss or reordering),
                         // we must switch to next valid CID
                         self.update_rem_cid().unwrap();
+                        self.events.push_back(Event::ConnectionIdRotated);
                     }
                 }
                 Frame::NewToken { token } => {
@@ -2550,6 +2711,21 @@ where
                     self.datagrams.recv_buffered += datagram.data.len();
                     self.datagrams.incoming.push_back(datagram);
                 }
+                Frame::Extension(frame) => {
+                    let buffered = self.extension_frames.recv_buffered + frame.payload.len();
+                    if buffered > self.config.extension_frame_receive_buffer_size {
+                        return Err(TransportError::INTERNAL_ERROR(
+                            "extension frame buffer exceeded",
+                        ));
+                    }
+                    if self.extension_frames.incoming.is_empty() {
+                        self.events.push_back(Event::ExtensionFrameReceived);
+                    }
+                    self.extension_frames.recv_buffered = buffered;
+                    self.extension_frames
+                        .incoming
+                        .push_back((frame.ty, frame.payload));
+                }
                 Frame::HandshakeDone => {
                     if self.side.is_server() {
                         return Err(TransportError::PROTOCOL_VIOLATION(
@@ -2582,7 +2758,9 @@ where
             );
             self.migrate(now, remote);
             // Break linkability, if possible
-            let _ = self.update_rem_cid();
+            if self.update_rem_cid().is_ok() {
+                self.events.push_back(Event::ConnectionIdRotated);
+            }
         }
 
         Ok(())
@@ -2604,7 +2782,7 @@ where
             PathData::new(
                 remote,
                 self.config.initial_rtt,
-                self.config.congestion_controller_factory.build(now),
+                self.build_congestion_controller(now),
                 now,
             )
         };
@@ -2623,6 +2801,7 @@ where
             Timer::PathValidation,
             now + 3 * cmp::max(self.pto(), 2 * self.config.initial_rtt),
         );
+        self.events.push_back(Event::PathMigrated { remote });
     }
 
     /// Returns Err(()) if no CIDs were available
@@ -2830,6 +3009,22 @@ where
             self.stats.frame_tx.datagram += 1;
         }
 
+        // Extension frames
+        if space_id == SpaceId::Data {
+            while let Some(frame) = space.pending.extension_frames.front() {
+                if buf.len() + frame.encoded.len() > max_size {
+                    break;
+                }
+                let frame = space.pending.extension_frames.pop_front().unwrap();
+                trace!(len = frame.encoded.len(), "extension frame");
+                buf.extend_from_slice(&frame.encoded);
+                self.stats.frame_tx.extension += 1;
+                if frame.retransmittable {
+                    sent.retransmits.extension_frames.push_back(frame);
+                }
+            }
+        }
+
         // STREAM
         if space_id == SpaceId::Data {
             sent.stream_frames = self.streams.write_stream_frames(buf, max_size);
@@ -2883,8 +3078,11 @@ where
         self.peer_params = params;
     }
 
-    /// Permit an additional remote `ty` stream.
-    fn alloc_remote_stream(&mut self, dir: Dir) {
+    /// Permit additional remote `dir` streams, up to the configured window
+    fn alloc_remote_streams(&mut self, dir: Dir) {
+        if !self.streams.alloc_remote_streams(&self.peer_params, dir) {
+            return;
+        }
         let space = &mut self.spaces[SpaceId::Data];
         match dir {
             Dir::Bi => {
@@ -2894,7 +3092,13 @@ where
                 space.pending.max_uni_stream_id = true;
             }
         }
-        self.streams.alloc_remote_stream(&self.peer_params, dir);
+    }
+
+    fn build_congestion_controller(&self, now: Instant) -> Box<dyn congestion::Controller> {
+        match self.congestion_controller_factory {
+            Some(ref factory) => factory.build(now),
+            None => self.config.congestion_controller_factory.build(now),
+        }
     }
 
     fn add_read_credits(
@@ -3017,6 +3221,7 @@ where
             update_unacked: remote,
         });
         self.key_phase = !self.key_phase;
+        self.events.push_back(Event::KeysUpdated { by_peer: remote });
     }
 
     /// The number of bytes of packets containing retransmittable frames that have not been
@@ -3101,17 +3306,22 @@ where
         debug_assert!(self.side.is_client());
         debug!("0-RTT rejected");
         self.accepted_0rtt = false;
+        self.events.push_back(Event::ZeroRttRejected);
         self.streams.zero_rtt_rejected();
-        // Discard already-queued frames
-        self.spaces[SpaceId::Data].pending = Retransmits::default();
-        // Discard 0-RTT packets
+        // Discard already-queued frames and 0-RTT packets, except for extension frames, which
+        // don't depend on 0-RTT state and are sent again in 1-RTT packets
+        let pending = mem::take(&mut self.spaces[SpaceId::Data].pending);
         let sent_packets = mem::replace(
             &mut self.spaces[SpaceId::Data].sent_packets,
             BTreeMap::new(),
         );
+        let mut extension_frames = VecDeque::new();
         for (_, packet) in sent_packets {
             self.remove_in_flight(SpaceId::Data, &packet);
+            extension_frames.extend(packet.retransmits.extension_frames);
         }
+        extension_frames.extend(pending.extension_frames);
+        self.spaces[SpaceId::Data].pending.extension_frames = extension_frames;
     }
 
     /// Update counters to account for a packet becoming acknowledged, lost, or abandoned
@@ -3166,6 +3376,11 @@ pub enum ConnectionError {
     /// and [`TransportConfig::keep_alive_interval()`].
     #[error("timed out")]
     TimedOut,
+    /// The handshake did not complete within the configured limit
+    ///
+    /// See [`TransportConfig::handshake_timeout()`].
+    #[error("handshake timed out")]
+    HandshakeTimedOut,
     /// The local application closed the connection
     #[error("closed")]
     LocallyClosed,
@@ -3185,7 +3400,7 @@ impl From<ConnectionError> for io::Error {
     fn from(x: ConnectionError) -> io::Error {
         use self::ConnectionError::*;
         let kind = match x {
-            TimedOut => io::ErrorKind::TimedOut,
+            TimedOut | HandshakeTimedOut => io::ErrorKind::TimedOut,
             Reset => io::ErrorKind::ConnectionReset,
             ApplicationClosed(_) | ConnectionClosed(_) => io::ErrorKind::ConnectionAborted,
             TransportError(_) | VersionMismatch | LocallyClosed => io::ErrorKind::Other,
@@ -3325,6 +3540,37 @@ pub enum Event {
     Stream(StreamEvent),
     /// One or more application datagrams have been received
     DatagramReceived,
+    /// The peer began sending from a new address, which is now being validated
+    ///
+    /// Only occurs on servers, when a client migrates or its NAT rebinds.
+    PathMigrated {
+        /// The peer's new address
+        remote: SocketAddr,
+    },
+    /// New 1-RTT packet protection keys have been put in use
+    KeysUpdated {
+        /// Whether the update was initiated by the peer
+        by_peer: bool,
+    },
+    /// A new connection ID has been put in use for addressing packets to the peer
+    ConnectionIdRotated,
+    /// The server declined to process 0-RTT data, which must be resent if still needed
+    ///
+    /// Only occurs on clients. Streams opened before the handshake completed are discarded.
+    ZeroRttRejected,
+    /// One or more application-defined frames were received and can be read with
+    /// `Connection::recv_extension_frame`
+    ///
+    /// See `TransportConfig::extension_frame`.
+    ExtensionFrameReceived,
+    /// The congestion controller reduced the sending rate in response to congestion
+    Congested {
+        /// Whether congestion was signaled by ECN marks rather than packet loss
+        ecn: bool,
+        /// Whether enough consecutive packets were lost to reset the congestion window to
+        /// its minimum
+        persistent: bool,
+    },
 }
 
 impl From<ConnectionError> for Event {
@@ -3369,6 +3615,17 @@ pub enum SendDatagramError {
     TooLarge,
 }
 
+/// Errors that can arise when sending an application-defined frame
+#[derive(Debug, Error, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum SendExtensionFrameError {
+    /// The frame type was not registered with `TransportConfig::extension_frame`
+    #[error("unknown frame type")]
+    UnknownType,
+    /// The encoded frame does not fit in a single packet
+    #[error("frame too large")]
+    TooLarge,
+}
+
 struct DatagramState {
     /// Number of bytes of datagrams that have been received by the local transport but not
     /// delivered to the application
@@ -3389,6 +3646,13 @@ impl DatagramState {
     }
 }
 
+#[derive(Default)]
+struct ExtensionFrameState {
+    /// Number of payload bytes of the frames in `incoming`
+    recv_buffered: usize,
+    incoming: VecDeque<(VarInt, Bytes)>,
+}
+
 struct ZeroRttCrypto<S: crypto::Session> {
     header: S::HeaderKey,
     packet: S::PacketKey,
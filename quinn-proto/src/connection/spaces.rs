@@ -8,8 +8,8 @@ use std::{
 
 use super::assembler::Assembler;
 use crate::{
-    crypto, crypto::Keys, frame, packet::SpaceId, range_set::RangeSet, shared::IssuedCid, StreamId,
-    VarInt,
+    crypto, crypto::Keys, extension, frame, packet::SpaceId, range_set::RangeSet,
+    shared::IssuedCid, StreamId, VarInt,
 };
 
 pub(crate) struct PacketSpace<S>
@@ -201,10 +201,30 @@ pub struct Retransmits {
     pub(crate) new_cids: Vec<IssuedCid>,
     pub(crate) retire_cids: Vec<u64>,
     pub(crate) handshake_done: bool,
+    pub(crate) extension_frames: VecDeque<extension::Outgoing>,
 }
 
 impl Retransmits {
     pub fn is_empty(&self) -> bool {
+        self.is_builtin_empty() && self.extension_frames.is_empty()
+    }
+
+    /// Whether sending this data requires an ack-eliciting packet
+    pub fn is_ack_eliciting(&self) -> bool {
+        !self.is_builtin_empty() || self.extension_frames.iter().any(|x| x.ack_eliciting)
+    }
+
+    /// Whether sending this data is subject to congestion control
+    pub fn is_congestion_controlled(&self) -> bool {
+        !self.is_builtin_empty()
+            || self
+                .extension_frames
+                .iter()
+                .any(|x| x.ack_eliciting && x.congestion_controlled)
+    }
+
+    /// Whether there are no frames of types defined by QUIC itself
+    fn is_builtin_empty(&self) -> bool {
         !self.max_data
             && !self.max_uni_stream_id
             && !self.max_bi_stream_id
@@ -231,6 +251,7 @@ impl Default for Retransmits {
             new_cids: Vec::new(),
             retire_cids: Vec::new(),
             handshake_done: false,
+            extension_frames: VecDeque::new(),
         }
     }
 }
@@ -251,6 +272,7 @@ impl ::std::ops::BitOrAssign for Retransmits {
         self.new_cids.extend(&rhs.new_cids);
         self.retire_cids.extend(rhs.retire_cids);
         self.handshake_done |= rhs.handshake_done;
+        self.extension_frames.extend(rhs.extension_frames);
     }
 }
 
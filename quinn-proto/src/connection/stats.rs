@@ -1,4 +1,4 @@
-/// Statistics about UDP datagrams transmitted or received on a connection
+/// Statistics about UDP datagrams transmitted or received on a connection or endpoint
 #[derive(Default, Debug, Copy, Clone)]
 #[non_exhaustive]
 pub struct UdpStats {
@@ -8,6 +8,20 @@ pub struct UdpStats {
     pub bytes: u64,
 }
 
+impl UdpStats {
+    pub(crate) fn on_datagram(&mut self, len: usize) {
+        self.datagrams += 1;
+        self.bytes += len as u64;
+    }
+}
+
+impl std::ops::AddAssign for UdpStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.datagrams += rhs.datagrams;
+        self.bytes += rhs.bytes;
+    }
+}
+
 /// Statistics about frames transmitted or received on a connection
 #[derive(Default, Copy, Clone)]
 #[non_exhaustive]
@@ -28,6 +42,7 @@ pub struct FrameStats {
     pub retire_connection_id: u64,
     pub stop_sending: u64,
     pub stream: u64,
+    pub extension: u64,
 }
 
 impl std::fmt::Debug for FrameStats {
@@ -49,6 +64,7 @@ impl std::fmt::Debug for FrameStats {
             .field("RETIRE_CONNECTION_ID", &self.retire_connection_id)
             .field("STOP_SENDING", &self.stop_sending)
             .field("STREAM", &self.stream)
+            .field("extension", &self.extension)
             .finish()
     }
 }
@@ -31,6 +31,8 @@ pub struct Streams {
     max: [u64; 2],
     // Maximum that can be remotely initiated
     max_remote: [u64; 2],
+    /// Maximum number of remotely initiated streams which may be open but not yet accepted
+    remote_window: [u64; 2],
     // Lowest that hasn't actually been opened
     next_remote: [u64; 2],
     /// Whether the remote endpoint has opened any streams the application doesn't know about yet,
@@ -70,6 +72,11 @@ pub struct Streams {
     send_window: u64,
     /// Configured upper bound for how much unacked data the peer can send us per stream
     stream_receive_window: u64,
+    /// Largest `stream_receive_window` in effect over the connection's lifetime
+    ///
+    /// Bounds the credit the peer may have been issued on any stream, which must still be honored
+    /// after the window shrinks.
+    max_stream_receive_window: u64,
 }
 
 impl Streams {
@@ -88,6 +95,7 @@ impl Streams {
             next: [0, 0],
             max: [0, 0],
             max_remote: [max_remote_bi.into(), max_remote_uni.into()],
+            remote_window: [max_remote_bi.into(), max_remote_uni.into()],
             next_remote: [0, 0],
             opened: [false, false],
             next_reported_remote: [0, 0],
@@ -104,6 +112,7 @@ impl Streams {
             unacked_data: 0,
             send_window,
             stream_receive_window: stream_receive_window.into(),
+            max_stream_receive_window: stream_receive_window.into(),
         };
 
         for dir in Dir::iter() {
@@ -142,10 +151,64 @@ impl Streams {
         self.send_streams
     }
 
-    pub fn alloc_remote_stream(&mut self, params: &TransportParameters, dir: Dir) {
-        self.max_remote[dir as usize] += 1;
-        let id = StreamId::new(!self.side, dir, self.max_remote[dir as usize] - 1);
-        self.insert(Some(params), true, id);
+    /// Permit remotely initiated streams until `remote_window` are open but not yet accepted
+    ///
+    /// Returns whether any were permitted, in which case a `MAX_STREAMS` frame must be sent.
+    pub fn alloc_remote_streams(&mut self, params: &TransportParameters, dir: Dir) -> bool {
+        let mut allocated = false;
+        while self.max_remote[dir as usize] - self.next_reported_remote[dir as usize]
+            < self.remote_window[dir as usize]
+            && self.max_remote[dir as usize] < MAX_STREAM_COUNT
+        {
+            self.max_remote[dir as usize] += 1;
+            let id = StreamId::new(!self.side, dir, self.max_remote[dir as usize] - 1);
+            self.insert(Some(params), true, id);
+            allocated = true;
+        }
+        allocated
+    }
+
+    /// Change the number of remotely initiated streams which may be open but not yet accepted
+    ///
+    /// Streams already permitted cannot be revoked, so a smaller window only takes effect as
+    /// streams are accepted.
+    pub fn set_remote_window(&mut self, dir: Dir, count: VarInt) {
+        self.remote_window[dir as usize] = count.into();
+    }
+
+    /// Change the connection-level receive window
+    ///
+    /// Returns whether a `MAX_DATA` frame should be sent.
+    pub fn set_receive_window(&mut self, receive_window: VarInt) -> ShouldTransmit {
+        let receive_window = receive_window.into_inner();
+        if receive_window >= self.receive_window {
+            self.local_max_data = self
+                .local_max_data
+                .saturating_add(receive_window - self.receive_window);
+        } else {
+            self.local_max_data = self
+                .local_max_data
+                .saturating_sub(self.receive_window - receive_window);
+        }
+        self.receive_window = receive_window;
+        self.add_read_credits(0)
+    }
+
+    /// Change the per-stream receive window
+    ///
+    /// Queues `MAX_STREAM_DATA` frames for streams which can now be issued significantly more
+    /// credit.
+    pub fn set_stream_receive_window(&mut self, window: VarInt, pending: &mut Retransmits) {
+        self.stream_receive_window = window.into_inner();
+        self.max_stream_receive_window = self
+            .max_stream_receive_window
+            .max(self.stream_receive_window);
+        for (&id, rs) in &mut self.recv {
+            let (_, transmit) = rs.max_stream_data(self.stream_receive_window);
+            if transmit.should_transmit() {
+                pending.max_stream_data.insert(id);
+            }
+        }
     }
 
     pub fn accept(&mut self, dir: Dir) -> Option<StreamId> {
@@ -285,11 +348,12 @@ impl Streams {
             return Ok(ShouldTransmit::new(false));
         }
 
+        // Credit which has already been issued is honored even if the windows have since shrunk
         let new_bytes = rs.ingest(
             frame,
             self.data_recvd,
-            self.local_max_data,
-            self.stream_receive_window,
+            self.local_max_data.max(self.sent_max_data.into()),
+            self.max_stream_receive_window,
         )?;
         self.data_recvd += new_bytes;
 
@@ -517,7 +581,9 @@ impl Streams {
             // `local_max_data` can grow bigger than `VarInt`.
             // For transmission inside QUIC frames we need to clamp it to the
             // maximum allowed `VarInt` size.
-            let max = VarInt::try_from(self.local_max_data).unwrap_or(VarInt::MAX);
+            let max = VarInt::try_from(self.local_max_data)
+                .unwrap_or(VarInt::MAX)
+                .max(self.sent_max_data);
 
             trace!(value = max.into_inner(), "MAX_DATA");
             self.record_sent_max_data(max);
@@ -865,7 +931,9 @@ impl Streams {
         // We use a fraction of the configured connection receive window to make
         // the decision, to accomodate for connection using bigger windows requring
         // less updates.
-        let diff = self.local_max_data - self.sent_max_data.into_inner();
+        let diff = self
+            .local_max_data
+            .saturating_sub(self.sent_max_data.into_inner());
         ShouldTransmit::new(diff >= (self.receive_window / 8))
     }
 
@@ -1158,7 +1226,7 @@ impl Recv {
         // less updates. A fixed size would also work - but it would need to be
         // smaller than `stream_receive_window` in order to make sure the stream
         // does not get stuck.
-        let diff = max_stream_data - self.sent_max_stream_data;
+        let diff = max_stream_data.saturating_sub(self.sent_max_stream_data);
         let transmit = self.receiving_unknown_size() && diff >= (stream_receive_window / 8);
         (max_stream_data, ShouldTransmit::new(transmit))
     }
@@ -1415,6 +1483,32 @@ mod tests {
         assert_eq!(client.local_max_data - initial_max, 4096);
     }
 
+    #[test]
+    fn shrink_receive_window() {
+        let mut client = make(Side::Client);
+        let id = StreamId::new(Side::Server, Dir::Uni, 0);
+        assert_eq!(
+            client.set_receive_window(1024u32.into()),
+            ShouldTransmit::new(false)
+        );
+        // Credit issued before the window shrank is still honored
+        assert_eq!(
+            client
+                .received(frame::Stream {
+                    id,
+                    offset: 0,
+                    fin: false,
+                    data: Bytes::from_static(&[0; 4096]),
+                })
+                .unwrap(),
+            ShouldTransmit::new(false)
+        );
+        // Reading doesn't issue more credit until the smaller window is exceeded
+        let result = client.read(id, &mut [0; 4096]).unwrap().unwrap();
+        assert_eq!(result.max_data, ShouldTransmit::new(false));
+        assert_eq!(client.local_max_data, 1024 + 4096);
+    }
+
     #[test]
     fn duplicate_reset_flow_control() {
         let mut client = make(Side::Client);
@@ -18,10 +18,12 @@ pub(crate) enum Timer {
     Pacing = 6,
     /// When to invalidate old CID and proactively push new one via NEW_CONNECTION_ID frame
     PushNewCid = 7,
+    /// When to give up on completing the handshake
+    Handshake = 8,
 }
 
 impl Timer {
-    pub(crate) const VALUES: [Self; 8] = [
+    pub(crate) const VALUES: [Self; 9] = [
         Timer::LossDetection,
         Timer::Idle,
         Timer::Close,
@@ -30,13 +32,14 @@ impl Timer {
         Timer::KeepAlive,
         Timer::Pacing,
         Timer::PushNewCid,
+        Timer::Handshake,
     ];
 }
 
 /// A table of data associated with each distinct kind of `Timer`
 #[derive(Debug, Copy, Clone, Default)]
 pub(crate) struct TimerTable {
-    data: [Option<Instant>; 8],
+    data: [Option<Instant>; 9],
 }
 
 impl TimerTable {
@@ -8,7 +8,7 @@
 //! Note that usage of any protocol (version) other than TLS 1.3 does not conform to any
 //! published versions of the specification, and will not be supported in QUIC v1.
 
-use std::str;
+use std::{str, sync::Arc};
 
 use bytes::BytesMut;
 
@@ -151,6 +151,13 @@ where
         server_name: &str,
         params: &TransportParameters,
     ) -> Result<S, ConnectError>;
+
+    /// Pass the secrets of sessions started with this configuration to `key_log`
+    ///
+    /// Does nothing for protocols whose secrets can't be exported.
+    fn capture_secrets(&mut self, key_log: Arc<dyn KeyLog>) {
+        let _ = key_log;
+    }
 }
 
 /// Server-side configuration for the crypto protocol
@@ -165,6 +172,20 @@ where
 
     /// Start a server session with this configuration
     fn start_session(&self, params: &TransportParameters) -> S;
+
+    /// Pass the secrets of sessions started with this configuration to `key_log`
+    ///
+    /// Does nothing for protocols whose secrets can't be exported.
+    fn capture_secrets(&mut self, key_log: Arc<dyn KeyLog>) {
+        let _ = key_log;
+    }
+}
+
+/// Receiver of the secrets derived by sessions, e.g. to decrypt captured traffic
+pub trait KeyLog: Send + Sync {
+    /// Record `secret`, described by `label` as in the NSS key log format, for the session
+    /// identified by `client_random`
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]);
 }
 
 /// Keys used to protect packet payloads
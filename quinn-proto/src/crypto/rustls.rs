@@ -316,6 +316,10 @@ impl crypto::ClientConfig<TlsSession> for Arc<rustls::ClientConfig> {
             )),
         })
     }
+
+    fn capture_secrets(&mut self, key_log: Arc<dyn crypto::KeyLog>) {
+        Arc::make_mut(self).key_log = Arc::new(KeyLog(key_log));
+    }
 }
 
 impl crypto::ServerConfig<TlsSession> for Arc<rustls::ServerConfig> {
@@ -333,6 +337,19 @@ impl crypto::ServerConfig<TlsSession> for Arc<rustls::ServerConfig> {
             inner: SessionKind::Server(rustls::ServerSession::new_quic(self, to_vec(params))),
         }
     }
+
+    fn capture_secrets(&mut self, key_log: Arc<dyn crypto::KeyLog>) {
+        Arc::make_mut(self).key_log = Arc::new(KeyLog(key_log));
+    }
+}
+
+/// Passes the secrets logged by rustls to a [`crypto::KeyLog`]
+struct KeyLog(Arc<dyn crypto::KeyLog>);
+
+impl rustls::KeyLog for KeyLog {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        self.0.log(label, client_random, secret);
+    }
 }
 
 fn to_vec(params: &TransportParameters) -> Vec<u8> {
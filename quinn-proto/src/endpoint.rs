@@ -18,12 +18,12 @@ use crate::{
     cid_generator::{ConnectionIdGenerator, RandomConnectionIdGenerator},
     coding::BufMutExt,
     config::{ClientConfig, ConfigError, EndpointConfig, ServerConfig},
-    connection::{Connection, ConnectionError},
+    connection::{Connection, ConnectionError, UdpStats},
     crypto::{
         self, ClientConfig as ClientCryptoConfig, Keys, PacketKey,
         ServerConfig as ServerCryptoConfig,
     },
-    frame,
+    frame, inspect,
     packet::{Header, Packet, PacketDecodeError, PacketNumber, PartialDecode},
     shared::{
         ConnectionEvent, ConnectionEventInner, ConnectionId, EcnCodepoint, EndpointEvent,
@@ -62,6 +62,7 @@ where
     ///
     /// Equivalent to a `ServerConfig.accept_buffer` of `0`, but can be changed after the endpoint is constructed.
     reject_new_connections: bool,
+    stats: EndpointStats,
 }
 
 impl<S> Endpoint<S>
@@ -85,6 +86,7 @@ where
             connections: Slab::new(),
             local_cid_generator: (config.connection_id_generator_factory.as_ref())(),
             reject_new_connections: false,
+            stats: EndpointStats::default(),
             config,
             server_config,
         }
@@ -97,7 +99,12 @@ where
     /// Get the next packet to transmit
     #[must_use]
     pub fn poll_transmit(&mut self) -> Option<Transmit> {
-        self.transmits.pop_front()
+        let transmit = self.transmits.pop_front()?;
+        self.stats.udp_tx.on_datagram(transmit.contents.len());
+        if let Some(ref capture) = self.config.packet_capture {
+            capture.sent(None, &transmit);
+        }
+        Some(transmit)
     }
 
     /// Process `EndpointEvent`s emitted from related `Connection`s
@@ -156,7 +163,11 @@ where
         ecn: Option<EcnCodepoint>,
         data: BytesMut,
     ) -> Option<(ConnectionHandle, DatagramEvent<S>)> {
+        if let Some(ref capture) = self.config.packet_capture {
+            capture.received(remote, local_ip, ecn, &data);
+        }
         let datagram_len = data.len();
+        self.stats.udp_rx.on_datagram(datagram_len);
         let (first_decode, remaining) =
             match PartialDecode::new(data, self.local_cid_generator.cid_len()) {
                 Ok(x) => x,
@@ -167,6 +178,7 @@ where
                 }) => {
                     if !self.is_server() {
                         debug!("dropping packet with unsupported version");
+                        self.stats.drops.unsupported_version += 1;
                         return None;
                     }
                     trace!("sending version negotiation");
@@ -185,6 +197,7 @@ where
                         buf.write::<u32>(0x0a1a_2a4a);
                     }
                     buf.write(*VERSION.start()); // supported version
+                    self.stats.version_negotiations += 1;
                     self.transmits.push_back(Transmit {
                         destination: remote,
                         ecn: None,
@@ -194,6 +207,7 @@ where
                 }
                 Err(e) => {
                     trace!("malformed header: {}", e);
+                    self.stats.drops.malformed += 1;
                     return None;
                 }
             };
@@ -262,10 +276,12 @@ where
                     "ignoring non-initial packet for unknown connection {}",
                     dst_cid
                 );
+                self.stats.drops.unknown_connection += 1;
                 return None;
             }
             if datagram_len < MIN_INITIAL_SIZE {
                 debug!("ignoring short initial for connection {}", dst_cid);
+                self.stats.drops.short_initial += 1;
                 return None;
             }
 
@@ -276,6 +292,7 @@ where
                     .map(|(ch, conn)| (ch, DatagramEvent::NewConnection(conn))),
                 Err(e) => {
                     trace!("unable to decode initial packet: {}", e);
+                    self.stats.drops.malformed += 1;
                     None
                 }
             };
@@ -290,6 +307,7 @@ where
             self.stateless_reset(datagram_len, remote, &dst_cid);
         } else {
             trace!("dropping unrecognized short packet without ID");
+            self.stats.drops.unknown_connection += 1;
         }
         None
     }
@@ -309,6 +327,7 @@ where
             Some(headroom) if headroom > MIN_PADDING_LEN => headroom - 1,
             _ => {
                 debug!("ignoring unexpected {} byte packet: not larger than minimum stateless reset size", inciting_dgram_len);
+                self.stats.drops.unknown_connection += 1;
                 return;
             }
         };
@@ -329,6 +348,7 @@ where
         buf.extend_from_slice(&ResetToken::new(&*self.config.reset_key, dst_cid));
 
         debug_assert!(buf.len() < inciting_dgram_len);
+        self.stats.stateless_resets += 1;
 
         self.transmits.push_back(Transmit {
             destination: remote,
@@ -428,16 +448,16 @@ where
                 )
             }
             ConnectionOpts::Server {
+                config,
                 orig_dst_cid,
                 retry_src_cid,
             } => {
-                let config = self.server_config.as_ref().unwrap();
                 let params = TransportParameters::new(
                     &config.transport,
                     &self.config,
                     self.local_cid_generator.as_ref(),
                     loc_cid,
-                    Some(config),
+                    Some(&config),
                 );
                 let server_params = TransportParameters {
                     stateless_reset_token: Some(ResetToken::new(&*self.config.reset_key, &loc_cid)),
@@ -463,6 +483,7 @@ where
             local_ip,
             tls,
             self.local_cid_generator.as_ref(),
+            self.config.packet_capture.clone(),
             now,
         );
         let id = self.connections.insert(ConnectionMeta {
@@ -514,11 +535,13 @@ where
             .is_err()
         {
             debug!(packet_number, "failed to authenticate initial packet");
+            self.stats.drops.authentication_failed += 1;
             return None;
         };
 
         if !packet.reserved_bits_valid() {
             debug!("dropping connection attempt with invalid reserved bits");
+            self.stats.drops.invalid_reserved_bits += 1;
             return None;
         }
 
@@ -531,6 +554,7 @@ where
             || self.is_full()
         {
             debug!("refusing connection");
+            self.stats.refused_connections += 1;
             self.initial_close(
                 remote,
                 crypto,
@@ -556,6 +580,7 @@ where
                 &temp_loc_cid,
                 TransportError::PROTOCOL_VIOLATION("invalid destination CID length"),
             );
+            self.stats.drops.invalid_dst_cid += 1;
             return None;
         }
 
@@ -588,6 +613,7 @@ where
                     ecn: None,
                     contents: buf,
                 });
+                self.stats.retries += 1;
                 return None;
             }
 
@@ -610,6 +636,7 @@ where
                         &temp_loc_cid,
                         TransportError::INVALID_TOKEN(""),
                     );
+                    self.stats.drops.invalid_token += 1;
                     return None;
                 }
             }
@@ -617,6 +644,17 @@ where
             (None, dst_cid)
         };
 
+        let config = match server_config.server_name_resolver {
+            Some(_) => {
+                // Only a ClientHello contained in this packet can be inspected
+                let server_name = inspect::parse_initial_payload(packet.payload.clone().freeze())
+                    .ok()
+                    .and_then(|x| x.server_name);
+                server_config.resolve(server_name.as_deref())
+            }
+            None => server_config.clone(),
+        };
+
         let (ch, mut conn) = self
             .add_connection(
                 dst_cid,
@@ -624,6 +662,7 @@ where
                 remote,
                 local_ip,
                 ConnectionOpts::Server {
+                    config,
                     retry_src_cid,
                     orig_dst_cid,
                 },
@@ -636,10 +675,12 @@ where
         match conn.handle_first_packet(now, remote, ecn, packet_number as u64, packet, rest) {
             Ok(()) => {
                 trace!(id = ch.0, icid = %dst_cid, "connection incoming");
+                self.stats.accepted_connections += 1;
                 Some((ch, conn))
             }
             Err(e) => {
                 debug!("handshake failed: {}", e);
+                self.stats.drops.handshake_failed += 1;
                 self.handle_event(ch, EndpointEvent(EndpointEventInner::Drained));
                 if let ConnectionError::TransportError(e) = e {
                     self.initial_close(remote, crypto, &src_cid, &temp_loc_cid, e);
@@ -687,11 +728,37 @@ where
         self.reject_new_connections = true;
     }
 
+    /// Replace the server configuration, affecting new incoming connections only
+    ///
+    /// Established connections and those already handshaking keep the configuration they were
+    /// accepted with. `None` stops the endpoint from accepting new connections, as if it had been
+    /// constructed without a server configuration. Retry tokens issued before the change are only
+    /// honored if the new configuration shares the old one's token key.
+    pub fn set_server_config(&mut self, server_config: Option<Arc<ServerConfig<S>>>) {
+        self.server_config = server_config;
+    }
+
     /// Access the configuration used by this endpoint
     pub fn config(&self) -> &EndpointConfig<S> {
         &self.config
     }
 
+    /// Returns endpoint statistics
+    pub fn stats(&self) -> EndpointStats {
+        EndpointStats {
+            active_connections: self.connections.len() as u64,
+            ..self.stats
+        }
+    }
+
+    /// Length of the connection IDs issued by this endpoint
+    ///
+    /// Short header packets addressed to this endpoint carry a destination CID of this length,
+    /// which is useful for routing datagrams between several endpoints sharing a socket address.
+    pub fn local_cid_len(&self) -> usize {
+        self.local_cid_generator.cid_len()
+    }
+
     #[cfg(test)]
     pub(crate) fn known_connections(&self) -> usize {
         let x = self.connections.len();
@@ -737,10 +804,96 @@ where
             .field("config", &self.config)
             .field("server_config", &self.server_config)
             .field("reject_new_connections", &self.reject_new_connections)
+            .field("stats", &self.stats)
             .finish()
     }
 }
 
+/// Endpoint statistics
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct EndpointStats {
+    /// Statistics about UDP datagrams passed to `Endpoint::handle`
+    pub udp_rx: UdpStats,
+    /// Statistics about UDP datagrams returned by `Endpoint::poll_transmit`
+    ///
+    /// Only covers stateless packets generated by the endpoint itself, such as version
+    /// negotiation, retry, and stateless reset packets. Datagrams sent by connections are not
+    /// included.
+    pub udp_tx: UdpStats,
+    /// Incoming datagrams which were discarded, by reason
+    pub drops: DropStats,
+    /// The number of version negotiation packets sent
+    pub version_negotiations: u64,
+    /// The number of retry packets sent
+    pub retries: u64,
+    /// The number of stateless reset packets sent
+    pub stateless_resets: u64,
+    /// The number of incoming connections accepted
+    pub accepted_connections: u64,
+    /// The number of incoming connections refused due to connection limits or
+    /// `Endpoint::reject_new_connections`
+    pub refused_connections: u64,
+    /// The number of connections currently associated with the endpoint
+    pub active_connections: u64,
+}
+
+impl std::ops::AddAssign for EndpointStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.udp_rx += rhs.udp_rx;
+        self.udp_tx += rhs.udp_tx;
+        self.drops += rhs.drops;
+        self.version_negotiations += rhs.version_negotiations;
+        self.retries += rhs.retries;
+        self.stateless_resets += rhs.stateless_resets;
+        self.accepted_connections += rhs.accepted_connections;
+        self.refused_connections += rhs.refused_connections;
+        self.active_connections += rhs.active_connections;
+    }
+}
+
+/// Reasons for which an endpoint discarded incoming datagrams
+///
+/// Datagrams answered with a version negotiation, retry, or stateless reset packet, and connection
+/// attempts refused due to connection limits, are not counted here; see the corresponding fields
+/// of [`EndpointStats`].
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct DropStats {
+    /// Packets with a version this client does not support
+    pub unsupported_version: u64,
+    /// Packets whose header could not be decoded
+    pub malformed: u64,
+    /// Packets for an unknown connection which could not be answered with a stateless reset
+    pub unknown_connection: u64,
+    /// Initial packets in datagrams smaller than the minimum initial size
+    pub short_initial: u64,
+    /// Initial packets which failed authentication
+    pub authentication_failed: u64,
+    /// Initial packets with nonzero reserved bits
+    pub invalid_reserved_bits: u64,
+    /// Initial packets with a destination connection ID of invalid length
+    pub invalid_dst_cid: u64,
+    /// Initial packets carrying an invalid or expired retry token
+    pub invalid_token: u64,
+    /// Initial packets whose handshake data was rejected
+    pub handshake_failed: u64,
+}
+
+impl std::ops::AddAssign for DropStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.unsupported_version += rhs.unsupported_version;
+        self.malformed += rhs.malformed;
+        self.unknown_connection += rhs.unknown_connection;
+        self.short_initial += rhs.short_initial;
+        self.authentication_failed += rhs.authentication_failed;
+        self.invalid_reserved_bits += rhs.invalid_reserved_bits;
+        self.invalid_dst_cid += rhs.invalid_dst_cid;
+        self.invalid_token += rhs.invalid_token;
+        self.handshake_failed += rhs.handshake_failed;
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ConnectionMeta {
     init_cid: ConnectionId,
@@ -797,6 +950,7 @@ enum ConnectionOpts<S: crypto::Session> {
         server_name: String,
     },
     Server {
+        config: Arc<ServerConfig<S>>,
         retry_src_cid: Option<ConnectionId>,
         orig_dst_cid: ConnectionId,
     },
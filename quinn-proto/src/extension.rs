@@ -0,0 +1,119 @@
+//! Application-defined frame types
+//!
+//! Experimental QUIC extensions, such as ACK_FREQUENCY or multipath, introduce new frame types.
+//! Rather than requiring changes to this crate, such frames can be prototyped by implementing
+//! [`ExtensionFrame`] and registering it with [`TransportConfig::extension_frame()`]. Frames are
+//! then sent with [`Connection::send_extension_frame()`] and received with
+//! [`Connection::recv_extension_frame()`] once [`Event::ExtensionFrameReceived`] is reported.
+//!
+//! A peer which does not recognize a frame type closes the connection upon receiving it, so
+//! support should be negotiated before sending, e.g. with
+//! [`TransportConfig::custom_transport_parameter()`].
+//!
+//! [`TransportConfig::extension_frame()`]: crate::TransportConfig::extension_frame
+//! [`TransportConfig::custom_transport_parameter()`]: crate::TransportConfig::custom_transport_parameter
+//! [`Connection::send_extension_frame()`]: crate::generic::Connection::send_extension_frame
+//! [`Connection::recv_extension_frame()`]: crate::generic::Connection::recv_extension_frame
+//! [`Event::ExtensionFrameReceived`]: crate::Event::ExtensionFrameReceived
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use bytes::{Buf, BufMut, Bytes};
+
+use crate::{
+    coding::{BufExt, BufMutExt},
+    VarInt,
+};
+
+/// A type of application-defined frame
+///
+/// The default methods describe a reliable, ack-eliciting, congestion controlled frame whose
+/// payload is prefixed by its length.
+pub trait ExtensionFrame: Send + Sync {
+    /// The frame type, which must not be one defined by QUIC itself
+    fn ty(&self) -> VarInt;
+
+    /// Whether receipt of the frame obliges the peer to send an acknowledgement
+    ///
+    /// Packets carrying only frames which are not ack-eliciting, like packets carrying only ACK
+    /// frames, do not count towards bytes in flight.
+    fn ack_eliciting(&self) -> bool {
+        true
+    }
+
+    /// Whether the frame should be sent again if the packet carrying it is lost
+    ///
+    /// A packet deemed lost may still arrive, so retransmittable frames can be delivered more than
+    /// once.
+    fn retransmittable(&self) -> bool {
+        true
+    }
+
+    /// Whether sending the frame is limited by the congestion window and pacing
+    ///
+    /// Packets carrying no other data may be sent regardless of congestion if this is `false`.
+    /// Ack-eliciting packets still count towards bytes in flight.
+    fn congestion_controlled(&self) -> bool {
+        true
+    }
+
+    /// Write the frame body for `payload`, following the frame type
+    fn encode(&self, payload: &[u8], out: &mut Vec<u8>) {
+        out.write_var(payload.len() as u64);
+        out.put_slice(payload);
+    }
+
+    /// Consume the frame body from the start of `buf`, which holds the remainder of the packet
+    ///
+    /// Returns the payload to deliver to the application, or `None` if the frame is malformed.
+    fn decode(&self, buf: &mut Bytes) -> Option<Bytes> {
+        let len = buf.get_var().ok()?;
+        if len > buf.remaining() as u64 {
+            return None;
+        }
+        Some(buf.split_to(len as usize))
+    }
+}
+
+/// Extension frame types registered on a `TransportConfig`, by frame type
+pub(crate) type Registry = HashMap<u64, Arc<dyn ExtensionFrame>>;
+
+/// Whether `ty` is a frame type defined by QUIC itself
+pub(crate) fn is_reserved(ty: u64) -> bool {
+    ty <= 0x1e || ty == 0x30 || ty == 0x31
+}
+
+/// An encoded extension frame awaiting transmission
+#[derive(Clone)]
+pub(crate) struct Outgoing {
+    /// The frame type and body
+    pub(crate) encoded: Bytes,
+    pub(crate) ack_eliciting: bool,
+    pub(crate) retransmittable: bool,
+    pub(crate) congestion_controlled: bool,
+}
+
+impl Outgoing {
+    pub(crate) fn new(frame: &dyn ExtensionFrame, payload: &[u8]) -> Self {
+        let mut encoded = Vec::new();
+        encoded.write(frame.ty());
+        frame.encode(payload, &mut encoded);
+        Self {
+            encoded: encoded.into(),
+            ack_eliciting: frame.ack_eliciting(),
+            retransmittable: frame.retransmittable(),
+            congestion_controlled: frame.congestion_controlled(),
+        }
+    }
+}
+
+impl fmt::Debug for Outgoing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Outgoing")
+            .field("len", &self.encoded.len())
+            .field("ack_eliciting", &self.ack_eliciting)
+            .field("retransmittable", &self.retransmittable)
+            .field("congestion_controlled", &self.congestion_controlled)
+            .finish()
+    }
+}
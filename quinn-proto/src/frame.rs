@@ -1,12 +1,14 @@
 use std::{
     fmt, io, mem,
     ops::{Range, RangeInclusive},
+    sync::Arc,
 };
 
 use bytes::{Buf, BufMut, Bytes};
 
 use crate::{
     coding::{self, BufExt, BufMutExt, UnexpectedEnd},
+    extension,
     range_set::RangeSet,
     shared::{ConnectionId, EcnCodepoint},
     Dir, ResetToken, StreamId, TransportError, TransportErrorCode, VarInt, MAX_CID_SIZE,
@@ -157,6 +159,7 @@ pub enum Frame {
     Datagram(Datagram),
     Invalid { ty: Type, reason: &'static str },
     HandshakeDone,
+    Extension(Extension),
 }
 
 impl Frame {
@@ -197,6 +200,7 @@ impl Frame {
             Datagram(_) => Type(*DATAGRAM_TYS.start()),
             Invalid { ty, .. } => ty,
             HandshakeDone => Type::HANDSHAKE_DONE,
+            Extension(ref x) => Type(x.ty.into_inner()),
         }
     }
 }
@@ -473,6 +477,7 @@ pub struct Iter {
     // TODO: ditch io::Cursor after bytes 0.5
     bytes: io::Cursor<Bytes>,
     last_ty: Option<Type>,
+    extensions: Option<Arc<extension::Registry>>,
 }
 
 enum IterErr {
@@ -503,9 +508,16 @@ impl Iter {
         Iter {
             bytes: io::Cursor::new(payload),
             last_ty: None,
+            extensions: None,
         }
     }
 
+    /// Also decode the application-defined frame types in `registry`
+    pub fn with_extensions(mut self, registry: Arc<extension::Registry>) -> Self {
+        self.extensions = Some(registry);
+        self
+    }
+
     fn take_len(&mut self) -> Result<Bytes, UnexpectedEnd> {
         let len = self.bytes.get_var()?;
         if len > self.bytes.remaining() as u64 {
@@ -659,6 +671,16 @@ impl Iter {
                             self.take_remaining()
                         },
                     })
+                } else if let Some(ext) = self.extensions.as_ref().and_then(|x| x.get(&ty.0)) {
+                    let ext = ext.clone();
+                    let mut rest = self.take_remaining();
+                    let payload = ext.decode(&mut rest).ok_or(IterErr::Malformed)?;
+                    self.bytes = io::Cursor::new(rest);
+                    Frame::Extension(Extension {
+                        ty: VarInt(ty.0),
+                        ack_eliciting: ext.ack_eliciting(),
+                        payload,
+                    })
                 } else {
                     return Err(IterErr::InvalidFrameId);
                 }
@@ -735,6 +757,14 @@ impl<'a> Iterator for AckIter<'a> {
     }
 }
 
+/// An application-defined frame
+#[derive(Debug, Clone)]
+pub struct Extension {
+    pub ty: VarInt,
+    pub ack_eliciting: bool,
+    pub payload: Bytes,
+}
+
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
 #[derive(Debug, Copy, Clone)]
 pub struct ResetStream {
@@ -0,0 +1,523 @@
+//! Stateless inspection of QUIC packets
+//!
+//! These functions allow middleboxes such as load balancers, firewalls and monitoring tools to
+//! examine QUIC traffic without participating in a connection. Only the parts of a packet that are
+//! visible to an on-path observer can be recovered, plus the contents of Initial packets, whose
+//! keys are derived from the client's initial destination connection ID.
+
+use std::{convert::TryInto, io, str};
+
+use bytes::{Buf, Bytes, BytesMut};
+use thiserror::Error;
+
+use crate::{
+    coding::{self, BufExt},
+    crypto::{self, HmacKey, PacketKey},
+    frame::{self, Frame},
+    packet::{LongHeaderType, LongType, PacketDecodeError, PartialDecode, LONG_HEADER_FORM},
+    token::ResetToken,
+    ConnectionId, Side, RESET_TOKEN_SIZE, VERSION,
+};
+
+/// The visible header of a QUIC packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// Type of the packet
+    pub ty: PacketType,
+    /// QUIC version of a long header packet, or 0 for Version Negotiation
+    pub version: Option<u32>,
+    /// Destination connection ID
+    pub dst_cid: ConnectionId,
+    /// Source connection ID of a long header packet
+    pub src_cid: Option<ConnectionId>,
+    /// Address validation token carried by Initial and Retry packets
+    pub token: Option<Bytes>,
+    /// Versions listed by a Version Negotiation packet
+    pub supported_versions: Vec<u32>,
+    /// Length of the packet, which may be followed by further packets in the same datagram
+    pub len: usize,
+}
+
+/// Type of a QUIC packet
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PacketType {
+    /// Carries the first messages of the cryptographic handshake
+    Initial,
+    /// Carries application data sent before the handshake completes
+    ZeroRtt,
+    /// Carries the remainder of the cryptographic handshake
+    Handshake,
+    /// Requests that the client prove it can receive packets at its address
+    Retry,
+    /// Lists the versions supported by a server which didn't recognize the client's version
+    VersionNegotiation,
+    /// Carries data after the handshake; only the destination connection ID is visible
+    Short,
+    /// Long header packet of an unsupported version; only the version and connection IDs are known
+    Unknown,
+}
+
+/// Information about the client gathered from its TLS ClientHello
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientHello {
+    /// Host name the client is connecting to, from the server name indication extension
+    pub server_name: Option<String>,
+    /// Application protocols offered by the client, in order of preference
+    pub alpn_protocols: Vec<Vec<u8>>,
+}
+
+/// Reasons a packet could not be inspected
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum InspectError {
+    /// The packet is not well-formed
+    #[error("malformed packet: {0}")]
+    Malformed(&'static str),
+    /// The packet's version is not supported, so its contents can't be interpreted
+    #[error("unsupported version {0:x}")]
+    UnsupportedVersion(u32),
+    /// The packet is not of the type required by the operation
+    #[error("unexpected packet type")]
+    UnexpectedType,
+    /// The packet could not be authenticated with the keys derived from the given connection ID
+    #[error("decryption failed")]
+    DecryptionFailed,
+    /// The ClientHello does not fit in a single Initial packet
+    #[error("incomplete ClientHello")]
+    IncompleteClientHello,
+}
+
+impl From<coding::UnexpectedEnd> for InspectError {
+    fn from(_: coding::UnexpectedEnd) -> Self {
+        InspectError::Malformed("unexpected end of packet")
+    }
+}
+
+impl From<PacketDecodeError> for InspectError {
+    fn from(x: PacketDecodeError) -> Self {
+        match x {
+            PacketDecodeError::UnsupportedVersion { version, .. } => {
+                InspectError::UnsupportedVersion(version)
+            }
+            PacketDecodeError::InvalidHeader(reason) => InspectError::Malformed(reason),
+        }
+    }
+}
+
+/// Parse the header of the first packet in `datagram`
+///
+/// Short headers don't encode the length of their destination connection ID, so it must be
+/// supplied as `local_cid_len`. Packet numbers are protected and are not decoded.
+pub fn parse(datagram: &[u8], local_cid_len: usize) -> Result<Header, InspectError> {
+    let mut buf = io::Cursor::new(datagram);
+    let first = buf.get::<u8>()?;
+    if first & LONG_HEADER_FORM == 0 {
+        let dst_cid = datagram
+            .get(1..1 + local_cid_len)
+            .ok_or(InspectError::Malformed("cid out of bounds"))?;
+        return Ok(Header {
+            ty: PacketType::Short,
+            version: None,
+            dst_cid: ConnectionId::new(dst_cid),
+            src_cid: None,
+            token: None,
+            supported_versions: Vec::new(),
+            len: datagram.len(),
+        });
+    }
+
+    let version = buf.get::<u32>()?;
+    let dst_cid =
+        ConnectionId::decode_long(&mut buf).ok_or(InspectError::Malformed("malformed cid"))?;
+    let src_cid =
+        ConnectionId::decode_long(&mut buf).ok_or(InspectError::Malformed("malformed cid"))?;
+    let mut header = Header {
+        ty: PacketType::Unknown,
+        version: Some(version),
+        dst_cid,
+        src_cid: Some(src_cid),
+        token: None,
+        supported_versions: Vec::new(),
+        len: datagram.len(),
+    };
+
+    if version == 0 {
+        header.ty = PacketType::VersionNegotiation;
+        if buf.remaining() % 4 != 0 {
+            return Err(InspectError::Malformed("truncated version list"));
+        }
+        while buf.has_remaining() {
+            header.supported_versions.push(buf.get::<u32>()?);
+        }
+        return Ok(header);
+    }
+    if !VERSION.contains(&version) {
+        return Ok(header);
+    }
+
+    let payload_len = match LongHeaderType::from_byte(first)? {
+        LongHeaderType::Initial => {
+            header.ty = PacketType::Initial;
+            let token_len = buf.get_var()? as usize;
+            header.token = Some(take(&mut buf, token_len)?);
+            buf.get_var()?
+        }
+        LongHeaderType::Retry => {
+            header.ty = PacketType::Retry;
+            // The token occupies everything but the integrity tag
+            let token_len = buf
+                .remaining()
+                .checked_sub(16)
+                .ok_or(InspectError::Malformed("missing retry integrity tag"))?;
+            header.token = Some(take(&mut buf, token_len)?);
+            return Ok(header);
+        }
+        LongHeaderType::Standard(ty) => {
+            header.ty = match ty {
+                LongType::Handshake => PacketType::Handshake,
+                LongType::ZeroRtt => PacketType::ZeroRtt,
+            };
+            buf.get_var()?
+        }
+    };
+    header.len = (buf.position() + payload_len)
+        .try_into()
+        .ok()
+        .filter(|&x| x <= datagram.len())
+        .ok_or(InspectError::Malformed(
+            "packet too short to contain payload length",
+        ))?;
+    Ok(header)
+}
+
+/// Check the integrity tag of a Retry packet
+///
+/// `orig_dst_cid` is the destination connection ID of the client's Initial packet that prompted
+/// the Retry.
+pub fn is_valid_retry<S>(packet: &[u8], orig_dst_cid: &ConnectionId) -> bool
+where
+    S: crypto::Session,
+{
+    let header = match parse(packet, 0) {
+        Ok(x) if x.ty == PacketType::Retry => x,
+        _ => return false,
+    };
+    // First byte, version, and length-prefixed connection IDs
+    let header_len = 1 + 4 + 1 + header.dst_cid.len() + 1 + header.src_cid.unwrap().len();
+    S::is_valid_retry(orig_dst_cid, &packet[..header_len], &packet[header_len..])
+}
+
+/// Determine whether `datagram` is a stateless reset sent by an endpoint using `reset_key`
+///
+/// `cid` is the connection ID the endpoint issued for the connection being reset, i.e. the
+/// destination connection ID of packets sent *to* that endpoint. `reset_key` corresponds to
+/// [`EndpointConfig::new`](crate::generic::EndpointConfig::new)'s argument.
+pub fn is_stateless_reset<K>(datagram: &[u8], reset_key: &K, cid: &ConnectionId) -> bool
+where
+    K: HmacKey,
+{
+    // Resets must be indistinguishable from a short header packet with a minimal payload
+    const MIN_RESET_LEN: usize = 5 + RESET_TOKEN_SIZE;
+    if datagram.len() < MIN_RESET_LEN || datagram[0] & LONG_HEADER_FORM != 0 {
+        return false;
+    }
+    let token: [u8; RESET_TOKEN_SIZE] = datagram[datagram.len() - RESET_TOKEN_SIZE..]
+        .try_into()
+        .unwrap();
+    ResetToken::from(token) == ResetToken::new(reset_key, cid)
+}
+
+/// Decrypt a client's Initial packet and extract information from its TLS ClientHello
+///
+/// `client_dst_cid` is the destination connection ID of the client's first Initial packet, or
+/// of its Initial packets following a Retry, from which the Initial keys are derived. Only
+/// ClientHellos contained in a single Initial packet are supported.
+pub fn client_hello<S>(
+    packet: &[u8],
+    client_dst_cid: &ConnectionId,
+) -> Result<ClientHello, InspectError>
+where
+    S: crypto::Session,
+{
+    let (decode, _) = PartialDecode::new(BytesMut::from(packet), 0)?;
+    if !decode.is_initial() {
+        return Err(InspectError::UnexpectedType);
+    }
+    let keys = S::initial_keys(client_dst_cid, Side::Server);
+    let mut packet = decode.finish(Some(&keys.header.remote))?;
+    let number = packet.header.number().unwrap().expand(0);
+    keys.packet
+        .remote
+        .decrypt(number, &packet.header_data, &mut packet.payload)
+        .map_err(|_| InspectError::DecryptionFailed)?;
+    parse_initial_payload(packet.payload.freeze())
+}
+
+/// Extract information from the TLS ClientHello carried by the decrypted `payload` of an Initial
+/// packet
+pub(crate) fn parse_initial_payload(payload: Bytes) -> Result<ClientHello, InspectError> {
+    let mut crypto = frame::Iter::new(payload)
+        .filter_map(|frame| match frame {
+            Frame::Crypto(x) => Some(x),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    crypto.sort_by_key(|x| x.offset);
+    let mut data = Vec::new();
+    for frame in crypto {
+        // Skip data which has already been assembled, and stop at the first gap
+        let start = match (data.len() as u64).checked_sub(frame.offset) {
+            Some(x) => x as usize,
+            None => break,
+        };
+        if start < frame.data.len() {
+            data.extend_from_slice(&frame.data[start..]);
+        }
+    }
+    parse_client_hello(&data)
+}
+
+fn parse_client_hello(data: &[u8]) -> Result<ClientHello, InspectError> {
+    /// TLS handshake message type of a ClientHello
+    const CLIENT_HELLO: u8 = 1;
+    const SERVER_NAME: u16 = 0;
+    const ALPN: u16 = 16;
+    const HOST_NAME: u8 = 0;
+
+    let mut buf = io::Cursor::new(data);
+    let ty = buf
+        .get::<u8>()
+        .map_err(|_| InspectError::IncompleteClientHello)?;
+    if ty != CLIENT_HELLO {
+        return Err(InspectError::Malformed("not a ClientHello"));
+    }
+    let mut len = [0; 4];
+    if buf.remaining() < 3 {
+        return Err(InspectError::IncompleteClientHello);
+    }
+    buf.copy_to_slice(&mut len[1..]);
+    let len = u32::from_be_bytes(len) as usize;
+    if buf.remaining() < len {
+        return Err(InspectError::IncompleteClientHello);
+    }
+    let mut buf = io::Cursor::new(take(&mut buf, len)?);
+
+    // Legacy version and random
+    take(&mut buf, 2 + 32)?;
+    let session_id_len = buf.get::<u8>()? as usize;
+    take(&mut buf, session_id_len)?;
+    let cipher_suites_len = buf.get::<u16>()? as usize;
+    take(&mut buf, cipher_suites_len)?;
+    let compression_methods_len = buf.get::<u8>()? as usize;
+    take(&mut buf, compression_methods_len)?;
+
+    let mut hello = ClientHello {
+        server_name: None,
+        alpn_protocols: Vec::new(),
+    };
+    if !buf.has_remaining() {
+        return Ok(hello);
+    }
+    let extensions_len = buf.get::<u16>()? as usize;
+    let mut extensions = io::Cursor::new(take(&mut buf, extensions_len)?);
+    while extensions.has_remaining() {
+        let ty = extensions.get::<u16>()?;
+        let len = extensions.get::<u16>()? as usize;
+        let mut ext = io::Cursor::new(take(&mut extensions, len)?);
+        match ty {
+            SERVER_NAME => {
+                let list_len = ext.get::<u16>()? as usize;
+                let mut list = io::Cursor::new(take(&mut ext, list_len)?);
+                while list.has_remaining() {
+                    let name_type = list.get::<u8>()?;
+                    let name_len = list.get::<u16>()? as usize;
+                    let name = take(&mut list, name_len)?;
+                    if name_type == HOST_NAME {
+                        hello.server_name = Some(
+                            str::from_utf8(&name)
+                                .map_err(|_| InspectError::Malformed("invalid server name"))?
+                                .into(),
+                        );
+                    }
+                }
+            }
+            ALPN => {
+                let list_len = ext.get::<u16>()? as usize;
+                let mut list = io::Cursor::new(take(&mut ext, list_len)?);
+                while list.has_remaining() {
+                    let proto_len = list.get::<u8>()? as usize;
+                    hello
+                        .alpn_protocols
+                        .push(take(&mut list, proto_len)?.to_vec());
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(hello)
+}
+
+/// Read `len` bytes from `buf`
+fn take<B: AsRef<[u8]>>(buf: &mut io::Cursor<B>, len: usize) -> Result<Bytes, InspectError> {
+    if buf.remaining() < len {
+        return Err(coding::UnexpectedEnd.into());
+    }
+    let start = buf.position() as usize;
+    buf.advance(len);
+    Ok(Bytes::copy_from_slice(
+        &buf.get_ref().as_ref()[start..start + len],
+    ))
+}
+
+#[cfg(all(test, feature = "rustls"))]
+mod tests {
+    use std::{net::SocketAddr, sync::Arc, time::Instant};
+
+    use rand::RngCore;
+    use ring::hmac;
+
+    use super::*;
+    use crate::{
+        crypto::rustls::TlsSession, Certificate, CertificateChain, ClientConfig, Endpoint,
+        EndpointConfig, PrivateKey, ServerConfig,
+    };
+
+    fn client_initial() -> Vec<u8> {
+        let mut config = ClientConfig::default();
+        Arc::make_mut(&mut config.crypto).alpn_protocols = vec![b"h3-29".to_vec(), b"hq".to_vec()];
+        let mut endpoint = Endpoint::new(Default::default(), None);
+        let (_, mut conn) = endpoint
+            .connect(config, "[::1]:4433".parse().unwrap(), "example.com")
+            .unwrap();
+        conn.poll_transmit(Instant::now()).unwrap().contents
+    }
+
+    fn server(config: EndpointConfig) -> Endpoint {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let key = PrivateKey::from_der(&cert.serialize_private_key_der()).unwrap();
+        let cert = Certificate::from_der(&cert.serialize_der().unwrap()).unwrap();
+        let mut server_config = ServerConfig::default();
+        server_config
+            .certificate(CertificateChain::from_certs(vec![cert]), key)
+            .unwrap();
+        server_config.use_stateless_retry(true);
+        Endpoint::new(Arc::new(config), Some(Arc::new(server_config)))
+    }
+
+    fn client_addr() -> SocketAddr {
+        "[::2]:7890".parse().unwrap()
+    }
+
+    #[test]
+    fn initial() {
+        let datagram = client_initial();
+        let header = parse(&datagram, 0).unwrap();
+        assert_eq!(header.ty, PacketType::Initial);
+        assert!(VERSION.contains(&header.version.unwrap()));
+        assert_eq!(header.token, Some(Bytes::new()));
+        assert_eq!(header.len, datagram.len());
+
+        let hello = client_hello::<TlsSession>(&datagram, &header.dst_cid).unwrap();
+        assert_eq!(hello.server_name.as_deref(), Some("example.com"));
+        assert_eq!(
+            hello.alpn_protocols,
+            vec![b"h3-29".to_vec(), b"hq".to_vec()]
+        );
+
+        let wrong_cid = ConnectionId::new(&[0xab; 8]);
+        assert_eq!(
+            client_hello::<TlsSession>(&datagram, &wrong_cid),
+            Err(InspectError::DecryptionFailed)
+        );
+    }
+
+    #[test]
+    fn retry() {
+        let datagram = client_initial();
+        let orig_dst_cid = parse(&datagram, 0).unwrap().dst_cid;
+        let mut server = server(EndpointConfig::default());
+        server.handle(
+            Instant::now(),
+            client_addr(),
+            None,
+            None,
+            datagram.as_slice().into(),
+        );
+        let retry = server.poll_transmit().unwrap().contents;
+
+        let header = parse(&retry, 0).unwrap();
+        assert_eq!(header.ty, PacketType::Retry);
+        assert!(!header.token.unwrap().is_empty());
+        assert!(is_valid_retry::<TlsSession>(&retry, &orig_dst_cid));
+        assert!(!is_valid_retry::<TlsSession>(
+            &retry,
+            &ConnectionId::new(&[0xab; 8])
+        ));
+        assert!(!is_valid_retry::<TlsSession>(&datagram, &orig_dst_cid));
+    }
+
+    #[test]
+    fn version_negotiation() {
+        let mut datagram = client_initial();
+        datagram[1..5].copy_from_slice(&0x0a1a_2a3au32.to_be_bytes());
+        let header = parse(&datagram, 0).unwrap();
+        assert_eq!(header.ty, PacketType::Unknown);
+        assert_eq!(header.version, Some(0x0a1a_2a3a));
+
+        let mut server = server(EndpointConfig::default());
+        server.handle(
+            Instant::now(),
+            client_addr(),
+            None,
+            None,
+            datagram.as_slice().into(),
+        );
+        let response = server.poll_transmit().unwrap().contents;
+        let vn = parse(&response, 0).unwrap();
+        assert_eq!(vn.ty, PacketType::VersionNegotiation);
+        assert_eq!(vn.version, Some(0));
+        assert_eq!(vn.dst_cid, header.src_cid.unwrap());
+        assert_eq!(Some(vn.src_cid.unwrap()), Some(header.dst_cid));
+        assert!(vn.supported_versions.iter().any(|x| VERSION.contains(x)));
+    }
+
+    #[test]
+    fn stateless_reset() {
+        let mut key_bytes = [0; 64];
+        rand::thread_rng().fill_bytes(&mut key_bytes);
+        let reset_key = hmac::Key::new(hmac::HMAC_SHA256, &key_bytes);
+        let mut server = server(EndpointConfig::new(reset_key.clone()));
+
+        // A short header packet for a connection the server doesn't know about
+        let cid = ConnectionId::new(&[0x42; 8]);
+        let mut datagram = vec![0x40];
+        datagram.extend_from_slice(&cid);
+        datagram.resize(100, 0);
+        let header = parse(&datagram, cid.len()).unwrap();
+        assert_eq!(header.ty, PacketType::Short);
+        assert_eq!(header.dst_cid, cid);
+
+        server.handle(
+            Instant::now(),
+            client_addr(),
+            None,
+            None,
+            datagram.as_slice().into(),
+        );
+        let reset = server.poll_transmit().unwrap().contents;
+        assert!(is_stateless_reset(&reset, &reset_key, &cid));
+        assert!(!is_stateless_reset(
+            &reset,
+            &reset_key,
+            &ConnectionId::new(&[0x43; 8])
+        ));
+        assert!(!is_stateless_reset(&datagram, &reset_key, &cid));
+    }
+
+    #[test]
+    fn truncated() {
+        let datagram = client_initial();
+        assert!(parse(&datagram[..20], 0).is_err());
+        assert!(parse(&[], 0).is_err());
+        assert!(parse(&[0x40, 1, 2], 8).is_err());
+    }
+}
@@ -34,11 +34,13 @@ mod varint;
 pub use varint::{VarInt, VarIntBoundsExceeded};
 
 mod connection;
-pub use crate::connection::{ConnectionError, ConnectionStats, Event, SendDatagramError};
+pub use crate::connection::{
+    ConnectionError, ConnectionStats, Event, SendDatagramError, SendExtensionFrameError, UdpStats,
+};
 pub use crate::connection::{FinishError, ReadError, StreamEvent, UnknownStream, WriteError};
 
 mod config;
-pub use config::{ConfigError, TransportConfig};
+pub use config::{ConfigError, ServerNameResolver, TransportConfig};
 
 pub mod crypto;
 #[cfg(feature = "rustls")]
@@ -49,7 +51,9 @@ use crate::frame::Frame;
 pub use crate::frame::{ApplicationClose, ConnectionClose, Datagram};
 
 mod endpoint;
-pub use crate::endpoint::{ConnectError, ConnectionHandle, DatagramEvent};
+pub use crate::endpoint::{
+    ConnectError, ConnectionHandle, DatagramEvent, DropStats, EndpointStats,
+};
 
 mod shared;
 pub use crate::shared::{ConnectionEvent, ConnectionId, EcnCodepoint, EndpointEvent};
@@ -59,8 +63,16 @@ pub use crate::transport_error::{Code as TransportErrorCode, Error as TransportE
 
 pub mod congestion;
 
+pub mod extension;
+
+pub mod inspect;
+
+pub mod pcap;
+
 mod cid_generator;
-pub use crate::cid_generator::{ConnectionIdGenerator, RandomConnectionIdGenerator};
+pub use crate::cid_generator::{
+    ConnectionIdGenerator, RandomConnectionIdGenerator, ShardedConnectionIdGenerator,
+};
 
 mod token;
 use token::{ResetToken, RetryToken};
@@ -68,7 +80,7 @@ use token::{ResetToken, RetryToken};
 /// Types that are generic over the crypto protocol implementation
 pub mod generic {
     pub use crate::{
-        config::{ClientConfig, EndpointConfig, ServerConfig},
+        config::{ClientConfig, EndpointConfig, ServerConfig, ServerNameConfig, ServerNameStore},
         connection::Connection,
         endpoint::Endpoint,
     };
@@ -88,6 +100,10 @@ mod rustls_impls {
     pub type ServerConfig = generic::ServerConfig<crypto::rustls::TlsSession>;
     /// A `EndpointConfig` using rustls keys
     pub type EndpointConfig = generic::EndpointConfig<crypto::rustls::TlsSession>;
+    /// A `ServerNameConfig` containing server-side rustls configuration
+    pub type ServerNameConfig = generic::ServerNameConfig<crypto::rustls::TlsSession>;
+    /// A `ServerNameStore` containing server-side rustls configuration
+    pub type ServerNameStore = generic::ServerNameStore<crypto::rustls::TlsSession>;
 }
 
 #[cfg(feature = "rustls")]
@@ -675,7 +675,7 @@ pub(crate) enum LongHeaderType {
 }
 
 impl LongHeaderType {
-    fn from_byte(b: u8) -> Result<Self, PacketDecodeError> {
+    pub(crate) fn from_byte(b: u8) -> Result<Self, PacketDecodeError> {
         use self::{LongHeaderType::*, LongType::*};
         if b & FIXED_BIT == 0 {
             return Err(PacketDecodeError::InvalidHeader("fixed bit unset"));
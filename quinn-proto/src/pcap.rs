@@ -0,0 +1,480 @@
+//! Recording of the datagrams exchanged by an endpoint
+//!
+//! A [`PacketCapture`] installed with [`EndpointConfig::packet_capture()`] observes every datagram
+//! passed to `Endpoint::handle` and every datagram returned by the `poll_transmit` methods of the
+//! endpoint and its connections. [`PcapNgWriter`] records them in the pcapng format understood by
+//! Wireshark and similar tools.
+//!
+//! [`EndpointConfig::packet_capture()`]: crate::generic::EndpointConfig::packet_capture
+
+use std::{
+    fmt,
+    io::{self, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tracing::warn;
+
+use crate::{crypto, EcnCodepoint, Transmit};
+
+/// Observer of the datagrams exchanged by an endpoint and its connections
+pub trait PacketCapture: Send + Sync {
+    /// Called with each datagram passed to `Endpoint::handle`, before it is processed
+    ///
+    /// `local_ip` is the address the datagram was sent to, if known.
+    fn received(
+        &self,
+        remote: SocketAddr,
+        local_ip: Option<IpAddr>,
+        ecn: Option<EcnCodepoint>,
+        contents: &[u8],
+    );
+
+    /// Called with each datagram returned by `Endpoint::poll_transmit` or
+    /// `Connection::poll_transmit`
+    ///
+    /// `local_ip` is the address the datagram should be sent from, if known.
+    fn sent(&self, local_ip: Option<IpAddr>, transmit: &Transmit);
+}
+
+/// A [`PacketCapture`] that writes a pcapng file, including the TLS secrets needed to decrypt it
+///
+/// Each datagram is recorded on a raw IP interface with synthesized IP and UDP headers. Since the
+/// local port is not known to quinn-proto, it should be supplied with
+/// [`set_local_addr()`](Self::set_local_addr) once the socket is bound.
+///
+/// The writer also implements [`crypto::KeyLog`], and `rustls::KeyLog` when the rustls feature is
+/// enabled. Passing it to `capture_secrets()` on the crypto configuration, or installing it as the
+/// `key_log` of a TLS configuration, embeds each secret in a Decryption Secrets Block as soon as
+/// it is derived, so that Wireshark can decrypt the capture without a separate key log file.
+/// Secrets may instead be supplied in the NSS key log format with
+/// [`write_secrets()`](Self::write_secrets).
+///
+/// Errors encountered while recording datagrams or secrets are logged and otherwise ignored.
+pub struct PcapNgWriter {
+    inner: Mutex<Inner>,
+}
+
+impl PcapNgWriter {
+    /// Start a capture, writing the section and interface headers to `out`
+    ///
+    /// `out` is written to once per datagram, so should be buffered if the capture is
+    /// expected to be large.
+    pub fn new<W: Write + Send + 'static>(out: W) -> io::Result<Self> {
+        let mut inner = Inner {
+            out: Box::new(out),
+            local: None,
+            buf: Vec::new(),
+        };
+        block(&mut inner.buf, SECTION_HEADER_BLOCK, |buf| {
+            put_u32(buf, BYTE_ORDER_MAGIC);
+            put_u16(buf, 1); // Major version
+            put_u16(buf, 0); // Minor version
+            buf.extend_from_slice(&(-1i64).to_le_bytes()); // Section length not specified
+        });
+        block(&mut inner.buf, INTERFACE_DESCRIPTION_BLOCK, |buf| {
+            put_u16(buf, LINKTYPE_RAW);
+            put_u16(buf, 0); // Reserved
+            put_u32(buf, 0); // No snapshot length limit
+        });
+        inner.flush_buf()?;
+        Ok(Self {
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Set the address of the socket the captured datagrams are exchanged on
+    ///
+    /// Until this is called, the local endpoint is recorded with an unspecified address and port
+    /// zero. The IP address of individual datagrams is taken from their `local_ip` when known.
+    pub fn set_local_addr(&self, addr: SocketAddr) {
+        self.inner.lock().unwrap().local = Some(addr);
+    }
+
+    /// Embed TLS secrets in the NSS key log format in a Decryption Secrets Block
+    pub fn write_secrets(&self, key_log: &[u8]) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        block(&mut inner.buf, DECRYPTION_SECRETS_BLOCK, |buf| {
+            put_u32(buf, TLS_KEY_LOG);
+            put_u32(buf, key_log.len() as u32);
+            buf.extend_from_slice(key_log);
+        });
+        inner.flush_buf()
+    }
+
+    /// Flush any buffered output to the underlying writer
+    pub fn flush(&self) -> io::Result<()> {
+        self.inner.lock().unwrap().out.flush()
+    }
+
+    fn record(
+        &self,
+        direction: Direction,
+        local_ip: Option<IpAddr>,
+        remote: SocketAddr,
+        ecn: Option<EcnCodepoint>,
+        contents: &[u8],
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        let local = local_addr(inner.local, local_ip, remote);
+        let (src, dst) = match direction {
+            Direction::Inbound => (remote, local),
+            Direction::Outbound => (local, remote),
+        };
+        let result = inner.write_packet(direction, src, dst, ecn.map_or(0, |x| x as u8), contents);
+        if let Err(e) = result {
+            warn!("failed to capture datagram: {}", e);
+        }
+    }
+}
+
+impl PacketCapture for PcapNgWriter {
+    fn received(
+        &self,
+        remote: SocketAddr,
+        local_ip: Option<IpAddr>,
+        ecn: Option<EcnCodepoint>,
+        contents: &[u8],
+    ) {
+        self.record(Direction::Inbound, local_ip, remote, ecn, contents);
+    }
+
+    fn sent(&self, local_ip: Option<IpAddr>, transmit: &Transmit) {
+        self.record(
+            Direction::Outbound,
+            local_ip,
+            transmit.destination,
+            transmit.ecn,
+            &transmit.contents,
+        );
+    }
+}
+
+impl crypto::KeyLog for PcapNgWriter {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        use std::fmt::Write;
+        let mut line =
+            String::with_capacity(label.len() + 2 * (client_random.len() + secret.len()) + 3);
+        line.push_str(label);
+        line.push(' ');
+        for byte in client_random {
+            write!(line, "{:02x}", byte).unwrap();
+        }
+        line.push(' ');
+        for byte in secret {
+            write!(line, "{:02x}", byte).unwrap();
+        }
+        line.push('\n');
+        if let Err(e) = self.write_secrets(line.as_bytes()) {
+            warn!("failed to capture TLS secret: {}", e);
+        }
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl rustls::KeyLog for PcapNgWriter {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        crypto::KeyLog::log(self, label, client_random, secret);
+    }
+}
+
+impl fmt::Debug for PcapNgWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapNgWriter").finish()
+    }
+}
+
+struct Inner {
+    out: Box<dyn Write + Send>,
+    local: Option<SocketAddr>,
+    /// Scratch space for assembling blocks, so that each is written with a single call
+    buf: Vec<u8>,
+}
+
+impl Inner {
+    fn write_packet(
+        &mut self,
+        direction: Direction,
+        src: SocketAddr,
+        dst: SocketAddr,
+        ecn: u8,
+        contents: &[u8],
+    ) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let micros = timestamp.as_secs() * 1_000_000 + u64::from(timestamp.subsec_micros());
+        let mut packet = Vec::with_capacity(IPV6_HEADER_LEN + UDP_HEADER_LEN + contents.len());
+        ip_udp(&mut packet, src, dst, ecn, contents)?;
+        block(&mut self.buf, ENHANCED_PACKET_BLOCK, |buf| {
+            put_u32(buf, 0); // Interface ID
+            put_u32(buf, (micros >> 32) as u32);
+            put_u32(buf, micros as u32);
+            put_u32(buf, packet.len() as u32); // Captured length
+            put_u32(buf, packet.len() as u32); // Original length
+            buf.extend_from_slice(&packet);
+            pad(buf);
+            put_u16(buf, EPB_FLAGS);
+            put_u16(buf, 4);
+            put_u32(buf, direction as u32);
+            put_u16(buf, OPT_ENDOFOPT);
+            put_u16(buf, 0);
+        });
+        self.flush_buf()
+    }
+
+    fn flush_buf(&mut self) -> io::Result<()> {
+        let result = self.out.write_all(&self.buf);
+        self.buf.clear();
+        result
+    }
+}
+
+/// Value of the `epb_flags` option indicating the direction of a packet
+#[derive(Debug, Copy, Clone)]
+enum Direction {
+    Inbound = 1,
+    Outbound = 2,
+}
+
+/// Determine the address to record for the local side of a datagram exchanged with `remote`
+fn local_addr(
+    configured: Option<SocketAddr>,
+    ip: Option<IpAddr>,
+    remote: SocketAddr,
+) -> SocketAddr {
+    let port = configured.map_or(0, |x| x.port());
+    let ip = ip
+        .or_else(|| configured.map(|x| x.ip()))
+        .filter(|x| !x.is_unspecified())
+        .unwrap_or_else(|| match remote.ip() {
+            IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        });
+    SocketAddr::new(ip, port)
+}
+
+/// Append an IP packet carrying `payload` in a UDP datagram from `src` to `dst`
+///
+/// IPv4-mapped IPv6 addresses are recorded as IPv4 when both addresses can be, and IPv4
+/// addresses are otherwise mapped into IPv6.
+fn ip_udp(
+    buf: &mut Vec<u8>,
+    src: SocketAddr,
+    dst: SocketAddr,
+    ecn: u8,
+    payload: &[u8],
+) -> io::Result<()> {
+    let udp_len = UDP_HEADER_LEN + payload.len();
+    if udp_len + IPV6_HEADER_LEN > usize::from(u16::max_value()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "datagram too large to capture",
+        ));
+    }
+    let mut pseudo_header = Vec::with_capacity(36);
+    match (to_ipv4(src.ip()), to_ipv4(dst.ip())) {
+        (Some(src_ip), Some(dst_ip)) => {
+            let start = buf.len();
+            buf.push(0x45); // Version 4, 5 word header
+            buf.push(ecn);
+            put_u16_be(buf, (IPV4_HEADER_LEN + udp_len) as u16);
+            put_u16_be(buf, 0); // Identification
+            put_u16_be(buf, 0x4000); // Don't fragment
+            buf.push(64); // TTL
+            buf.push(UDP_PROTOCOL);
+            put_u16_be(buf, 0); // Checksum, filled in below
+            buf.extend_from_slice(&src_ip.octets());
+            buf.extend_from_slice(&dst_ip.octets());
+            let checksum = fold(sum(&buf[start..], 0));
+            buf[start + 10..start + 12].copy_from_slice(&checksum.to_be_bytes());
+
+            pseudo_header.extend_from_slice(&src_ip.octets());
+            pseudo_header.extend_from_slice(&dst_ip.octets());
+            pseudo_header.extend_from_slice(&[0, UDP_PROTOCOL]);
+            put_u16_be(&mut pseudo_header, udp_len as u16);
+        }
+        _ => {
+            let src_ip = to_ipv6(src.ip());
+            let dst_ip = to_ipv6(dst.ip());
+            buf.extend_from_slice(&(6u32 << 28 | u32::from(ecn) << 20).to_be_bytes());
+            put_u16_be(buf, udp_len as u16);
+            buf.push(UDP_PROTOCOL);
+            buf.push(64); // Hop limit
+            buf.extend_from_slice(&src_ip.octets());
+            buf.extend_from_slice(&dst_ip.octets());
+
+            pseudo_header.extend_from_slice(&src_ip.octets());
+            pseudo_header.extend_from_slice(&dst_ip.octets());
+            pseudo_header.extend_from_slice(&(udp_len as u32).to_be_bytes());
+            pseudo_header.extend_from_slice(&[0, 0, 0, UDP_PROTOCOL]);
+        }
+    }
+
+    let start = buf.len();
+    put_u16_be(buf, src.port());
+    put_u16_be(buf, dst.port());
+    put_u16_be(buf, udp_len as u16);
+    put_u16_be(buf, 0); // Checksum, filled in below
+    buf.extend_from_slice(payload);
+    let checksum = match fold(sum(&buf[start..], sum(&pseudo_header, 0))) {
+        // Zero indicates the absence of a checksum
+        0 => 0xffff,
+        x => x,
+    };
+    buf[start + 6..start + 8].copy_from_slice(&checksum.to_be_bytes());
+    Ok(())
+}
+
+fn to_ipv4(ip: IpAddr) -> Option<Ipv4Addr> {
+    match ip {
+        IpAddr::V4(x) => Some(x),
+        IpAddr::V6(x) => match x.segments() {
+            [0, 0, 0, 0, 0, 0xffff, ..] => {
+                let octets = x.octets();
+                Some(Ipv4Addr::new(
+                    octets[12], octets[13], octets[14], octets[15],
+                ))
+            }
+            _ => None,
+        },
+    }
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(x) => x.to_ipv6_mapped(),
+        IpAddr::V6(x) => x,
+    }
+}
+
+/// Accumulate the ones' complement sum of `data` as a sequence of big-endian 16-bit words
+fn sum(data: &[u8], mut acc: u64) -> u64 {
+    for chunk in data.chunks(2) {
+        let word = match *chunk {
+            [a, b] => u16::from_be_bytes([a, b]),
+            [a] => u16::from_be_bytes([a, 0]),
+            _ => unreachable!(),
+        };
+        acc += u64::from(word);
+    }
+    acc
+}
+
+/// Compute an internet checksum from an accumulated sum
+fn fold(mut acc: u64) -> u16 {
+    while acc >> 16 != 0 {
+        acc = (acc & 0xffff) + (acc >> 16);
+    }
+    !(acc as u16)
+}
+
+/// Append a pcapng block of type `ty` whose body is written by `body`
+fn block(buf: &mut Vec<u8>, ty: u32, body: impl FnOnce(&mut Vec<u8>)) {
+    let start = buf.len();
+    put_u32(buf, ty);
+    put_u32(buf, 0); // Block length, filled in below
+    body(buf);
+    pad(buf);
+    let len = (buf.len() - start + 4) as u32;
+    buf[start + 4..start + 8].copy_from_slice(&len.to_le_bytes());
+    put_u32(buf, len);
+}
+
+/// Pad `buf` to a multiple of 32 bits
+fn pad(buf: &mut Vec<u8>) {
+    let len = (buf.len() + 3) & !3;
+    buf.resize(len, 0);
+}
+
+fn put_u16(buf: &mut Vec<u8>, x: u16) {
+    buf.extend_from_slice(&x.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, x: u32) {
+    buf.extend_from_slice(&x.to_le_bytes());
+}
+
+fn put_u16_be(buf: &mut Vec<u8>, x: u16) {
+    buf.extend_from_slice(&x.to_be_bytes());
+}
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const DECRYPTION_SECRETS_BLOCK: u32 = 0x0000_000A;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+/// Secrets type of a Decryption Secrets Block holding an NSS key log
+const TLS_KEY_LOG: u32 = 0x544C_534B;
+/// Link type of packets beginning with an IPv4 or IPv6 header
+const LINKTYPE_RAW: u16 = 101;
+const OPT_ENDOFOPT: u16 = 0;
+const EPB_FLAGS: u16 = 2;
+
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const UDP_HEADER_LEN: usize = 8;
+const UDP_PROTOCOL: u8 = 17;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ipv4_checksums() {
+        let mut buf = Vec::new();
+        ip_udp(
+            &mut buf,
+            "192.0.2.1:4433".parse().unwrap(),
+            "[::ffff:198.51.100.7]:50000".parse().unwrap(),
+            EcnCodepoint::ECT0 as u8,
+            b"hello",
+        )
+        .unwrap();
+        assert_eq!(buf.len(), IPV4_HEADER_LEN + UDP_HEADER_LEN + 5);
+        assert_eq!(buf[0], 0x45);
+        assert_eq!(buf[1], 0b10);
+        assert_eq!(&buf[16..20], &[198, 51, 100, 7]);
+        // Summing a header including its correct checksum yields zero
+        assert_eq!(fold(sum(&buf[..IPV4_HEADER_LEN], 0)), 0);
+        let mut pseudo_header = vec![192, 0, 2, 1, 198, 51, 100, 7, 0, UDP_PROTOCOL, 0, 13];
+        pseudo_header.extend_from_slice(&buf[IPV4_HEADER_LEN..]);
+        assert_eq!(fold(sum(&pseudo_header, 0)), 0);
+    }
+
+    #[test]
+    fn ipv6_mapping() {
+        let mut buf = Vec::new();
+        ip_udp(
+            &mut buf,
+            "[2001:db8::1]:4433".parse().unwrap(),
+            "198.51.100.7:50000".parse().unwrap(),
+            0,
+            b"hello",
+        )
+        .unwrap();
+        assert_eq!(buf.len(), IPV6_HEADER_LEN + UDP_HEADER_LEN + 5);
+        assert_eq!(buf[0] >> 4, 6);
+        assert_eq!(
+            &buf[24..40],
+            &"::ffff:198.51.100.7".parse::<Ipv6Addr>().unwrap().octets()
+        );
+    }
+
+    #[test]
+    fn local_address() {
+        let remote = "198.51.100.7:50000".parse().unwrap();
+        assert_eq!(local_addr(None, None, remote), "0.0.0.0:0".parse().unwrap());
+        let configured = "[::]:4433".parse().ok();
+        assert_eq!(
+            local_addr(configured, None, remote),
+            "0.0.0.0:4433".parse().unwrap()
+        );
+        assert_eq!(
+            local_addr(configured, Some("192.0.2.1".parse().unwrap()), remote),
+            "192.0.2.1:4433".parse().unwrap()
+        );
+    }
+}
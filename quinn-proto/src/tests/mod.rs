@@ -1,6 +1,8 @@
 use std::{
+    io,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr},
-    sync::Arc,
+    str,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
@@ -15,6 +17,8 @@ use tracing::info;
 use super::*;
 use crate::cid_generator::{ConnectionIdGenerator, RandomConnectionIdGenerator};
 use crate::crypto::Session as _;
+use crate::extension::ExtensionFrame;
+use crate::pcap::PcapNgWriter;
 mod util;
 use util::*;
 
@@ -42,6 +46,12 @@ fn version_negotiate_server() {
         assert!(contents[15..].chunks(4).any(is_supported_version));
     }
     assert_matches!(server.poll_transmit(), None);
+
+    let stats = server.stats();
+    assert_eq!(stats.udp_rx.datagrams, 1);
+    assert_eq!(stats.udp_tx.datagrams, 1);
+    assert_eq!(stats.version_negotiations, 1);
+    assert_eq!(stats.active_connections, 0);
 }
 
 #[test]
@@ -123,6 +133,12 @@ fn stateless_retry() {
         },
     );
     pair.connect();
+
+    let stats = pair.server.stats();
+    assert_eq!(stats.retries, 1);
+    assert_eq!(stats.accepted_connections, 1);
+    assert_eq!(stats.active_connections, 1);
+    assert_eq!(stats.drops.invalid_token, 0);
 }
 
 #[test]
@@ -152,6 +168,7 @@ fn server_stateless_reset() {
             reason: ConnectionError::Reset
         })
     );
+    assert_eq!(pair.server.stats().stateless_resets, 1);
 }
 
 #[test]
@@ -209,6 +226,81 @@ fn export_keying_material() {
     assert_eq!(&client_buf[..], &server_buf[..]);
 }
 
+#[test]
+fn packet_capture() {
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let _guard = subscribe();
+    let out = SharedBuf(Arc::new(Mutex::new(Vec::new())));
+    let capture = Arc::new(PcapNgWriter::new(out.clone()).unwrap());
+    let mut endpoint_config = EndpointConfig::default();
+    endpoint_config.packet_capture(capture.clone());
+    let mut pair = Pair::new(Arc::new(endpoint_config), server_config());
+    capture.set_local_addr(pair.server.addr);
+    let mut client_config = client_config();
+    Arc::make_mut(&mut client_config.crypto).key_log = capture;
+    let client_ch = pair.begin_connect(client_config);
+    pair.drive();
+    pair.server.assert_accept();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::HandshakeDataReady)
+    );
+
+    // Walk the blocks of the capture
+    let data = out.0.lock().unwrap();
+    let u32_at = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+    let (mut types, mut sent, mut received, mut secrets) = (Vec::new(), 0, 0, String::new());
+    let mut i = 0;
+    while i < data.len() {
+        let (ty, len) = (u32_at(i), u32_at(i + 4) as usize);
+        assert_eq!(u32_at(i + len - 4) as usize, len);
+        match ty {
+            // Enhanced packet block; the flags option follows the padded packet
+            6 => {
+                let captured = u32_at(i + 20) as usize;
+                let flags = u32_at(i + 28 + ((captured + 3) & !3) + 4);
+                match flags {
+                    1 => received += 1,
+                    2 => sent += 1,
+                    _ => panic!("unexpected direction {}", flags),
+                }
+                // Raw IPv6 packet carrying UDP
+                assert_eq!(data[i + 28] >> 4, 6);
+                assert_eq!(data[i + 28 + 6], 17);
+            }
+            // Decryption secrets block
+            10 => {
+                assert_eq!(u32_at(i + 8), 0x544c_534b);
+                let n = u32_at(i + 12) as usize;
+                secrets.push_str(str::from_utf8(&data[i + 16..i + 16 + n]).unwrap());
+            }
+            _ => {}
+        }
+        types.push(ty);
+        i += len;
+    }
+    assert_eq!(i, data.len());
+    assert_eq!(&types[..2], &[0x0a0d_0d0a, 1]);
+    // Both endpoints share the capture, so each datagram is recorded once sent and once received
+    assert!(sent > 0);
+    assert_eq!(sent, received);
+    assert!(secrets.contains("CLIENT_HANDSHAKE_TRAFFIC_SECRET "));
+    assert!(secrets.contains("SERVER_TRAFFIC_SECRET_0 "));
+}
+
 #[test]
 fn finish_stream_simple() {
     let _guard = subscribe();
@@ -488,6 +580,14 @@ fn zero_rtt_rejection() {
     pair.client_conn_mut(client_ch).write(s, MSG).unwrap();
     pair.drive();
     assert!(!pair.client_conn_mut(client_ch).accepted_0rtt());
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::HandshakeDataReady)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::ZeroRttRejected)
+    );
     let server_conn = pair.server.assert_accept();
     assert_matches!(
         pair.server_conn_mut(server_conn).poll(),
@@ -595,6 +695,98 @@ fn alpn_mismatch() {
     );
 }
 
+#[test]
+fn server_name_resolver() {
+    let _guard = subscribe();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let cert_der = cert.serialize_der().unwrap();
+    let mut config = ServerNameConfig::default();
+    config
+        .certificate(
+            CertificateChain::from_certs(Certificate::from_der(&cert_der)),
+            PrivateKey::from_der(&cert.serialize_private_key_der()).unwrap(),
+        )
+        .unwrap()
+        .protocols(&[b"foo"]);
+    config.transport = Some(Arc::new(TransportConfig {
+        stream_window_uni: 0u32.into(),
+        ..TransportConfig::default()
+    }));
+    let mut store = ServerNameStore::new();
+    store.insert("LocalHost", config);
+    let mut server = server_config();
+    server.server_name_resolver(store.clone());
+    let mut pair = Pair::new(Default::default(), server);
+
+    // Trust only the certificate selected for the server name
+    let anchor = webpki::trust_anchor_util::cert_der_as_trust_anchor(&cert_der).unwrap();
+    let mut crypto = crypto::ClientConfig::new();
+    Arc::make_mut(&mut crypto)
+        .root_store
+        .add_server_trust_anchors(&webpki::TLSServerTrustAnchors(&[anchor]));
+    Arc::make_mut(&mut crypto).set_protocols(&["foo".into()]);
+    let client = ClientConfig {
+        transport: Default::default(),
+        crypto,
+    };
+    let (client_ch, _) = pair.connect_with(client);
+    let hd = pair
+        .client_conn_mut(client_ch)
+        .crypto_session()
+        .handshake_data()
+        .unwrap();
+    assert_eq!(hd.protocol.unwrap(), &b"foo"[..]);
+    assert_eq!(pair.client_conn_mut(client_ch).open(Dir::Uni), None);
+
+    // Clients requesting other server names get the endpoint's own configuration
+    store.remove("localhost").unwrap();
+    let mut server = server_config();
+    server.server_name_resolver(store);
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, _) = pair.connect();
+    assert!(pair.client_conn_mut(client_ch).open(Dir::Uni).is_some());
+}
+
+#[test]
+fn set_server_config() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+
+    // New handshakes use the new configuration
+    let mut server = server_config();
+    Arc::get_mut(&mut server.crypto)
+        .unwrap()
+        .set_protocols(&["foo".into()]);
+    pair.server.set_server_config(Some(Arc::new(server)));
+    let mut client = client_config();
+    Arc::get_mut(&mut client.crypto)
+        .unwrap()
+        .set_protocols(&["foo".into()]);
+    let (new_client_ch, _) = pair.connect_with(client);
+    let hd = pair
+        .client_conn_mut(new_client_ch)
+        .crypto_session()
+        .handshake_data()
+        .unwrap();
+    assert_eq!(hd.protocol.unwrap(), &b"foo"[..]);
+
+    // Without a configuration, new connections are ignored but existing ones carry on
+    pair.server.set_server_config(None);
+    pair.begin_connect(client_config());
+    pair.drive_client();
+    pair.drive_server();
+    assert_eq!(pair.server.stats().accepted_connections, 2);
+
+    let s = pair.client_conn_mut(client_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = b"hello";
+    pair.client_conn_mut(client_ch).write(s, MSG).unwrap();
+    pair.drive_client();
+    pair.drive_server();
+    assert_matches!(pair.server_conn_mut(server_ch).accept(Dir::Uni), Some(stream) if stream == s);
+    assert_matches!(pair.server_conn_mut(server_ch).read_unordered(s), Ok(Some((msg, 0))) if msg == MSG);
+}
+
 #[test]
 fn stream_id_backpressure() {
     let _guard = subscribe();
@@ -664,6 +856,85 @@ fn stream_id_backpressure() {
     assert_matches!(pair.server_conn_mut(server_ch).read_unordered(s), Ok(None));
 }
 
+#[test]
+fn reconfigure_stream_window() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            stream_window_uni: 1u32.into(),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    let mut streams = vec![pair.client_conn_mut(client_ch).open(Dir::Uni).unwrap()];
+    assert_eq!(pair.client_conn_mut(client_ch).open(Dir::Uni), None);
+
+    // A larger window is announced without waiting for streams to be accepted
+    pair.server_conn_mut(server_ch)
+        .set_max_concurrent_streams(Dir::Uni, 3u32.into());
+    pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Stream(StreamEvent::Available { dir: Dir::Uni }))
+    );
+    for _ in 0..2 {
+        streams.push(pair.client_conn_mut(client_ch).open(Dir::Uni).unwrap());
+    }
+    assert_eq!(pair.client_conn_mut(client_ch).open(Dir::Uni), None);
+
+    // A smaller window withholds credit as streams are accepted
+    pair.server_conn_mut(server_ch)
+        .set_max_concurrent_streams(Dir::Uni, 1u32.into());
+    for &s in &streams {
+        pair.client_conn_mut(client_ch).finish(s).unwrap();
+    }
+    pair.drive();
+    for _ in 0..2 {
+        assert!(pair.server_conn_mut(server_ch).accept(Dir::Uni).is_some());
+    }
+    pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).open(Dir::Uni), None);
+    assert!(pair.server_conn_mut(server_ch).accept(Dir::Uni).is_some());
+    pair.drive();
+    assert!(pair.client_conn_mut(client_ch).open(Dir::Uni).is_some());
+}
+
+#[test]
+fn reconfigure_receive_window() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            receive_window: 2048u32.into(),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    let s = pair.client_conn_mut(client_ch).open(Dir::Uni).unwrap();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).write(s, &[0; 4096]),
+        Ok(2048)
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch).write(s, &[0; 4096]),
+        Err(WriteError::Blocked)
+    );
+    pair.drive();
+
+    pair.server_conn_mut(server_ch)
+        .set_receive_window(8192u32.into());
+    pair.drive();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).write(s, &[0; 4096]),
+        Ok(4096)
+    );
+}
+
 #[test]
 fn key_update_simple() {
     let _guard = subscribe();
@@ -700,11 +971,19 @@ fn key_update_simple() {
     pair.drive();
 
     assert_matches!(pair.server_conn_mut(server_ch).poll(), Some(Event::Stream(StreamEvent::Readable { id })) if id == s);
+    assert_matches!(
+        pair.server_conn_mut(server_ch).poll(),
+        Some(Event::KeysUpdated { by_peer: true })
+    );
     assert_matches!(pair.server_conn_mut(server_ch).poll(), None);
     assert_matches!(
         pair.server_conn_mut(server_ch).read_unordered(s),
         Ok(Some((ref data, 6))) if data == MSG2
     );
+    assert!(
+        std::iter::from_fn(|| pair.client_conn_mut(client_ch).poll())
+            .any(|e| matches!(e, Event::KeysUpdated { by_peer: false }))
+    );
 
     assert_eq!(pair.client_conn_mut(client_ch).lost_packets(), 0);
     assert_eq!(pair.server_conn_mut(server_ch).lost_packets(), 0);
@@ -762,6 +1041,10 @@ fn initial_retransmit() {
     pair.client.drive(pair.time, pair.server.addr);
     pair.client.outbound.clear(); // Drop initial
     pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Congested { ecn: false, .. })
+    );
     assert_matches!(
         pair.client_conn_mut(client_ch).poll(),
         Some(Event::HandshakeDataReady)
@@ -872,6 +1155,312 @@ fn idle_timeout() {
     );
 }
 
+#[test]
+fn handshake_timeout() {
+    let _guard = subscribe();
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(100);
+    let transport = Arc::new(TransportConfig {
+        handshake_timeout: Some(HANDSHAKE_TIMEOUT),
+        ..TransportConfig::default()
+    });
+    let server = ServerConfig {
+        transport: transport.clone(),
+        ..server_config()
+    };
+    let client = ClientConfig {
+        transport,
+        ..client_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let start = pair.time;
+    let client_ch = pair.begin_connect(client);
+    // Deliver the client's first flight, leaving the server with a half-open connection
+    pair.drive_client();
+    pair.server.drive(pair.time, pair.client.addr);
+    let server_ch = pair.server.assert_accept();
+
+    while !pair.client_conn_mut(client_ch).is_closed()
+        || !pair.server_conn_mut(server_ch).is_closed()
+    {
+        if !pair.step() {
+            if let Some(t) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
+                pair.time = t;
+            }
+        }
+        pair.client.inbound.clear(); // Simulate total S->C packet loss
+    }
+
+    let elapsed = pair.time - start;
+    assert!(elapsed >= HANDSHAKE_TIMEOUT && elapsed < 2 * HANDSHAKE_TIMEOUT);
+    let lost = |conn: &mut Connection| {
+        std::iter::from_fn(|| conn.poll()).find_map(|e| match e {
+            Event::ConnectionLost { reason } => Some(reason),
+            _ => None,
+        })
+    };
+    assert_matches!(
+        lost(pair.client_conn_mut(client_ch)),
+        Some(ConnectionError::HandshakeTimedOut)
+    );
+    assert_matches!(
+        lost(pair.server_conn_mut(server_ch)),
+        Some(ConnectionError::HandshakeTimedOut)
+    );
+}
+
+#[test]
+fn custom_transport_parameters() {
+    let _guard = subscribe();
+    const ID: VarInt = VarInt::from_u32(0x4242);
+    let mut server_transport = TransportConfig::default();
+    server_transport
+        .custom_transport_parameter(ID, &b"server"[..])
+        .unwrap();
+    let mut client_transport = TransportConfig::default();
+    client_transport
+        .custom_transport_parameter(ID, &b"client"[..])
+        .unwrap();
+    assert_matches!(
+        client_transport.custom_transport_parameter(VarInt::from_u32(0x0c), &b""[..]),
+        Err(ConfigError::ReservedTransportParameter)
+    );
+    let server = ServerConfig {
+        transport: Arc::new(server_transport),
+        ..server_config()
+    };
+    let client = ClientConfig {
+        transport: Arc::new(client_transport),
+        ..client_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect_with(client);
+    assert_eq!(
+        pair.client_conn_mut(client_ch).peer_transport_parameter(ID),
+        Some(&b"server"[..])
+    );
+    assert_eq!(
+        pair.server_conn_mut(server_ch).peer_transport_parameter(ID),
+        Some(&b"client"[..])
+    );
+    assert_eq!(
+        pair.server_conn_mut(server_ch)
+            .peer_transport_parameter(VarInt::from_u32(0x4243)),
+        None
+    );
+}
+
+struct TestFrame {
+    ty: VarInt,
+    retransmittable: bool,
+}
+
+impl ExtensionFrame for TestFrame {
+    fn ty(&self) -> VarInt {
+        self.ty
+    }
+
+    fn retransmittable(&self) -> bool {
+        self.retransmittable
+    }
+}
+
+#[test]
+fn extension_frames() {
+    let _guard = subscribe();
+    const RELIABLE: VarInt = VarInt::from_u32(0x3f00);
+    const UNRELIABLE: VarInt = VarInt::from_u32(0x3f01);
+    let mut transport = TransportConfig::default();
+    for &(ty, retransmittable) in &[(RELIABLE, true), (UNRELIABLE, false)] {
+        transport
+            .extension_frame(TestFrame {
+                ty,
+                retransmittable,
+            })
+            .unwrap();
+    }
+    assert_matches!(
+        transport.extension_frame(TestFrame {
+            ty: VarInt::from_u32(0x1e),
+            retransmittable: true,
+        }),
+        Err(ConfigError::ReservedFrameType)
+    );
+    let transport = Arc::new(transport);
+    let server = ServerConfig {
+        transport: transport.clone(),
+        ..server_config()
+    };
+    let client = ClientConfig {
+        transport,
+        ..client_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect_with(client);
+
+    let received = |conn: &mut Connection| {
+        std::iter::from_fn(|| conn.recv_extension_frame()).collect::<Vec<_>>()
+    };
+
+    pair.client_conn_mut(client_ch)
+        .send_extension_frame(RELIABLE, b"hello")
+        .unwrap();
+    pair.drive();
+    assert!(
+        std::iter::from_fn(|| pair.server_conn_mut(server_ch).poll())
+            .any(|e| matches!(e, Event::ExtensionFrameReceived))
+    );
+    assert_eq!(
+        received(pair.server_conn_mut(server_ch)),
+        [(RELIABLE, Bytes::from_static(b"hello"))]
+    );
+
+    // Lose the packet carrying both frames; only the reliable one is retransmitted, possibly in
+    // more than one probe
+    pair.client_conn_mut(client_ch)
+        .send_extension_frame(RELIABLE, b"reliable")
+        .unwrap();
+    pair.client_conn_mut(client_ch)
+        .send_extension_frame(UNRELIABLE, b"unreliable")
+        .unwrap();
+    pair.client.drive(pair.time, pair.server.addr);
+    pair.client.outbound.clear();
+    pair.drive();
+    let frames = received(pair.server_conn_mut(server_ch));
+    assert!(!frames.is_empty());
+    assert!(frames
+        .iter()
+        .all(|x| *x == (RELIABLE, Bytes::from_static(b"reliable"))));
+
+    assert_matches!(
+        pair.client_conn_mut(client_ch)
+            .send_extension_frame(VarInt::from_u32(0x3f02), b""),
+        Err(SendExtensionFrameError::UnknownType)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch)
+            .send_extension_frame(RELIABLE, &[0; 2048]),
+        Err(SendExtensionFrameError::TooLarge)
+    );
+}
+
+/// Transport configuration registering `TEST_FRAME`
+fn extension_transport() -> Arc<TransportConfig> {
+    let mut transport = TransportConfig::default();
+    transport
+        .extension_frame(TestFrame {
+            ty: TEST_FRAME,
+            retransmittable: true,
+        })
+        .unwrap();
+    transport.extension_frame_receive_buffer_size(16);
+    Arc::new(transport)
+}
+
+const TEST_FRAME: VarInt = VarInt::from_u32(0x3f00);
+
+#[test]
+fn extension_frame_buffer_exceeded() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: extension_transport(),
+        ..server_config()
+    };
+    let client = ClientConfig {
+        transport: extension_transport(),
+        ..client_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect_with(client);
+
+    // Frames read by the application no longer count towards the limit
+    for _ in 0..2 {
+        pair.client_conn_mut(client_ch)
+            .send_extension_frame(TEST_FRAME, &[0; 10])
+            .unwrap();
+        pair.drive();
+        assert_matches!(
+            pair.server_conn_mut(server_ch).recv_extension_frame(),
+            Some((TEST_FRAME, _))
+        );
+    }
+
+    for _ in 0..2 {
+        pair.client_conn_mut(client_ch)
+            .send_extension_frame(TEST_FRAME, &[0; 10])
+            .unwrap();
+    }
+    pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::ConnectionLost {
+            reason: ConnectionError::ConnectionClosed(frame::ConnectionClose {
+                error_code: TransportErrorCode::INTERNAL_ERROR,
+                ..
+            })
+        })
+    );
+}
+
+#[test]
+fn extension_frames_resent_after_0rtt_rejection() {
+    let _guard = subscribe();
+    let mut server_config = ServerConfig {
+        transport: extension_transport(),
+        ..server_config()
+    };
+    Arc::get_mut(&mut server_config.crypto)
+        .unwrap()
+        .set_protocols(&["foo".into(), "bar".into()]);
+    let mut pair = Pair::new(Arc::new(EndpointConfig::default()), server_config);
+    let mut client_config = ClientConfig {
+        transport: extension_transport(),
+        ..client_config()
+    };
+    Arc::get_mut(&mut client_config.crypto)
+        .unwrap()
+        .set_protocols(&["foo".into()]);
+
+    // Establish a session to resume
+    let client_ch = pair.begin_connect(client_config.clone());
+    pair.drive();
+    pair.server.assert_accept();
+    pair.client
+        .connections
+        .get_mut(&client_ch)
+        .unwrap()
+        .close(pair.time, VarInt(0), [][..].into());
+    pair.drive();
+    pair.client.connections.clear();
+    pair.server.connections.clear();
+
+    // Changing protocols invalidates 0-RTT
+    Arc::get_mut(&mut client_config.crypto)
+        .unwrap()
+        .set_protocols(&["bar".into()]);
+    let client_ch = pair.begin_connect(client_config);
+    assert!(pair.client_conn_mut(client_ch).has_0rtt());
+    pair.client_conn_mut(client_ch)
+        .send_extension_frame(TEST_FRAME, b"sent")
+        .unwrap();
+    pair.client.drive(pair.time, pair.server.addr);
+    pair.client_conn_mut(client_ch)
+        .send_extension_frame(TEST_FRAME, b"queued")
+        .unwrap();
+    pair.drive();
+    assert!(!pair.client_conn_mut(client_ch).accepted_0rtt());
+
+    let server_ch = pair.server.assert_accept();
+    let frames = std::iter::from_fn(|| pair.server_conn_mut(server_ch).recv_extension_frame())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        frames,
+        [
+            (TEST_FRAME, Bytes::from_static(b"sent")),
+            (TEST_FRAME, Bytes::from_static(b"queued"))
+        ]
+    );
+}
+
 #[test]
 fn concurrent_connections_full() {
     let _guard = subscribe();
@@ -897,6 +1486,10 @@ fn concurrent_connections_full() {
     assert_eq!(pair.server.connections.len(), 0);
     assert_eq!(pair.server.known_connections(), 0);
     assert_eq!(pair.server.known_cids(), 0);
+
+    let stats = pair.server.stats();
+    assert_eq!(stats.refused_connections, 1);
+    assert_eq!(stats.accepted_connections, 0);
 }
 
 #[test]
@@ -934,6 +1527,15 @@ fn migration() {
         pair.server_conn_mut(server_ch).remote_address(),
         pair.client.addr
     );
+    let client_addr = pair.client.addr;
+    assert_matches!(
+        pair.server_conn_mut(server_ch).poll(),
+        Some(Event::PathMigrated { remote }) if remote == client_addr
+    );
+    assert_matches!(
+        pair.server_conn_mut(server_ch).poll(),
+        Some(Event::ConnectionIdRotated)
+    );
 }
 
 fn test_flow_control(config: TransportConfig, window_size: usize) {
@@ -128,8 +128,12 @@ impl Pair {
     }
 
     pub fn connect(&mut self) -> (ConnectionHandle, ConnectionHandle) {
+        self.connect_with(client_config())
+    }
+
+    pub fn connect_with(&mut self, config: ClientConfig) -> (ConnectionHandle, ConnectionHandle) {
         info!("connecting");
-        let client_ch = self.begin_connect(client_config());
+        let client_ch = self.begin_connect(config);
         self.drive();
         let server_ch = self.server.assert_accept();
         assert_matches!(
@@ -252,14 +256,14 @@ impl TestEndpoint {
         }
 
         let mut endpoint_events: Vec<(ConnectionHandle, EndpointEvent)> = vec![];
+        let mut timeout = None;
         for (ch, conn) in self.connections.iter_mut() {
-            if self.timeout.map_or(false, |x| x <= now) {
-                self.timeout = None;
+            if conn.poll_timeout().map_or(false, |x| x <= now) {
                 conn.handle_timeout(now);
             }
 
-            for (_, mut events) in self.conn_events.drain() {
-                for event in events.drain(..) {
+            if let Some(events) = self.conn_events.remove(ch) {
+                for event in events {
                     conn.handle_event(event);
                 }
             }
@@ -271,8 +275,9 @@ impl TestEndpoint {
             while let Some(x) = conn.poll_transmit(now) {
                 self.outbound.push_back(x);
             }
-            self.timeout = conn.poll_timeout();
+            timeout = min_opt(timeout, conn.poll_timeout());
         }
+        self.timeout = timeout;
 
         for (ch, event) in endpoint_events {
             if let Some(event) = self.handle_event(ch, event) {
@@ -7,11 +7,12 @@
 //! implementations of the `crypto::Session` trait.
 
 use std::{
+    collections::BTreeMap,
     convert::{TryFrom, TryInto},
     net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6},
 };
 
-use bytes::{buf::ext::BufExt as _, Buf, BufMut};
+use bytes::{buf::ext::BufExt as _, Buf, BufMut, Bytes};
 use thiserror::Error;
 
 use crate::{
@@ -67,7 +68,7 @@ macro_rules! apply_params {
 macro_rules! make_struct {
     {$($(#[$doc:meta])* $name:ident ($code:expr) = $default:expr,)*} => {
         /// Transport parameters used to negotiate connection-level preferences between peers
-        #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+        #[derive(Debug, Clone, Eq, PartialEq)]
         pub struct TransportParameters {
             $($(#[$doc])* pub(crate) $name : VarInt,)*
 
@@ -90,6 +91,9 @@ macro_rules! make_struct {
             pub(crate) stateless_reset_token: Option<ResetToken>,
             /// The server's preferred address for communication after handshake completion
             pub(crate) preferred_address: Option<PreferredAddress>,
+
+            /// Application-defined parameters, opaque to the transport
+            pub(crate) custom: BTreeMap<VarInt, Bytes>,
         }
 
         impl Default for TransportParameters {
@@ -106,6 +110,8 @@ macro_rules! make_struct {
                     retry_src_cid: None,
                     stateless_reset_token: None,
                     preferred_address: None,
+
+                    custom: BTreeMap::new(),
                 }
             }
         }
@@ -150,6 +156,7 @@ impl TransportParameters {
             max_datagram_frame_size: config
                 .datagram_receive_buffer_size
                 .map(|x| (x.min(u16::max_value().into()) as u16).into()),
+            custom: config.custom_transport_parameters.clone(),
             ..Self::default()
         }
     }
@@ -183,6 +190,14 @@ impl TransportParameters {
     pub(crate) fn issue_cids_limit(&self) -> u64 {
         self.active_connection_id_limit.0.min(LOC_CID_COUNT)
     }
+
+    /// Whether `id` is unavailable for application-defined parameters
+    ///
+    /// Covers every parameter understood by this implementation, as well as the IDs reserved for
+    /// greasing.
+    pub(crate) fn is_reserved_id(id: u64) -> bool {
+        id <= 0x10 || id == 0x20 || id % 31 == 27
+    }
 }
 
 /// A server's preferred address
@@ -330,6 +345,12 @@ impl TransportParameters {
                 w.put_slice(cid);
             }
         }
+
+        for (&id, value) in &self.custom {
+            w.write(id);
+            w.write_var(value.len() as u64);
+            w.put_slice(value);
+        }
     }
 
     /// Decode `TransportParameters` from buffer
@@ -400,7 +421,16 @@ impl TransportParameters {
                                     params.$name = value.into();
                                     got.$name = true;
                                 })*
-                                _ => r.advance(len as usize),
+                                _ if Self::is_reserved_id(id) => r.advance(len as usize),
+                                _ => {
+                                    let id = VarInt::from_u64(id).unwrap();
+                                    if params.custom.contains_key(&id) {
+                                        return Err(Error::Malformed);
+                                    }
+                                    let mut value = vec![0; len];
+                                    r.copy_to_slice(&mut value);
+                                    params.custom.insert(id, value.into());
+                                }
                             }
                         }
                     }
@@ -455,6 +485,9 @@ mod test {
                 connection_id: ConnectionId::new(&[]),
                 stateless_reset_token: [0xab; RESET_TOKEN_SIZE].into(),
             }),
+            custom: vec![(VarInt::from_u32(0x4242), Bytes::from_static(b"custom"))]
+                .into_iter()
+                .collect(),
             ..TransportParameters::default()
         };
         params.write(&mut buf);
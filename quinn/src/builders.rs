@@ -1,15 +1,19 @@
 use std::{io, net::SocketAddr, sync::Arc};
 
 use proto::{
+    crypto::ClientConfig as _,
     generic::{ClientConfig, EndpointConfig, ServerConfig},
-    ConnectionIdGenerator,
+    pcap::PcapNgWriter,
+    ConnectionIdGenerator, ServerNameResolver,
 };
 use thiserror::Error;
 use tracing::error;
 
 use crate::{
-    endpoint::{Endpoint, EndpointDriver, EndpointRef, Incoming},
-    udp::UdpSocket,
+    endpoint::{Endpoint, EndpointDriver, EndpointRef, Incoming, Shard},
+    platform,
+    resolve::{Resolver, SystemResolver},
+    runtime::{default_runtime, AsyncUdpSocket, Runtime},
 };
 #[cfg(feature = "rustls")]
 use crate::{Certificate, CertificateChain, PrivateKey};
@@ -20,7 +24,7 @@ use crate::{Certificate, CertificateChain, PrivateKey};
 ///
 /// [`Endpoint`]: crate::generic::Endpoint
 /// [`ClientConfigBuilder`]: crate::generic::ClientConfigBuilder
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct EndpointBuilder<S>
 where
     S: proto::crypto::Session,
@@ -28,6 +32,10 @@ where
     server_config: Option<ServerConfig<S>>,
     config: EndpointConfig<S>,
     default_client_config: ClientConfig<S>,
+    shards: usize,
+    runtime: Option<Arc<dyn Runtime>>,
+    resolver: Arc<dyn Resolver>,
+    capture: Option<Arc<PcapNgWriter>>,
 }
 
 #[allow(missing_docs)]
@@ -41,53 +49,161 @@ where
             server_config: None,
             config,
             default_client_config,
+            shards: 1,
+            runtime: None,
+            resolver: Arc::new(SystemResolver),
+            capture: None,
         }
     }
 
     /// Build an endpoint bound to `addr`
     ///
-    /// Must be called from within a tokio runtime context. To avoid consuming the
-    /// `EndpointBuilder`, call `clone()` first.
+    /// Must be called from within the configured runtime's context, e.g. from within a tokio
+    /// runtime when using tokio. To avoid consuming the `EndpointBuilder`, call `clone()` first.
     ///
     /// Platform defaults for dual-stack sockets vary. For example, any socket bound to a wildcard
     /// IPv6 address on Windows will not by default be able to communicate with IPv4
     /// addresses. Portable applications should bind an address that matches the family they wish to
     /// communicate within.
     pub fn bind(self, addr: &SocketAddr) -> Result<(Endpoint<S>, Incoming<S>), EndpointError> {
-        let socket = std::net::UdpSocket::bind(addr).map_err(EndpointError::Socket)?;
-        self.with_socket(socket)
+        if self.shards == 1 {
+            let socket = std::net::UdpSocket::bind(addr).map_err(EndpointError::Socket)?;
+            return self.with_socket(socket);
+        }
+        let first = platform::bind_reuse_port(addr).map_err(EndpointError::Socket)?;
+        // Binding port 0 picks an arbitrary port, which the remaining shards must share
+        let addr = first.local_addr().map_err(EndpointError::Socket)?;
+        let mut sockets = vec![first];
+        for _ in 1..self.shards {
+            sockets.push(platform::bind_reuse_port(&addr).map_err(EndpointError::Socket)?);
+        }
+        self.with_sockets(sockets)
     }
 
     /// Build an endpoint around a pre-configured socket
     ///
-    /// Must be called from within a tokio runtime context. To avoid consuming the
-    /// `EndpointBuilder`, call `clone()` first.
+    /// Must be called from within the configured runtime's context, e.g. from within a tokio
+    /// runtime when using tokio. To avoid consuming the `EndpointBuilder`, call `clone()` first.
     pub fn with_socket(
         self,
         socket: std::net::UdpSocket,
     ) -> Result<(Endpoint<S>, Incoming<S>), EndpointError> {
-        let addr = socket.local_addr().map_err(EndpointError::Socket)?;
-        let socket = UdpSocket::from_std(socket).map_err(EndpointError::Socket)?;
-        let rc = EndpointRef::new(
-            socket,
-            proto::generic::Endpoint::new(Arc::new(self.config), self.server_config.map(Arc::new)),
-            addr.is_ipv6(),
-        );
-        let driver = EndpointDriver(rc.clone());
-        tokio::spawn(async {
-            if let Err(e) = driver.await {
-                error!("I/O error: {}", e);
+        self.with_sockets(vec![socket])
+    }
+
+    /// Build an endpoint around a custom datagram transport
+    ///
+    /// Allows QUIC to be carried by anything able to exchange datagrams, such as an in-memory
+    /// channel, a TUN device, or a relay tunnel, rather than a UDP socket provided by the
+    /// runtime. The configured runtime is still used to drive the endpoint and its connections.
+    ///
+    /// To avoid consuming the `EndpointBuilder`, call `clone()` first.
+    pub fn with_async_socket(
+        self,
+        socket: Box<dyn AsyncUdpSocket>,
+    ) -> Result<(Endpoint<S>, Incoming<S>), EndpointError> {
+        let runtime = self.resolve_runtime()?;
+        self.with_async_sockets(vec![socket], runtime)
+    }
+
+    /// Build an endpoint with one shard per socket
+    fn with_sockets(
+        self,
+        sockets: Vec<std::net::UdpSocket>,
+    ) -> Result<(Endpoint<S>, Incoming<S>), EndpointError> {
+        let runtime = self.resolve_runtime()?;
+        let sockets = sockets
+            .into_iter()
+            .map(|socket| runtime.wrap_udp_socket(socket))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(EndpointError::Socket)?;
+        self.with_async_sockets(sockets, runtime)
+    }
+
+    fn with_async_sockets(
+        self,
+        sockets: Vec<Box<dyn AsyncUdpSocket>>,
+        runtime: Arc<dyn Runtime>,
+    ) -> Result<(Endpoint<S>, Incoming<S>), EndpointError> {
+        let config = self.config;
+        let mut server_config = self.server_config;
+        let mut default_client_config = self.default_client_config;
+        if let Some(ref capture) = self.capture {
+            if let Some(ref mut server_config) = server_config {
+                server_config.capture_secrets(capture.clone());
+            }
+            default_client_config
+                .crypto
+                .capture_secrets(capture.clone());
+        }
+        let server_config = server_config.map(Arc::new);
+        let endpoints = if sockets.len() == 1 {
+            vec![proto::generic::Endpoint::new(
+                Arc::new(config),
+                server_config,
+            )]
+        } else {
+            (0..sockets.len())
+                .map(|index| {
+                    let mut config = config.clone();
+                    config.shard(index as u8);
+                    proto::generic::Endpoint::new(Arc::new(config), server_config.clone())
+                })
+                .collect()
+        };
+
+        let mut shards = if sockets.len() == 1 {
+            Vec::new()
+        } else {
+            let cid_len = endpoints[0].local_cid_len();
+            if cid_len == 0 {
+                return Err(EndpointError::ZeroLengthConnectionIds);
+            }
+            Shard::new_set(sockets.len(), cid_len)
+        }
+        .into_iter();
+
+        let mut refs = Vec::with_capacity(sockets.len());
+        for (socket, endpoint) in sockets.into_iter().zip(endpoints) {
+            let addr = socket.local_addr().map_err(EndpointError::Socket)?;
+            if let Some(ref capture) = self.capture {
+                capture.set_local_addr(addr);
             }
-        });
+            refs.push(EndpointRef::new(
+                socket,
+                endpoint,
+                addr.is_ipv6(),
+                shards.next(),
+                runtime.clone(),
+            ));
+        }
+
+        for rc in &refs {
+            let driver = EndpointDriver::new(rc.clone());
+            runtime.spawn(Box::pin(async {
+                if let Err(e) = driver.await {
+                    error!("I/O error: {}", e);
+                }
+            }));
+        }
         Ok((
-            Endpoint {
-                inner: rc.clone(),
-                default_client_config: self.default_client_config,
-            },
-            Incoming::new(rc),
+            Endpoint::new(
+                refs.clone(),
+                default_client_config,
+                self.resolver,
+                self.capture,
+            ),
+            Incoming::new(refs),
         ))
     }
 
+    fn resolve_runtime(&self) -> Result<Arc<dyn Runtime>, EndpointError> {
+        self.runtime
+            .clone()
+            .or_else(default_runtime)
+            .ok_or(EndpointError::NoRuntime)
+    }
+
     /// Accept incoming connections.
     pub fn listen(&mut self, config: ServerConfig<S>) -> &mut Self {
         self.server_config = Some(config);
@@ -104,6 +220,68 @@ where
         self
     }
 
+    /// Spread the endpoint across `count` shards, each with its own socket and driver task
+    ///
+    /// The shards' sockets share the bound address using `SO_REUSEPORT`, so that the kernel
+    /// balances incoming datagrams across them and a multi-threaded runtime can drive them in
+    /// parallel. Each shard embeds its index in the connection IDs it issues, allowing datagrams
+    /// that reach the wrong shard, e.g. after the peer's NAT rebinds, to be forwarded to the one that
+    /// owns the connection. Incoming connections from every shard are yielded by the same
+    /// [`Incoming`] stream, and outgoing connections are distributed across shards.
+    ///
+    /// Only applies to [`bind()`](Self::bind), and requires a connection ID generator which
+    /// produces non-empty connection IDs. Defaults to 1.
+    ///
+    /// # Panics
+    ///
+    /// If `count` is zero or greater than 256.
+    ///
+    /// [`Incoming`]: crate::generic::Incoming
+    pub fn shards(&mut self, count: usize) -> &mut Self {
+        assert!(count > 0 && count <= 256, "shard count must be in 1..=256");
+        self.shards = count;
+        self
+    }
+
+    /// Set the async runtime used to drive the endpoint and its connections
+    ///
+    /// Defaults to [`default_runtime()`], which selects a runtime according to the enabled cargo
+    /// features.
+    ///
+    /// [`default_runtime()`]: crate::default_runtime
+    pub fn runtime(&mut self, runtime: Arc<dyn Runtime>) -> &mut Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Set the resolver used by [`Endpoint::connect_host()`] to look up hostnames
+    ///
+    /// Defaults to [`SystemResolver`].
+    ///
+    /// [`Endpoint::connect_host()`]: crate::generic::Endpoint::connect_host
+    /// [`SystemResolver`]: crate::SystemResolver
+    pub fn resolver(&mut self, resolver: Arc<dyn Resolver>) -> &mut Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Record every datagram sent or received by the endpoint to a pcapng capture
+    ///
+    /// The TLS secrets of incoming connections and of those using the default client configuration
+    /// are embedded in the capture, so that Wireshark can decrypt it without a separate key log
+    /// file. This includes connections accepted with a configuration selected by server name, or
+    /// passed to [`Endpoint::set_server_config()`] later on. Connections initiated with
+    /// [`Endpoint::connect_with()`] only have their secrets recorded if the `key_log` of their
+    /// configuration is also set to `capture`.
+    ///
+    /// [`Endpoint::set_server_config()`]: crate::generic::Endpoint::set_server_config
+    /// [`Endpoint::connect_with()`]: crate::generic::Endpoint::connect_with
+    pub fn capture(&mut self, capture: Arc<PcapNgWriter>) -> &mut Self {
+        self.config.packet_capture(capture.clone());
+        self.capture = Some(capture);
+        self
+    }
+
     /// Use a customized cid generator factory in the endpoint
     pub fn connection_id_generator<
         F: Fn() -> Box<dyn ConnectionIdGenerator> + Send + Sync + 'static,
@@ -116,6 +294,25 @@ where
     }
 }
 
+impl<S> Clone for EndpointBuilder<S>
+where
+    S: proto::crypto::Session,
+    S::ClientConfig: Clone,
+    S::ServerConfig: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            server_config: self.server_config.clone(),
+            config: self.config.clone(),
+            default_client_config: self.default_client_config.clone(),
+            shards: self.shards,
+            runtime: self.runtime.clone(),
+            resolver: self.resolver.clone(),
+            capture: self.capture.clone(),
+        }
+    }
+}
+
 impl<S> Default for EndpointBuilder<S>
 where
     S: proto::crypto::Session,
@@ -125,6 +322,10 @@ where
             server_config: None,
             config: EndpointConfig::default(),
             default_client_config: ClientConfig::default(),
+            shards: 1,
+            runtime: None,
+            resolver: Arc::new(SystemResolver),
+            capture: None,
         }
     }
 }
@@ -135,6 +336,12 @@ pub enum EndpointError {
     /// An error during setup of the underlying UDP socket.
     #[error("failed to set up UDP socket: {0}")]
     Socket(io::Error),
+    /// Sharded endpoints require non-empty connection IDs to route datagrams between shards.
+    #[error("sharded endpoints cannot use zero-length connection IDs")]
+    ZeroLengthConnectionIds,
+    /// No runtime was configured, and no runtime feature is enabled to provide a default.
+    #[error("no async runtime available")]
+    NoRuntime,
 }
 
 /// Helper for constructing a [`ServerConfig`] to be passed to [`EndpointBuilder::listen()`] to
@@ -168,6 +375,22 @@ where
         self.config.use_stateless_retry(enabled);
         self
     }
+
+    /// Select the certificate chain, application protocols and transport configuration of
+    /// incoming connections by the server name requested by the client
+    ///
+    /// The configuration set on this builder is used for clients which don't send a server name,
+    /// or for which the resolver returns `None`. [`ServerNameStore`] holds a fixed configuration for
+    /// each of any number of server names.
+    ///
+    /// [`ServerNameStore`]: crate::generic::ServerNameStore
+    pub fn server_name_resolver(
+        &mut self,
+        resolver: impl ServerNameResolver<S> + 'static,
+    ) -> &mut Self {
+        self.config.server_name_resolver(resolver);
+        self
+    }
 }
 
 #[cfg(feature = "rustls")]
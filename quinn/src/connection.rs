@@ -15,13 +15,15 @@ use futures::{
     channel::{mpsc, oneshot},
     FutureExt, StreamExt,
 };
-use proto::{ConnectionError, ConnectionHandle, ConnectionStats, Dir, StreamEvent, StreamId};
+use proto::{
+    congestion, ConnectionError, ConnectionHandle, ConnectionStats, Dir, StreamEvent, StreamId,
+};
 use thiserror::Error;
-use tokio::time::{delay_until, Delay, Instant as TokioInstant};
 use tracing::info_span;
 
 use crate::{
     broadcast::{self, Broadcast},
+    runtime::{AsyncTimer, Runtime},
     streams::{RecvStream, SendStream, WriteError},
     ConnectionEvent, EndpointEvent, VarInt,
 };
@@ -46,6 +48,7 @@ where
         conn: proto::generic::Connection<S>,
         endpoint_events: mpsc::UnboundedSender<(ConnectionHandle, EndpointEvent)>,
         conn_events: mpsc::UnboundedReceiver<ConnectionEvent>,
+        runtime: Arc<dyn Runtime>,
     ) -> Connecting<S> {
         let (on_handshake_data_send, on_handshake_data_recv) = oneshot::channel();
         let (on_connected_send, on_connected_recv) = oneshot::channel();
@@ -56,9 +59,10 @@ where
             conn_events,
             on_handshake_data_send,
             on_connected_send,
+            runtime.clone(),
         );
 
-        tokio::spawn(ConnectionDriver(conn.clone()));
+        runtime.spawn(Box::pin(ConnectionDriver(conn.clone())));
 
         Connecting {
             conn: Some(conn),
@@ -241,6 +245,8 @@ where
     pub bi_streams: IncomingBiStreams<S>,
     /// Unordered, unreliable datagrams sent by the peer
     pub datagrams: Datagrams<S>,
+    /// Application-defined frames sent by the peer, in the order they were received
+    pub extension_frames: ExtensionFrames<S>,
 }
 
 impl<S> NewConnection<S>
@@ -252,7 +258,8 @@ where
             connection: Connection(conn.clone()),
             uni_streams: IncomingUniStreams(conn.clone()),
             bi_streams: IncomingBiStreams(conn.clone()),
-            datagrams: Datagrams(conn),
+            datagrams: Datagrams(conn.clone()),
+            extension_frames: ExtensionFrames(conn),
         }
     }
 }
@@ -395,6 +402,34 @@ where
         }
     }
 
+    /// Transmit an application-defined frame
+    ///
+    /// `ty` must have been registered with [`TransportConfig::extension_frame()`], which determines
+    /// how the frame is encoded and whether it is retransmitted if lost.
+    ///
+    /// [`TransportConfig::extension_frame()`]: crate::TransportConfig::extension_frame
+    pub fn send_extension_frame(
+        &self,
+        ty: VarInt,
+        payload: &[u8],
+    ) -> Result<(), SendExtensionFrameError> {
+        let conn = &mut *self.0.lock().unwrap();
+        if let Some(ref x) = conn.error {
+            return Err(SendExtensionFrameError::ConnectionClosed(x.clone()));
+        }
+        use proto::SendExtensionFrameError::*;
+        match conn.inner.send_extension_frame(ty, payload) {
+            Ok(()) => {
+                conn.wake();
+                Ok(())
+            }
+            Err(e) => Err(match e {
+                UnknownType => SendExtensionFrameError::UnknownType,
+                TooLarge => SendExtensionFrameError::TooLarge,
+            }),
+        }
+    }
+
     /// Compute the maximum size of datagrams that may be passed to [`send_datagram()`].
     ///
     /// Returns `None` if datagrams are unsupported by the peer or disabled locally.
@@ -471,6 +506,79 @@ where
             .peer_identity()
     }
 
+    /// Value of an application-defined transport parameter sent by the peer
+    ///
+    /// Guaranteed to be available on fully established connections or after
+    /// [`Connecting::handshake_data()`] succeeds. See
+    /// [`TransportConfig::custom_transport_parameter()`].
+    ///
+    /// [`Connecting::handshake_data()`]: crate::generic::Connecting::handshake_data
+    /// [`TransportConfig::custom_transport_parameter()`]: crate::TransportConfig::custom_transport_parameter
+    pub fn peer_transport_parameter(&self, id: VarInt) -> Option<Bytes> {
+        self.0
+            .lock()
+            .unwrap()
+            .inner
+            .peer_transport_parameter(id)
+            .map(Bytes::copy_from_slice)
+    }
+
+    /// Change the number of incoming streams which may be open but not yet accepted
+    ///
+    /// Overrides `TransportConfig::stream_window_bidi` or `stream_window_uni` for this connection.
+    /// A larger limit is announced to the peer immediately, while a smaller one takes effect as
+    /// incoming streams are accepted.
+    pub fn set_max_concurrent_streams(&self, dir: Dir, count: VarInt) {
+        let mut conn = self.0.lock().unwrap();
+        conn.inner.set_max_concurrent_streams(dir, count);
+        conn.wake();
+    }
+
+    /// Change the maximum amount of data the peer may send across all streams without
+    /// acknowledgement
+    ///
+    /// Overrides `TransportConfig::receive_window` for this connection. Credit already issued to
+    /// the peer cannot be revoked, so a smaller window takes effect as data is read.
+    pub fn set_receive_window(&self, receive_window: VarInt) {
+        let mut conn = self.0.lock().unwrap();
+        conn.inner.set_receive_window(receive_window);
+        conn.wake();
+    }
+
+    /// Change the maximum amount of data the peer may send on any one stream without
+    /// acknowledgement
+    ///
+    /// Overrides `TransportConfig::stream_receive_window` for this connection. Credit already
+    /// issued to the peer cannot be revoked, so a smaller window takes effect as data is read.
+    pub fn set_stream_receive_window(&self, window: VarInt) {
+        let mut conn = self.0.lock().unwrap();
+        conn.inner.set_stream_receive_window(window);
+        conn.wake();
+    }
+
+    /// Change the interval at which keep-alives are sent, or disable them with `None`
+    ///
+    /// Overrides `TransportConfig::keep_alive_interval` for this connection.
+    pub fn set_keep_alive_interval(&self, interval: Option<Duration>) {
+        let mut conn = self.0.lock().unwrap();
+        conn.inner.set_keep_alive_interval(Instant::now(), interval);
+        conn.wake();
+    }
+
+    /// Replace the congestion controller with one constructed by `factory`
+    ///
+    /// The new controller starts from its initial state. Overrides
+    /// `TransportConfig::congestion_controller_factory` for this connection.
+    pub fn set_congestion_controller_factory(
+        &self,
+        factory: impl congestion::ControllerFactory + Send + Sync + 'static,
+    ) {
+        let mut conn = self.0.lock().unwrap();
+        conn.inner
+            .set_congestion_controller_factory(Instant::now(), factory);
+        conn.wake();
+    }
+
     /// A stable identifier for this connection
     ///
     /// Peer addresses and connection IDs can change, but this value will remain
@@ -485,6 +593,41 @@ where
         self.0.lock().unwrap().inner.initiate_key_update()
     }
 
+    /// Subscribe to changes in the state of the connection
+    ///
+    /// Only events which occur after this call are reported. The stream ends after yielding
+    /// [`LifecycleEvent::ConnectionLost`], and yields only that event if the connection has already
+    /// been lost. Unlike other handles, the stream does not keep the connection open.
+    pub fn events(&self) -> LifecycleEvents {
+        let (send, recv) = mpsc::unbounded();
+        let mut conn = self.0.lock().unwrap();
+        match conn.error {
+            Some(ref reason) => {
+                let _ = send.unbounded_send(LifecycleEvent::ConnectionLost {
+                    reason: reason.clone(),
+                });
+            }
+            None => conn.event_subscribers.push(send),
+        }
+        LifecycleEvents(recv)
+    }
+
+    /// Wait for the connection to be closed for any reason
+    ///
+    /// Resolves to [`ConnectionError::LocallyClosed`] if the connection was closed by the
+    /// application. Unlike other handles, the future does not keep the connection open.
+    pub fn closed(&self) -> Closed {
+        let (send, recv) = oneshot::channel();
+        let mut conn = self.0.lock().unwrap();
+        match conn.error {
+            Some(ref reason) => {
+                let _ = send.send(reason.clone());
+            }
+            None => conn.on_closed.push(send),
+        }
+        Closed(recv)
+    }
+
     /// Derive keying material from this connection's TLS session secrets.
     ///
     /// When both peers call this method with the same `label` and `context`
@@ -517,6 +660,82 @@ where
     }
 }
 
+/// A change in the state of a connection, reported by [`Connection::events()`]
+///
+/// [`Connection::events()`]: crate::generic::Connection::events
+#[derive(Debug, Clone)]
+pub enum LifecycleEvent {
+    /// The handshake completed
+    ///
+    /// Only reported to subscribers of a connection obtained from [`Connecting::into_0rtt()`].
+    ///
+    /// [`Connecting::into_0rtt()`]: crate::generic::Connecting::into_0rtt
+    Connected,
+    /// The peer began sending from a new address, which is now being validated
+    ///
+    /// Only occurs on servers, when a client migrates or its NAT rebinds.
+    PathMigrated {
+        /// The peer's new address
+        remote: SocketAddr,
+    },
+    /// New 1-RTT packet protection keys have been put in use
+    KeysUpdated {
+        /// Whether the update was initiated by the peer
+        by_peer: bool,
+    },
+    /// A new connection ID has been put in use for addressing packets to the peer
+    ConnectionIdRotated,
+    /// The server declined to process 0-RTT data, which must be resent if still needed
+    ///
+    /// Only occurs on clients.
+    ZeroRttRejected,
+    /// The congestion controller reduced the sending rate in response to congestion
+    Congested {
+        /// Whether congestion was signaled by ECN marks rather than packet loss
+        ecn: bool,
+        /// Whether enough consecutive packets were lost to reset the congestion window to
+        /// its minimum
+        persistent: bool,
+    },
+    /// The connection was lost, and no further events will occur
+    ConnectionLost {
+        /// Reason that the connection was closed
+        reason: ConnectionError,
+    },
+}
+
+/// Stream of [`LifecycleEvent`]s, obtained from [`Connection::events()`]
+///
+/// [`Connection::events()`]: crate::generic::Connection::events
+#[derive(Debug)]
+pub struct LifecycleEvents(mpsc::UnboundedReceiver<LifecycleEvent>);
+
+impl futures::Stream for LifecycleEvents {
+    type Item = LifecycleEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// Future that completes with the reason a connection was closed, obtained from
+/// [`Connection::closed()`]
+///
+/// [`Connection::closed()`]: crate::generic::Connection::closed
+#[derive(Debug)]
+pub struct Closed(oneshot::Receiver<ConnectionError>);
+
+impl Future for Closed {
+    type Output = ConnectionError;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // The sender is only dropped without sending if the connection state was torn down
+        // without being closed, which requires every handle to have been dropped
+        self.0
+            .poll_unpin(cx)
+            .map(|x| x.unwrap_or(ConnectionError::LocallyClosed))
+    }
+}
+
 /// A stream of unidirectional QUIC streams initiated by a remote peer.
 ///
 /// Incoming streams are *always* opened in the same order that the peer created them, but data can
@@ -611,6 +830,37 @@ where
     }
 }
 
+/// Stream of application-defined frames sent by the peer
+///
+/// Yields each frame's type and payload. Frames are buffered until read, so the stream should be
+/// polled by applications which register extension frame types: the connection is closed once
+/// more than [`TransportConfig::extension_frame_receive_buffer_size()`] bytes are buffered.
+///
+/// [`TransportConfig::extension_frame_receive_buffer_size()`]: crate::TransportConfig::extension_frame_receive_buffer_size
+#[derive(Debug)]
+pub struct ExtensionFrames<S: proto::crypto::Session>(ConnectionRef<S>);
+
+impl<S> futures::Stream for ExtensionFrames<S>
+where
+    S: proto::crypto::Session,
+{
+    type Item = Result<(VarInt, Bytes), ConnectionError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut conn = self.0.lock().unwrap();
+        if let Some(x) = conn.inner.recv_extension_frame() {
+            Poll::Ready(Some(Ok(x)))
+        } else if let Some(ConnectionError::LocallyClosed) = conn.error {
+            Poll::Ready(None)
+        } else if let Some(ref e) = conn.error {
+            Poll::Ready(Some(Err(e.clone())))
+        } else {
+            conn.extension_frame_reader = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
 /// A future that will resolve into an opened outgoing unidirectional stream
 pub struct OpenUni<S>
 where
@@ -690,6 +940,7 @@ where
         conn_events: mpsc::UnboundedReceiver<ConnectionEvent>,
         on_handshake_data: oneshot::Sender<()>,
         on_connected: oneshot::Sender<bool>,
+        runtime: Arc<dyn Runtime>,
     ) -> Self {
         Self(Arc::new(Mutex::new(ConnectionInner {
             inner: conn,
@@ -709,10 +960,14 @@ where
             incoming_uni_streams_reader: None,
             incoming_bi_streams_reader: None,
             datagram_reader: None,
+            extension_frame_reader: None,
             finishing: HashMap::new(),
             stopped: HashMap::new(),
+            event_subscribers: Vec::new(),
+            on_closed: Vec::new(),
             error: None,
             ref_count: 0,
+            runtime,
         })))
     }
 
@@ -770,8 +1025,8 @@ where
     on_handshake_data: Option<oneshot::Sender<()>>,
     on_connected: Option<oneshot::Sender<bool>>,
     connected: bool,
-    timer: Option<Delay>,
-    timer_deadline: Option<TokioInstant>,
+    timer: Option<Pin<Box<dyn AsyncTimer>>>,
+    timer_deadline: Option<Instant>,
     conn_events: mpsc::UnboundedReceiver<ConnectionEvent>,
     endpoint_events: mpsc::UnboundedSender<(ConnectionHandle, EndpointEvent)>,
     pub(crate) blocked_writers: HashMap<StreamId, Waker>,
@@ -781,12 +1036,16 @@ where
     incoming_uni_streams_reader: Option<Waker>,
     incoming_bi_streams_reader: Option<Waker>,
     datagram_reader: Option<Waker>,
+    extension_frame_reader: Option<Waker>,
     pub(crate) finishing: HashMap<StreamId, oneshot::Sender<Option<WriteError>>>,
     pub(crate) stopped: HashMap<StreamId, Waker>,
+    event_subscribers: Vec<mpsc::UnboundedSender<LifecycleEvent>>,
+    on_closed: Vec<oneshot::Sender<ConnectionError>>,
     /// Always set to Some before the connection becomes drained
     pub(crate) error: Option<ConnectionError>,
     /// Number of live handles that can be used to initiate or handle I/O; excludes the driver
     ref_count: usize,
+    runtime: Arc<dyn Runtime>,
 }
 
 impl<S> ConnectionInner<S>
@@ -851,6 +1110,7 @@ where
                         // We don't care if the on-connected future was dropped
                         let _ = x.send(self.inner.accepted_0rtt());
                     }
+                    self.publish(LifecycleEvent::Connected);
                 }
                 ConnectionLost { reason } => {
                     self.terminate(reason);
@@ -875,6 +1135,18 @@ where
                         x.wake();
                     }
                 }
+                ExtensionFrameReceived => {
+                    if let Some(x) = self.extension_frame_reader.take() {
+                        x.wake();
+                    }
+                }
+                PathMigrated { remote } => self.publish(LifecycleEvent::PathMigrated { remote }),
+                KeysUpdated { by_peer } => self.publish(LifecycleEvent::KeysUpdated { by_peer }),
+                ConnectionIdRotated => self.publish(LifecycleEvent::ConnectionIdRotated),
+                ZeroRttRejected => self.publish(LifecycleEvent::ZeroRttRejected),
+                Congested { ecn, persistent } => {
+                    self.publish(LifecycleEvent::Congested { ecn, persistent })
+                }
                 Stream(StreamEvent::Readable { id }) => {
                     if let Some(reader) = self.blocked_readers.remove(&id) {
                         reader.wake();
@@ -912,20 +1184,20 @@ where
         // Check whether we need to (re)set the timer. If so, we must poll again to ensure the
         // timer is registered with the runtime (and check whether it's already
         // expired).
-        match self.inner.poll_timeout().map(TokioInstant::from_std) {
+        match self.inner.poll_timeout() {
             Some(deadline) => {
                 if let Some(delay) = &mut self.timer {
-                    // There is no need to reset the tokio timer if the deadline
+                    // There is no need to reset the timer if the deadline
                     // did not change
                     if self
                         .timer_deadline
                         .map(|current_deadline| current_deadline != deadline)
                         .unwrap_or(true)
                     {
-                        delay.reset(deadline);
+                        delay.as_mut().reset(deadline);
                     }
                 } else {
-                    self.timer = Some(delay_until(deadline));
+                    self.timer = Some(self.runtime.new_timer(deadline));
                 }
                 // Store the actual expiration time of the timer
                 self.timer_deadline = Some(deadline);
@@ -941,7 +1213,7 @@ where
         }
 
         let delay = self.timer.as_mut().expect("timer must exist in this state");
-        if delay.poll_unpin(cx).is_pending() {
+        if delay.as_mut().poll(cx).is_pending() {
             // Since there wasn't a timeout event, there is nothing new
             // for the connection to do
             return false;
@@ -954,6 +1226,12 @@ where
         true
     }
 
+    /// Report `event` to every live subscriber
+    fn publish(&mut self, event: LifecycleEvent) {
+        self.event_subscribers
+            .retain(|x| x.unbounded_send(event.clone()).is_ok());
+    }
+
     /// Wake up a blocked `Driver` task to process I/O
     pub(crate) fn wake(&mut self) {
         if let Some(x) = self.driver.take() {
@@ -981,6 +1259,9 @@ where
         if let Some(x) = self.datagram_reader.take() {
             x.wake();
         }
+        if let Some(x) = self.extension_frame_reader.take() {
+            x.wake();
+        }
         for (_, x) in self.finishing.drain() {
             let _ = x.send(Some(WriteError::ConnectionClosed(reason.clone())));
         }
@@ -990,6 +1271,14 @@ where
         for (_, waker) in self.stopped.drain() {
             waker.wake();
         }
+        self.publish(LifecycleEvent::ConnectionLost {
+            reason: reason.clone(),
+        });
+        // Dropping the senders ends the subscribers' streams
+        self.event_subscribers.clear();
+        for x in self.on_closed.drain(..) {
+            let _ = x.send(reason.clone());
+        }
     }
 
     fn close(&mut self, error_code: VarInt, reason: Bytes) {
@@ -1041,6 +1330,22 @@ where
     }
 }
 
+/// Errors that can arise when sending an application-defined frame
+#[derive(Debug, Error, Clone, Eq, PartialEq)]
+pub enum SendExtensionFrameError {
+    /// The frame type was not registered with [`TransportConfig::extension_frame()`]
+    ///
+    /// [`TransportConfig::extension_frame()`]: crate::TransportConfig::extension_frame
+    #[error("unknown frame type")]
+    UnknownType,
+    /// The encoded frame does not fit in a single packet
+    #[error("frame too large")]
+    TooLarge,
+    /// The connection was closed
+    #[error("connection closed: {0}")]
+    ConnectionClosed(#[source] ConnectionError),
+}
+
 /// Errors that can arise when sending a datagram
 #[derive(Debug, Error, Clone, Eq, PartialEq)]
 pub enum SendDatagramError {
@@ -3,41 +3,64 @@ use std::{
     future::Future,
     io,
     io::IoSliceMut,
-    mem::MaybeUninit,
+    mem::{self, MaybeUninit},
     net::{SocketAddr, SocketAddrV6},
     pin::Pin,
     str,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     task::{Context, Poll, Waker},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use bytes::Bytes;
-use futures::{channel::mpsc, StreamExt};
-use proto::{self as proto, generic::ClientConfig, ConnectError, ConnectionHandle, DatagramEvent};
+use bytes::{Bytes, BytesMut};
+use futures::{channel::mpsc, future, stream::FuturesUnordered, StreamExt};
+use proto::{
+    self as proto,
+    generic::{ClientConfig, ServerConfig},
+    pcap::PcapNgWriter,
+    ConnectError, ConnectionHandle, DatagramEvent, EndpointStats, ShardedConnectionIdGenerator,
+    UdpStats,
+};
 
 use crate::{
     broadcast::{self, Broadcast},
     builders::EndpointBuilder,
-    connection::Connecting,
+    connection::{Connecting, NewConnection},
     platform::BATCH_SIZE,
-    udp::{RecvMeta, UdpSocket},
+    resolve::{sort_addresses, split_host, ConnectHostError, Resolver},
+    runtime::{AsyncTimer, AsyncUdpSocket, Runtime},
+    udp::RecvMeta,
     ConnectionEvent, EndpointEvent, VarInt, IO_LOOP_BOUND,
 };
 
+/// Delay between successive attempts of [`Endpoint::connect_host()`], as recommended by RFC 8305
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
 /// A QUIC endpoint.
 ///
 /// An endpoint corresponds to a single UDP socket, may host many connections, and may act as both
-/// client and server for different connections.
+/// client and server for different connections. An endpoint built with
+/// [`EndpointBuilder::shards()`] instead spreads its connections across several sockets sharing
+/// the same address.
 ///
 /// May be cloned to obtain another handle to the same endpoint.
+///
+/// [`EndpointBuilder::shards()`]: crate::generic::EndpointBuilder::shards
 #[derive(Debug)]
 pub struct Endpoint<S>
 where
     S: proto::crypto::Session,
 {
-    pub(crate) inner: EndpointRef<S>,
+    pub(crate) shards: Vec<EndpointRef<S>>,
     pub(crate) default_client_config: ClientConfig<S>,
+    /// Used to spread outgoing connections across shards
+    next_shard: Arc<AtomicUsize>,
+    resolver: Arc<dyn Resolver>,
+    /// Capture in which the secrets of replacement server configurations are embedded
+    capture: Option<Arc<PcapNgWriter>>,
 }
 
 impl<S> Endpoint<S>
@@ -49,6 +72,21 @@ where
         EndpointBuilder::default()
     }
 
+    pub(crate) fn new(
+        shards: Vec<EndpointRef<S>>,
+        default_client_config: ClientConfig<S>,
+        resolver: Arc<dyn Resolver>,
+        capture: Option<Arc<PcapNgWriter>>,
+    ) -> Self {
+        Self {
+            shards,
+            default_client_config,
+            next_shard: Arc::new(AtomicUsize::new(0)),
+            resolver,
+            capture,
+        }
+    }
+
     /// Connect to a remote endpoint
     ///
     /// `server_name` must be covered by the certificate presented by the server. This prevents a
@@ -76,7 +114,8 @@ where
         addr: &SocketAddr,
         server_name: &str,
     ) -> Result<Connecting<S>, ConnectError> {
-        let mut endpoint = self.inner.lock().unwrap();
+        let shard = self.next_shard.fetch_add(1, Ordering::Relaxed) % self.shards.len();
+        let mut endpoint = self.shards[shard].lock().unwrap();
         if endpoint.driver_lost {
             return Err(ConnectError::EndpointStopping);
         }
@@ -92,24 +131,196 @@ where
         Ok(endpoint.connections.insert(ch, conn))
     }
 
+    /// Connect to a remote endpoint by hostname, e.g. `"example.com:443"`
+    ///
+    /// The host is looked up with the endpoint's [`Resolver`], and connections to the resulting
+    /// addresses are raced as described in RFC 8305: IPv6 and IPv4 addresses are interleaved, and
+    /// a new attempt is started every 250ms, or as soon as the previous one fails, until one
+    /// completes its handshake. The remaining attempts are then abandoned. The hostname is used
+    /// as the server name; see [`connect()`] for details.
+    ///
+    /// Fails if every attempt fails, with the error of the last one.
+    ///
+    /// [`Resolver`]: crate::Resolver
+    /// [`connect()`]: Endpoint::connect
+    pub async fn connect_host(&self, host: &str) -> Result<NewConnection<S>, ConnectHostError> {
+        self.connect_host_with(self.default_client_config.clone(), host)
+            .await
+    }
+
+    /// Connect to a remote endpoint by hostname using a custom configuration.
+    ///
+    /// See [`connect_host()`] for details.
+    ///
+    /// [`connect_host()`]: Endpoint::connect_host
+    pub async fn connect_host_with(
+        &self,
+        config: ClientConfig<S>,
+        host: &str,
+    ) -> Result<NewConnection<S>, ConnectHostError> {
+        let (name, port) = split_host(host)?;
+        self.connect_resolved_with(config, &*self.resolver, name, port, name)
+            .await
+    }
+
+    /// Connect to `host` on `port` as looked up by `resolver`
+    ///
+    /// Addresses are raced as for [`connect_host()`], but the server is authenticated as
+    /// `server_name`, which may differ from `host`, e.g. when reaching an alternative service.
+    ///
+    /// [`connect_host()`]: Endpoint::connect_host
+    pub async fn connect_resolved(
+        &self,
+        resolver: &dyn Resolver,
+        host: &str,
+        port: u16,
+        server_name: &str,
+    ) -> Result<NewConnection<S>, ConnectHostError> {
+        let config = self.default_client_config.clone();
+        self.connect_resolved_with(config, resolver, host, port, server_name)
+            .await
+    }
+
+    /// Connect to `host` on `port` as looked up by `resolver`, using a custom configuration
+    ///
+    /// See [`connect_resolved()`] for details.
+    ///
+    /// [`connect_resolved()`]: Endpoint::connect_resolved
+    pub async fn connect_resolved_with(
+        &self,
+        config: ClientConfig<S>,
+        resolver: &dyn Resolver,
+        host: &str,
+        port: u16,
+        server_name: &str,
+    ) -> Result<NewConnection<S>, ConnectHostError> {
+        let addrs = resolver
+            .resolve(host, port)
+            .await
+            .map_err(ConnectHostError::Resolve)?;
+        let (ipv6, runtime) = {
+            let endpoint = self.shards[0].lock().unwrap();
+            (endpoint.ipv6, endpoint.connections.runtime.clone())
+        };
+
+        let mut candidates = sort_addresses(addrs, ipv6).into_iter();
+        let mut attempts = FuturesUnordered::new();
+        let mut timer: Option<Pin<Box<dyn AsyncTimer>>> = None;
+        let mut start_next = true;
+        let mut error = ConnectHostError::NoAddresses;
+        future::poll_fn(|cx| loop {
+            if start_next
+                || attempts.is_empty()
+                || timer
+                    .as_mut()
+                    .map_or(false, |t| t.as_mut().poll(cx).is_ready())
+            {
+                start_next = false;
+                match candidates.next() {
+                    Some(addr) => {
+                        match self.connect_with(config.clone(), &addr, server_name) {
+                            Ok(connecting) => {
+                                attempts.push(connecting);
+                                let deadline = Instant::now() + CONNECTION_ATTEMPT_DELAY;
+                                timer = Some(runtime.new_timer(deadline));
+                            }
+                            Err(e) => {
+                                error = e.into();
+                                start_next = true;
+                            }
+                        }
+                        continue;
+                    }
+                    None => {
+                        timer = None;
+                        if attempts.is_empty() {
+                            let error = mem::replace(&mut error, ConnectHostError::NoAddresses);
+                            return Poll::Ready(Err(error));
+                        }
+                    }
+                }
+            }
+            match attempts.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(conn))) => return Poll::Ready(Ok(conn)),
+                Poll::Ready(Some(Err(e))) => {
+                    error = e.into();
+                    start_next = true;
+                }
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        })
+        .await
+    }
+
     /// Switch to a new UDP socket
     ///
     /// Allows the endpoint's address to be updated live, affecting all active connections. Incoming
     /// connections and connections to servers unreachable from the new address will be lost.
     ///
-    /// On error, the old UDP socket is retained.
+    /// On error, the old UDP socket is retained. Sharded endpoints cannot be rebound.
     pub fn rebind(&self, socket: std::net::UdpSocket) -> io::Result<()> {
+        let runtime = self.shards[0].lock().unwrap().connections.runtime.clone();
+        self.rebind_async(runtime.wrap_udp_socket(socket)?)
+    }
+
+    /// Switch to a new custom datagram transport
+    ///
+    /// Like [`rebind()`](Self::rebind), but for transports supplied by the application, as with
+    /// [`EndpointBuilder::with_async_socket()`].
+    pub fn rebind_async(&self, socket: Box<dyn AsyncUdpSocket>) -> io::Result<()> {
+        if self.shards.len() > 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "sharded endpoints cannot be rebound",
+            ));
+        }
         let addr = socket.local_addr()?;
-        let socket = UdpSocket::from_std(socket)?;
-        let mut inner = self.inner.lock().unwrap();
+        let mut inner = self.shards[0].lock().unwrap();
         inner.socket = socket;
         inner.ipv6 = addr.is_ipv6();
         Ok(())
     }
 
+    /// Returns endpoint statistics
+    ///
+    /// Unlike [`proto::Endpoint::stats()`], `udp_tx` covers every datagram sent on the endpoint's
+    /// socket, including those sent on behalf of connections. Statistics of sharded endpoints are
+    /// summed across all shards.
+    ///
+    /// [`proto::Endpoint::stats()`]: proto::generic::Endpoint::stats
+    pub fn stats(&self) -> EndpointStats {
+        let mut stats = EndpointStats::default();
+        for shard in &self.shards {
+            let endpoint = shard.lock().unwrap();
+            let mut shard_stats = endpoint.inner.stats();
+            shard_stats.udp_tx = endpoint.udp_tx;
+            stats += shard_stats;
+        }
+        stats
+    }
+
+    /// Replace the server configuration, affecting new incoming connections only
+    ///
+    /// Established connections and those already handshaking are unaffected, so certificates can
+    /// be rotated or transport parameters changed without a restart. `None` stops the endpoint
+    /// from accepting new connections without closing existing ones.
+    pub fn set_server_config(&self, mut server_config: Option<ServerConfig<S>>) {
+        if let (Some(config), Some(capture)) = (server_config.as_mut(), self.capture.as_ref()) {
+            config.capture_secrets(capture.clone());
+        }
+        let server_config = server_config.map(Arc::new);
+        for shard in &self.shards {
+            shard
+                .lock()
+                .unwrap()
+                .inner
+                .set_server_config(server_config.clone());
+        }
+    }
+
     /// Get the local `SocketAddr` the underlying socket is bound to
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        self.inner.lock().unwrap().socket.local_addr()
+        self.shards[0].lock().unwrap().socket.local_addr()
     }
 
     /// Close all of this endpoint's connections immediately and cease accepting new connections.
@@ -119,17 +330,19 @@ where
     /// [`Connection::close()`]: crate::generic::Connection::close
     pub fn close(&self, error_code: VarInt, reason: &[u8]) {
         let reason = Bytes::copy_from_slice(reason);
-        let mut endpoint = self.inner.lock().unwrap();
-        endpoint.connections.close = Some((error_code, reason.clone()));
-        for sender in endpoint.connections.senders.values() {
-            // Ignoring errors from dropped connections
-            let _ = sender.unbounded_send(ConnectionEvent::Close {
-                error_code,
-                reason: reason.clone(),
-            });
-        }
-        if let Some(task) = endpoint.incoming_reader.take() {
-            task.wake();
+        for shard in &self.shards {
+            let mut endpoint = shard.lock().unwrap();
+            endpoint.connections.close = Some((error_code, reason.clone()));
+            for sender in endpoint.connections.senders.values() {
+                // Ignoring errors from dropped connections
+                let _ = sender.unbounded_send(ConnectionEvent::Close {
+                    error_code,
+                    reason: reason.clone(),
+                });
+            }
+            if let Some(task) = endpoint.incoming_reader.take() {
+                task.wake();
+            }
         }
     }
 
@@ -146,16 +359,18 @@ where
     /// [`close()`]: Endpoint::close
     /// [`Incoming`]: crate::generic::Incoming
     pub async fn wait_idle(&self) {
-        let mut state = broadcast::State::default();
-        futures::future::poll_fn(|cx| {
-            let endpoint = &mut *self.inner.lock().unwrap();
-            if endpoint.connections.is_empty() {
-                return Poll::Ready(());
-            }
-            endpoint.idle.register(cx, &mut state);
-            Poll::Pending
-        })
-        .await;
+        for shard in &self.shards {
+            let mut state = broadcast::State::default();
+            futures::future::poll_fn(|cx| {
+                let endpoint = &mut *shard.lock().unwrap();
+                if endpoint.connections.is_empty() {
+                    return Poll::Ready(());
+                }
+                endpoint.idle.register(cx, &mut state);
+                Poll::Pending
+            })
+            .await;
+        }
     }
 }
 
@@ -165,8 +380,11 @@ where
 {
     fn clone(&self) -> Self {
         Endpoint {
-            inner: self.inner.clone(),
+            shards: self.shards.clone(),
             default_client_config: self.default_client_config.clone(),
+            next_shard: self.next_shard.clone(),
+            resolver: self.resolver.clone(),
+            capture: self.capture.clone(),
         }
     }
 }
@@ -183,7 +401,29 @@ where
 /// have been dropped, or when an I/O error occurs.
 #[must_use = "endpoint drivers must be spawned for I/O to occur"]
 #[derive(Debug)]
-pub(crate) struct EndpointDriver<S: proto::crypto::Session>(pub(crate) EndpointRef<S>);
+pub(crate) struct EndpointDriver<S: proto::crypto::Session> {
+    endpoint: EndpointRef<S>,
+    recv_buf: Box<[u8]>,
+}
+
+impl<S> EndpointDriver<S>
+where
+    S: proto::crypto::Session,
+{
+    pub(crate) fn new(endpoint: EndpointRef<S>) -> Self {
+        let max_udp_payload_size = endpoint
+            .lock()
+            .unwrap()
+            .inner
+            .config()
+            .get_max_udp_payload_size();
+        let recv_buf = vec![0; max_udp_payload_size.min(64 * 1024) as usize * BATCH_SIZE];
+        Self {
+            endpoint,
+            recv_buf: recv_buf.into(),
+        }
+    }
+}
 
 impl<S> Future for EndpointDriver<S>
 where
@@ -191,14 +431,16 @@ where
 {
     type Output = Result<(), io::Error>;
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-        let endpoint = &mut *self.0.lock().unwrap();
+        let this = self.get_mut();
+        let endpoint = &mut *this.endpoint.lock().unwrap();
         if endpoint.driver.is_none() {
             endpoint.driver = Some(cx.waker().clone());
         }
         loop {
             let now = Instant::now();
             let mut keep_going = false;
-            keep_going |= endpoint.drive_recv(cx, now)?;
+            keep_going |= endpoint.drive_forwarded(cx, now);
+            keep_going |= endpoint.drive_recv(cx, now, &mut this.recv_buf)?;
             endpoint.handle_events(cx);
             keep_going |= endpoint.drive_send(cx)?;
             if !keep_going {
@@ -223,7 +465,7 @@ where
     S: proto::crypto::Session,
 {
     fn drop(&mut self) {
-        let mut endpoint = self.0.lock().unwrap();
+        let mut endpoint = self.endpoint.lock().unwrap();
         endpoint.driver_lost = true;
         if let Some(task) = endpoint.incoming_reader.take() {
             task.wake();
@@ -239,9 +481,11 @@ pub(crate) struct EndpointInner<S>
 where
     S: proto::crypto::Session,
 {
-    socket: UdpSocket,
+    socket: Box<dyn AsyncUdpSocket>,
     inner: proto::generic::Endpoint<S>,
     outgoing: VecDeque<proto::Transmit>,
+    /// Datagrams sent on the socket, both stateless and on behalf of connections
+    udp_tx: UdpStats,
     incoming: VecDeque<Connecting<S>>,
     incoming_reader: Option<Waker>,
     driver: Option<Waker>,
@@ -251,20 +495,27 @@ where
     /// Number of live handles that can be used to initiate or handle I/O; excludes the driver
     ref_count: usize,
     driver_lost: bool,
-    recv_buf: Box<[u8]>,
     idle: Broadcast,
+    /// Set if this is one of several endpoints sharing a socket address
+    shard: Option<Shard>,
 }
 
 impl<S> EndpointInner<S>
 where
     S: proto::crypto::Session + 'static,
 {
-    fn drive_recv<'a>(&'a mut self, cx: &mut Context, now: Instant) -> Result<bool, io::Error> {
+    fn drive_recv<'a>(
+        &mut self,
+        cx: &mut Context,
+        now: Instant,
+        recv_buf: &'a mut [u8],
+    ) -> Result<bool, io::Error> {
         let mut recvd = 0;
         let mut metas = [RecvMeta::default(); BATCH_SIZE];
         let mut iovs = MaybeUninit::<[IoSliceMut<'a>; BATCH_SIZE]>::uninit();
-        self.recv_buf
-            .chunks_mut(self.recv_buf.len() / BATCH_SIZE)
+        let chunk_size = recv_buf.len() / BATCH_SIZE;
+        recv_buf
+            .chunks_mut(chunk_size)
             .enumerate()
             .for_each(|(i, buf)| unsafe {
                 iovs.as_mut_ptr()
@@ -278,26 +529,16 @@ where
                 Poll::Ready(Ok(msgs)) => {
                     recvd += msgs;
                     for (meta, buf) in metas.iter().zip(iovs.iter()).take(msgs) {
-                        let data = buf[0..meta.len].into();
-                        match self
-                            .inner
-                            .handle(now, meta.addr, meta.dst_ip, meta.ecn, data)
-                        {
-                            Some((handle, DatagramEvent::NewConnection(conn))) => {
-                                let conn = self.connections.insert(handle, conn);
-                                self.incoming.push_back(conn);
-                            }
-                            Some((handle, DatagramEvent::ConnectionEvent(event))) => {
-                                // Ignoring errors from dropped connections that haven't yet been cleaned up
-                                let _ = self
-                                    .connections
-                                    .senders
-                                    .get_mut(&handle)
-                                    .unwrap()
-                                    .unbounded_send(ConnectionEvent::Proto(event));
+                        let data: BytesMut = buf[0..meta.len].into();
+                        if let Some(shard) = &self.shard {
+                            let owner = shard.owner(&data);
+                            if owner != shard.index {
+                                // Ignoring errors from shards that have already shut down
+                                let _ = shard.peers[owner].unbounded_send((*meta, data));
+                                continue;
                             }
-                            None => {}
                         }
+                        self.handle_datagram(now, meta, data);
                     }
                 }
                 Poll::Pending => {
@@ -319,6 +560,48 @@ where
         Ok(false)
     }
 
+    /// Handle datagrams that other shards received on our behalf
+    fn drive_forwarded(&mut self, cx: &mut Context, now: Instant) -> bool {
+        let mut recvd = 0;
+        loop {
+            let (meta, data) = match self.shard {
+                Some(ref mut shard) => match shard.forwarded.poll_next_unpin(cx) {
+                    Poll::Ready(Some(x)) => x,
+                    Poll::Ready(None) => unreachable!("each shard holds a sender to itself"),
+                    Poll::Pending => return false,
+                },
+                None => return false,
+            };
+            self.handle_datagram(now, &meta, data);
+            recvd += 1;
+            if recvd >= IO_LOOP_BOUND {
+                return true;
+            }
+        }
+    }
+
+    fn handle_datagram(&mut self, now: Instant, meta: &RecvMeta, data: BytesMut) {
+        match self
+            .inner
+            .handle(now, meta.addr, meta.dst_ip, meta.ecn, data)
+        {
+            Some((handle, DatagramEvent::NewConnection(conn))) => {
+                let conn = self.connections.insert(handle, conn);
+                self.incoming.push_back(conn);
+            }
+            Some((handle, DatagramEvent::ConnectionEvent(event))) => {
+                // Ignoring errors from dropped connections that haven't yet been cleaned up
+                let _ = self
+                    .connections
+                    .senders
+                    .get_mut(&handle)
+                    .unwrap()
+                    .unbounded_send(ConnectionEvent::Proto(event));
+            }
+            None => {}
+        }
+    }
+
     fn drive_send(&mut self, cx: &mut Context) -> Result<bool, io::Error> {
         let mut calls = 0;
         loop {
@@ -333,7 +616,10 @@ where
             }
             match self.socket.poll_send(cx, self.outgoing.as_slices().0) {
                 Poll::Ready(Ok(n)) => {
-                    self.outgoing.drain(..n);
+                    for transmit in self.outgoing.drain(..n) {
+                        self.udp_tx.datagrams += 1;
+                        self.udp_tx.bytes += transmit.contents.len() as u64;
+                    }
                     calls += 1;
                     if calls == IO_LOOP_BOUND {
                         return Ok(true);
@@ -391,6 +677,8 @@ struct ConnectionSet {
     senders: HashMap<ConnectionHandle, mpsc::UnboundedSender<ConnectionEvent>>,
     /// Stored to give out clones to new ConnectionInners
     sender: mpsc::UnboundedSender<(ConnectionHandle, EndpointEvent)>,
+    /// Drives and times the endpoint's connections
+    runtime: Arc<dyn Runtime>,
     /// Set if the endpoint has been manually closed
     close: Option<(VarInt, Bytes)>,
 }
@@ -410,7 +698,13 @@ impl ConnectionSet {
             .unwrap();
         }
         self.senders.insert(handle, send);
-        Connecting::new(handle, conn, self.sender.clone(), recv)
+        Connecting::new(
+            handle,
+            conn,
+            self.sender.clone(),
+            recv,
+            self.runtime.clone(),
+        )
     }
 
     fn is_empty(&self) -> bool {
@@ -418,6 +712,62 @@ impl ConnectionSet {
     }
 }
 
+/// Routing state for one of several endpoints sharing a socket address
+///
+/// The kernel balances incoming datagrams across the shards' sockets by hashing the 4-tuple, so a
+/// shard may receive datagrams for a connection owned by another, e.g. after the peer's NAT
+/// rebinds. Every shard embeds its index in the CIDs it issues, which allows such datagrams to be
+/// forwarded to their owner.
+#[derive(Debug)]
+pub(crate) struct Shard {
+    index: usize,
+    /// Length of the CIDs issued by every shard
+    cid_len: usize,
+    /// Channels to every shard's driver, including our own, indexed by shard
+    peers: Arc<[mpsc::UnboundedSender<(RecvMeta, BytesMut)>]>,
+    /// Datagrams received by other shards on our behalf
+    forwarded: mpsc::UnboundedReceiver<(RecvMeta, BytesMut)>,
+}
+
+impl Shard {
+    /// Construct the routing state for `count` shards issuing CIDs of length `cid_len`
+    pub(crate) fn new_set(count: usize, cid_len: usize) -> Vec<Self> {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..count).map(|_| mpsc::unbounded()).unzip();
+        let peers = Arc::<[_]>::from(senders);
+        receivers
+            .into_iter()
+            .enumerate()
+            .map(|(index, forwarded)| Self {
+                index,
+                cid_len,
+                peers: peers.clone(),
+                forwarded,
+            })
+            .collect()
+    }
+
+    /// Identify the shard responsible for a datagram
+    ///
+    /// Datagrams addressed to a CID chosen by the peer, e.g. a client's first Initial, are
+    /// assigned to a shard deterministically so that all of them reach the same one. Datagrams
+    /// whose destination CID can't be found are handled by the shard that received them.
+    fn owner(&self, datagram: &[u8]) -> usize {
+        let dst_cid = match datagram.first() {
+            // Long header: version, then a length-prefixed destination CID
+            Some(&first) if first & 0x80 != 0 => match datagram.get(5) {
+                Some(&len) => datagram.get(6..6 + len as usize),
+                None => None,
+            },
+            Some(_) => datagram.get(1..1 + self.cid_len),
+            None => None,
+        };
+        match dst_cid.and_then(ShardedConnectionIdGenerator::shard_of) {
+            Some(shard) => shard as usize % self.peers.len(),
+            None => self.index,
+        }
+    }
+}
+
 fn ensure_ipv6(x: SocketAddr) -> SocketAddrV6 {
     match x {
         SocketAddr::V6(x) => x,
@@ -426,15 +776,21 @@ fn ensure_ipv6(x: SocketAddr) -> SocketAddrV6 {
 }
 
 /// Stream of incoming connections.
+///
+/// For sharded endpoints, yields the incoming connections of every shard.
 #[derive(Debug)]
-pub struct Incoming<S: proto::crypto::Session>(EndpointRef<S>);
+pub struct Incoming<S: proto::crypto::Session> {
+    shards: Vec<EndpointRef<S>>,
+    /// Shard to check first on the next poll, so that no shard is starved
+    next: usize,
+}
 
 impl<S> Incoming<S>
 where
     S: proto::crypto::Session,
 {
-    pub(crate) fn new(inner: EndpointRef<S>) -> Self {
-        Self(inner)
+    pub(crate) fn new(shards: Vec<EndpointRef<S>>) -> Self {
+        Self { shards, next: 0 }
     }
 }
 
@@ -444,17 +800,23 @@ where
 {
     type Item = Connecting<S>;
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        let endpoint = &mut *self.0.lock().unwrap();
-        if endpoint.driver_lost {
-            Poll::Ready(None)
-        } else if let Some(conn) = endpoint.incoming.pop_front() {
-            Poll::Ready(Some(conn))
-        } else if endpoint.connections.close.is_some() {
-            Poll::Ready(None)
-        } else {
-            endpoint.incoming_reader = Some(cx.waker().clone());
-            Poll::Pending
+        let this = self.get_mut();
+        let count = this.shards.len();
+        for i in 0..count {
+            let shard = (this.next + i) % count;
+            let endpoint = &mut *this.shards[shard].lock().unwrap();
+            if endpoint.driver_lost {
+                return Poll::Ready(None);
+            } else if let Some(conn) = endpoint.incoming.pop_front() {
+                this.next = (shard + 1) % count;
+                return Poll::Ready(Some(conn));
+            } else if endpoint.connections.close.is_some() {
+                return Poll::Ready(None);
+            } else {
+                endpoint.incoming_reader = Some(cx.waker().clone());
+            }
         }
+        Poll::Pending
     }
 }
 
@@ -463,9 +825,11 @@ where
     S: proto::crypto::Session,
 {
     fn drop(&mut self) {
-        let endpoint = &mut *self.0.lock().unwrap();
-        endpoint.inner.reject_new_connections();
-        endpoint.incoming_reader = None;
+        for shard in &self.shards {
+            let endpoint = &mut *shard.lock().unwrap();
+            endpoint.inner.reject_new_connections();
+            endpoint.incoming_reader = None;
+        }
     }
 }
 
@@ -476,9 +840,13 @@ impl<S> EndpointRef<S>
 where
     S: proto::crypto::Session,
 {
-    pub(crate) fn new(socket: UdpSocket, inner: proto::generic::Endpoint<S>, ipv6: bool) -> Self {
-        let recv_buf =
-            vec![0; inner.config().get_max_udp_payload_size().min(64 * 1024) as usize * BATCH_SIZE];
+    pub(crate) fn new(
+        socket: Box<dyn AsyncUdpSocket>,
+        inner: proto::generic::Endpoint<S>,
+        ipv6: bool,
+        shard: Option<Shard>,
+        runtime: Arc<dyn Runtime>,
+    ) -> Self {
         let (sender, events) = mpsc::unbounded();
         Self(Arc::new(Mutex::new(EndpointInner {
             socket,
@@ -486,18 +854,20 @@ where
             ipv6,
             events,
             outgoing: VecDeque::new(),
+            udp_tx: UdpStats::default(),
             incoming: VecDeque::new(),
             incoming_reader: None,
             driver: None,
             connections: ConnectionSet {
                 senders: HashMap::new(),
                 sender,
+                runtime,
                 close: None,
             },
             ref_count: 0,
             driver_lost: false,
-            recv_buf: recv_buf.into(),
             idle: Broadcast::new(),
+            shard,
         })))
     }
 }
@@ -55,28 +55,44 @@ mod builders;
 mod connection;
 mod endpoint;
 mod platform;
+mod resolve;
+mod runtime;
 mod streams;
 mod udp;
 
 pub use proto::{
-    crypto, ApplicationClose, Certificate, CertificateChain, ConnectError, ConnectionClose,
-    ConnectionError, ParseError, PrivateKey, StreamId, Transmit, TransportConfig, VarInt,
+    congestion, crypto, extension, pcap::PcapNgWriter, ApplicationClose, Certificate,
+    CertificateChain, ConnectError, ConnectionClose, ConnectionError, Dir, DropStats,
+    EndpointStats, ParseError, PrivateKey, ServerNameResolver, StreamId, Transmit, TransportConfig,
+    UdpStats, VarInt,
 };
 
 pub use crate::builders::EndpointError;
-pub use crate::connection::{SendDatagramError, ZeroRttAccepted};
+pub use crate::connection::{
+    Closed, LifecycleEvent, LifecycleEvents, SendDatagramError, SendExtensionFrameError,
+    ZeroRttAccepted,
+};
+pub use crate::resolve::{ConnectHostError, Resolver, StaticResolver, SystemResolver};
+#[cfg(feature = "runtime-async-std")]
+pub use crate::runtime::AsyncStdRuntime;
+#[cfg(feature = "runtime-smol")]
+pub use crate::runtime::SmolRuntime;
+#[cfg(feature = "runtime-tokio")]
+pub use crate::runtime::TokioRuntime;
+pub use crate::runtime::{default_runtime, AsyncTimer, AsyncUdpSocket, Runtime};
 pub use crate::streams::{ReadError, ReadExactError, ReadToEndError, StoppedError, WriteError};
+pub use crate::udp::RecvMeta;
 
 /// Types that are generic over the crypto protocol implementation
 pub mod generic {
     pub use crate::builders::{ClientConfigBuilder, EndpointBuilder, ServerConfigBuilder};
     pub use crate::connection::{
-        Connecting, Connection, Datagrams, IncomingBiStreams, IncomingUniStreams, NewConnection,
-        OpenBi, OpenUni,
+        Connecting, Connection, Datagrams, ExtensionFrames, IncomingBiStreams, IncomingUniStreams,
+        NewConnection, OpenBi, OpenUni,
     };
     pub use crate::endpoint::{Endpoint, Incoming};
     pub use crate::streams::{Read, ReadExact, ReadToEnd, ReadUnordered, RecvStream, SendStream};
-    pub use proto::generic::{ClientConfig, ServerConfig};
+    pub use proto::generic::{ClientConfig, ServerConfig, ServerNameConfig, ServerNameStore};
 }
 
 #[cfg(feature = "rustls")]
@@ -88,6 +104,10 @@ mod rustls_impls {
     pub type ClientConfig = generic::ClientConfig<TlsSession>;
     /// A `ServerConfig` using rustls for the cryptography protocol
     pub type ServerConfig = generic::ServerConfig<TlsSession>;
+    /// A `ServerNameConfig` using rustls for the cryptography protocol
+    pub type ServerNameConfig = generic::ServerNameConfig<TlsSession>;
+    /// A `ServerNameStore` using rustls for the cryptography protocol
+    pub type ServerNameStore = generic::ServerNameStore<TlsSession>;
 
     /// A `ClientConfigBuilder` using rustls for the cryptography protocol
     pub type ClientConfigBuilder = generic::ClientConfigBuilder<TlsSession>;
@@ -102,6 +122,8 @@ mod rustls_impls {
     pub type Connection = generic::Connection<TlsSession>;
     /// A `Datagrams` using rustls for the cryptography protocol
     pub type Datagrams = generic::Datagrams<TlsSession>;
+    /// An `ExtensionFrames` using rustls for the cryptography protocol
+    pub type ExtensionFrames = generic::ExtensionFrames<TlsSession>;
     /// An `IncomingBiStreams` using rustls for the cryptography protocol
     pub type IncomingBiStreams = generic::IncomingBiStreams<TlsSession>;
     /// An `IncomingUniStreams` using rustls for the cryptography protocol
@@ -1,4 +1,4 @@
-use std::{io, io::IoSliceMut};
+use std::{io, io::IoSliceMut, net::SocketAddr};
 
 use mio::net::UdpSocket;
 
@@ -44,4 +44,12 @@ impl super::UdpExt for UdpSocket {
     }
 }
 
+/// `SO_REUSEPORT` is not available, so sockets cannot share an address
+pub fn bind_reuse_port(_addr: &SocketAddr) -> io::Result<std::net::UdpSocket> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
+
 pub const BATCH_SIZE: usize = 1;
@@ -18,6 +18,8 @@ mod imp;
 /// Number of UDP packets to send/receive at a time
 pub const BATCH_SIZE: usize = imp::BATCH_SIZE;
 
+pub use imp::bind_reuse_port;
+
 pub trait UdpExt {
     fn init_ext(&self) -> io::Result<()>;
     fn send_ext(&self, transmits: &[Transmit]) -> io::Result<usize>;
@@ -3,7 +3,7 @@ use std::{
     io::IoSliceMut,
     mem::{self, MaybeUninit},
     net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6},
-    os::unix::io::AsRawFd,
+    os::unix::io::{AsRawFd, FromRawFd},
     ptr,
 };
 
@@ -267,6 +267,45 @@ impl super::UdpExt for UdpSocket {
     }
 }
 
+/// Bind a UDP socket with `SO_REUSEPORT` set, so that several sockets may share `addr`
+pub fn bind_reuse_port(addr: &SocketAddr) -> io::Result<std::net::UdpSocket> {
+    let domain = if addr.is_ipv4() {
+        libc::AF_INET
+    } else {
+        libc::AF_INET6
+    };
+    let fd = unsafe { libc::socket(domain, libc::SOCK_DGRAM, 0) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // Take ownership immediately so the descriptor is closed on error
+    let socket = unsafe { std::net::UdpSocket::from_raw_fd(fd) };
+
+    let on: libc::c_int = 1;
+    let rc = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            &on as *const _ as _,
+            mem::size_of_val(&on) as _,
+        )
+    };
+    if rc == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let (name, namelen) = match addr {
+        SocketAddr::V4(ref addr) => (addr as *const _ as _, mem::size_of::<libc::sockaddr_in>()),
+        SocketAddr::V6(ref addr) => (addr as *const _ as _, mem::size_of::<libc::sockaddr_in6>()),
+    };
+    let rc = unsafe { libc::bind(fd, name, namelen as _) };
+    if rc == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(socket)
+}
+
 const CMSG_LEN: usize = 64;
 
 fn prepare_msg(
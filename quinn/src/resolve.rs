@@ -0,0 +1,175 @@
+//! Hostname resolution for [`Endpoint::connect_host()`]
+//!
+//! [`Endpoint::connect_host()`]: crate::generic::Endpoint::connect_host
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    pin::Pin,
+    thread,
+};
+
+use futures::channel::oneshot;
+use proto::{ConnectError, ConnectionError};
+use thiserror::Error;
+
+/// Resolves hostnames into the addresses [`Endpoint::connect_host()`] races connections to
+///
+/// [`Endpoint::connect_host()`]: crate::generic::Endpoint::connect_host
+pub trait Resolver: Send + Sync + Debug + 'static {
+    /// Look up the addresses of `host`, combining each with `port`
+    ///
+    /// Addresses should be returned in order of preference, e.g. as sorted by RFC 6724.
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send>>;
+}
+
+/// Resolves hostnames using the operating system's resolver
+///
+/// Each lookup blocks a dedicated thread, so as not to stall the runtime driving the endpoint.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send>> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Box::pin(futures::future::ready(Ok(vec![SocketAddr::new(ip, port)])));
+        }
+        let host = host.to_owned();
+        let (send, recv) = oneshot::channel();
+        let spawned = thread::Builder::new()
+            .name("quinn-resolver".into())
+            .spawn(move || {
+                let _ = send.send(
+                    (host.as_str(), port)
+                        .to_socket_addrs()
+                        .map(|addrs| addrs.collect()),
+                );
+            });
+        Box::pin(async move {
+            spawned?;
+            recv.await
+                .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "resolver panicked")))
+        })
+    }
+}
+
+/// Resolves hostnames from a fixed table
+///
+/// Useful for tests, or to pin hosts to known addresses. Hostnames are matched
+/// case-insensitively, and hosts absent from the table fail to resolve.
+#[derive(Debug, Default, Clone)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticResolver {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve `host` to `addrs`, in order of preference, replacing any previous entry
+    pub fn insert(&mut self, host: &str, addrs: impl IntoIterator<Item = IpAddr>) -> &mut Self {
+        self.hosts
+            .insert(host.to_ascii_lowercase(), addrs.into_iter().collect());
+        self
+    }
+
+    /// Remove the entry for `host`, if any
+    pub fn remove(&mut self, host: &str) -> &mut Self {
+        self.hosts.remove(&host.to_ascii_lowercase());
+        self
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send>> {
+        let result = match self.hosts.get(&host.to_ascii_lowercase()) {
+            Some(addrs) => Ok(addrs.iter().map(|&ip| SocketAddr::new(ip, port)).collect()),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("unknown host {}", host),
+            )),
+        };
+        Box::pin(futures::future::ready(result))
+    }
+}
+
+/// Errors that can occur while connecting to a host by name
+#[derive(Debug, Error)]
+pub enum ConnectHostError {
+    /// The host was not of the form `host:port`
+    #[error("invalid host {0:?}")]
+    InvalidHost(String),
+    /// The resolver failed
+    #[error("failed to resolve host: {0}")]
+    Resolve(#[source] io::Error),
+    /// The resolver returned no addresses reachable from the endpoint's socket
+    #[error("no usable addresses")]
+    NoAddresses,
+    /// Every attempt failed to start, the last with this error
+    #[error("failed to connect: {0}")]
+    Connect(#[from] ConnectError),
+    /// Every attempt failed, the last with this error
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+}
+
+/// Split `host:port` into its parts, stripping brackets from IPv6 literals
+pub(crate) fn split_host(host: &str) -> Result<(&str, u16), ConnectHostError> {
+    let invalid = || ConnectHostError::InvalidHost(host.to_owned());
+    let colon = host.rfind(':').ok_or_else(invalid)?;
+    let port = host[colon + 1..].parse().map_err(|_| invalid())?;
+    let name = &host[..colon];
+    let name = if name.starts_with('[') && name.ends_with(']') {
+        &name[1..name.len() - 1]
+    } else if name.contains(':') {
+        return Err(invalid());
+    } else {
+        name
+    };
+    if name.is_empty() {
+        return Err(invalid());
+    }
+    Ok((name, port))
+}
+
+/// Order `addrs` for connection attempts as described in RFC 8305 §4
+///
+/// Address families are interleaved, starting with the family of the most preferred address.
+/// IPv6 addresses are dropped if `ipv6` is false, since an IPv4 socket cannot reach them.
+pub(crate) fn sort_addresses(addrs: Vec<SocketAddr>, ipv6: bool) -> Vec<SocketAddr> {
+    let mut unique = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        if (ipv6 || addr.is_ipv4()) && !unique.contains(&addr) {
+            unique.push(addr);
+        }
+    }
+    let prefer_v6 = unique.first().map_or(false, |x| x.is_ipv6());
+    let (preferred, other): (Vec<_>, Vec<_>) =
+        unique.into_iter().partition(|x| x.is_ipv6() == prefer_v6);
+    let mut result = Vec::with_capacity(preferred.len() + other.len());
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (a, b) => result.extend(a.into_iter().chain(b)),
+        }
+    }
+    result
+}
@@ -0,0 +1,116 @@
+use std::{
+    future::Future,
+    io::{self, IoSliceMut},
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use async_io::{Async, Timer};
+use futures::ready;
+use proto::Transmit;
+
+use super::{AsyncTimer, AsyncUdpSocket, Runtime};
+use crate::{platform::UdpExt, udp::RecvMeta};
+
+/// A Quinn runtime for async-std
+#[cfg(feature = "runtime-async-std")]
+#[derive(Debug)]
+pub struct AsyncStdRuntime;
+
+#[cfg(feature = "runtime-async-std")]
+impl Runtime for AsyncStdRuntime {
+    fn new_timer(&self, t: Instant) -> Pin<Box<dyn AsyncTimer>> {
+        Box::pin(Timer::at(t))
+    }
+
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        async_std::task::spawn(future);
+    }
+
+    fn wrap_udp_socket(&self, sock: std::net::UdpSocket) -> io::Result<Box<dyn AsyncUdpSocket>> {
+        Ok(Box::new(UdpSocket::from_std(sock)?))
+    }
+}
+
+/// A Quinn runtime for smol
+#[cfg(feature = "runtime-smol")]
+#[derive(Debug)]
+pub struct SmolRuntime;
+
+#[cfg(feature = "runtime-smol")]
+impl Runtime for SmolRuntime {
+    fn new_timer(&self, t: Instant) -> Pin<Box<dyn AsyncTimer>> {
+        Box::pin(Timer::at(t))
+    }
+
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        smol::spawn(future).detach();
+    }
+
+    fn wrap_udp_socket(&self, sock: std::net::UdpSocket) -> io::Result<Box<dyn AsyncUdpSocket>> {
+        Ok(Box::new(UdpSocket::from_std(sock)?))
+    }
+}
+
+impl AsyncTimer for Timer {
+    fn reset(mut self: Pin<&mut Self>, t: Instant) {
+        self.set_at(t)
+    }
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        Future::poll(self.as_mut(), cx).map(|_| ())
+    }
+}
+
+/// UDP socket registered with the async-io reactor, shared by async-std and smol
+///
+/// The platform layer's ECN-aware I/O is implemented for mio sockets, which are plain non-blocking
+/// sockets until registered with a mio event loop, so one is used here as the underlying handle.
+#[derive(Debug)]
+struct UdpSocket {
+    io: Async<mio::net::UdpSocket>,
+}
+
+impl UdpSocket {
+    fn from_std(socket: std::net::UdpSocket) -> io::Result<UdpSocket> {
+        let io = mio::net::UdpSocket::from_socket(socket)?;
+        io.init_ext()?;
+        Ok(UdpSocket {
+            io: Async::new(io)?,
+        })
+    }
+}
+
+impl AsyncUdpSocket for UdpSocket {
+    fn poll_send(&self, cx: &mut Context, transmits: &[Transmit]) -> Poll<io::Result<usize>> {
+        loop {
+            ready!(self.io.poll_writable(cx))?;
+            match self.io.get_ref().send_ext(transmits) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        debug_assert!(!bufs.is_empty());
+        loop {
+            ready!(self.io.poll_readable(cx))?;
+            match self.io.get_ref().recv_ext(bufs, meta) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().local_addr()
+    }
+}
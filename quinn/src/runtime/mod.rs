@@ -0,0 +1,109 @@
+//! Abstraction over the async runtime driving endpoints and connections
+//!
+//! Quinn needs a runtime to spawn its background tasks, to schedule timers, and to be notified when
+//! a UDP socket becomes readable or writable. Implementations for tokio, async-std and smol are
+//! provided behind the `runtime-tokio`, `runtime-async-std` and `runtime-smol` features
+//! respectively; other executors can be supported by implementing [`Runtime`].
+use std::{
+    fmt::Debug,
+    future::Future,
+    io::{self, IoSliceMut},
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use proto::Transmit;
+
+use crate::udp::RecvMeta;
+
+/// Abstracts I/O and timer operations for runtime independence
+pub trait Runtime: Send + Sync + Debug + 'static {
+    /// Construct a timer that will expire at `i`
+    fn new_timer(&self, i: Instant) -> Pin<Box<dyn AsyncTimer>>;
+    /// Drive `future` to completion in the background
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>);
+    /// Convert `t` into the socket type used by this runtime
+    ///
+    /// Called from within the builder or [`Endpoint::rebind()`], so implementations may rely on
+    /// the same context requirements as their runtime's own socket constructors.
+    ///
+    /// [`Endpoint::rebind()`]: crate::generic::Endpoint::rebind
+    fn wrap_udp_socket(&self, t: std::net::UdpSocket) -> io::Result<Box<dyn AsyncUdpSocket>>;
+}
+
+/// Abstract implementation of an async timer for runtime independence
+pub trait AsyncTimer: Send + Debug + 'static {
+    /// Update the timer to expire at `i`
+    fn reset(self: Pin<&mut Self>, i: Instant);
+    /// Check whether the timer has expired, and register to be woken if not
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()>;
+}
+
+/// Abstract implementation of a UDP socket for runtime independence
+///
+/// The runtimes provided by this crate implement this for operating system UDP sockets, reading
+/// and writing ECN bits where the platform allows. Applications may implement it themselves to run
+/// an endpoint over any other datagram transport using
+/// [`EndpointBuilder::with_async_socket()`].
+///
+/// [`EndpointBuilder::with_async_socket()`]: crate::generic::EndpointBuilder::with_async_socket
+pub trait AsyncUdpSocket: Send + Debug + 'static {
+    /// Send UDP datagrams from `transmits`, or register to be woken if sending may succeed in the
+    /// future
+    ///
+    /// Returns the number of datagrams sent, which may be fewer than `transmits.len()`.
+    fn poll_send(&self, cx: &mut Context, transmits: &[Transmit]) -> Poll<io::Result<usize>>;
+
+    /// Receive UDP datagrams into `bufs`, describing each in the corresponding entry of `meta`, or
+    /// register to be woken if receiving may succeed in the future
+    ///
+    /// Returns the number of datagrams received, which must not exceed `bufs.len()`. Each datagram
+    /// must fit in its buffer; larger datagrams should be truncated or dropped.
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>>;
+
+    /// Look up the local IP address and port used by this socket
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+}
+
+/// The runtime used by endpoints which were not configured with one explicitly
+///
+/// Picks the first of tokio, async-std and smol enabled by cargo features, if any.
+#[allow(unreachable_code)]
+pub fn default_runtime() -> Option<Arc<dyn Runtime>> {
+    #[cfg(feature = "runtime-tokio")]
+    {
+        return Some(Arc::new(TokioRuntime));
+    }
+
+    #[cfg(feature = "runtime-async-std")]
+    {
+        return Some(Arc::new(AsyncStdRuntime));
+    }
+
+    #[cfg(feature = "runtime-smol")]
+    {
+        return Some(Arc::new(SmolRuntime));
+    }
+
+    None
+}
+
+#[cfg(feature = "runtime-tokio")]
+mod tokio;
+#[cfg(feature = "runtime-tokio")]
+pub use self::tokio::TokioRuntime;
+
+#[cfg(any(feature = "runtime-async-std", feature = "runtime-smol"))]
+mod async_io;
+#[cfg(feature = "runtime-async-std")]
+pub use self::async_io::AsyncStdRuntime;
+#[cfg(feature = "runtime-smol")]
+pub use self::async_io::SmolRuntime;
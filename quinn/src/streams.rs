@@ -1,7 +1,6 @@
 use std::{
     future::Future,
     io,
-    mem::MaybeUninit,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -197,6 +196,7 @@ where
     }
 }
 
+#[cfg(feature = "runtime-tokio")]
 impl<S> tokio::io::AsyncWrite for SendStream<S>
 where
     S: proto::crypto::Session,
@@ -557,11 +557,12 @@ where
     }
 }
 
+#[cfg(feature = "runtime-tokio")]
 impl<S> tokio::io::AsyncRead for RecvStream<S>
 where
     S: proto::crypto::Session,
 {
-    unsafe fn prepare_uninitialized_buffer(&self, _: &mut [MaybeUninit<u8>]) -> bool {
+    unsafe fn prepare_uninitialized_buffer(&self, _: &mut [std::mem::MaybeUninit<u8>]) -> bool {
         false
     }
 
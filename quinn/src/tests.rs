@@ -1,13 +1,14 @@
 #![cfg(feature = "rustls")]
 
 use std::{
-    io,
+    io::{self, IoSliceMut},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
     str,
-    sync::Arc,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
 };
 
-use futures::{future, StreamExt};
+use futures::{channel::mpsc, future, ready, StreamExt};
 use tokio::{
     runtime::{Builder, Runtime},
     time::{Duration, Instant},
@@ -16,8 +17,8 @@ use tracing::{info, info_span};
 use tracing_futures::Instrument as _;
 
 use super::{
-    ClientConfigBuilder, Endpoint, Incoming, NewConnection, RecvStream, SendStream,
-    ServerConfigBuilder,
+    AsyncUdpSocket, ClientConfig, ClientConfigBuilder, Endpoint, EndpointBuilder, Incoming,
+    NewConnection, RecvMeta, RecvStream, SendStream, ServerConfig, ServerConfigBuilder, Transmit,
 };
 
 #[test]
@@ -59,6 +60,46 @@ fn handshake_timeout() {
     assert!(dt > IDLE_TIMEOUT && dt < 2 * IDLE_TIMEOUT);
 }
 
+#[test]
+fn handshake_timeout_without_idle_timeout() {
+    let _guard = subscribe();
+    let mut runtime = rt_threaded();
+    let (client, _) = runtime.enter(|| {
+        Endpoint::builder()
+            .bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+            .unwrap()
+    });
+
+    let mut client_config = crate::ClientConfig::default();
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(500);
+    let mut transport_config = crate::TransportConfig::default();
+    transport_config
+        .max_idle_timeout(None)
+        .unwrap()
+        .handshake_timeout(Some(HANDSHAKE_TIMEOUT))
+        .initial_rtt(Duration::from_millis(10));
+    client_config.transport = Arc::new(transport_config);
+
+    let start = Instant::now();
+    runtime.block_on(async move {
+        match client
+            .connect_with(
+                client_config,
+                &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1),
+                "localhost",
+            )
+            .unwrap()
+            .await
+        {
+            Err(crate::ConnectionError::HandshakeTimedOut) => {}
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+    });
+    let dt = start.elapsed();
+    assert!(dt > HANDSHAKE_TIMEOUT && dt < 2 * HANDSHAKE_TIMEOUT);
+}
+
 #[tokio::test]
 async fn close_endpoint() {
     let _guard = subscribe();
@@ -135,6 +176,84 @@ fn read_after_close() {
     });
 }
 
+#[tokio::test]
+async fn connect_host() {
+    let _guard = subscribe();
+    let mut resolver = crate::StaticResolver::new();
+    // Nothing listens on the first address, so the second attempt must win
+    resolver.insert(
+        "localhost",
+        vec![
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)),
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+        ],
+    );
+    let mut builder = endpoint_builder();
+    builder.resolver(Arc::new(resolver));
+    let (endpoint, mut incoming) = builder
+        .bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .unwrap();
+    let server_addr = endpoint.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _conn = incoming
+            .next()
+            .await
+            .expect("endpoint")
+            .await
+            .expect("connection");
+        futures::future::pending::<()>().await;
+    });
+
+    let start = Instant::now();
+    let new_conn = endpoint
+        .connect_host(&format!("localhost:{}", server_addr.port()))
+        .await
+        .expect("connect");
+    assert_eq!(new_conn.connection.remote_address(), server_addr);
+    assert!(start.elapsed() >= Duration::from_millis(250));
+
+    match endpoint.connect_host("unknown:443").await {
+        Err(crate::ConnectHostError::Resolve(_)) => {}
+        x => panic!("unexpected result {:?}", x.map(|_| ())),
+    }
+    match endpoint.connect_host("localhost").await {
+        Err(crate::ConnectHostError::InvalidHost(_)) => {}
+        x => panic!("unexpected result {:?}", x.map(|_| ())),
+    }
+}
+
+#[test]
+fn split_host() {
+    use crate::resolve::split_host;
+    assert_eq!(split_host("example.com:443").unwrap(), ("example.com", 443));
+    assert_eq!(split_host("[::1]:4433").unwrap(), ("::1", 4433));
+    assert_eq!(split_host("127.0.0.1:80").unwrap(), ("127.0.0.1", 80));
+    assert!(split_host("example.com").is_err());
+    assert!(split_host("::1:443").is_err());
+    assert!(split_host(":443").is_err());
+}
+
+#[test]
+fn sort_addresses() {
+    use crate::resolve::sort_addresses;
+    let addrs = [
+        "[::1]:1",
+        "[::2]:1",
+        "[::3]:1",
+        "127.0.0.1:1",
+        "[::1]:1",
+        "127.0.0.2:1",
+    ]
+    .iter()
+    .map(|x| x.parse().unwrap())
+    .collect::<Vec<SocketAddr>>();
+    assert_eq!(
+        sort_addresses(addrs.clone(), true),
+        [addrs[0], addrs[3], addrs[1], addrs[5], addrs[2]]
+    );
+    assert_eq!(sort_addresses(addrs.clone(), false), [addrs[3], addrs[5]]);
+}
+
 #[test]
 fn export_keying_material() {
     let _guard = subscribe();
@@ -212,26 +331,88 @@ async fn accept_after_close() {
     assert!(receiver.connection.open_uni().await.is_err());
 }
 
+#[tokio::test]
+async fn lifecycle_events() {
+    let _guard = subscribe();
+    let (endpoint, mut incoming) = endpoint();
+
+    let client = endpoint
+        .connect(&endpoint.local_addr().unwrap(), "localhost")
+        .unwrap()
+        .await
+        .expect("connect")
+        .connection;
+    let server = incoming
+        .next()
+        .await
+        .expect("endpoint")
+        .await
+        .expect("connection")
+        .connection;
+    let mut events = server.events();
+    let closed = server.closed();
+
+    client.close(42u32.into(), b"done");
+    match closed.await {
+        crate::ConnectionError::ApplicationClosed(close) => {
+            assert_eq!(close.error_code, 42u32.into());
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+    match events.next().await {
+        Some(crate::LifecycleEvent::ConnectionLost {
+            reason: crate::ConnectionError::ApplicationClosed(_),
+        }) => {}
+        e => panic!("unexpected event: {:?}", e),
+    }
+    assert!(events.next().await.is_none());
+
+    // Subscribing after the fact reports the loss immediately
+    assert!(matches!(
+        server.events().next().await,
+        Some(crate::LifecycleEvent::ConnectionLost { .. })
+    ));
+    assert!(matches!(
+        client.closed().await,
+        crate::ConnectionError::LocallyClosed
+    ));
+}
+
 /// Construct an endpoint suitable for connecting to itself
 fn endpoint() -> (Endpoint, Incoming) {
+    endpoint_with_shards(1)
+}
+
+/// Construct an endpoint suitable for connecting to itself, spread across `shards` sockets
+fn endpoint_with_shards(shards: usize) -> (Endpoint, Incoming) {
+    let mut endpoint = endpoint_builder();
+    endpoint.shards(shards);
+    let (x, y) = endpoint
+        .bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .unwrap();
+    (x, y)
+}
+
+fn endpoint_builder() -> EndpointBuilder {
     let mut endpoint = Endpoint::builder();
+    let (server_config, client_config) = configs();
+    endpoint.listen(server_config);
+    endpoint.default_client_config(client_config);
+    endpoint
+}
 
+/// A server configuration and a client configuration trusting its certificate
+fn configs() -> (ServerConfig, ClientConfig) {
     let mut server_config = ServerConfigBuilder::default();
     let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
     let key = crate::PrivateKey::from_der(&cert.serialize_private_key_der()).unwrap();
     let cert = crate::Certificate::from_der(&cert.serialize_der().unwrap()).unwrap();
     let cert_chain = crate::CertificateChain::from_certs(vec![cert.clone()]);
     server_config.certificate(cert_chain, key).unwrap();
-    endpoint.listen(server_config.build());
 
     let mut client_config = ClientConfigBuilder::default();
     client_config.add_certificate_authority(cert).unwrap();
-    endpoint.default_client_config(client_config.build());
-
-    let (x, y) = endpoint
-        .bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
-        .unwrap();
-    (x, y)
+    (server_config.build(), client_config.build())
 }
 
 #[tokio::test]
@@ -322,6 +503,263 @@ async fn zero_rtt() {
     endpoint.wait_idle().await;
 }
 
+#[test]
+#[cfg(unix)]
+fn echo_sharded() {
+    let _guard = subscribe();
+    let mut runtime = rt_threaded();
+    let (endpoint, incoming) = runtime.enter(|| endpoint_with_shards(4));
+    runtime.spawn(incoming.for_each(|conn| async move {
+        let new_conn = conn.instrument(info_span!("server")).await.unwrap();
+        tokio::spawn(
+            new_conn
+                .bi_streams
+                .take_while(|x| future::ready(x.is_ok()))
+                .for_each(|s| echo(s.unwrap())),
+        );
+    }));
+    runtime.block_on(async move {
+        let addr = endpoint.local_addr().unwrap();
+        // Enough connections that every shard is likely to own some, and to receive datagrams for
+        // connections owned by others
+        future::join_all((0..16).map(|_| async {
+            let new_conn = endpoint
+                .connect(&addr, "localhost")
+                .unwrap()
+                .instrument(info_span!("client"))
+                .await
+                .expect("connect");
+            let (mut send, recv) = new_conn.connection.open_bi().await.expect("stream open");
+            send.write_all(b"foo").await.expect("write");
+            send.finish().await.expect("finish");
+            let data = recv.read_to_end(usize::max_value()).await.expect("read");
+            assert_eq!(&data[..], b"foo");
+            new_conn.connection.close(0u32.into(), b"done");
+        }))
+        .await;
+        endpoint.wait_idle().await;
+
+        let stats = endpoint.stats();
+        assert_eq!(stats.accepted_connections, 16);
+        assert_eq!(stats.active_connections, 0);
+        assert!(stats.udp_rx.datagrams > 0);
+        assert!(stats.udp_tx.datagrams > 0);
+    });
+}
+
+#[test]
+#[cfg(feature = "runtime-async-std")]
+fn echo_async_std() {
+    let _guard = subscribe();
+    async_std::task::block_on(async {
+        let mut builder = endpoint_builder();
+        builder.runtime(Arc::new(crate::AsyncStdRuntime));
+        let (endpoint, mut incoming) = builder
+            .bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+            .unwrap();
+        async_std::task::spawn(async move {
+            let mut new_conn = incoming.next().await.unwrap().await.unwrap();
+            echo(new_conn.bi_streams.next().await.unwrap().unwrap()).await;
+        });
+
+        let new_conn = endpoint
+            .connect(&endpoint.local_addr().unwrap(), "localhost")
+            .unwrap()
+            .await
+            .expect("connect");
+        let (mut send, recv) = new_conn.connection.open_bi().await.expect("stream open");
+        send.write_all(b"foo").await.expect("write");
+        send.finish().await.expect("finish");
+        let data = recv.read_to_end(usize::max_value()).await.expect("read");
+        assert_eq!(&data[..], b"foo");
+        new_conn.connection.close(0u32.into(), b"done");
+        endpoint.wait_idle().await;
+    });
+}
+
+#[tokio::test]
+async fn echo_in_memory() {
+    let _guard = subscribe();
+    let server_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 4433);
+    let client_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 50000);
+    let (server_socket, client_socket) = MemorySocket::pair(server_addr, client_addr);
+
+    // Both ends share a builder so that the client trusts the server's certificate
+    let builder = endpoint_builder();
+    let (_, mut incoming) = builder
+        .clone()
+        .with_async_socket(Box::new(server_socket))
+        .unwrap();
+    let (endpoint, _) = builder.with_async_socket(Box::new(client_socket)).unwrap();
+    assert_eq!(endpoint.local_addr().unwrap(), client_addr);
+
+    tokio::spawn(async move {
+        let mut new_conn = incoming.next().await.unwrap().await.unwrap();
+        echo(new_conn.bi_streams.next().await.unwrap().unwrap()).await;
+    });
+    let new_conn = endpoint
+        .connect(&server_addr, "localhost")
+        .unwrap()
+        .await
+        .expect("connect");
+    assert_eq!(new_conn.connection.remote_address(), server_addr);
+    let (mut send, recv) = new_conn.connection.open_bi().await.expect("stream open");
+    send.write_all(b"foo").await.expect("write");
+    send.finish().await.expect("finish");
+    let data = recv.read_to_end(usize::max_value()).await.expect("read");
+    assert_eq!(&data[..], b"foo");
+    new_conn.connection.close(0u32.into(), b"done");
+    endpoint.wait_idle().await;
+}
+
+#[tokio::test]
+async fn capture() {
+    let _guard = subscribe();
+    let out = SharedBuf::default();
+    let capture = Arc::new(crate::PcapNgWriter::new(out.clone()).unwrap());
+    // The capture applies to configurations set after it, including on the running endpoint
+    let (server_config, client_config) = configs();
+    let mut builder = Endpoint::builder();
+    builder.capture(capture);
+    builder.default_client_config(client_config);
+    let (endpoint, mut incoming) = builder
+        .bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+        .unwrap();
+    endpoint.set_server_config(Some(server_config));
+    let port = endpoint.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        let mut new_conn = incoming.next().await.unwrap().await.unwrap();
+        echo(new_conn.bi_streams.next().await.unwrap().unwrap()).await;
+    });
+    let new_conn = endpoint
+        .connect(&endpoint.local_addr().unwrap(), "localhost")
+        .unwrap()
+        .await
+        .expect("connect");
+    let (mut send, recv) = new_conn.connection.open_bi().await.expect("stream open");
+    send.write_all(b"foo").await.expect("write");
+    send.finish().await.expect("finish");
+    recv.read_to_end(usize::max_value()).await.expect("read");
+    new_conn.connection.close(0u32.into(), b"done");
+    endpoint.wait_idle().await;
+
+    let data = out.0.lock().unwrap();
+    let u32_at = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+    let (mut packets, mut secrets) = (0, String::new());
+    let mut i = 0;
+    while i < data.len() {
+        let len = u32_at(i + 4) as usize;
+        match u32_at(i) {
+            // Enhanced packet block holding an IPv4 packet between two sockets on `port`
+            6 => {
+                let packet = &data[i + 28..];
+                assert_eq!(packet[0], 0x45);
+                assert_eq!(&packet[12..16], &[127, 0, 0, 1]);
+                assert_eq!(&packet[20..22], &port.to_be_bytes());
+                assert_eq!(&packet[22..24], &port.to_be_bytes());
+                packets += 1;
+            }
+            // Decryption secrets block
+            10 => {
+                let n = u32_at(i + 12) as usize;
+                secrets.push_str(str::from_utf8(&data[i + 16..i + 16 + n]).unwrap());
+            }
+            _ => {}
+        }
+        i += len;
+    }
+    assert!(packets > 0);
+    for label in &[
+        "CLIENT_HANDSHAKE_TRAFFIC_SECRET ",
+        "SERVER_HANDSHAKE_TRAFFIC_SECRET ",
+        "CLIENT_TRAFFIC_SECRET_0 ",
+        "SERVER_TRAFFIC_SECRET_0 ",
+    ] {
+        assert!(secrets.contains(label), "missing {}", label);
+    }
+}
+
+/// An in-memory sink for output which is inspected by a test
+#[derive(Debug, Default, Clone)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// One end of an in-memory datagram link standing in for a UDP socket
+#[derive(Debug)]
+struct MemorySocket {
+    addr: SocketAddr,
+    peer: mpsc::UnboundedSender<(SocketAddr, Vec<u8>)>,
+    incoming: Mutex<mpsc::UnboundedReceiver<(SocketAddr, Vec<u8>)>>,
+}
+
+impl MemorySocket {
+    fn pair(a: SocketAddr, b: SocketAddr) -> (Self, Self) {
+        let (a_send, a_recv) = mpsc::unbounded();
+        let (b_send, b_recv) = mpsc::unbounded();
+        (
+            Self {
+                addr: a,
+                peer: b_send,
+                incoming: Mutex::new(a_recv),
+            },
+            Self {
+                addr: b,
+                peer: a_send,
+                incoming: Mutex::new(b_recv),
+            },
+        )
+    }
+}
+
+impl AsyncUdpSocket for MemorySocket {
+    fn poll_send(&self, _: &mut Context, transmits: &[Transmit]) -> Poll<io::Result<usize>> {
+        for transmit in transmits {
+            // Datagrams sent after the peer is gone are lost, as they would be on a real network
+            let _ = self
+                .peer
+                .unbounded_send((self.addr, transmit.contents.clone()));
+        }
+        Poll::Ready(Ok(transmits.len()))
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        let mut incoming = self.incoming.lock().unwrap();
+        let (addr, data) = match ready!(incoming.poll_next_unpin(cx)) {
+            Some(x) => x,
+            None => return Poll::Pending,
+        };
+        let len = data.len().min(bufs[0].len());
+        bufs[0][..len].copy_from_slice(&data[..len]);
+        meta[0] = RecvMeta {
+            addr,
+            len,
+            ecn: None,
+            dst_ip: None,
+        };
+        Poll::Ready(Ok(1))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+}
+
 #[test]
 fn echo_v6() {
     run_echo(